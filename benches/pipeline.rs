@@ -0,0 +1,100 @@
+//! Benchmarks the steps between an image and its caption over the fixtures in
+//! `tests/fixtures`: reading the header in range requests from a local server, reading a
+//! file held in memory, and rendering the caption in every style.
+//!
+//! `cargo bench -- --save-baseline before` records a run to compare a refactor with
+//! `cargo bench -- --baseline before`.
+
+use std::hint::black_box;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use fotobot_rs::exif::{self, CaptionOptions, ParsedExif};
+use fotobot_rs::styles::{self, CaptionStyle};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Every image in the corpus, by file name.
+fn corpus() -> Vec<(String, Vec<u8>)> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut images: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension != "py"))
+        .map(|path| {
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            (name, std::fs::read(&path).unwrap())
+        })
+        .collect();
+    images.sort();
+    images
+}
+
+fn ranged_read(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let server = runtime.block_on(MockServer::start());
+    let client = reqwest::Client::new();
+
+    let mut group = c.benchmark_group("ranged_read");
+    for (name, bytes) in corpus() {
+        // The fixtures fit in the first block, which the server answers as a range.
+        let range = format!("bytes 0-{}/{}", bytes.len() - 1, bytes.len());
+        runtime.block_on(
+            Mock::given(method("GET"))
+                .and(path(format!("/{name}")))
+                .respond_with(
+                    ResponseTemplate::new(206)
+                        .insert_header("content-range", range.as_str())
+                        .set_body_bytes(bytes.clone()),
+                )
+                .mount(&server),
+        );
+        let url = format!("{}/{name}", server.uri());
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(&name), &url, |b, url| {
+            b.to_async(&runtime)
+                .iter(|| async { black_box(exif::read_metadata_from_url(&client, url).await) });
+        });
+    }
+    group.finish();
+}
+
+fn full_file(c: &mut Criterion) {
+    let mut group = c.benchmark_group("full_file");
+    for (name, bytes) in corpus() {
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(&name), &bytes, |b, bytes| {
+            b.iter(|| black_box(exif::read_metadata(Cursor::new(bytes.as_slice()))));
+        });
+    }
+    group.finish();
+}
+
+fn caption(c: &mut Criterion) {
+    let parsed: Vec<ParsedExif> = corpus()
+        .iter()
+        .filter_map(|(_, bytes)| exif::read_exif_from_bytes(bytes).ok().flatten())
+        .map(|exif| ParsedExif::from_exif(&exif, None))
+        .collect();
+
+    let mut group = c.benchmark_group("caption");
+    group.throughput(Throughput::Elements(parsed.len() as u64));
+    for style in CaptionStyle::ALL {
+        let options = CaptionOptions {
+            style,
+            ..CaptionOptions::default()
+        };
+        group.bench_function(style.as_str(), |b| {
+            b.iter(|| {
+                for data in &parsed {
+                    black_box(styles::render(data, options, "en"));
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, ranged_read, full_file, caption);
+criterion_main!(benches);
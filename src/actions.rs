@@ -0,0 +1,180 @@
+use std::borrow::Cow;
+
+use reqwest::Url;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+use crate::geo::links;
+use crate::strip::{Categories, Category};
+
+/// Callback data of the result buttons is this prefix followed by the action name.
+const CALLBACK_PREFIX: &str = "action:";
+/// Callback data of the removal menu; the selection travels along as a bit set.
+const STRIP_MENU_PREFIX: &str = "strip:menu:";
+const STRIP_APPLY_PREFIX: &str = "strip:apply:";
+const STRIP_ALL: &str = "strip:all";
+const STRIP_BACK: &str = "strip:back";
+
+/// Follow-up actions offered under every EXIF answer.
+#[derive(Clone, Copy)]
+pub enum ResultAction {
+    /// Sends the GPS position as a location pin.
+    Map,
+    /// Lists every EXIF field instead of the curated summary.
+    Dump,
+    /// Opens the menu to remove all or selected metadata.
+    Strip,
+    /// Sends every EXIF field as a JSON file.
+    Json,
+    /// Suggests hashtags for posting the photo elsewhere.
+    Hashtags,
+    /// Sends a QR code of the GPS position to scan with another device.
+    Qr,
+}
+
+impl ResultAction {
+    const ALL: [ResultAction; 6] = [
+        ResultAction::Map,
+        ResultAction::Dump,
+        ResultAction::Strip,
+        ResultAction::Json,
+        ResultAction::Hashtags,
+        ResultAction::Qr,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            ResultAction::Map => "map",
+            ResultAction::Dump => "dump",
+            ResultAction::Strip => "strip",
+            ResultAction::Json => "json",
+            ResultAction::Hashtags => "hashtags",
+            ResultAction::Qr => "qr",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|action| action.as_str() == value)
+    }
+
+    fn label(self, locale: &str) -> Cow<'static, str> {
+        match self {
+            ResultAction::Map => rust_i18n::t!("actions.map_button", locale = locale),
+            ResultAction::Dump => rust_i18n::t!("actions.dump_button", locale = locale),
+            ResultAction::Strip => rust_i18n::t!("actions.strip_button", locale = locale),
+            ResultAction::Json => rust_i18n::t!("actions.json_button", locale = locale),
+            ResultAction::Hashtags => rust_i18n::t!("actions.hashtags_button", locale = locale),
+            ResultAction::Qr => rust_i18n::t!("actions.qr_button", locale = locale),
+        }
+    }
+}
+
+/// Rows of two buttons, so the labels are not cut off on phones.
+pub fn keyboard(locale: &str) -> InlineKeyboardMarkup {
+    let buttons: Vec<InlineKeyboardButton> = ResultAction::ALL
+        .into_iter()
+        .map(|action| {
+            InlineKeyboardButton::callback(
+                action.label(locale),
+                format!("{CALLBACK_PREFIX}{}", action.as_str()),
+            )
+        })
+        .collect();
+
+    InlineKeyboardMarkup::new(buttons.chunks(2).map(<[_]>::to_vec))
+}
+
+/// A press on one of the buttons under a result.
+pub enum ResultCallback {
+    Action(ResultAction),
+    /// Redraws the removal menu with a changed selection.
+    StripMenu(Categories),
+    /// Removes the selected categories and sends the rewritten file.
+    StripSelected(Categories),
+    /// Removes all metadata and sends the rewritten file.
+    StripAll,
+    /// Leaves the removal menu for the action buttons.
+    Back,
+}
+
+/// The removal menu: one toggle per category, then the buttons to apply it.
+pub fn strip_keyboard(selection: Categories, locale: &str) -> InlineKeyboardMarkup {
+    let toggles = Category::ALL.into_iter().map(|category| {
+        let mark = if selection.contains(category) {
+            "✅"
+        } else {
+            "⬜"
+        };
+        let toggled = selection.toggled(category).bits();
+        vec![InlineKeyboardButton::callback(
+            format!("{mark} {}", category_label(category, locale)),
+            format!("{STRIP_MENU_PREFIX}{toggled}"),
+        )]
+    });
+
+    let apply = vec![
+        InlineKeyboardButton::callback(
+            rust_i18n::t!("actions.strip_selected_button", locale = locale),
+            format!("{STRIP_APPLY_PREFIX}{}", selection.bits()),
+        ),
+        InlineKeyboardButton::callback(
+            rust_i18n::t!("actions.strip_all_button", locale = locale),
+            STRIP_ALL,
+        ),
+    ];
+    let back = vec![InlineKeyboardButton::callback(
+        rust_i18n::t!("actions.back_button", locale = locale),
+        STRIP_BACK,
+    )];
+
+    InlineKeyboardMarkup::new(toggles.chain([apply, back]))
+}
+
+/// Buttons opening the point in the common map apps, attached to location messages.
+pub fn map_links_keyboard(latitude: f64, longitude: f64) -> InlineKeyboardMarkup {
+    let providers = [
+        ("Google Maps", links::google_maps(latitude, longitude)),
+        ("OpenStreetMap", links::openstreetmap(latitude, longitude)),
+        ("Apple Maps", links::apple_maps(latitude, longitude)),
+    ];
+    let buttons = providers.into_iter().filter_map(|(label, link)| {
+        Url::parse(&link)
+            .ok()
+            .map(|url| InlineKeyboardButton::url(label, url))
+    });
+
+    InlineKeyboardMarkup::new([buttons])
+}
+
+fn category_label(category: Category, locale: &str) -> Cow<'static, str> {
+    match category {
+        Category::Gps => rust_i18n::t!("actions.category_gps", locale = locale),
+        Category::Serials => rust_i18n::t!("actions.category_serials", locale = locale),
+        Category::Author => rust_i18n::t!("actions.category_author", locale = locale),
+    }
+}
+
+/// Returns what a result button asks for, or `None` for other callback data.
+pub fn parse_callback(data: &str) -> Option<ResultCallback> {
+    if let Some(action) = data.strip_prefix(CALLBACK_PREFIX) {
+        return ResultAction::parse(action).map(ResultCallback::Action);
+    }
+    if let Some(bits) = data.strip_prefix(STRIP_MENU_PREFIX) {
+        return bits
+            .parse()
+            .ok()
+            .map(|bits| ResultCallback::StripMenu(Categories::from_bits(bits)));
+    }
+    if let Some(bits) = data.strip_prefix(STRIP_APPLY_PREFIX) {
+        return bits
+            .parse()
+            .ok()
+            .map(|bits| ResultCallback::StripSelected(Categories::from_bits(bits)));
+    }
+    match data {
+        STRIP_ALL => Some(ResultCallback::StripAll),
+        STRIP_BACK => Some(ResultCallback::Back),
+        _ => None,
+    }
+}
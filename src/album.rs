@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use teloxide::types::{Message, UserId};
+
+/// How long to wait for the remaining messages of an album after its first one arrives.
+pub const ALBUM_COLLECT_WINDOW: Duration = Duration::from_millis(1500);
+/// Albums shared through Redis are dropped this long after their last message, in case
+/// the replica collecting one stopped.
+const SHARED_ALBUM_TTL: Duration = Duration::from_secs(60);
+
+/// Groups the separate messages Telegram delivers for one album (`media_group_id`), which
+/// can reach different replicas when they coordinate through Redis.
+pub struct AlbumCollector {
+    /// Two bots in one group both get the album's messages, under the same id.
+    bot: UserId,
+    pending: Mutex<HashMap<String, Vec<Message>>>,
+}
+
+impl AlbumCollector {
+    pub fn new(bot: UserId) -> Self {
+        Self {
+            bot,
+            pending: Mutex::default(),
+        }
+    }
+
+    /// Adds `msg` to its album.
+    ///
+    /// Returns `true` for the first message of an album: its handler owns the batch and
+    /// should call [`AlbumCollector::take`] once the collect window elapsed.
+    pub async fn push(&self, media_group_id: &str, msg: Message) -> bool {
+        if let Some(length) =
+            crate::coordination::push(&self.shared(media_group_id), &msg, SHARED_ALBUM_TTL).await
+        {
+            return length == 1;
+        }
+        let mut pending = self.pending.lock().unwrap_or_else(|err| err.into_inner());
+        match pending.get_mut(media_group_id) {
+            Some(messages) => {
+                messages.push(msg);
+                false
+            }
+            None => {
+                pending.insert(media_group_id.to_string(), vec![msg]);
+                true
+            }
+        }
+    }
+
+    /// Removes the album and returns its messages in the order they were sent.
+    pub async fn take(&self, media_group_id: &str) -> Vec<Message> {
+        let shared = crate::coordination::take(&self.shared(media_group_id)).await;
+        let mut messages = shared.unwrap_or_else(|| {
+            let mut pending = self.pending.lock().unwrap_or_else(|err| err.into_inner());
+            pending.remove(media_group_id).unwrap_or_default()
+        });
+        messages.sort_by_key(|msg| msg.id.0);
+        messages
+    }
+
+    fn shared(&self, media_group_id: &str) -> String {
+        format!("album:{}:{media_group_id}", self.bot)
+    }
+}
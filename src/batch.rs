@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use teloxide::types::{ChatId, UserId};
+
+use crate::exif::ShotFacts;
+
+/// A batch is dropped when no image was added to it for this long.
+const BATCH_IDLE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+/// Keeps one forgotten batch from growing without bounds.
+const MAX_BATCH_SIZE: usize = 500;
+
+struct Batch {
+    photos: Vec<ShotFacts>,
+    updated: Instant,
+}
+
+/// Facts of the images a user sent recently, per chat, waiting for an export command.
+#[derive(Default)]
+pub struct BatchCollector {
+    batches: Mutex<HashMap<(ChatId, UserId), Batch>>,
+}
+
+impl BatchCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an analyzed image to the user's batch, starting a new batch if needed.
+    pub fn push(&self, chat_id: ChatId, user_id: UserId, facts: ShotFacts) {
+        let mut batches = self.batches.lock().unwrap_or_else(|err| err.into_inner());
+        batches.retain(|_, batch| batch.updated.elapsed() < BATCH_IDLE_TIMEOUT);
+
+        let batch = batches.entry((chat_id, user_id)).or_insert_with(|| Batch {
+            photos: Vec::new(),
+            updated: Instant::now(),
+        });
+        if batch.photos.len() < MAX_BATCH_SIZE {
+            batch.photos.push(facts);
+        }
+        batch.updated = Instant::now();
+    }
+
+    /// Returns the images of the user's batch in the order they were sent.
+    pub fn photos(&self, chat_id: ChatId, user_id: UserId) -> Vec<ShotFacts> {
+        let batches = self.batches.lock().unwrap_or_else(|err| err.into_inner());
+        match batches.get(&(chat_id, user_id)) {
+            Some(batch) if batch.updated.elapsed() < BATCH_IDLE_TIMEOUT => batch.photos.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Ends the user's batch, so the next image starts a new one.
+    pub fn clear(&self, chat_id: ChatId, user_id: UserId) {
+        let mut batches = self.batches.lock().unwrap_or_else(|err| err.into_inner());
+        batches.remove(&(chat_id, user_id));
+    }
+
+    /// Ends the user's batches in every chat.
+    pub fn forget(&self, user_id: UserId) {
+        let mut batches = self.batches.lock().unwrap_or_else(|err| err.into_inner());
+        batches.retain(|(_, owner), _| *owner != user_id);
+    }
+}
@@ -0,0 +1,4880 @@
+use anyhow::{Context, Result, anyhow};
+use grammers_client::{
+    Client as GramClient,
+    types::{Message as GramMessage, Peer as GramPeer},
+};
+use grammers_mtsender::{ConnectionParams, InvocationError, SenderPool, SenderPoolHandle};
+use grammers_session::{
+    storages::SqliteSession,
+    types::{PeerAuth, PeerId, PeerRef},
+};
+use reqwest::Url;
+use std::{
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use teloxide::{
+    dispatching::{Dispatcher, UpdateFilterExt},
+    net::Download,
+    prelude::*,
+    types::{
+        CallbackQuery, ChatId, FileMeta, InlineKeyboardMarkup, InlineQuery, InlineQueryResult,
+        InlineQueryResultCachedDocument, InputFile, InputMedia, InputMediaDocument,
+        InputMediaPhoto, LabeledPrice, MediaKind, Message, MessageEntityKind, MessageId,
+        MessageKind, ParseMode, PreCheckoutQuery, SuccessfulPayment, Update, UserId,
+    },
+    update_listeners::webhooks,
+    utils::command::BotCommands,
+};
+use tokio::{fs, task, task::JoinHandle};
+use tracing::Instrument;
+
+use crate::actions::{ResultAction, ResultCallback};
+use crate::album::{ALBUM_COLLECT_WINDOW, AlbumCollector};
+use crate::batch::BatchCollector;
+use crate::broadcast::KnownChats;
+use crate::canvas::Typeface;
+use crate::card::{CardContent, Theme};
+use crate::commands::Command;
+use crate::compare::{ComparePairings, PairingStep};
+use crate::config::{
+    Config, ExtraBot, UpdateMode, database_path_from_env, history_path_from_env,
+    session_path_from_env, settings_path_from_env, stats_path_from_env, tagged_path,
+};
+use crate::crypto::{CacheReader, CacheWriter, Key, SessionFile};
+use crate::error_report::{ErrorReports, Failure};
+use crate::exif::{CaptionOptions, ExifSummary, ParsedExif, ShotFacts};
+use crate::export::ExportFormat;
+use crate::geo::Lookups;
+use crate::geo::ratelimit::RateLimiter;
+use crate::health::Health;
+use crate::history::{HistoryEntry, HistoryStore};
+use crate::janitor::{self, Janitor};
+use crate::locale::locale_from_language_code;
+use crate::metrics::Metric;
+use crate::moderation::{Access, Moderation};
+#[cfg(feature = "ocr")]
+use crate::ocr::OcrProvider;
+use crate::premium::{Offer, Premium, STARS_CURRENCY};
+use crate::queue::{Turn, WorkQueue};
+use crate::quota::{Admission, Quota};
+use crate::recent::{RecentResult, RecentResults};
+use crate::remote::{ProbeError, REMOTE_FETCH_TIMEOUT};
+use crate::replies::{ReplyIndex, TrackedReply};
+use crate::settings::{
+    CoordinatePrecision, GpsPrivacy, MAX_PRIVATE_ZONES, PrivateZone, SettingsStore, TriggerMode,
+    ZoneMode,
+};
+use crate::shutdown::{DRAIN_TIMEOUT, InFlight};
+use crate::state::AppState;
+use crate::staticmap::StaticMapProvider;
+use crate::stats::StatsStore;
+use crate::storage::Storage;
+use crate::strip::{Categories, Removal, StripError};
+use crate::styles::{CaptionStyle, LabelStyle};
+use crate::telegraph::Telegraph;
+use crate::watermark::WatermarkOptions;
+use crate::{
+    actions, card, compare, exif, export, frame, geo, hashtags, history, markup, metrics, premium,
+    qr, remote, resend_help, retry, stats, strip, styles, telemetry, watermark,
+};
+
+const MAX_INLINE_SIZE: u64 = 20 * 1024 * 1024; // 20 MB telegram download limit.
+/// What a local Bot API server serves instead.
+const LOCAL_SERVER_SIZE_LIMIT: u64 = 2000 * 1024 * 1024;
+/// Download progress is edited into its status message at most this often.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(3);
+/// Files with no EXIF data in their header are downloaded whole only up to this size.
+const MAX_FULL_DOWNLOAD: u64 = 200 * 1024 * 1024;
+/// How long a replica's claim on a message keeps the others from answering it too.
+const UPDATE_CLAIM_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// The secondary client's requests are spaced at least this far apart.
+const SECONDARY_INTERVAL: Duration = Duration::from_millis(100);
+/// How often a flood wait of the secondary client is waited out before giving up.
+const MAX_FLOOD_RETRIES: u32 = 3;
+/// Longer flood waits fail the request rather than keep the user waiting.
+const MAX_FLOOD_WAIT: Duration = Duration::from_secs(120);
+
+/// Shared by every task, so a flood wait holds back all of the secondary client's requests.
+static SECONDARY_LIMITER: LazyLock<RateLimiter> =
+    LazyLock::new(|| RateLimiter::new(SECONDARY_INTERVAL));
+const TELEGRAM_CAPTION_LIMIT: usize = 1024;
+const CAPTION_LIMIT: usize = 1000; // stay below Telegram's 1024 char limit.
+const MAX_INLINE_RESULTS: usize = 50; // Telegram accepts at most 50 inline results.
+const INLINE_CACHE_SECONDS: u32 = 10;
+const TELEGRAM_MESSAGE_LIMIT: usize = 4096;
+
+enum ImageSelection {
+    Inline {
+        file_id: String,
+        unique_id: String,
+        media_kind: ReceivedImage,
+    },
+    TooLarge {
+        file_id: String,
+        unique_id: String,
+        media_kind: ReceivedImage,
+        size: u64,
+    },
+}
+
+impl ImageSelection {
+    fn unique_id(&self) -> &str {
+        match self {
+            Self::Inline { unique_id, .. } | Self::TooLarge { unique_id, .. } => unique_id,
+        }
+    }
+
+    /// How the image is read, for the logs.
+    fn path(&self) -> &'static str {
+        match self {
+            Self::Inline { .. } => "inline",
+            Self::TooLarge { .. } => "large_file",
+        }
+    }
+
+    fn media_kind(&self) -> ReceivedImage {
+        match self {
+            Self::Inline { media_kind, .. } | Self::TooLarge { media_kind, .. } => *media_kind,
+        }
+    }
+}
+
+/// Loads the configuration and answers updates until the process is stopped.
+pub async fn run() -> Result<()> {
+    tracing::info!("Starting Telegram EXIF bot...");
+
+    let config = Arc::new(Config::load()?);
+    rust_i18n::set_locale(config.default_locale);
+    let key = Key::from_env()?;
+    if key.is_some() {
+        tracing::info!("Encrypting the session and cached downloads at rest");
+    }
+    Key::install(key);
+    let telegram = crate::config::with_proxy(
+        teloxide::net::default_reqwest_settings(),
+        config.proxy.as_ref(),
+    )
+    .build()
+    .context("Failed to build the Telegram HTTP client")?;
+    #[cfg(feature = "sentry")]
+    let _sentry = crate::crash_reports::init(&config);
+    #[cfg(not(feature = "sentry"))]
+    if config.sentry_dsn.is_some() {
+        tracing::warn!("Ignoring the Sentry DSN: build with `--features sentry` to report errors");
+    }
+    if let Some(url) = &config.bot_api_url {
+        tracing::info!("Using the Bot API server at {url}");
+    }
+    crate::coordination::connect_configured(config.redis_url.as_deref()).await?;
+    crate::translations::spawn_watcher(config.locales_dir.as_deref());
+    Janitor::from_config(&config).start().await;
+
+    // One queue for every bot, so together they read no more images at once than one would,
+    // and one set of lookups, so they share their caches and rate limits.
+    let http = crate::http::client(config.proxy.as_ref());
+    let shared = Shared {
+        queue: Arc::new(WorkQueue::from_config(&config)),
+        lookups: Arc::new(Lookups::new(
+            &config.lookups,
+            &config.data_dir,
+            http.clone(),
+        )),
+        http,
+        links: remote::client(config.proxy.as_ref()),
+    };
+    let mut served = vec![start_bot(&config, &telegram, None, &shared).await?];
+    for extra in &config.extra_bots {
+        served.push(start_bot(&config, &telegram, Some(extra), &shared).await?);
+    }
+
+    if let Some(address) = config.metrics_addr {
+        let health = Health {
+            bots: served.iter().map(|served| served.bot.clone()).collect(),
+            extra_clients: served
+                .iter()
+                .filter_map(|served| served.state.extra_client.clone())
+                .collect(),
+            cache_dir: config.cache_dir.clone(),
+        };
+        telemetry::spawn_server(address, health.routes());
+    }
+
+    let mut bots = task::JoinSet::new();
+    for served in served {
+        bots.spawn(served.serve());
+    }
+    while let Some(finished) = bots.join_next().await {
+        finished.context("A bot stopped unexpectedly")??;
+    }
+    shared.lookups.flush().await;
+    tracing::info!("Stopped.");
+
+    Ok(())
+}
+
+/// What every bot the process answers as shares.
+struct Shared {
+    http: reqwest::Client,
+    links: reqwest::Client,
+    queue: Arc<WorkQueue>,
+    lookups: Arc<Lookups>,
+}
+
+/// A bot ready to answer updates, with what it takes to stop it cleanly.
+struct ServedBot {
+    bot: Bot,
+    state: Arc<AppState>,
+    secondary: Option<SecondaryClient>,
+}
+
+/// Signs in the bot of `extra`, or the primary one, and opens its stores. The primary
+/// bot's files keep their names; an extra bot's are tagged with its id.
+async fn start_bot(
+    config: &Arc<Config>,
+    telegram: &reqwest::Client,
+    extra: Option<&ExtraBot>,
+    shared: &Shared,
+) -> Result<ServedBot> {
+    let token = extra.map_or(&config.bot_token, |extra| &extra.token);
+    let mut bot = Bot::with_client(token.clone(), telegram.clone());
+    if let Some(url) = &config.bot_api_url {
+        bot = bot.set_api_url(url.clone());
+    }
+    let me = bot
+        .get_me()
+        .await
+        .context("Failed to fetch bot account information")?;
+    let bot_id = me.id;
+    let tag = extra.map(|_| bot_id);
+    let secondary = init_extra_client(
+        config,
+        token,
+        tagged_path(session_path_from_env(&config.data_dir)?, tag),
+    )
+    .await?;
+    let extra_client = secondary.as_ref().map(|secondary| secondary.client.clone());
+    let storage = Storage::open(&tagged_path(database_path_from_env(&config.data_dir)?, tag))?;
+    metrics::spawn_flusher(storage.clone());
+    // An extra bot's own quota replaces the shared one; 0 means no limit.
+    let quota = |configured: Option<u32>, shared: Option<u32>| {
+        configured.or(shared).filter(|limit| *limit > 0)
+    };
+    tracing::info!("Answering as @{}", me.username());
+    let state = Arc::new(AppState {
+        me,
+        extra_client,
+        recent: RecentResults::new(),
+        settings: SettingsStore::open(
+            storage.clone(),
+            &tagged_path(settings_path_from_env(&config.data_dir)?, tag),
+        )?,
+        moderation: Moderation::open(storage.clone())?,
+        known_chats: KnownChats::new(storage.clone()),
+        quota: Quota::new(
+            storage.clone(),
+            quota(
+                extra.and_then(|extra| extra.daily_quota),
+                config.daily_quota,
+            ),
+            quota(
+                extra.and_then(|extra| extra.premium_quota),
+                config.premium_quota,
+            ),
+        ),
+        premium: Premium::open(storage.clone(), Offer::from_config(config))?,
+        storage,
+        config: Arc::clone(config),
+        stats: StatsStore::open(tagged_path(stats_path_from_env(&config.data_dir)?, tag))?,
+        history: HistoryStore::open(tagged_path(history_path_from_env(&config.data_dir)?, tag))?,
+        albums: AlbumCollector::new(bot_id),
+        replies: ReplyIndex::new(),
+        http: shared.http.clone(),
+        links: shared.links.clone(),
+        lookups: Arc::clone(&shared.lookups),
+        static_map: StaticMapProvider::from_config(config),
+        compare: ComparePairings::new(),
+        telegraph: Telegraph::from_config(config),
+        batches: BatchCollector::new(),
+        typeface: Typeface::from_config(config),
+        #[cfg(feature = "ocr")]
+        ocr: OcrProvider::from_config(config),
+        in_flight: InFlight::default(),
+        error_reports: ErrorReports::new(config.error_chat),
+        queue: Arc::clone(&shared.queue),
+    });
+
+    if let Err(err) = bot.set_my_commands(Command::bot_commands()).await {
+        tracing::warn!("Failed to register bot commands: {err:?}");
+    }
+    Ok(ServedBot {
+        bot,
+        state,
+        secondary,
+    })
+}
+
+impl ServedBot {
+    /// Answers updates until the process is stopped, then finishes the images in flight.
+    async fn serve(self) -> Result<()> {
+        let Self {
+            bot,
+            state,
+            secondary,
+        } = self;
+        let in_flight = state.in_flight.clone();
+        let storage = state.storage.clone();
+        let config = Arc::clone(&state.config);
+
+        let handler = dptree::entry()
+            .branch(Update::filter_message().endpoint(spawn_message_handler))
+            .branch(Update::filter_edited_message().endpoint(spawn_edited_message_handler))
+            .branch(Update::filter_channel_post().endpoint(spawn_channel_post_handler))
+            .branch(Update::filter_inline_query().endpoint(handle_inline_query))
+            .branch(Update::filter_callback_query().endpoint(handle_callback_query))
+            .branch(Update::filter_pre_checkout_query().endpoint(handle_pre_checkout_query));
+
+        let mut dispatcher = Dispatcher::builder(bot.clone(), handler)
+            .dependencies(dptree::deps![state])
+            .build();
+
+        let shutdown = dispatcher.shutdown_token();
+        tokio::spawn(async move {
+            crate::shutdown::signal().await;
+            tracing::info!("Shutting down, no longer accepting updates...");
+            if let Ok(stopped) = shutdown.shutdown() {
+                stopped.await;
+            }
+        });
+
+        match &config.mode {
+            UpdateMode::Polling => dispatcher.dispatch().await,
+            UpdateMode::Webhook(webhook) => {
+                let mut options = webhooks::Options::new(webhook.address, webhook.url.clone());
+                if let Some(path) = &webhook.path {
+                    options = options.path(path.clone());
+                }
+                if let Some(secret) = &webhook.secret_token {
+                    options = options.secret_token(secret.clone());
+                }
+                tracing::info!("Listening for webhook updates on {}", webhook.address);
+                let listener = webhooks::axum(bot, options)
+                    .await
+                    .context("Failed to set up the webhook")?;
+                dispatcher
+                    .dispatch_with_listener(
+                        listener,
+                        LoggingErrorHandler::with_custom_text("An error from the webhook listener"),
+                    )
+                    .await;
+            }
+        }
+
+        let running = in_flight.running();
+        if running > 0 {
+            tracing::info!("Waiting for {running} images still being processed...");
+        }
+        if !in_flight.drain(DRAIN_TIMEOUT).await {
+            tracing::warn!(
+                "Stopping with {} images still being processed",
+                in_flight.running()
+            );
+        }
+        if let Err(err) = metrics::flush(&storage).await {
+            tracing::warn!("Failed to store usage metrics: {err:?}");
+        }
+        // Lets the runner close the connection and save the session before exiting.
+        if let Some(secondary) = secondary {
+            secondary.pool.quit();
+            if let Err(err) = secondary.runner.await {
+                tracing::warn!("Grammers sender runner failed: {err:?}");
+            }
+            if let Err(err) = secondary.session.close() {
+                tracing::error!("Failed to seal the session: {err:?}");
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn spawn_message_handler(
+    bot: Bot,
+    state: Arc<AppState>,
+    msg: Message,
+) -> Result<(), teloxide::RequestError> {
+    // Detach the heavy handler so the dispatcher keeps polling new updates.
+    let in_flight = state.in_flight.clone();
+    let span = update_span("message", &msg, &state.config);
+    in_flight.spawn(
+        async move {
+            if !claim_update(&state, &msg).await {
+                tracing::debug!("Another replica answers this message");
+                return;
+            }
+            if let Err(err) = handle_message(bot, state, msg).await {
+                telemetry::telegram_error(&err);
+                tracing::error!("Failed to process update concurrently: {err:?}");
+            }
+        }
+        .instrument(span),
+    );
+
+    respond(())
+}
+
+/// The span the logs of one update are grouped under, so the lines of concurrent
+/// downloads can be told apart.
+fn update_span(kind: &'static str, msg: &Message, config: &Config) -> tracing::Span {
+    let file_unique_id =
+        image_file_id(msg, config).map(|selection| selection.unique_id().to_owned());
+    tracing::info_span!(
+        "update",
+        kind = kind,
+        chat_id = msg.chat.id.0,
+        message_id = msg.id.0,
+        file_unique_id = file_unique_id.as_deref()
+    )
+}
+
+/// Whether this replica answers the image in `msg`; replicas coordinating through Redis
+/// leave a message to whichever claimed it first. Messages without an image are answered
+/// by every replica that gets them.
+async fn claim_update(state: &AppState, msg: &Message) -> bool {
+    let Some(selection) = image_file_id(msg, &state.config) else {
+        return true;
+    };
+    let name = format!(
+        "update:{}:{}:{}:{}",
+        state.me.id,
+        msg.chat.id,
+        msg.id.0,
+        selection.unique_id()
+    );
+    crate::coordination::claim(&name, UPDATE_CLAIM_TTL).await
+}
+
+async fn handle_message(
+    bot: Bot,
+    state: Arc<AppState>,
+    msg: Message,
+) -> Result<(), teloxide::RequestError> {
+    telemetry::message_handled();
+    let chat_id = msg.chat.id;
+    let reply = ReplyTarget::for_message(&msg);
+    let user_language = msg.from().and_then(|user| user.language_code.clone());
+    let locale = locale_from_language_code(user_language.as_deref());
+
+    tracing::info!(
+        "username {}, language {}",
+        msg.chat.username().unwrap_or("<unknown>"),
+        user_language.as_deref().unwrap_or("<unknown>")
+    );
+
+    // A payment that went through is honoured even if the payer lost access meanwhile.
+    if let Some(payment) = msg.successful_payment() {
+        return handle_successful_payment(&bot, &state, &msg, payment, locale).await;
+    }
+
+    if let Some(user) = msg.from()
+        && !may_use_bot(&state, user.id)
+    {
+        tracing::info!("Ignoring message from user {} without access", user.id.0);
+        // Banned users are not told; others learn why the bot stays quiet.
+        if msg.chat.is_private() && !state.moderation.is_blocked(user.id) {
+            send_text(
+                &bot,
+                reply,
+                rust_i18n::t!("access.private_bot", locale = locale),
+            )
+            .await?;
+        }
+        return Ok(());
+    }
+    state.known_chats.note(chat_id).await;
+
+    // A document captioned with a command counts as that command, too.
+    let command = msg
+        .text()
+        .or_else(|| msg.caption())
+        .and_then(|text| Command::parse(text, state.me.username()).ok());
+
+    if let Some(Command::Trigger(mode)) = &command {
+        return handle_trigger_command(&bot, &state.settings, &msg, mode, locale).await;
+    }
+
+    if let Some(Command::ChatStyle(style)) = &command {
+        return handle_chat_style_command(&bot, &state.settings, &msg, style, locale).await;
+    }
+
+    if let Some(Command::ChatPrivacy(mode)) = &command {
+        return handle_chat_privacy_command(&bot, &state.settings, &msg, mode, locale).await;
+    }
+
+    if let Some(Command::Privacy(mode)) = &command {
+        return handle_privacy_command(&bot, &state.settings, &msg, mode, locale).await;
+    }
+
+    if let Some(Command::Precision(precision)) = &command {
+        return handle_precision_command(&bot, &state.settings, &msg, precision, locale).await;
+    }
+
+    if let Some(Command::Style(style)) = &command {
+        return handle_style_command(&bot, &state.settings, &msg, style, locale).await;
+    }
+
+    if let Some(Command::Labels(labels)) = &command {
+        return handle_labels_command(&bot, &state.settings, &msg, labels, locale).await;
+    }
+
+    if let Some(Command::Units(units)) = &command {
+        return handle_units_command(&bot, &state.settings, &msg, units, locale).await;
+    }
+
+    if let Some(Command::Ocr(toggle)) = &command {
+        return handle_ocr_command(&bot, &state, &msg, toggle, locale).await;
+    }
+
+    if let Some(Command::Nearby(toggle)) = &command {
+        return handle_nearby_command(&bot, &state.settings, &state.lookups, &msg, toggle, locale)
+            .await;
+    }
+
+    if let Some(Command::PrivateZone(args)) = &command {
+        return handle_private_zone_command(&bot, &state.settings, &msg, args, locale).await;
+    }
+
+    if let Some(Command::Sections) = &command {
+        return handle_sections_command(&bot, &state.settings, &msg, locale).await;
+    }
+
+    if let Some(Command::Compare) = &command {
+        return handle_compare_command(&bot, &state, &msg, locale).await;
+    }
+
+    if let Some(Command::Premium) = &command {
+        return handle_premium_command(&bot, &state, &msg, locale).await;
+    }
+
+    if let Some(Command::Access(args)) = &command {
+        return handle_access_command(&bot, &state, &msg, args, locale).await;
+    }
+
+    if let Some(Command::Broadcast(text)) = &command {
+        return handle_broadcast_command(&bot, &state, &msg, text, locale).await;
+    }
+
+    if let Some(Command::AdminStats) = &command {
+        return handle_admin_stats_command(&bot, &state, &msg, locale).await;
+    }
+
+    if let Some(Command::Stats) = &command {
+        return handle_stats_command(&bot, &state.stats, &msg, locale).await;
+    }
+
+    if let Some(Command::History) = &command {
+        return handle_history_command(&bot, &state.history, &state.settings, &msg, locale).await;
+    }
+
+    if let Some(Command::DeleteMyData(confirmation)) = &command {
+        return handle_delete_my_data_command(&bot, &state, &msg, confirmation, locale).await;
+    }
+
+    if let Some(Command::Gpx) = &command {
+        return handle_export_command(&bot, &state, &msg, "gpx", locale).await;
+    }
+
+    if let Some(Command::Export(format)) = &command {
+        return handle_export_command(&bot, &state, &msg, format, locale).await;
+    }
+
+    // While a `/compare` pairing runs, the user's next images belong to it.
+    if command.is_none() && image_file_id(&msg, &state.config).is_some() {
+        if let Some(user) = msg.from() {
+            if let Some(step) = state.compare.push(chat_id, user.id, msg.clone()) {
+                return continue_comparison(&bot, &state, reply, step, locale).await;
+            }
+        }
+    }
+
+    if let Some(media_group_id) = msg.media_group_id() {
+        if image_file_id(&msg, &state.config).is_some() {
+            let media_group_id = media_group_id.to_string();
+            if !state.albums.push(&media_group_id, msg.clone()).await {
+                // The handler of the album's first message replies for the whole batch.
+                return Ok(());
+            }
+
+            tokio::time::sleep(ALBUM_COLLECT_WINDOW).await;
+            let messages = state.albums.take(&media_group_id).await;
+
+            if is_group_chat(&msg)
+                && state.settings.chat(chat_id).trigger == TriggerMode::Explicit
+                && !messages
+                    .iter()
+                    .any(|item| mentions_bot(item, state.me.username()))
+            {
+                return Ok(());
+            }
+
+            let images = messages.len() as u32;
+            if !within_quota(&bot, &state, &msg, reply, images, locale).await? {
+                return Ok(());
+            }
+            return reply_with_album(
+                &bot,
+                &state,
+                &msg,
+                reply,
+                &messages,
+                user_language.as_deref(),
+            )
+            .await;
+        }
+    }
+
+    let trigger = is_group_chat(&msg).then(|| state.settings.chat(chat_id).trigger);
+
+    if command.is_none() {
+        if let Some(url) = msg.text().and_then(remote::find_image_url) {
+            // In groups links are only followed when the bot is asked explicitly.
+            if trigger.is_none() || mentions_bot(&msg, state.me.username()) {
+                if !within_quota(&bot, &state, &msg, reply, 1, locale).await? {
+                    return Ok(());
+                }
+                return reply_with_remote_exif(
+                    &bot,
+                    &state,
+                    &msg,
+                    reply,
+                    url,
+                    user_language.as_deref(),
+                )
+                .await;
+            }
+        }
+    }
+
+    let target = match resolve_target(
+        &msg,
+        command.as_ref(),
+        trigger,
+        state.me.username(),
+        &state.config,
+    ) {
+        MessageTarget::Message(target) => target,
+        MessageTarget::MissingReply => {
+            let key = match command {
+                Some(Command::Strip) => "messages.strip_reply_to_image",
+                Some(Command::Map) => "messages.map_reply_to_image",
+                Some(Command::Json) => "messages.json_reply_to_image",
+                Some(Command::Watermark(_)) => "messages.watermark_reply_to_image",
+                Some(Command::Frame) => "messages.frame_reply_to_image",
+                Some(Command::Card(_)) => "messages.card_reply_to_image",
+                _ => "messages.reply_to_image",
+            };
+            send_text(&bot, reply, rust_i18n::t!(key, locale = locale)).await?;
+            return Ok(());
+        }
+        MessageTarget::Ignore => return Ok(()),
+    };
+    if matches!(command, Some(Command::Frame))
+        && let Some(user) = msg.from()
+        && !premium_unlocked(&state, user.id)
+    {
+        send_text(
+            &bot,
+            reply,
+            rust_i18n::t!("premium.required", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+    if !within_quota(&bot, &state, &msg, reply, 1, locale).await? {
+        return Ok(());
+    }
+
+    match command {
+        Some(Command::Strip) => {
+            return reply_with_stripped(&bot, &state, reply, target, locale).await;
+        }
+        Some(Command::Map) => {
+            let options = caption_options(&state.settings, target);
+            return reply_with_location(&bot, &state, reply, target, options, locale).await;
+        }
+        Some(Command::Json) => return reply_with_json(&bot, &state, reply, target, locale).await,
+        Some(Command::Watermark(args)) => {
+            let Some(options) = WatermarkOptions::parse(&args) else {
+                send_text(
+                    &bot,
+                    reply,
+                    rust_i18n::t!("watermark.usage", locale = locale),
+                )
+                .await?;
+                return Ok(());
+            };
+            let rendering = Rendering::Watermark(options);
+            return reply_with_rendering(&bot, &state, reply, target, rendering, locale).await;
+        }
+        Some(Command::Frame) => {
+            return reply_with_rendering(&bot, &state, reply, target, Rendering::Frame, locale)
+                .await;
+        }
+        Some(Command::Card(theme)) => {
+            let Some(theme) = Theme::parse(&theme) else {
+                send_text(&bot, reply, rust_i18n::t!("card.usage", locale = locale)).await?;
+                return Ok(());
+            };
+            let options = caption_options(&state.settings, target);
+            return reply_with_card(&bot, &state, reply, target, theme, options, locale).await;
+        }
+        _ => {}
+    }
+
+    reply_with_exif(&bot, &state, &msg, reply, target, user_language.as_deref()).await
+}
+
+/// Runs the EXIF pipeline for `target` and answers in the chat `request` came from.
+async fn reply_with_exif(
+    bot: &Bot,
+    state: &AppState,
+    request: &Message,
+    reply: ReplyTarget,
+    target: &Message,
+    user_language: Option<&str>,
+) -> Result<(), teloxide::RequestError> {
+    let locale = locale_from_language_code(user_language);
+
+    // The caption goes out under the settings of whoever sent the image, also when
+    // someone else asked for it with `/exif`.
+    let options = caption_options(&state.settings, target);
+    let Some(processing_result) = analyze_message(bot, state, target, user_language, options).await
+    else {
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.request_image", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    // Quote the image itself, also when a `/exif` reply asked for it.
+    let reply = reply.replying_to(target.id);
+    let delivery_result = match processing_result {
+        Ok(analyzed) => {
+            remember_result(&state.recent, request, &analyzed);
+            record_usage(
+                state,
+                request,
+                &analyzed.facts,
+                analyzed.gps,
+                target.url().map(String::from),
+            );
+            match send_caption_for_media(
+                bot,
+                reply,
+                &analyzed.file_id,
+                analyzed.media_kind,
+                analyzed.caption.clone(),
+                locale,
+            )
+            .await
+            {
+                Ok(sent) => {
+                    track_reply(state, &analyzed, &sent);
+                    send_continuation(bot, reply, &sent, &analyzed.continuation).await;
+                    if let Some((latitude, longitude)) = analyzed.gps {
+                        let spoiler = options.spoiler_location;
+                        send_static_map(bot, state, reply, &sent, latitude, longitude, spoiler)
+                            .await;
+                    }
+                    Ok(())
+                }
+                Err(err) => Err(err),
+            }
+        }
+        Err(err) => Err(err),
+    };
+
+    if let Err(err) = delivery_result {
+        tracing::error!("Failed to process image: {err:?}");
+        // A compressed photo without any metadata left is expected, not a failure.
+        if is_compressed_photo(target, &state.config) {
+            let request = reply.apply(
+                bot.send_message(
+                    reply.chat_id,
+                    rust_i18n::t!("messages.resend_document", locale = locale),
+                )
+                .reply_markup(resend_help::keyboard(locale)),
+            );
+            request.await?;
+        } else {
+            send_text(
+                bot,
+                reply,
+                rust_i18n::t!("messages.process_error", locale = locale),
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Streams the EXIF data of an image behind a direct URL and answers with the caption.
+async fn reply_with_remote_exif(
+    bot: &Bot,
+    state: &AppState,
+    request: &Message,
+    reply: ReplyTarget,
+    url: Url,
+    user_language: Option<&str>,
+) -> Result<(), teloxide::RequestError> {
+    let locale = locale_from_language_code(user_language);
+
+    let options = caption_options(&state.settings, request);
+    let link = url.to_string();
+    // Links wait in the chat's queue like uploads, so they add nothing to the images read
+    // at once.
+    let processed = {
+        let _turn = wait_turn(bot, state, request, user_language).await;
+        process_remote_image(state, url, user_language, options).await
+    };
+    match processed {
+        Ok(summary) => {
+            metrics::record(Metric::Processed);
+            record_usage(state, request, &summary.facts, summary.gps, Some(link));
+            let sent = send_html(bot, reply, summary.caption).await?;
+            if let Some((latitude, longitude)) = summary.gps {
+                let spoiler = options.spoiler_location;
+                send_static_map(bot, state, reply, &sent, latitude, longitude, spoiler).await;
+            }
+        }
+        Err(err) => {
+            tracing::warn!("Failed to process image URL: {err:?}");
+            metrics::record(Metric::Failed);
+            let key = match err.downcast_ref::<ProbeError>() {
+                Some(ProbeError::NotImage) => "messages.url_not_image",
+                Some(ProbeError::TooLarge(_)) => "messages.url_too_large",
+                Some(ProbeError::Blocked) => "messages.url_blocked",
+                _ => "messages.process_error",
+            };
+            send_text(bot, reply, rust_i18n::t!(key, locale = locale)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Answers an album with one media group carrying every image's caption.
+async fn reply_with_album(
+    bot: &Bot,
+    state: &AppState,
+    request: &Message,
+    reply: ReplyTarget,
+    messages: &[Message],
+    user_language: Option<&str>,
+) -> Result<(), teloxide::RequestError> {
+    let locale = locale_from_language_code(user_language);
+    let mut analyzed_images = Vec::with_capacity(messages.len());
+    let mut failed = false;
+    let options = caption_options(&state.settings, request);
+    // Quote the album's first image; Telegram shows the whole album above the reply.
+    let reply = messages
+        .first()
+        .map_or(reply, |first| reply.replying_to(first.id));
+
+    // Process sequentially so one album cannot start a burst of parallel downloads.
+    for item in messages {
+        match analyze_message(bot, state, item, user_language, options).await {
+            Some(Ok(analyzed)) => {
+                remember_result(&state.recent, request, &analyzed);
+                record_usage(
+                    state,
+                    request,
+                    &analyzed.facts,
+                    analyzed.gps,
+                    item.url().map(String::from),
+                );
+                analyzed_images.push(analyzed);
+            }
+            Some(Err(err)) => {
+                tracing::error!("Failed to process album image {}: {err:?}", item.id.0);
+                failed = true;
+            }
+            None => {}
+        }
+    }
+
+    let delivery_result = match analyzed_images.len() {
+        0 => Ok(()),
+        1 => {
+            let analyzed = &analyzed_images[0];
+            match send_caption_for_media(
+                bot,
+                reply,
+                &analyzed.file_id,
+                analyzed.media_kind,
+                analyzed.caption.clone(),
+                locale,
+            )
+            .await
+            {
+                Ok(sent) => {
+                    track_reply(state, analyzed, &sent);
+                    send_continuation(bot, reply, &sent, &analyzed.continuation).await;
+                    Ok(())
+                }
+                Err(err) => Err(err),
+            }
+        }
+        _ => match send_album_captions(bot, reply, &analyzed_images).await {
+            Ok(sent) => {
+                // Telegram returns the album's messages in the order they were sent.
+                for (analyzed, sent) in analyzed_images.iter().zip(&sent) {
+                    track_reply(state, analyzed, sent);
+                    send_continuation(bot, reply, sent, &analyzed.continuation).await;
+                }
+                Ok(())
+            }
+            Err(err) => Err(err),
+        },
+    };
+
+    match delivery_result {
+        Ok(()) if analyzed_images.len() > 1 => offer_csv_export(bot, reply, locale).await,
+        Ok(()) => {}
+        Err(err) => {
+            tracing::error!("Failed to send album results: {err:?}");
+            failed = true;
+        }
+    }
+
+    if failed {
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.process_error", locale = locale),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Counts the image towards the requesting user's `/stats`, adds it to their `/history`
+/// and to the batch `/export` writes out.
+///
+/// The batch gets `gps`, the position the caption may show, so an export never reveals
+/// a location the user's privacy settings hide.
+fn record_usage(
+    state: &AppState,
+    request: &Message,
+    facts: &ShotFacts,
+    gps: Option<(f64, f64)>,
+    link: Option<String>,
+) {
+    let Some(user) = request.from() else {
+        return;
+    };
+
+    let exported = ShotFacts {
+        gps,
+        ..facts.clone()
+    };
+    state.batches.push(request.chat.id, user.id, exported);
+
+    if let Err(err) = state.stats.record(user.id, facts) {
+        tracing::warn!("Failed to record stats for user {}: {err:?}", user.id.0);
+    }
+
+    let entry = HistoryEntry {
+        date: request.date.format("%Y-%m-%d %H:%M UTC").to_string(),
+        camera: facts.camera.clone(),
+        taken: facts.datetime.clone(),
+        link,
+    };
+    if let Err(err) = state.history.record(user.id, entry) {
+        tracing::warn!("Failed to record history for user {}: {err:?}", user.id.0);
+    }
+}
+
+/// Media groups cannot carry buttons, so the CSV download follows in its own message.
+async fn offer_csv_export(bot: &Bot, reply: ReplyTarget, locale: &str) {
+    let request = reply.apply(
+        bot.send_message(
+            reply.chat_id,
+            rust_i18n::t!("export.csv_offer", locale = locale),
+        )
+        .reply_markup(export::csv_keyboard(locale)),
+    );
+    if let Err(err) = request.await {
+        tracing::warn!("Failed to offer CSV export: {err:?}");
+    }
+}
+
+fn remember_result(recent: &RecentResults, request: &Message, analyzed: &AnalyzedImage) {
+    // Inline mode answers with cached documents, which photo file ids cannot fill.
+    if analyzed.media_kind == ReceivedImage::Photo {
+        return;
+    }
+
+    if let Some(user) = request.from() {
+        recent.record(
+            user.id,
+            RecentResult {
+                file_id: analyzed.file_id.clone(),
+                file_unique_id: analyzed.unique_id.clone(),
+                caption: analyzed.caption.clone(),
+            },
+        );
+    }
+}
+
+/// Compressed photos keep little or no EXIF data, so point users to sending a file.
+fn with_compressed_photo_note(caption: String, user_language: Option<&str>) -> String {
+    let locale = locale_from_language_code(user_language);
+    let note = rust_i18n::t!("messages.compressed_photo_note", locale = locale);
+    format!("{caption}\n\n⚠️ {}", markup::escape(&note))
+}
+
+/// Replies to `result` with a map thumbnail of the point, if a provider is configured.
+/// With `spoiler`, as for a caption that hides its location in a spoiler, the map is
+/// blurred too and goes without the map links.
+///
+/// Failures are only logged: the caption already went out and the map is optional.
+async fn send_static_map(
+    bot: &Bot,
+    state: &AppState,
+    reply: ReplyTarget,
+    result: &Message,
+    latitude: f64,
+    longitude: f64,
+    spoiler: bool,
+) {
+    let Some(provider) = &state.static_map else {
+        return;
+    };
+
+    let image = match provider.fetch(&state.http, latitude, longitude).await {
+        Ok(image) => image,
+        Err(err) => {
+            tracing::warn!("Skipping static map: {err:?}");
+            return;
+        }
+    };
+
+    let mut photo = bot
+        .send_photo(reply.chat_id, InputFile::memory(image).file_name("map.png"))
+        .has_spoiler(spoiler);
+    if !spoiler {
+        photo = photo.reply_markup(actions::map_links_keyboard(latitude, longitude));
+    }
+    let request = reply.replying_to(result.id).apply(photo);
+    if let Err(err) = request.await {
+        tracing::warn!("Failed to send static map: {err:?}");
+    }
+}
+
+/// Caption options chosen by the user who sent `msg`, under what the chat's admins set
+/// for the chat.
+fn caption_options(settings: &SettingsStore, msg: &Message) -> CaptionOptions {
+    let user = msg
+        .from()
+        .map(|user| settings.user(user.id))
+        .unwrap_or_default();
+    settings.chat(msg.chat.id).apply(user).caption_options()
+}
+
+/// Waits for the turn of `target` in its chat's queue, telling the chat the position
+/// while it waits.
+async fn wait_turn(
+    bot: &Bot,
+    state: &AppState,
+    target: &Message,
+    user_language: Option<&str>,
+) -> Turn {
+    let ticket = state.queue.enter(target.chat.id);
+    let Some(position) = ticket.position() else {
+        return ticket.turn().await;
+    };
+
+    tracing::info!("Queued at position {position}");
+    let locale = locale_from_language_code(user_language);
+    let status = send_text(
+        bot,
+        ReplyTarget::for_message(target),
+        rust_i18n::t!("messages.queued", locale = locale, position = position),
+    )
+    .await;
+    let turn = ticket.turn().await;
+    match status {
+        Ok(status) => {
+            if let Err(err) = bot.delete_message(status.chat.id, status.id).await {
+                tracing::warn!("Failed to delete the queue status message: {err:?}");
+            }
+        }
+        Err(err) => tracing::warn!("Failed to send the queue status message: {err:?}"),
+    }
+    turn
+}
+
+/// Remembers the bot's answer so an edit of the source message can update it.
+fn track_reply(state: &AppState, analyzed: &AnalyzedImage, sent: &Message) {
+    state.replies.record(
+        sent.chat.id,
+        analyzed.source_id,
+        TrackedReply {
+            reply_id: sent.id,
+            file_unique_id: analyzed.unique_id.clone(),
+        },
+    );
+}
+
+/// An image document together with the caption generated for it.
+struct AnalyzedImage {
+    source_id: MessageId,
+    file_id: String,
+    unique_id: String,
+    media_kind: ReceivedImage,
+    caption: String,
+    /// The rest of a report too long for the caption, sent as follow-up messages.
+    continuation: Vec<String>,
+    /// GPS position to show on a map, unless the user's privacy settings hide it.
+    gps: Option<(f64, f64)>,
+    facts: ShotFacts,
+}
+
+/// Extracts the EXIF caption for the image document in `target`.
+///
+/// Returns `None` when the message carries no image document.
+async fn analyze_message(
+    bot: &Bot,
+    state: &AppState,
+    target: &Message,
+    user_language: Option<&str>,
+    options: CaptionOptions,
+) -> Option<Result<AnalyzedImage>> {
+    let selection = image_file_id(target, &state.config)?;
+    let chat_id = target.chat.id;
+    let message_id = target.id.0;
+    let username = target.chat.username();
+    // The target can be another message than the update's, e.g. for `/exif` replies.
+    let span = tracing::info_span!(
+        "image",
+        message_id,
+        file_unique_id = selection.unique_id(),
+        path = selection.path()
+    );
+    let _turn = wait_turn(bot, state, target, user_language).await;
+    let path = selection.path();
+    let size = match &selection {
+        ImageSelection::Inline { .. } => None,
+        ImageSelection::TooLarge { size, .. } => Some(*size),
+    };
+
+    let (file_id, unique_id, media_kind, processing) = match selection {
+        ImageSelection::Inline {
+            file_id,
+            unique_id,
+            media_kind,
+        } => {
+            let processing = process_image(bot, &state.lookups, &file_id, user_language, options)
+                .instrument(span)
+                .await;
+            (file_id, unique_id, media_kind, processing)
+        }
+        ImageSelection::TooLarge {
+            file_id,
+            unique_id,
+            media_kind,
+            size,
+        } => {
+            tracing::info!(
+                "Image is {size} bytes (> {}) – using secondary client download",
+                inline_size_limit(&state.config)
+            );
+            metrics::record(Metric::LargeFile);
+            telemetry::large_file();
+            let file = LargeFile {
+                chat_id,
+                message_id,
+                media_kind,
+                username,
+                size,
+                cache_dir: &state.config.cache_dir,
+            };
+            let locale = locale_from_language_code(user_language);
+            let mut progress =
+                Progress::start(bot, ReplyTarget::for_message(target), size, locale).await;
+            let processing =
+                process_large_image(state, &file, &mut progress, user_language, options)
+                    .instrument(span)
+                    .await;
+            progress.finish().await;
+            (file_id, unique_id, media_kind, processing)
+        }
+    };
+
+    let summary = match processing {
+        Ok(summary) => summary,
+        Err(err) => {
+            metrics::record(Metric::Failed);
+            let document = target.document();
+            let failure = Failure {
+                path,
+                chat_id,
+                message_id,
+                user_id: target.from().map(|user| user.id),
+                file_id: &file_id,
+                file_unique_id: &unique_id,
+                size,
+                file_name: document.and_then(|document| document.file_name.as_deref()),
+                mime_type: document
+                    .and_then(|document| document.mime_type.as_ref())
+                    .map(ToString::to_string),
+                error: &err,
+            };
+            state.error_reports.send(bot, failure).await;
+            return Some(Err(err));
+        }
+    };
+    metrics::record(Metric::Processed);
+    let caption = match media_kind {
+        ReceivedImage::Document => summary.caption,
+        ReceivedImage::Photo => with_compressed_photo_note(summary.caption, user_language),
+    };
+    let caption = match detect_text(bot, state, target, options).await {
+        Some(text) => with_detected_text(caption, &text, user_language),
+        None => caption,
+    };
+    let (caption, continuation) =
+        fit_caption(state, caption, &summary.facts.camera, user_language).await;
+
+    Some(Ok(AnalyzedImage {
+        source_id: target.id,
+        file_id,
+        unique_id,
+        media_kind,
+        caption,
+        continuation,
+        gps: summary.gps,
+        facts: summary.facts,
+    }))
+}
+
+/// Recognizes the text in `target`'s image for users who opted in with `/ocr on`.
+///
+/// Only images the Bot API hands out in full are read, and failures just leave the text out.
+#[cfg(feature = "ocr")]
+async fn detect_text(
+    bot: &Bot,
+    state: &AppState,
+    target: &Message,
+    options: CaptionOptions,
+) -> Option<String> {
+    if !options.detect_text {
+        return None;
+    }
+    let ocr = state.ocr.as_ref()?;
+    let Some(ImageSelection::Inline { file_id, .. }) = image_file_id(target, &state.config) else {
+        return None;
+    };
+
+    let recognized = async {
+        let image = download_telegram_file(bot, &file_id)
+            .await
+            .context("Failed to download image for OCR")?;
+        ocr.recognize(&state.http, image).await
+    }
+    .await;
+    match recognized {
+        Ok(text) if !text.is_empty() => Some(text),
+        Ok(_) => None,
+        Err(err) => {
+            tracing::warn!("Failed to recognize text: {err:?}");
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "ocr"))]
+async fn detect_text(
+    _bot: &Bot,
+    _state: &AppState,
+    _target: &Message,
+    _options: CaptionOptions,
+) -> Option<String> {
+    None
+}
+
+#[cfg(feature = "ocr")]
+fn ocr_available(state: &AppState) -> bool {
+    state.ocr.is_some()
+}
+
+#[cfg(not(feature = "ocr"))]
+fn ocr_available(_state: &AppState) -> bool {
+    false
+}
+
+fn with_detected_text(caption: String, text: &str, user_language: Option<&str>) -> String {
+    let locale = locale_from_language_code(user_language);
+    let heading = rust_i18n::t!("ocr.heading", locale = locale);
+    let lines: Vec<String> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(markup::escape)
+        .collect();
+    format!(
+        "{caption}\n\n🔤 {}\n{}",
+        markup::bold(&heading),
+        lines.join("\n")
+    )
+}
+
+/// Keeps a caption within Telegram's limit. An overlong one is published in full on
+/// Telegraph and linked; without Telegraph the caption keeps the first lines and the
+/// rest comes back as follow-up messages.
+async fn fit_caption(
+    state: &AppState,
+    caption: String,
+    title: &str,
+    user_language: Option<&str>,
+) -> (String, Vec<String>) {
+    if caption.len() <= CAPTION_LIMIT {
+        return (caption, Vec::new());
+    }
+
+    if let Some(telegraph) = &state.telegraph {
+        match telegraph
+            .publish(&state.http, title, &markup::to_plain(&caption))
+            .await
+        {
+            Ok(url) => {
+                let locale = locale_from_language_code(user_language);
+                let label = rust_i18n::t!("messages.full_report", locale = locale);
+                let link = format!("\n\n📄 {}", markup::link(&label, &url));
+                let mut caption =
+                    markup::truncate(&caption, CAPTION_LIMIT.saturating_sub(link.len()));
+                caption.push_str(&link);
+                return (caption, Vec::new());
+            }
+            Err(err) => tracing::warn!("Failed to publish report to Telegraph: {err:?}"),
+        }
+    }
+
+    let mut parts = markup::split(&caption, CAPTION_LIMIT, TELEGRAM_MESSAGE_LIMIT).into_iter();
+    let caption = parts.next().unwrap_or_default();
+    (caption, parts.collect())
+}
+
+async fn spawn_edited_message_handler(
+    bot: Bot,
+    state: Arc<AppState>,
+    msg: Message,
+) -> Result<(), teloxide::RequestError> {
+    let in_flight = state.in_flight.clone();
+    let span = update_span("edited_message", &msg, &state.config);
+    in_flight.spawn(
+        async move {
+            if let Err(err) = handle_edited_message(bot, state, msg).await {
+                telemetry::telegram_error(&err);
+                tracing::error!("Failed to process edited message: {err:?}");
+            }
+        }
+        .instrument(span),
+    );
+
+    respond(())
+}
+
+/// Re-runs the pipeline when a user swaps the document of a message the bot answered,
+/// and edits the earlier answer in place.
+async fn handle_edited_message(
+    bot: Bot,
+    state: Arc<AppState>,
+    msg: Message,
+) -> Result<(), teloxide::RequestError> {
+    if msg.from().is_some_and(|user| !may_use_bot(&state, user.id)) {
+        return Ok(());
+    }
+    let Some(tracked) = state.replies.get(msg.chat.id, msg.id) else {
+        return Ok(());
+    };
+
+    let selection = image_file_id(&msg, &state.config);
+    let unique_id = selection.as_ref().map(ImageSelection::unique_id);
+    if unique_id == Some(tracked.file_unique_id.as_str()) {
+        // Only the caption text changed; the EXIF data is still the same.
+        return Ok(());
+    }
+
+    let user_language = msg.from().and_then(|user| user.language_code.clone());
+    let Some(processing_result) = analyze_message(
+        &bot,
+        &state,
+        &msg,
+        user_language.as_deref(),
+        caption_options(&state.settings, &msg),
+    )
+    .await
+    else {
+        return Ok(());
+    };
+
+    let analyzed = match processing_result {
+        Ok(analyzed) => analyzed,
+        Err(err) => {
+            tracing::error!("Failed to process edited image {}: {err:?}", msg.id.0);
+            let locale = locale_from_language_code(user_language.as_deref());
+            bot.edit_message_caption(msg.chat.id, tracked.reply_id)
+                .caption(rust_i18n::t!("messages.process_error", locale = locale))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    remember_result(&state.recent, &msg, &analyzed);
+
+    // Editing the media drops the keyboard unless it is sent again.
+    let locale = locale_from_language_code(user_language.as_deref());
+    let edited = bot
+        .edit_message_media(msg.chat.id, tracked.reply_id, input_media_for(&analyzed))
+        .reply_markup(result_keyboard(analyzed.media_kind, locale))
+        .await?;
+    track_reply(&state, &analyzed, &edited);
+
+    Ok(())
+}
+
+async fn spawn_channel_post_handler(
+    bot: Bot,
+    state: Arc<AppState>,
+    post: Message,
+) -> Result<(), teloxide::RequestError> {
+    let in_flight = state.in_flight.clone();
+    let span = update_span("channel_post", &post, &state.config);
+    in_flight.spawn(
+        async move {
+            if let Err(err) = handle_channel_post(bot, state, post).await {
+                telemetry::telegram_error(&err);
+                tracing::error!("Failed to process channel post: {err:?}");
+            }
+        }
+        .instrument(span),
+    );
+
+    respond(())
+}
+
+/// Appends the EXIF caption to image documents posted in channels the bot administers.
+///
+/// Falls back to replying with a separate post when the combined caption would be too
+/// long or the bot lacks the right to edit other admins' posts.
+async fn handle_channel_post(
+    bot: Bot,
+    state: Arc<AppState>,
+    post: Message,
+) -> Result<(), teloxide::RequestError> {
+    let command = post
+        .text()
+        .and_then(|text| Command::parse(text, state.me.username()).ok());
+    let locale = locale_from_language_code(None);
+    state.known_chats.note(post.chat.id).await;
+    if let Some(Command::ChatStyle(style)) = &command {
+        return handle_chat_style_command(&bot, &state.settings, &post, style, locale).await;
+    }
+    if let Some(Command::ChatPrivacy(mode)) = &command {
+        return handle_chat_privacy_command(&bot, &state.settings, &post, mode, locale).await;
+    }
+
+    if !is_image_document(&post, &state.config) {
+        return Ok(());
+    }
+
+    let options = caption_options(&state.settings, &post);
+    let Some(processing_result) = analyze_message(&bot, &state, &post, None, options).await else {
+        return Ok(());
+    };
+
+    let analyzed = match processing_result {
+        Ok(analyzed) => analyzed,
+        // Errors are only logged; channel subscribers should never see them.
+        Err(err) => {
+            tracing::error!(
+                "Failed to process image in channel {}: {err:?}",
+                post.chat.id.0
+            );
+            return Ok(());
+        }
+    };
+
+    let combined = match post
+        .caption()
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+    {
+        Some(existing) => format!("{}\n\n{}", markup::escape(existing), analyzed.caption),
+        None => analyzed.caption.clone(),
+    };
+
+    // Telegram counts the limit on the visible text, after the markup is parsed.
+    if markup::to_plain(&combined).chars().count() <= TELEGRAM_CAPTION_LIMIT {
+        match bot
+            .edit_message_caption(post.chat.id, post.id)
+            .caption(combined)
+            .parse_mode(ParseMode::Html)
+            .await
+        {
+            Ok(edited) => {
+                send_continuation(
+                    &bot,
+                    ReplyTarget::for_message(&post),
+                    &edited,
+                    &analyzed.continuation,
+                )
+                .await;
+                return Ok(());
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "Could not edit post {} in channel {}, replying instead: {err:?}",
+                    post.id.0,
+                    post.chat.id.0
+                );
+            }
+        }
+    }
+
+    let sent = retry::send(
+        ReplyTarget::for_message(&post).apply(
+            bot.send_document(post.chat.id, InputFile::file_id(analyzed.file_id.clone()))
+                .caption(analyzed.caption)
+                .parse_mode(ParseMode::Html),
+        ),
+    )
+    .await?;
+    send_continuation(
+        &bot,
+        ReplyTarget::for_message(&post),
+        &sent,
+        &analyzed.continuation,
+    )
+    .await;
+
+    Ok(())
+}
+
+enum MessageTarget<'a> {
+    Message(&'a Message),
+    MissingReply,
+    Ignore,
+}
+
+fn is_group_chat(msg: &Message) -> bool {
+    msg.chat.is_group() || msg.chat.is_supergroup()
+}
+
+/// Decides which message (if any) `msg` asks the bot to analyze.
+///
+/// `/exif` points at the image it captions or at the message it replies to, in any
+/// chat. Otherwise private chats always analyze `msg` itself, while groups (which pass
+/// their `trigger`) only do so when the trigger mode allows it.
+fn resolve_target<'a>(
+    msg: &'a Message,
+    command: Option<&Command>,
+    trigger: Option<TriggerMode>,
+    bot_username: &str,
+    config: &Config,
+) -> MessageTarget<'a> {
+    let has_image = image_file_id(msg, config).is_some();
+
+    if matches!(
+        command,
+        Some(
+            Command::Exif
+                | Command::Strip
+                | Command::Map
+                | Command::Json
+                | Command::Watermark(_)
+                | Command::Frame
+                | Command::Card(_)
+        )
+    ) {
+        if has_image {
+            return MessageTarget::Message(msg);
+        }
+        return match msg.reply_to_message() {
+            Some(replied) => MessageTarget::Message(replied),
+            None => MessageTarget::MissingReply,
+        };
+    }
+
+    let Some(trigger) = trigger else {
+        return MessageTarget::Message(msg);
+    };
+
+    if !has_image {
+        return MessageTarget::Ignore;
+    }
+
+    match trigger {
+        // Compressed photos rarely carry EXIF data, so only documents are picked up
+        // automatically; photos still work when the bot is asked explicitly.
+        TriggerMode::All if is_image_document(msg, config) => MessageTarget::Message(msg),
+        TriggerMode::All => MessageTarget::Ignore,
+        TriggerMode::Explicit if mentions_bot(msg, bot_username) => MessageTarget::Message(msg),
+        TriggerMode::Explicit => MessageTarget::Ignore,
+    }
+}
+
+fn is_compressed_photo(msg: &Message, config: &Config) -> bool {
+    image_file_id(msg, config)
+        .map(|selection| selection.media_kind() == ReceivedImage::Photo)
+        .unwrap_or(false)
+}
+
+fn is_image_document(msg: &Message, config: &Config) -> bool {
+    image_file_id(msg, config)
+        .map(|selection| selection.media_kind() == ReceivedImage::Document)
+        .unwrap_or(false)
+}
+
+/// Whether a mention in `msg` names the bot. Only whole mentions count, so neither a
+/// longer username starting with the bot's nor an email address does.
+fn mentions_bot(msg: &Message, bot_username: &str) -> bool {
+    msg.parse_caption_entities()
+        .or_else(|| msg.parse_entities())
+        .unwrap_or_default()
+        .iter()
+        .any(|entity| {
+            matches!(entity.kind(), MessageEntityKind::Mention)
+                && entity
+                    .text()
+                    .strip_prefix('@')
+                    .is_some_and(|name| name.eq_ignore_ascii_case(bot_username))
+        })
+}
+
+/// Compares right away when `/compare` comes with two images, otherwise starts a
+/// pairing that collects the missing ones.
+async fn handle_compare_command(
+    bot: &Bot,
+    state: &AppState,
+    msg: &Message,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let reply = ReplyTarget::for_message(msg);
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+
+    let replied = msg
+        .reply_to_message()
+        .filter(|replied| image_file_id(replied, &state.config).is_some());
+    let own = Some(msg).filter(|msg| image_file_id(msg, &state.config).is_some());
+
+    match (replied, own) {
+        (Some(first), Some(second)) => {
+            reply_with_comparison(bot, state, reply, first, second, locale).await
+        }
+        (Some(first), None) | (None, Some(first)) => {
+            state
+                .compare
+                .start(msg.chat.id, user.id, Some(first.clone()));
+            send_text(
+                bot,
+                reply,
+                rust_i18n::t!("compare.send_second", locale = locale),
+            )
+            .await?;
+            Ok(())
+        }
+        (None, None) => {
+            state.compare.start(msg.chat.id, user.id, None);
+            send_text(
+                bot,
+                reply,
+                rust_i18n::t!("compare.send_first", locale = locale),
+            )
+            .await?;
+            Ok(())
+        }
+    }
+}
+
+async fn continue_comparison(
+    bot: &Bot,
+    state: &AppState,
+    reply: ReplyTarget,
+    step: PairingStep,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    match step {
+        PairingStep::AwaitingSecond => {
+            send_text(
+                bot,
+                reply,
+                rust_i18n::t!("compare.send_second", locale = locale),
+            )
+            .await?;
+            Ok(())
+        }
+        PairingStep::Complete(first, second) => {
+            reply_with_comparison(bot, state, reply, &first, &second, locale).await
+        }
+    }
+}
+
+/// Reads both images and answers with their side-by-side comparison.
+async fn reply_with_comparison(
+    bot: &Bot,
+    state: &AppState,
+    reply: ReplyTarget,
+    first: &Message,
+    second: &Message,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let facts = async {
+        let first = read_message_exif(bot, state, first).await?;
+        let second = read_message_exif(bot, state, second).await?;
+        anyhow::Ok((first, second))
+    };
+
+    let text = match facts.await {
+        Ok((first, second)) => {
+            let to_facts = |exif: Option<::exif::Exif>| {
+                exif.map_or_else(ShotFacts::unknown, |exif| ShotFacts::from_exif(&exif))
+            };
+            let units = second
+                .from()
+                .map(|user| state.settings.user(user.id).units)
+                .unwrap_or_default();
+            compare::format_comparison(&to_facts(first), &to_facts(second), locale, units)
+        }
+        Err(err) => {
+            tracing::error!("Failed to compare images: {err:?}");
+            rust_i18n::t!("messages.process_error", locale = locale).into_owned()
+        }
+    };
+
+    send_text(bot, reply, text).await?;
+    Ok(())
+}
+
+/// Answers `/stats` with the sender's most used cameras, lenses and focal lengths.
+async fn handle_stats_command(
+    bot: &Bot,
+    stats: &StatsStore,
+    msg: &Message,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+
+    let summary = stats::format_stats(&stats.user(user.id), locale);
+    send_text(bot, ReplyTarget::for_message(msg), summary).await?;
+
+    Ok(())
+}
+
+/// Counts `images` against the sender's daily quota. When it is used up the user is told
+/// when it resets and `false` is returned; operators have no quota.
+async fn within_quota(
+    bot: &Bot,
+    state: &AppState,
+    msg: &Message,
+    reply: ReplyTarget,
+    images: u32,
+    locale: &str,
+) -> Result<bool, teloxide::RequestError> {
+    let Some(user) = msg.from() else {
+        return Ok(true);
+    };
+    if state.config.admin_ids.contains(&user.id) {
+        return Ok(true);
+    }
+
+    let premium = state.premium.active_until(user.id).is_some();
+    match state.quota.admit(user.id, images, premium).await {
+        Ok(Admission::Allowed) => Ok(true),
+        Ok(Admission::Exceeded { limit, resets_at }) => {
+            tracing::info!("User {} is over the daily quota", user.id.0);
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let wait = resets_at.saturating_sub(now) / 60;
+            send_text(
+                bot,
+                reply,
+                rust_i18n::t!(
+                    "quota.exceeded",
+                    locale = locale,
+                    limit = limit,
+                    time = format_utc(resets_at),
+                    hours = wait / 60,
+                    minutes = wait % 60
+                ),
+            )
+            .await?;
+            Ok(false)
+        }
+        Err(err) => {
+            // A broken counter should not take the bot down for everyone.
+            tracing::error!("Failed to check the daily quota: {err:?}");
+            Ok(true)
+        }
+    }
+}
+
+/// Whether `user_id` may use what premium unlocks. Operators always may.
+fn premium_unlocked(state: &AppState, user_id: UserId) -> bool {
+    state.config.admin_ids.contains(&user_id) || state.premium.unlocked(user_id)
+}
+
+/// Tells the user about premium and sends them a Telegram Stars invoice for it.
+async fn handle_premium_command(
+    bot: &Bot,
+    state: &AppState,
+    msg: &Message,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let reply = ReplyTarget::for_message(msg);
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+    let Some(offer) = state.premium.offer() else {
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("premium.not_offered", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    if let Some(until) = state.premium.active_until(user.id) {
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!(
+                "premium.active",
+                locale = locale,
+                until = format_utc(until),
+                days = offer.days
+            ),
+        )
+        .await?;
+    }
+
+    let price = LabeledPrice::new(
+        rust_i18n::t!("premium.label", locale = locale, days = offer.days),
+        offer.stars,
+    );
+    // Stars invoices are paid inside Telegram and take no provider token.
+    let invoice = reply.apply(bot.send_invoice(
+        reply.chat_id,
+        rust_i18n::t!("premium.title", locale = locale),
+        rust_i18n::t!("premium.description", locale = locale, days = offer.days),
+        premium::invoice_payload(user.id),
+        "",
+        STARS_CURRENCY,
+        vec![price],
+    ));
+    invoice.await?;
+
+    Ok(())
+}
+
+/// Confirms a checkout only for the current price, paid in Stars by the user the
+/// invoice was made for.
+async fn handle_pre_checkout_query(
+    bot: Bot,
+    state: Arc<AppState>,
+    query: PreCheckoutQuery,
+) -> Result<(), teloxide::RequestError> {
+    let locale = locale_from_language_code(query.from.language_code.as_deref());
+    let allowed = may_use_bot(&state, query.from.id);
+    let valid = state.premium.offer().is_some_and(|offer| {
+        query.currency == STARS_CURRENCY
+            && query.total_amount as u64 == u64::from(offer.stars)
+            && premium::parse_payload(&query.invoice_payload) == Some(query.from.id)
+    });
+
+    let mut answer = bot.answer_pre_checkout_query(query.id, allowed && valid);
+    if !allowed {
+        tracing::info!(
+            "Declining checkout of user {} without access",
+            query.from.id.0
+        );
+        answer = answer.error_message(rust_i18n::t!("access.private_bot", locale = locale));
+    } else if !valid {
+        tracing::warn!(
+            "Declining checkout `{}` of user {}",
+            query.invoice_payload,
+            query.from.id.0
+        );
+        answer = answer.error_message(rust_i18n::t!("premium.invalid_checkout", locale = locale));
+    }
+    answer.await?;
+
+    Ok(())
+}
+
+/// Unlocks premium once Telegram reports the payment.
+async fn handle_successful_payment(
+    bot: &Bot,
+    state: &AppState,
+    msg: &Message,
+    payment: &SuccessfulPayment,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let reply = ReplyTarget::for_message(msg);
+    let Some(user_id) = premium::parse_payload(&payment.invoice_payload) else {
+        tracing::warn!(
+            "Ignoring payment with payload `{}`",
+            payment.invoice_payload
+        );
+        return Ok(());
+    };
+
+    let charge_id = payment.telegram_payment_charge_id.clone();
+    match state.premium.grant(user_id, charge_id).await {
+        Ok(until) => {
+            tracing::info!("User {} bought premium", user_id.0);
+            send_text(
+                bot,
+                reply,
+                rust_i18n::t!(
+                    "premium.activated",
+                    locale = locale,
+                    until = format_utc(until)
+                ),
+            )
+            .await?;
+        }
+        Err(err) => {
+            tracing::error!(
+                "Failed to unlock premium for user {} after charge {}: {err:?}",
+                user_id.0,
+                payment.telegram_payment_charge_id
+            );
+            send_text(
+                bot,
+                reply,
+                rust_i18n::t!("premium.activation_failed", locale = locale),
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// `secs` since the Unix epoch as a UTC date and time for messages.
+fn format_utc(secs: u64) -> String {
+    chrono::DateTime::from_timestamp(secs as i64, 0)
+        .map(|time| time.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_default()
+}
+
+/// Whether the bot answers `user_id`. Operators always get through.
+fn may_use_bot(state: &AppState, user_id: UserId) -> bool {
+    state.config.admin_ids.contains(&user_id) || state.moderation.permits(user_id)
+}
+
+/// Lets operators ban users (`block`), allow them (`allow`), take them off both lists
+/// (`remove`), or turn the allowlist on and off (`allowlist on`). The user is given by id
+/// or by replying to one of their messages.
+async fn handle_access_command(
+    bot: &Bot,
+    state: &AppState,
+    msg: &Message,
+    args: &str,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let reply = ReplyTarget::for_message(msg);
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+    if !state.config.admin_ids.contains(&user.id) {
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("access.admins_only", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let mut words = args.split_whitespace();
+    let action = words.next().unwrap_or_default().to_ascii_lowercase();
+    let argument = words.next();
+    let target = argument
+        .and_then(|id| id.parse().ok())
+        .map(UserId)
+        .or_else(|| {
+            msg.reply_to_message()
+                .and_then(|replied| replied.from())
+                .map(|sender| sender.id)
+        });
+
+    let (stored, confirmation) = match (action.as_str(), target) {
+        ("allowlist", _) => {
+            let enabled = match argument.map(str::to_ascii_lowercase).as_deref() {
+                Some("on") => true,
+                Some("off") => false,
+                _ => {
+                    send_access_status(bot, state, reply, locale).await?;
+                    return Ok(());
+                }
+            };
+            let key = if enabled {
+                "access.allowlist_on"
+            } else {
+                "access.allowlist_off"
+            };
+            (
+                state.moderation.set_allowlist_only(enabled).await,
+                rust_i18n::t!(key, locale = locale),
+            )
+        }
+        ("allow" | "block" | "remove", Some(target)) => {
+            let (access, key) = match action.as_str() {
+                "allow" => (Some(Access::Allowed), "access.allowed"),
+                "block" => (Some(Access::Blocked), "access.blocked"),
+                _ => (None, "access.removed"),
+            };
+            (
+                state.moderation.set(target, access).await,
+                rust_i18n::t!(key, locale = locale, user = target.0),
+            )
+        }
+        _ => {
+            send_access_status(bot, state, reply, locale).await?;
+            return Ok(());
+        }
+    };
+
+    if let Err(err) = stored {
+        tracing::error!("Failed to store the access lists: {err:?}");
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.settings_error", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+    send_text(bot, reply, confirmation).await?;
+
+    Ok(())
+}
+
+/// The allowlist mode and both lists, with how to change them.
+async fn send_access_status(
+    bot: &Bot,
+    state: &AppState,
+    reply: ReplyTarget,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let (allowed, blocked) = state.moderation.lists();
+    let join = |ids: Vec<u64>| {
+        if ids.is_empty() {
+            String::from("—")
+        } else {
+            ids.iter()
+                .map(u64::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+    };
+    let mode = if state.moderation.allowlist_only() {
+        "on"
+    } else {
+        "off"
+    };
+    send_text(
+        bot,
+        reply,
+        rust_i18n::t!(
+            "access.usage",
+            locale = locale,
+            mode = mode,
+            allowed = join(allowed),
+            blocked = join(blocked)
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Sends the operator's announcement to every known chat, keeping a status message up
+/// to date with how far it got.
+async fn handle_broadcast_command(
+    bot: &Bot,
+    state: &AppState,
+    msg: &Message,
+    text: &str,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let reply = ReplyTarget::for_message(msg);
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+    if !state.config.admin_ids.contains(&user.id) {
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("broadcast.admins_only", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+    let text = text.trim();
+    if text.is_empty() {
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("broadcast.usage", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let status = send_text(
+        bot,
+        reply,
+        rust_i18n::t!("broadcast.started", locale = locale),
+    )
+    .await?;
+    let progress = state
+        .known_chats
+        .broadcast(bot, text, |progress| {
+            let status = &status;
+            async move {
+                let update = bot.edit_message_text(
+                    status.chat.id,
+                    status.id,
+                    rust_i18n::t!(
+                        "broadcast.progress",
+                        locale = locale,
+                        done = progress.sent + progress.failed,
+                        total = progress.total,
+                        failed = progress.failed
+                    ),
+                );
+                if let Err(err) = update.await {
+                    tracing::debug!("Failed to update broadcast progress: {err:?}");
+                }
+            }
+        })
+        .await;
+    tracing::info!(
+        "Broadcast by {} reached {} of {} chats",
+        user.id.0,
+        progress.sent,
+        progress.total
+    );
+    send_text(
+        bot,
+        reply,
+        rust_i18n::t!(
+            "broadcast.done",
+            locale = locale,
+            sent = progress.sent,
+            total = progress.total
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Shows the bot's usage over the last days to the operators in `FOTOBOT_ADMIN_IDS`.
+async fn handle_admin_stats_command(
+    bot: &Bot,
+    state: &AppState,
+    msg: &Message,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let reply = ReplyTarget::for_message(msg);
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+    if !state.config.admin_ids.contains(&user.id) {
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("adminstats.admins_only", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let report = match metrics::report(&state.storage, locale).await {
+        Ok(report) => report,
+        Err(err) => {
+            tracing::error!("Failed to build usage report: {err:?}");
+            rust_i18n::t!("actions.failed", locale = locale).into_owned()
+        }
+    };
+    send_text(bot, reply, report).await?;
+
+    Ok(())
+}
+
+async fn handle_export_command(
+    bot: &Bot,
+    state: &AppState,
+    msg: &Message,
+    format: &str,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let reply = ReplyTarget::for_message(msg);
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+
+    let Some(format) = ExportFormat::parse(format) else {
+        send_text(bot, reply, rust_i18n::t!("export.usage", locale = locale)).await?;
+        return Ok(());
+    };
+
+    match send_batch_export(bot, state, reply, msg.id, user.id, format, locale).await {
+        Ok(None) => {}
+        Ok(Some(notice)) => {
+            send_text(bot, reply, notice).await?;
+        }
+        Err(err) => {
+            tracing::error!("Failed to send {format:?} export: {err:?}");
+            send_text(bot, reply, rust_i18n::t!("actions.failed", locale = locale)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends the user's batch as a file and ends the batch.
+///
+/// Returns a notice instead when the batch holds nothing the format can carry; the batch
+/// is kept then, so another format can still be exported.
+async fn send_batch_export(
+    bot: &Bot,
+    state: &AppState,
+    reply: ReplyTarget,
+    reply_to: MessageId,
+    user_id: UserId,
+    format: ExportFormat,
+    locale: &str,
+) -> Result<Option<String>> {
+    if !premium_unlocked(state, user_id) {
+        return Ok(Some(
+            rust_i18n::t!("premium.required", locale = locale).into_owned(),
+        ));
+    }
+    let batches = &state.batches;
+    let mut photos = batches.photos(reply.chat_id, user_id);
+    if photos.is_empty() {
+        return Ok(Some(
+            rust_i18n::t!("export.empty", locale = locale).into_owned(),
+        ));
+    }
+    if format.needs_location() && photos.iter().all(|photo| photo.gps.is_none()) {
+        return Ok(Some(
+            rust_i18n::t!("export.no_locations", locale = locale).into_owned(),
+        ));
+    }
+
+    let precision = state.settings.user(user_id).precision;
+    for photo in &mut photos {
+        photo.gps = photo.gps.map(|gps| precision.round(gps));
+    }
+
+    let file = InputFile::memory(format.write(&photos).into_bytes()).file_name(format.file_name());
+    send_reply_document(bot, reply, reply_to, file).await?;
+    batches.clear(reply.chat_id, user_id);
+
+    Ok(None)
+}
+
+/// Wipes everything the bot keeps about the sender, once they confirm with
+/// `/deletemydata confirm`.
+async fn handle_delete_my_data_command(
+    bot: &Bot,
+    state: &AppState,
+    msg: &Message,
+    confirmation: &str,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let reply = ReplyTarget::for_message(msg);
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+
+    if !confirmation.trim().eq_ignore_ascii_case("confirm") {
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("deletemydata.usage", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    state.recent.forget(user.id);
+    state.batches.forget(user.id);
+    state.compare.forget(user.id);
+    let stored = [
+        state.settings.forget_user(user.id).await,
+        state.stats.forget(user.id),
+        state.history.forget(user.id),
+    ];
+    if let Some(err) = stored.into_iter().find_map(Result::err) {
+        tracing::error!("Failed to delete the data of user {}: {err:?}", user.id.0);
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("deletemydata.failed", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    tracing::info!("Deleted the data of user {}", user.id.0);
+    send_text(
+        bot,
+        reply,
+        rust_i18n::t!("deletemydata.done", locale = locale),
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn handle_history_command(
+    bot: &Bot,
+    history: &HistoryStore,
+    settings: &SettingsStore,
+    msg: &Message,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+
+    let clock = settings.user(user.id).units.clock;
+    let listing = history::format_history(&history.list(user.id), locale, clock);
+    send_text(bot, ReplyTarget::for_message(msg), listing).await?;
+
+    Ok(())
+}
+
+async fn handle_sections_command(
+    bot: &Bot,
+    settings: &SettingsStore,
+    msg: &Message,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let reply = ReplyTarget::for_message(msg);
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+
+    let hidden = settings.user(user.id).hidden_sections;
+    let request = reply.apply(
+        bot.send_message(
+            reply.chat_id,
+            rust_i18n::t!("sections.prompt", locale = locale),
+        )
+        .reply_markup(styles::sections_keyboard(hidden, locale)),
+    );
+    request.await?;
+
+    Ok(())
+}
+
+async fn handle_style_command(
+    bot: &Bot,
+    settings: &SettingsStore,
+    msg: &Message,
+    style: &str,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let reply = ReplyTarget::for_message(msg);
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+
+    let Some(style) = CaptionStyle::parse(style) else {
+        let current = settings.user(user.id).style;
+        let styles: Vec<&str> = CaptionStyle::ALL
+            .into_iter()
+            .map(CaptionStyle::as_str)
+            .collect();
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!(
+                "messages.style_usage",
+                locale = locale,
+                style = current.as_str(),
+                styles = styles.join(", ")
+            ),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    if let Err(err) = settings
+        .update_user(user.id, |settings| settings.style = style)
+        .await
+    {
+        tracing::error!(
+            "Failed to store caption style for user {}: {err:?}",
+            user.id.0
+        );
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.settings_error", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    send_text(
+        bot,
+        reply,
+        rust_i18n::t!(
+            "messages.style_updated",
+            locale = locale,
+            style = style.as_str()
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn handle_labels_command(
+    bot: &Bot,
+    settings: &SettingsStore,
+    msg: &Message,
+    labels: &str,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let reply = ReplyTarget::for_message(msg);
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+
+    let Some(labels) = LabelStyle::parse(labels) else {
+        let current = settings.user(user.id).labels;
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!(
+                "messages.labels_usage",
+                locale = locale,
+                labels = current.as_str()
+            ),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    if let Err(err) = settings
+        .update_user(user.id, |settings| settings.labels = labels)
+        .await
+    {
+        tracing::error!(
+            "Failed to store caption labels for user {}: {err:?}",
+            user.id.0
+        );
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.settings_error", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    send_text(
+        bot,
+        reply,
+        rust_i18n::t!(
+            "messages.labels_updated",
+            locale = locale,
+            labels = labels.as_str()
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn handle_units_command(
+    bot: &Bot,
+    settings: &SettingsStore,
+    msg: &Message,
+    units: &str,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let reply = ReplyTarget::for_message(msg);
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+
+    let current = settings.user(user.id).units;
+    let Some(units) = current.parse_change(units) else {
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!(
+                "messages.units_usage",
+                locale = locale,
+                units = current.system.as_str(),
+                clock = current.clock.as_str()
+            ),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    if let Err(err) = settings
+        .update_user(user.id, |settings| settings.units = units)
+        .await
+    {
+        tracing::error!("Failed to store units for user {}: {err:?}", user.id.0);
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.settings_error", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    send_text(
+        bot,
+        reply,
+        rust_i18n::t!(
+            "messages.units_updated",
+            locale = locale,
+            units = units.system.as_str(),
+            clock = units.clock.as_str()
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn handle_precision_command(
+    bot: &Bot,
+    settings: &SettingsStore,
+    msg: &Message,
+    precision: &str,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let reply = ReplyTarget::for_message(msg);
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+
+    let Some(precision) = CoordinatePrecision::parse(precision) else {
+        let current = settings.user(user.id).precision;
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!(
+                "messages.precision_usage",
+                locale = locale,
+                precision = current.as_str()
+            ),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    if let Err(err) = settings
+        .update_user(user.id, |settings| settings.precision = precision)
+        .await
+    {
+        tracing::error!(
+            "Failed to store coordinate precision for user {}: {err:?}",
+            user.id.0
+        );
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.settings_error", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    send_text(
+        bot,
+        reply,
+        rust_i18n::t!(
+            "messages.precision_updated",
+            locale = locale,
+            precision = precision.as_str()
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn handle_ocr_command(
+    bot: &Bot,
+    state: &AppState,
+    msg: &Message,
+    toggle: &str,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let reply = ReplyTarget::for_message(msg);
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+
+    if !ocr_available(state) {
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("ocr.unavailable", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let enabled = match toggle.trim().to_ascii_lowercase().as_str() {
+        "on" | "yes" => true,
+        "off" | "no" => false,
+        _ => {
+            let key = if state.settings.user(user.id).ocr {
+                "ocr.usage_on"
+            } else {
+                "ocr.usage_off"
+            };
+            send_text(bot, reply, rust_i18n::t!(key, locale = locale)).await?;
+            return Ok(());
+        }
+    };
+
+    if let Err(err) = state
+        .settings
+        .update_user(user.id, |settings| settings.ocr = enabled)
+        .await
+    {
+        tracing::error!("Failed to store OCR opt-in for user {}: {err:?}", user.id.0);
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.settings_error", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let key = if enabled {
+        "ocr.enabled"
+    } else {
+        "ocr.disabled"
+    };
+    send_text(bot, reply, rust_i18n::t!(key, locale = locale)).await?;
+
+    Ok(())
+}
+
+async fn handle_nearby_command(
+    bot: &Bot,
+    settings: &SettingsStore,
+    lookups: &Lookups,
+    msg: &Message,
+    toggle: &str,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let reply = ReplyTarget::for_message(msg);
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+
+    if !lookups.landmarks_available() {
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("nearby.unavailable", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let enabled = match toggle.trim().to_ascii_lowercase().as_str() {
+        "on" | "yes" => true,
+        "off" | "no" => false,
+        _ => {
+            let key = if settings.user(user.id).nearby {
+                "nearby.usage_on"
+            } else {
+                "nearby.usage_off"
+            };
+            send_text(bot, reply, rust_i18n::t!(key, locale = locale)).await?;
+            return Ok(());
+        }
+    };
+
+    if let Err(err) = settings
+        .update_user(user.id, |settings| settings.nearby = enabled)
+        .await
+    {
+        tracing::error!(
+            "Failed to store landmark opt-in for user {}: {err:?}",
+            user.id.0
+        );
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.settings_error", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let key = if enabled {
+        "nearby.enabled"
+    } else {
+        "nearby.disabled"
+    };
+    send_text(bot, reply, rust_i18n::t!(key, locale = locale)).await?;
+
+    Ok(())
+}
+
+async fn handle_private_zone_command(
+    bot: &Bot,
+    settings: &SettingsStore,
+    msg: &Message,
+    args: &str,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let reply = ReplyTarget::for_message(msg);
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+
+    // Zones are usually someone's home, so they are never set up or listed in front of
+    // other people.
+    if !msg.chat.is_private() {
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("privatezone.private_only", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let (action, rest) = args
+        .trim()
+        .split_once(char::is_whitespace)
+        .unwrap_or((args.trim(), ""));
+    let shared_location = msg
+        .reply_to_message()
+        .and_then(|replied| replied.location())
+        .map(|location| (location.latitude, location.longitude));
+    let mut zones = settings.user(user.id).private_zones;
+
+    let answer = match action.to_ascii_lowercase().as_str() {
+        "add" => {
+            let Some(zone) = PrivateZone::parse(rest, shared_location) else {
+                send_text(
+                    bot,
+                    reply,
+                    rust_i18n::t!("privatezone.invalid", locale = locale),
+                )
+                .await?;
+                return Ok(());
+            };
+            zones.retain(|existing| !existing.name.eq_ignore_ascii_case(&zone.name));
+            if zones.len() >= MAX_PRIVATE_ZONES {
+                send_text(
+                    bot,
+                    reply,
+                    rust_i18n::t!(
+                        "privatezone.too_many",
+                        locale = locale,
+                        max = MAX_PRIVATE_ZONES
+                    ),
+                )
+                .await?;
+                return Ok(());
+            }
+            let answer = rust_i18n::t!("privatezone.added", locale = locale, name = &zone.name);
+            zones.push(zone);
+            answer
+        }
+        "remove" | "delete" => {
+            let name = rest.trim();
+            let before = zones.len();
+            zones.retain(|zone| !zone.name.eq_ignore_ascii_case(name));
+            if zones.len() == before {
+                send_text(
+                    bot,
+                    reply,
+                    rust_i18n::t!("privatezone.not_found", locale = locale, name = name),
+                )
+                .await?;
+                return Ok(());
+            }
+            rust_i18n::t!("privatezone.removed", locale = locale, name = name)
+        }
+        _ => {
+            let mut lines = vec![rust_i18n::t!("privatezone.usage", locale = locale).into_owned()];
+            if zones.is_empty() {
+                lines.push(rust_i18n::t!("privatezone.none", locale = locale).into_owned());
+            } else {
+                lines.push(rust_i18n::t!("privatezone.list", locale = locale).into_owned());
+                lines.extend(zones.iter().map(|zone| {
+                    let key = match zone.mode {
+                        ZoneMode::Hide => "privatezone.zone",
+                        ZoneMode::Coarse => "privatezone.zone_coarse",
+                    };
+                    rust_i18n::t!(
+                        key,
+                        locale = locale,
+                        name = &zone.name,
+                        radius = format!("{:.0}", zone.radius_m),
+                        latitude = format!("{:.5}", zone.latitude),
+                        longitude = format!("{:.5}", zone.longitude)
+                    )
+                    .into_owned()
+                }));
+            }
+            send_text(bot, reply, lines.join("\n")).await?;
+            return Ok(());
+        }
+    };
+
+    if let Err(err) = settings
+        .update_user(user.id, |settings| settings.private_zones = zones)
+        .await
+    {
+        tracing::error!(
+            "Failed to store private zones for user {}: {err:?}",
+            user.id.0
+        );
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.settings_error", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+    send_text(bot, reply, answer).await?;
+
+    Ok(())
+}
+
+/// Shows or changes how much location data the sender's captions include.
+async fn handle_privacy_command(
+    bot: &Bot,
+    settings: &SettingsStore,
+    msg: &Message,
+    mode: &str,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let reply = ReplyTarget::for_message(msg);
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+
+    let Some(privacy) = GpsPrivacy::parse(mode) else {
+        let current = settings.user(user.id).gps;
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!(
+                "messages.privacy_usage",
+                locale = locale,
+                mode = current.as_str()
+            ),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    if let Err(err) = settings
+        .update_user(user.id, |settings| settings.gps = privacy)
+        .await
+    {
+        tracing::error!(
+            "Failed to store GPS privacy for user {}: {err:?}",
+            user.id.0
+        );
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.settings_error", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    send_text(
+        bot,
+        reply,
+        rust_i18n::t!(
+            "messages.privacy_updated",
+            locale = locale,
+            mode = privacy.as_str()
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Whether the sender of `msg` may change the chat's settings: admins of a group, and
+/// whoever posts in a channel, which only admins can. Tells the sender why not otherwise.
+async fn may_configure_chat(
+    bot: &Bot,
+    msg: &Message,
+    locale: &str,
+) -> Result<bool, teloxide::RequestError> {
+    if msg.chat.is_channel() {
+        return Ok(true);
+    }
+
+    let reply = ReplyTarget::for_message(msg);
+    if !is_group_chat(msg) {
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.group_only", locale = locale),
+        )
+        .await?;
+        return Ok(false);
+    }
+
+    let Some(user) = msg.from() else {
+        return Ok(false);
+    };
+
+    let member = bot.get_chat_member(msg.chat.id, user.id).await?;
+    if !member.kind.is_privileged() {
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.admin_only", locale = locale),
+        )
+        .await?;
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Sets the caption style of everyone's captions in a group or channel, or with
+/// `default` goes back to each member's own.
+async fn handle_chat_style_command(
+    bot: &Bot,
+    settings: &SettingsStore,
+    msg: &Message,
+    style: &str,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let chat_id = msg.chat.id;
+    let reply = ReplyTarget::for_message(msg);
+    if !may_configure_chat(bot, msg, locale).await? {
+        return Ok(());
+    }
+
+    let style = match style.trim() {
+        "default" | "off" => None,
+        style => match CaptionStyle::parse(style) {
+            Some(style) => Some(style),
+            None => {
+                let current = settings.chat(chat_id).style;
+                let styles: Vec<&str> = CaptionStyle::ALL
+                    .into_iter()
+                    .map(CaptionStyle::as_str)
+                    .collect();
+                send_text(
+                    bot,
+                    reply,
+                    rust_i18n::t!(
+                        "messages.chat_style_usage",
+                        locale = locale,
+                        style = current.map_or("default", CaptionStyle::as_str),
+                        styles = styles.join(", ")
+                    ),
+                )
+                .await?;
+                return Ok(());
+            }
+        },
+    };
+
+    if let Err(err) = settings
+        .update_chat(chat_id, |chat| chat.style = style)
+        .await
+    {
+        tracing::error!(
+            "Failed to store caption style for chat {}: {err:?}",
+            chat_id.0
+        );
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.settings_error", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let confirmation = match style {
+        Some(style) => rust_i18n::t!(
+            "messages.chat_style_updated",
+            locale = locale,
+            style = style.as_str()
+        ),
+        None => rust_i18n::t!("messages.chat_style_cleared", locale = locale),
+    };
+    send_text(bot, reply, confirmation).await?;
+
+    Ok(())
+}
+
+/// Sets how much of the location captions in a group or channel reveal at most, or with
+/// `default` leaves it to each member.
+async fn handle_chat_privacy_command(
+    bot: &Bot,
+    settings: &SettingsStore,
+    msg: &Message,
+    mode: &str,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let chat_id = msg.chat.id;
+    let reply = ReplyTarget::for_message(msg);
+    if !may_configure_chat(bot, msg, locale).await? {
+        return Ok(());
+    }
+
+    let privacy = match mode.trim() {
+        "default" => None,
+        mode => match GpsPrivacy::parse(mode) {
+            Some(privacy) => Some(privacy),
+            None => {
+                let current = settings.chat(chat_id).gps;
+                send_text(
+                    bot,
+                    reply,
+                    rust_i18n::t!(
+                        "messages.chat_privacy_usage",
+                        locale = locale,
+                        mode = current.map_or("default", GpsPrivacy::as_str)
+                    ),
+                )
+                .await?;
+                return Ok(());
+            }
+        },
+    };
+
+    if let Err(err) = settings
+        .update_chat(chat_id, |chat| chat.gps = privacy)
+        .await
+    {
+        tracing::error!(
+            "Failed to store GPS privacy for chat {}: {err:?}",
+            chat_id.0
+        );
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.settings_error", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let confirmation = match privacy {
+        Some(privacy) => rust_i18n::t!(
+            "messages.chat_privacy_updated",
+            locale = locale,
+            mode = privacy.as_str()
+        ),
+        None => rust_i18n::t!("messages.chat_privacy_cleared", locale = locale),
+    };
+    send_text(bot, reply, confirmation).await?;
+
+    Ok(())
+}
+
+async fn handle_trigger_command(
+    bot: &Bot,
+    settings: &SettingsStore,
+    msg: &Message,
+    mode: &str,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let chat_id = msg.chat.id;
+    let reply = ReplyTarget::for_message(msg);
+
+    if !may_configure_chat(bot, msg, locale).await? {
+        return Ok(());
+    }
+
+    let Some(trigger) = TriggerMode::parse(mode) else {
+        let current = settings.chat(chat_id).trigger;
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!(
+                "messages.trigger_usage",
+                locale = locale,
+                mode = current.as_str()
+            ),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    if let Err(err) = settings
+        .update_chat(chat_id, |chat| chat.trigger = trigger)
+        .await
+    {
+        tracing::error!(
+            "Failed to store trigger mode for chat {}: {err:?}",
+            chat_id.0
+        );
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.settings_error", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    send_text(
+        bot,
+        reply,
+        rust_i18n::t!(
+            "messages.trigger_updated",
+            locale = locale,
+            mode = trigger.as_str()
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn process_image(
+    bot: &Bot,
+    lookups: &Lookups,
+    file_id: &str,
+    language_code: Option<&str>,
+    options: CaptionOptions,
+) -> Result<ExifSummary> {
+    let file = telegram_file(bot, file_id).await?;
+
+    let metadata = file
+        .read_metadata(bot.client())
+        .await
+        .context("Failed to parse EXIF data")?;
+    Ok(ExifSummary::build(lookups, metadata, language_code, options).await)
+}
+
+/// Where the Bot API serves a file from.
+enum TelegramFile {
+    Url(String),
+    /// A local Bot API server started with `--local` hands out paths on its own disk,
+    /// which the bot reads directly.
+    Local(PathBuf),
+}
+
+impl TelegramFile {
+    /// Reads the header of the file, with range requests through `client` for a URL.
+    async fn read_metadata(self, client: &reqwest::Client) -> Result<exif::ImageMetadata> {
+        match self {
+            Self::Url(url) => {
+                let started = Instant::now();
+                let metadata = exif::read_metadata_from_url(client, &url).await;
+                log_parsed(started.elapsed());
+                metadata
+            }
+            Self::Local(path) => read_local(path, exif::read_metadata_from_file).await,
+        }
+    }
+
+    async fn read_exif(self, client: &reqwest::Client) -> Result<Option<::exif::Exif>> {
+        match self {
+            Self::Url(url) => exif::read_exif_from_url(client, &url).await,
+            Self::Local(path) => read_local(path, exif::read_exif_from_file).await,
+        }
+    }
+}
+
+async fn telegram_file(bot: &Bot, file_id: &str) -> Result<TelegramFile> {
+    let file = retry::send(bot.get_file(file_id))
+        .await
+        .context("Failed to fetch file information from Telegram")?;
+
+    if Path::new(&file.path).is_absolute() {
+        return Ok(TelegramFile::Local(PathBuf::from(file.path)));
+    }
+    let base = bot.api_url();
+    Ok(TelegramFile::Url(format!(
+        "{}/file/bot{}/{}",
+        base.as_str().trim_end_matches('/'),
+        bot.token(),
+        file.path
+    )))
+}
+
+/// The whole file, for features that rewrite or re-encode the image.
+async fn download_telegram_file(bot: &Bot, file_id: &str) -> Result<Vec<u8>> {
+    let file = retry::send(bot.get_file(file_id))
+        .await
+        .context("Failed to fetch file information from Telegram")?;
+
+    if Path::new(&file.path).is_absolute() {
+        return fs::read(&file.path)
+            .await
+            .with_context(|| format!("Failed to read `{}` from the Bot API server", file.path));
+    }
+    let mut bytes = Vec::new();
+    bot.download_file(&file.path, &mut bytes).await?;
+    Ok(bytes)
+}
+
+async fn process_remote_image(
+    state: &AppState,
+    url: Url,
+    language_code: Option<&str>,
+    options: CaptionOptions,
+) -> Result<ExifSummary> {
+    let http = &state.links;
+    let image = remote::probe_image(http, url).await?;
+    tracing::info!(
+        "Processing remote image {} ({} bytes)",
+        image.url,
+        image
+            .size
+            .map(|size| size.to_string())
+            .unwrap_or_else(|| String::from("unknown"))
+    );
+
+    let started = Instant::now();
+    let parsing = exif::read_metadata_from_url(http, image.url.as_str());
+    let metadata = tokio::time::timeout(REMOTE_FETCH_TIMEOUT, parsing)
+        .await
+        .context("Timed out reading EXIF data from remote image")?
+        .context("Failed to parse EXIF data")?;
+    log_parsed(started.elapsed());
+    Ok(ExifSummary::build(&state.lookups, metadata, language_code, options).await)
+}
+
+/// Records how long reading the EXIF data took, download included when it was streamed.
+fn log_parsed(elapsed: Duration) {
+    telemetry::observe_exif_parse(elapsed);
+    tracing::info!(parse_ms = elapsed.as_millis() as u64, "Parsed EXIF data");
+}
+
+async fn process_large_image(
+    state: &AppState,
+    file: &LargeFile<'_>,
+    progress: &mut Progress<'_>,
+    language_code: Option<&str>,
+    options: CaptionOptions,
+) -> Result<ExifSummary> {
+    let metadata = read_large_file(
+        state.extra_client.as_ref(),
+        file,
+        progress,
+        exif::read_metadata,
+        exif::ImageMetadata::has_exif,
+    )
+    .await?;
+    Ok(ExifSummary::build(&state.lookups, metadata, language_code, options).await)
+}
+
+/// A file above the Bot API limit, which the secondary client fetches from its message.
+struct LargeFile<'a> {
+    chat_id: ChatId,
+    message_id: i32,
+    media_kind: ReceivedImage,
+    username: Option<&'a str>,
+    size: u64,
+    /// Where the file is downloaded to when its header is not enough.
+    cache_dir: &'a Path,
+}
+
+/// Reads a file above the Bot API limit from its leading bytes, and only downloads the
+/// whole file when `found` says the header had nothing and the format can keep its
+/// metadata at the end.
+async fn read_large_file<T: Send + 'static>(
+    extra_client: Option<&GramClient>,
+    file: &LargeFile<'_>,
+    progress: &mut Progress<'_>,
+    read: fn(BufReader<CacheReader>) -> Result<T>,
+    found: fn(&T) -> bool,
+) -> Result<T> {
+    let extra_client = extra_client
+        .context("The file is above the Bot API limit and no secondary client is configured")?;
+    let message =
+        fetch_secondary_message(extra_client, file.chat_id, file.message_id, file.username)
+            .await?
+            .context("Secondary client did not return the requested message")?;
+
+    let (header, may_trail) = download_large_header(&message, file).await?;
+    progress.parsing().await;
+    let from_header = read_cached(header.clone(), read).await;
+    janitor::remove(&header).await;
+    if matches!(&from_header, Ok(value) if found(value))
+        || file.size > MAX_FULL_DOWNLOAD
+        || !may_trail
+    {
+        return from_header;
+    }
+
+    tracing::info!("No EXIF data in the header; downloading the whole file");
+    let whole = download_large_whole(extra_client, &message, file, progress).await?;
+    progress.parsing().await;
+    let from_whole = read_cached(whole.clone(), read).await;
+    janitor::remove(&whole).await;
+    from_whole
+}
+
+async fn read_local<T: Send + 'static>(path: PathBuf, read: fn(&Path) -> Result<T>) -> Result<T> {
+    let started = Instant::now();
+    let span = tracing::Span::current();
+    let value = task::spawn_blocking(move || span.in_scope(|| read(&path)))
+        .await
+        .context("Failed to join EXIF parsing task for local file")?;
+    log_parsed(started.elapsed());
+    value
+}
+
+/// Parses a download in the cache, decrypting it as it goes when encryption is on.
+async fn read_cached<T: Send + 'static>(
+    path: PathBuf,
+    read: fn(BufReader<CacheReader>) -> Result<T>,
+) -> Result<T> {
+    let started = Instant::now();
+    let span = tracing::Span::current();
+    let value = task::spawn_blocking(move || {
+        span.in_scope(|| CacheReader::open(&path).and_then(|file| read(BufReader::new(file))))
+    })
+    .await
+    .context("Failed to join EXIF parsing task for local file")?;
+    log_parsed(started.elapsed());
+    value
+}
+
+/// Whether the file is TIFF-based (most raw formats), PNG or ISO BMFF (HEIF, AVIF, CR3),
+/// where an `eXIf` chunk, a `meta` box or the EXIF IFD may come after the image data.
+fn metadata_may_trail(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"II")
+        || bytes.starts_with(b"MM\0*")
+        || bytes.starts_with(b"\x89PNG")
+        || bytes.get(4..8) == Some(b"ftyp".as_slice())
+}
+
+/// A path in the cache directory for the file, ending in `suffix`.
+async fn large_file_path(file: &LargeFile<'_>, suffix: &str) -> Result<PathBuf> {
+    let cache_dir = file.cache_dir;
+    fs::create_dir_all(cache_dir)
+        .await
+        .context("Failed to ensure cache directory exists")?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("System clock is before UNIX_EPOCH")?
+        .as_millis();
+
+    let extension = match file.media_kind {
+        ReceivedImage::Document => "bin",
+        ReceivedImage::Photo => "jpg",
+    };
+
+    Ok(cache_dir.join(format!(
+        "tmp-{}-{}-{}{}.{}",
+        file.chat_id.0, file.message_id, timestamp, suffix, extension
+    )))
+}
+
+/// Downloads the whole file, for formats whose metadata was not in the header.
+async fn download_large_whole(
+    extra_client: &GramClient,
+    message: &GramMessage,
+    file: &LargeFile<'_>,
+    progress: &mut Progress<'_>,
+) -> Result<PathBuf> {
+    let media = message.media().ok_or_else(|| {
+        anyhow!(
+            "Secondary client reported no downloadable media for message {}",
+            message.id()
+        )
+    })?;
+    let local_path = large_file_path(file, "-full").await?;
+
+    let started = Instant::now();
+    let mut output = CacheWriter::create(&local_path).await?;
+    let mut download = extra_client.iter_download(&media);
+    let mut received = 0;
+    while let Some(chunk) = download
+        .next()
+        .await
+        .context("Failed to download the whole file with secondary client")?
+    {
+        output.write(&chunk).await?;
+        received += chunk.len() as u64;
+        progress.downloaded(received).await;
+    }
+    output.finish().await?;
+    telemetry::downloaded(received);
+    tracing::info!(
+        download_ms = started.elapsed().as_millis() as u64,
+        download_bytes = received,
+        "Downloaded whole large file"
+    );
+    Ok(local_path)
+}
+
+/// A status message for files above the Bot API limit, which take a while: edited as the
+/// download goes on and deleted once the answer is ready.
+struct Progress<'a> {
+    /// `None` when nobody is shown the progress.
+    status: Option<(&'a Bot, Message)>,
+    locale: &'static str,
+    size: u64,
+    last_edit: Instant,
+}
+
+impl<'a> Progress<'a> {
+    fn hidden() -> Self {
+        Self {
+            status: None,
+            locale: "en",
+            size: 0,
+            last_edit: Instant::now(),
+        }
+    }
+
+    /// Sends "Downloading" for a file of `size` bytes.
+    async fn start(bot: &'a Bot, reply: ReplyTarget, size: u64, locale: &'static str) -> Self {
+        let text = rust_i18n::t!(
+            "progress.downloading",
+            locale = locale,
+            size = megabytes(size)
+        );
+        let status = match send_text(bot, reply, text).await {
+            Ok(message) => Some((bot, message)),
+            Err(err) => {
+                tracing::warn!("Failed to send the download progress message: {err:?}");
+                None
+            }
+        };
+        Self {
+            status,
+            locale,
+            size,
+            last_edit: Instant::now(),
+        }
+    }
+
+    /// Shows how much of the file is in, at most every [`PROGRESS_INTERVAL`].
+    async fn downloaded(&mut self, received: u64) {
+        if self.last_edit.elapsed() < PROGRESS_INTERVAL || self.size == 0 {
+            return;
+        }
+        let percent = (received * 100 / self.size).min(100);
+        let text = rust_i18n::t!(
+            "progress.downloading_percent",
+            locale = self.locale,
+            size = megabytes(self.size),
+            percent = percent
+        );
+        self.edit(text.into()).await;
+    }
+
+    async fn parsing(&mut self) {
+        self.edit(rust_i18n::t!("progress.parsing", locale = self.locale).into())
+            .await;
+    }
+
+    async fn edit(&mut self, text: String) {
+        let Some((bot, message)) = &self.status else {
+            return;
+        };
+        self.last_edit = Instant::now();
+        if let Err(err) = bot
+            .edit_message_text(message.chat.id, message.id, text)
+            .await
+        {
+            tracing::debug!("Failed to update the download progress: {err:?}");
+        }
+    }
+
+    /// Deletes the message, making way for the answer.
+    async fn finish(self) {
+        let Some((bot, message)) = self.status else {
+            return;
+        };
+        if let Err(err) = bot.delete_message(message.chat.id, message.id).await {
+            tracing::warn!("Failed to delete the download progress message: {err:?}");
+        }
+    }
+}
+
+/// `bytes` in whole megabytes, rounded up, for the progress message.
+fn megabytes(bytes: u64) -> String {
+    bytes.div_ceil(1024 * 1024).to_string()
+}
+
+/// Saves the leading bytes of a file above the Bot API limit to the cache directory, and
+/// tells whether the rest of the file may hold metadata too.
+async fn download_large_header(
+    message: &GramMessage,
+    file: &LargeFile<'_>,
+) -> Result<(PathBuf, bool)> {
+    let local_path = large_file_path(file, "").await?;
+
+    let started = Instant::now();
+    let downloaded = message
+        .download_media_header()
+        .await
+        .context("Failed to download large media with secondary client")?;
+
+    let reader = downloaded.ok_or_else(|| {
+        anyhow!(
+            "Secondary client reported no downloadable media for message {}",
+            message.id()
+        )
+    })?;
+
+    let cursor = reader.into_inner();
+    let bytes = cursor.into_inner();
+    telemetry::downloaded(bytes.len() as u64);
+    tracing::info!(
+        download_ms = started.elapsed().as_millis() as u64,
+        download_bytes = bytes.len(),
+        "Downloaded large file header"
+    );
+
+    let mut output = CacheWriter::create(&local_path).await?;
+    output.write(&bytes).await?;
+    output.finish().await?;
+
+    Ok((local_path, metadata_may_trail(&bytes)))
+}
+
+/// Answers the buttons under the bot's results.
+async fn handle_callback_query(
+    bot: Bot,
+    state: Arc<AppState>,
+    query: CallbackQuery,
+) -> Result<(), teloxide::RequestError> {
+    let data = query.data.as_deref().unwrap_or_default();
+    let locale = locale_from_language_code(query.from.language_code.as_deref());
+
+    if !may_use_bot(&state, query.from.id) {
+        bot.answer_callback_query(query.id).await?;
+        return Ok(());
+    }
+
+    if let Some(platform) = resend_help::parse_callback(data) {
+        bot.answer_callback_query(query.id)
+            .text(platform.steps(locale))
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    }
+
+    if let Some(section) = styles::parse_sections_callback(data) {
+        // Each user toggles their own sections, whoever opened the menu.
+        let mut hidden = None;
+        let stored = state
+            .settings
+            .update_user(query.from.id, |settings| {
+                settings.hidden_sections = settings.hidden_sections.toggled(section);
+                hidden = Some(settings.hidden_sections);
+            })
+            .await;
+        let mut answer = bot.answer_callback_query(query.id);
+        match (stored, hidden, &query.message) {
+            (Ok(()), Some(hidden), Some(menu)) => {
+                if let Err(err) =
+                    replace_keyboard(&bot, menu, styles::sections_keyboard(hidden, locale)).await
+                {
+                    tracing::warn!("Failed to redraw caption sections: {err:?}");
+                }
+            }
+            (Ok(()), _, _) => {}
+            (Err(err), _, _) => {
+                tracing::error!(
+                    "Failed to store caption sections for user {}: {err:?}",
+                    query.from.id.0
+                );
+                answer = answer
+                    .text(rust_i18n::t!("messages.settings_error", locale = locale))
+                    .show_alert(true);
+            }
+        }
+        answer.await?;
+        return Ok(());
+    }
+
+    if let (Some(format), Some(offer)) = (export::parse_callback(data), query.message.clone()) {
+        let reply = ReplyTarget::for_message(&offer);
+        let outcome =
+            send_batch_export(&bot, &state, reply, offer.id, query.from.id, format, locale).await;
+        let mut answer = bot.answer_callback_query(query.id);
+        match outcome {
+            Ok(None) => {}
+            Ok(Some(notice)) => answer = answer.text(notice).show_alert(true),
+            Err(err) => {
+                tracing::error!("Failed to send {format:?} export: {err:?}");
+                answer = answer
+                    .text(rust_i18n::t!("actions.failed", locale = locale))
+                    .show_alert(true);
+            }
+        }
+        answer.await?;
+        return Ok(());
+    }
+
+    let (Some(callback), Some(result)) = (actions::parse_callback(data), query.message.clone())
+    else {
+        // Still answer, so the client stops showing a loading indicator.
+        bot.answer_callback_query(query.id).await?;
+        return Ok(());
+    };
+
+    // Actions download the image again, so keep them off the dispatcher.
+    let in_flight = state.in_flight.clone();
+    let span = update_span("callback_query", &result, &state.config);
+    in_flight.spawn(
+        async move {
+            let outcome = run_result_callback(&bot, &state, &result, callback, locale).await;
+            let mut answer = bot.answer_callback_query(query.id);
+            match outcome {
+                Ok(None) => {}
+                Ok(Some(notice)) => answer = answer.text(notice).show_alert(true),
+                Err(err) => {
+                    tracing::error!("Failed to run result action: {err:?}");
+                    answer = answer
+                        .text(rust_i18n::t!("actions.failed", locale = locale))
+                        .show_alert(true);
+                }
+            }
+            if let Err(err) = answer.await {
+                tracing::warn!("Failed to answer callback query: {err:?}");
+            }
+        }
+        .instrument(span),
+    );
+
+    Ok(())
+}
+
+/// Handles a button press under the bot's `result` message.
+///
+/// Returns a notice to show instead of a reply when there is nothing to send.
+async fn run_result_callback(
+    bot: &Bot,
+    state: &AppState,
+    result: &Message,
+    callback: ResultCallback,
+    locale: &str,
+) -> Result<Option<String>> {
+    let media_kind = image_file_id(result, &state.config)
+        .map(|selection| selection.media_kind())
+        .unwrap_or(ReceivedImage::Document);
+    let reply = ReplyTarget::for_message(result);
+
+    let removal = match callback {
+        ResultCallback::Action(ResultAction::Strip) => {
+            let keyboard = actions::strip_keyboard(Categories::default(), locale);
+            return replace_keyboard(bot, result, keyboard).await.map(|()| None);
+        }
+        ResultCallback::Action(action) => {
+            return run_result_action(bot, state, result, action, locale).await;
+        }
+        ResultCallback::StripMenu(selection) => {
+            let keyboard = actions::strip_keyboard(selection, locale);
+            return replace_keyboard(bot, result, keyboard).await.map(|()| None);
+        }
+        ResultCallback::Back => {
+            let keyboard = result_keyboard(media_kind, locale);
+            return replace_keyboard(bot, result, keyboard).await.map(|()| None);
+        }
+        ResultCallback::StripSelected(selection) if selection.is_empty() => {
+            return Ok(Some(
+                rust_i18n::t!("actions.strip_nothing_selected", locale = locale).into_owned(),
+            ));
+        }
+        ResultCallback::StripSelected(selection) => Removal::Selected(selection),
+        ResultCallback::StripAll => Removal::All,
+    };
+
+    let notice = strip_message_image(bot, state, reply, result, removal, locale).await?;
+    if notice.is_none() {
+        replace_keyboard(bot, result, result_keyboard(media_kind, locale)).await?;
+    }
+    Ok(notice)
+}
+
+async fn replace_keyboard(bot: &Bot, msg: &Message, keyboard: InlineKeyboardMarkup) -> Result<()> {
+    bot.edit_message_reply_markup(msg.chat.id, msg.id)
+        .reply_markup(keyboard)
+        .await
+        .context("Failed to update result buttons")?;
+    Ok(())
+}
+
+/// Runs `action` on the image attached to the bot's `result` message.
+async fn run_result_action(
+    bot: &Bot,
+    state: &AppState,
+    result: &Message,
+    action: ResultAction,
+    locale: &str,
+) -> Result<Option<String>> {
+    let reply = ReplyTarget::for_message(result);
+    // The location goes out under the settings of whoever sent the image.
+    let options = caption_options(&state.settings, result.reply_to_message().unwrap_or(result));
+
+    if let ResultAction::Map = action {
+        return send_image_location(bot, state, reply, result, options, locale).await;
+    }
+
+    let Some(exif) = read_message_exif(bot, state, result).await? else {
+        return Ok(Some(
+            rust_i18n::t!("actions.no_exif", locale = locale).into_owned(),
+        ));
+    };
+
+    match action {
+        ResultAction::Dump => {
+            let dump = exif::dump_fields(&exif, options);
+            if dump.chars().count() <= TELEGRAM_MESSAGE_LIMIT {
+                let request = reply
+                    .replying_to(result.id)
+                    .apply(bot.send_message(reply.chat_id, dump));
+                retry::send(request)
+                    .await
+                    .context("Failed to send EXIF dump")?;
+            } else {
+                let file = InputFile::memory(dump.into_bytes()).file_name("exif.txt");
+                send_reply_document(bot, reply, result.id, file).await?;
+            }
+        }
+        ResultAction::Json => {
+            send_exif_json(bot, &state.lookups, reply, result.id, exif, options, locale).await?
+        }
+        ResultAction::Hashtags => {
+            // Hashtags are shared across languages, so the city is looked up in English,
+            // at the position the caption may show.
+            let position = exif::gps_location(&exif);
+            let city = match options.shown_position(position) {
+                Some((latitude, longitude)) if !options.at_position(position).hide_location => {
+                    exif::locate_at(&state.lookups, latitude, longitude, Some("en"))
+                        .await
+                        .city
+                }
+                _ => None,
+            };
+            let facts = ShotFacts::from_exif(&exif);
+            let hashtags = hashtags::format_hashtags(&facts, city.as_deref());
+            let Some(hashtags) = hashtags else {
+                return Ok(Some(
+                    rust_i18n::t!("actions.no_hashtags", locale = locale).into_owned(),
+                ));
+            };
+
+            let request = reply
+                .replying_to(result.id)
+                .apply(bot.send_message(reply.chat_id, hashtags));
+            retry::send(request)
+                .await
+                .context("Failed to send hashtags")?;
+        }
+        ResultAction::Qr => {
+            let Some(position) = exif::gps_location(&exif) else {
+                return Ok(Some(
+                    rust_i18n::t!("actions.no_gps", locale = locale).into_owned(),
+                ));
+            };
+            let Some((latitude, longitude)) = options.shown_position(Some(position)) else {
+                return Ok(Some(
+                    rust_i18n::t!("actions.location_hidden", locale = locale).into_owned(),
+                ));
+            };
+            let uri = geo::links::geo_uri(latitude, longitude);
+            let image = qr::render_png(&uri)?;
+            let request = reply.replying_to(result.id).apply(
+                bot.send_photo(
+                    reply.chat_id,
+                    InputFile::memory(image).file_name("location.png"),
+                )
+                .caption(markup::code(&uri))
+                .parse_mode(ParseMode::Html),
+            );
+            request.await.context("Failed to send location QR code")?;
+        }
+        ResultAction::Map => unreachable!("handled above"),
+        ResultAction::Strip => unreachable!("opens the removal menu instead"),
+    }
+
+    Ok(None)
+}
+
+/// Sends the parsed summary and raw fields of `exif` as `exif.json`, replying to `reply_to`.
+/// The position is rounded or left out as in the caption `options`.
+async fn send_exif_json(
+    bot: &Bot,
+    lookups: &Lookups,
+    reply: ReplyTarget,
+    reply_to: MessageId,
+    exif: ::exif::Exif,
+    options: CaptionOptions,
+    locale: &str,
+) -> Result<()> {
+    let options = options.at_position(exif::gps_location(&exif));
+    let address = exif::geocode(lookups, &exif, Some(locale), options).await;
+    let document = exif::exif_json(&exif, address.as_ref(), options);
+
+    let json = serde_json::to_vec_pretty(&document).context("Failed to serialize EXIF fields")?;
+    let file = InputFile::memory(json).file_name("exif.json");
+    send_reply_document(bot, reply, reply_to, file).await?;
+    Ok(())
+}
+
+/// Sends the GPS position of `source`'s image replying to it: a tappable venue when
+/// the place could be geocoded, a plain location pin otherwise.
+///
+/// The position is rounded or hidden as in the caption `options`, and a coarse private
+/// zone or a hidden place name leaves out the venue.
+///
+/// Returns a notice to show instead when the image is not geotagged or its position
+/// is hidden.
+async fn send_image_location(
+    bot: &Bot,
+    state: &AppState,
+    reply: ReplyTarget,
+    source: &Message,
+    options: CaptionOptions,
+    locale: &str,
+) -> Result<Option<String>> {
+    let exif = read_message_exif(bot, state, source).await?;
+    let Some(position) = exif.as_ref().and_then(exif::gps_location) else {
+        return Ok(Some(
+            rust_i18n::t!("actions.no_gps", locale = locale).into_owned(),
+        ));
+    };
+    let Some((latitude, longitude)) = options.shown_position(Some(position)) else {
+        return Ok(Some(
+            rust_i18n::t!("actions.location_hidden", locale = locale).into_owned(),
+        ));
+    };
+
+    let options = options.at_position(Some(position));
+    let place = if options.hide_location || options.coarse_location {
+        exif::Place {
+            latitude,
+            longitude,
+            title: None,
+            address: None,
+            city: None,
+        }
+    } else {
+        exif::locate_at(&state.lookups, latitude, longitude, Some(locale)).await
+    };
+
+    let links = actions::map_links_keyboard(place.latitude, place.longitude);
+    let sent = match (place.title, place.address) {
+        (Some(title), Some(address)) => {
+            let request = reply.replying_to(source.id).apply(
+                bot.send_venue(
+                    reply.chat_id,
+                    place.latitude,
+                    place.longitude,
+                    title,
+                    address,
+                )
+                .reply_markup(links),
+            );
+            request.await
+        }
+        _ => {
+            let request = reply.replying_to(source.id).apply(
+                bot.send_location(reply.chat_id, place.latitude, place.longitude)
+                    .reply_markup(links),
+            );
+            request.await
+        }
+    };
+    sent.context("Failed to send image location")?;
+
+    Ok(None)
+}
+
+/// Answers `/json` with `target`'s EXIF data as a JSON file.
+async fn reply_with_json(
+    bot: &Bot,
+    state: &AppState,
+    reply: ReplyTarget,
+    target: &Message,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    if image_file_id(target, &state.config).is_none() {
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.request_image", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    // The position goes out under the settings of whoever sent the image.
+    let options = caption_options(&state.settings, target);
+    let outcome = match read_message_exif(bot, state, target).await {
+        Ok(Some(exif)) => {
+            send_exif_json(bot, &state.lookups, reply, target.id, exif, options, locale).await
+        }
+        Ok(None) => {
+            send_text(
+                bot,
+                reply,
+                rust_i18n::t!("actions.no_exif", locale = locale),
+            )
+            .await?;
+            return Ok(());
+        }
+        Err(err) => Err(err),
+    };
+
+    if let Err(err) = outcome {
+        tracing::error!("Failed to send EXIF JSON: {err:?}");
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.process_error", locale = locale),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Answers `/map` with a location pin for `target`'s image.
+async fn reply_with_location(
+    bot: &Bot,
+    state: &AppState,
+    reply: ReplyTarget,
+    target: &Message,
+    options: CaptionOptions,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    if image_file_id(target, &state.config).is_none() {
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.request_image", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    match send_image_location(bot, state, reply, target, options, locale).await {
+        Ok(None) => {}
+        Ok(Some(notice)) => {
+            send_text(bot, reply, notice).await?;
+        }
+        Err(err) => {
+            tracing::error!("Failed to send image location: {err:?}");
+            send_text(
+                bot,
+                reply,
+                rust_i18n::t!("messages.process_error", locale = locale),
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the complete EXIF block of the image attached to `msg`.
+async fn read_message_exif(
+    bot: &Bot,
+    state: &AppState,
+    msg: &Message,
+) -> Result<Option<::exif::Exif>> {
+    let selection = image_file_id(msg, &state.config).context("Message carries no image")?;
+
+    match selection {
+        ImageSelection::Inline { file_id, .. } => {
+            let file = telegram_file(bot, &file_id).await?;
+            file.read_exif(bot.client()).await
+        }
+        ImageSelection::TooLarge {
+            media_kind, size, ..
+        } => {
+            let file = LargeFile {
+                chat_id: msg.chat.id,
+                message_id: msg.id.0,
+                media_kind,
+                username: msg.chat.username(),
+                size,
+                cache_dir: &state.config.cache_dir,
+            };
+            read_large_file(
+                state.extra_client.as_ref(),
+                &file,
+                &mut Progress::hidden(),
+                exif::read_exif,
+                Option::is_some,
+            )
+            .await
+        }
+    }
+}
+
+/// Answers `/strip` with a copy of `target`'s image without its metadata.
+async fn reply_with_stripped(
+    bot: &Bot,
+    state: &AppState,
+    reply: ReplyTarget,
+    target: &Message,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    if image_file_id(target, &state.config).is_none() {
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.request_image", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    match strip_message_image(bot, state, reply, target, Removal::All, locale).await {
+        Ok(None) => {}
+        Ok(Some(notice)) => {
+            send_text(bot, reply, notice).await?;
+        }
+        Err(err) => {
+            tracing::error!("Failed to strip image metadata: {err:?}");
+            send_text(
+                bot,
+                reply,
+                rust_i18n::t!("messages.process_error", locale = locale),
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends the image of `source` back as a document without the metadata in `removal`.
+///
+/// Returns a notice to show instead when the file cannot be rewritten.
+async fn strip_message_image(
+    bot: &Bot,
+    state: &AppState,
+    reply: ReplyTarget,
+    source: &Message,
+    removal: Removal,
+    locale: &str,
+) -> Result<Option<String>> {
+    // Rewriting needs the whole file, which the Bot API only serves up to its size limit.
+    let file_id = match image_file_id(source, &state.config).context("Message carries no image")? {
+        ImageSelection::Inline { file_id, .. } => file_id,
+        ImageSelection::TooLarge { .. } => {
+            return Ok(Some(
+                rust_i18n::t!("actions.strip_too_large", locale = locale).into_owned(),
+            ));
+        }
+    };
+
+    let bytes = download_telegram_file(bot, &file_id)
+        .await
+        .context("Failed to download image for stripping")?;
+
+    let stripped = match strip::strip_metadata(&bytes, removal) {
+        Ok(stripped) => stripped,
+        Err(StripError::Unsupported) => {
+            return Ok(Some(
+                rust_i18n::t!("actions.strip_unsupported", locale = locale).into_owned(),
+            ));
+        }
+        Err(err) => return Err(err).context("Failed to strip image metadata"),
+    };
+
+    let file_name = source
+        .document()
+        .and_then(|doc| doc.file_name.clone())
+        .unwrap_or_else(|| String::from("image.jpg"));
+    let file = InputFile::memory(stripped).file_name(file_name);
+    send_reply_document(bot, reply, source.id, file).await?;
+
+    Ok(None)
+}
+
+/// The annotated copies of an image the bot can draw.
+#[derive(Clone, Copy)]
+enum Rendering {
+    Watermark(WatermarkOptions),
+    Frame,
+}
+
+async fn reply_with_rendering(
+    bot: &Bot,
+    state: &AppState,
+    reply: ReplyTarget,
+    target: &Message,
+    rendering: Rendering,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    let Some(selection) = image_file_id(target, &state.config) else {
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.request_image", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    };
+    let Some(typeface) = state.typeface.clone() else {
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("watermark.unavailable", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    };
+    // Drawing needs the whole file, which the Bot API only serves up to its size limit.
+    let ImageSelection::Inline { file_id, .. } = selection else {
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("watermark.too_large", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    let outcome = match render_image(bot, &file_id, typeface, rendering).await {
+        Ok(Some(image)) => {
+            let file_name = target
+                .document()
+                .and_then(|doc| doc.file_name.as_deref())
+                .and_then(|name| Path::new(name).file_stem())
+                .and_then(|stem| stem.to_str())
+                .unwrap_or("image")
+                .to_string();
+            let suffix = match rendering {
+                Rendering::Watermark(_) => "exif",
+                Rendering::Frame => "framed",
+            };
+            let file = InputFile::memory(image).file_name(format!("{file_name}-{suffix}.jpg"));
+            send_reply_document(bot, reply, target.id, file)
+                .await
+                .map(|_| ())
+        }
+        Ok(None) => {
+            send_text(
+                bot,
+                reply,
+                rust_i18n::t!("actions.no_exif", locale = locale),
+            )
+            .await?;
+            return Ok(());
+        }
+        Err(err) => Err(err),
+    };
+
+    if let Err(err) = outcome {
+        tracing::error!("Failed to render annotated image: {err:?}");
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.process_error", locale = locale),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Downloads the image and draws its EXIF values on; `None` when there is nothing to draw.
+async fn render_image(
+    bot: &Bot,
+    file_id: &str,
+    typeface: Typeface,
+    rendering: Rendering,
+) -> Result<Option<Vec<u8>>> {
+    let bytes = download_telegram_file(bot, file_id)
+        .await
+        .context("Failed to download image for rendering")?;
+
+    task::spawn_blocking(move || {
+        let Some(exif) = exif::read_exif_from_bytes(&bytes)? else {
+            return Ok(None);
+        };
+        let facts = ShotFacts::from_exif(&exif);
+        match rendering {
+            Rendering::Watermark(options) => {
+                let lines = watermark::overlay_lines(&facts);
+                if lines.is_empty() {
+                    return Ok(None);
+                }
+                watermark::render(&typeface, &bytes, &lines, options).map(Some)
+            }
+            Rendering::Frame => frame::render(&typeface, &bytes, &facts).map(Some),
+        }
+    })
+    .await
+    .context("Rendering task panicked")?
+}
+
+/// Sends the EXIF summary of `target` drawn as a card, for posting as an image.
+///
+/// The card honours the requesting user's caption settings, since it is made to be shared.
+async fn reply_with_card(
+    bot: &Bot,
+    state: &AppState,
+    reply: ReplyTarget,
+    target: &Message,
+    theme: Theme,
+    options: CaptionOptions,
+    locale: &str,
+) -> Result<(), teloxide::RequestError> {
+    if image_file_id(target, &state.config).is_none() {
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.request_image", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    }
+    let Some(typeface) = state.typeface.clone() else {
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("watermark.unavailable", locale = locale),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    let outcome = match read_message_exif(bot, state, target).await {
+        Ok(Some(exif)) => {
+            let options = options.at_position(exif::gps_location(&exif));
+            let address = exif::geocode(&state.lookups, &exif, Some(locale), options).await;
+            let language = locale.to_string();
+            let card = task::spawn_blocking(move || {
+                let data = ParsedExif::from_exif(&exif, address.as_ref());
+                let content = CardContent::new(&data, options, &language);
+                card::render(&typeface, &content, theme)
+            })
+            .await
+            .context("Card task panicked");
+            match card {
+                Ok(Ok(card)) => {
+                    let request =
+                        reply.replying_to(target.id).apply(bot.send_photo(
+                            reply.chat_id,
+                            InputFile::memory(card).file_name("exif.png"),
+                        ));
+                    request.await.map(|_| ()).context("Failed to send card")
+                }
+                Ok(Err(err)) | Err(err) => Err(err),
+            }
+        }
+        Ok(None) => {
+            send_text(
+                bot,
+                reply,
+                rust_i18n::t!("actions.no_exif", locale = locale),
+            )
+            .await?;
+            return Ok(());
+        }
+        Err(err) => Err(err),
+    };
+
+    if let Err(err) = outcome {
+        tracing::error!("Failed to render EXIF card: {err:?}");
+        send_text(
+            bot,
+            reply,
+            rust_i18n::t!("messages.process_error", locale = locale),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn send_reply_document(
+    bot: &Bot,
+    reply: ReplyTarget,
+    reply_to: MessageId,
+    file: InputFile,
+) -> Result<Message> {
+    let request = reply
+        .replying_to(reply_to)
+        .apply(bot.send_document(reply.chat_id, file));
+    retry::send(request)
+        .await
+        .context("Failed to send document")
+}
+
+async fn handle_inline_query(
+    bot: Bot,
+    state: Arc<AppState>,
+    query: InlineQuery,
+) -> Result<(), teloxide::RequestError> {
+    let locale = locale_from_language_code(query.from.language_code.as_deref());
+    let needle = query.query.trim().to_lowercase();
+
+    let results: Vec<InlineQueryResult> = state
+        .recent
+        .list(query.from.id)
+        .into_iter()
+        .filter(|entry| {
+            needle.is_empty()
+                || markup::to_plain(&entry.caption)
+                    .to_lowercase()
+                    .contains(&needle)
+        })
+        .take(MAX_INLINE_RESULTS)
+        .enumerate()
+        .map(|(index, entry)| {
+            let title = inline_result_title(&entry.caption);
+            InlineQueryResult::CachedDocument(
+                InlineQueryResultCachedDocument::new(
+                    format!("{index}-{}", entry.file_unique_id),
+                    title,
+                    entry.file_id,
+                )
+                .caption(entry.caption)
+                .parse_mode(ParseMode::Html),
+            )
+        })
+        .collect();
+
+    let mut answer = bot
+        .answer_inline_query(query.id, results.clone())
+        // Results are personal, so let Telegram cache them per user for a short while only.
+        .is_personal(true)
+        .cache_time(INLINE_CACHE_SECONDS);
+
+    if results.is_empty() {
+        answer = answer
+            .switch_pm_text(rust_i18n::t!("messages.inline_empty", locale = locale).to_string())
+            .switch_pm_parameter("inline");
+    }
+
+    answer.await?;
+    Ok(())
+}
+
+/// Picks the camera line of a caption as the inline result title.
+fn inline_result_title(caption: &str) -> String {
+    let caption = markup::to_plain(caption);
+    caption
+        .lines()
+        .find_map(|line| line.strip_prefix("📸: "))
+        .or_else(|| caption.lines().next())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// The signed-in user client, with what it takes to stop it.
+struct SecondaryClient {
+    client: GramClient,
+    pool: SenderPoolHandle,
+    runner: JoinHandle<()>,
+    /// Sealed again once the runner stopped, when the session is encrypted.
+    session: SessionFile,
+}
+
+/// Signs in the user client; `None` when no credentials are configured for it.
+async fn init_extra_client(
+    config: &Config,
+    bot_token: &str,
+    session_path: PathBuf,
+) -> Result<Option<SecondaryClient>> {
+    let Some(credentials) = &config.secondary_client else {
+        tracing::info!("No secondary client configured; large files go through the Bot API");
+        return Ok(None);
+    };
+    let api_id = credentials.api_id;
+    let mut session_file = SessionFile::open(session_path)?;
+
+    let session = Arc::new(SqliteSession::open(session_file.path()).with_context(|| {
+        format!(
+            "Failed to open session file at `{}`",
+            session_file.path().display()
+        )
+    })?);
+
+    let pool = match &config.proxy {
+        Some(proxy) => SenderPool::with_configuration(
+            Arc::clone(&session),
+            api_id,
+            ConnectionParams {
+                proxy_url: Some(proxy.to_string()),
+                ..ConnectionParams::default()
+            },
+        ),
+        None => SenderPool::new(Arc::clone(&session), api_id),
+    };
+    let client = GramClient::new(&pool);
+    let SenderPool {
+        runner,
+        updates: _,
+        handle,
+    } = pool;
+
+    let runner = tokio::spawn(async move {
+        runner.run().await;
+        tracing::info!("Grammers sender runner stopped.");
+    });
+
+    if !client.is_authorized().await? {
+        tracing::info!("Signing in secondary Telegram client...");
+        client
+            .bot_sign_in(bot_token, &credentials.api_hash)
+            .await
+            .context("Failed to sign in the secondary Telegram client")?;
+        tracing::info!("Secondary Telegram client signed in.");
+    }
+    session_file
+        .save()
+        .context("Failed to seal the session after signing in")?;
+
+    Ok(Some(SecondaryClient {
+        client,
+        pool: handle,
+        runner,
+        session: session_file,
+    }))
+}
+
+async fn fetch_secondary_message(
+    extra_client: &GramClient,
+    chat_id: ChatId,
+    message_id: i32,
+    username: Option<&str>,
+) -> Result<Option<GramMessage>> {
+    let ids = [message_id];
+    let messages = match (username, channel_id_from_bot_api(chat_id)) {
+        // Private channels and supergroups have no username to resolve, and bots cannot
+        // list their dialogs, so address the channel directly. Bots may use a zero
+        // access hash for channels they are a member of.
+        (None, Some(channel_id)) => {
+            let peer = PeerRef {
+                id: PeerId::channel(channel_id),
+                auth: PeerAuth::default(),
+            };
+            waiting_out_floods(|| extra_client.get_messages_by_id(peer, &ids)).await?
+        }
+        _ => {
+            let peer = resolve_peer_for_chat(extra_client, chat_id, username).await?;
+            waiting_out_floods(|| extra_client.get_messages_by_id(peer.clone(), &ids)).await?
+        }
+    };
+
+    Ok(messages.into_iter().next().flatten())
+}
+
+/// Paces the secondary client's requests, queueing them for as long as a `FLOOD_WAIT`
+/// asks and trying again, so large files wait their turn instead of failing.
+async fn waiting_out_floods<T, F, Fut>(mut call: F) -> Result<T, InvocationError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, InvocationError>>,
+{
+    let mut retries = 0;
+    loop {
+        SECONDARY_LIMITER.acquire().await;
+        let err = match call().await {
+            Ok(output) => return Ok(output),
+            Err(err) => err,
+        };
+        let wait = match flood_wait(&err) {
+            Some(wait) if retries < MAX_FLOOD_RETRIES && wait <= MAX_FLOOD_WAIT => wait,
+            _ => return Err(err),
+        };
+        retries += 1;
+        tracing::warn!(
+            "Secondary client was asked to wait {}s; queueing its requests",
+            wait.as_secs()
+        );
+        SECONDARY_LIMITER.back_off(wait);
+    }
+}
+
+/// The pause an error 420 (`FLOOD_WAIT_X` and the like) asks for.
+fn flood_wait(err: &InvocationError) -> Option<Duration> {
+    match err {
+        InvocationError::Rpc(rpc) if rpc.code == 420 => {
+            Some(Duration::from_secs(rpc.value.unwrap_or(1).into()))
+        }
+        _ => None,
+    }
+}
+
+/// Converts a Bot API `-100…` chat id into the MTProto channel id.
+fn channel_id_from_bot_api(chat_id: ChatId) -> Option<i64> {
+    const CHANNEL_ID_OFFSET: i64 = -1_000_000_000_000;
+    (chat_id.0 < CHANNEL_ID_OFFSET).then(|| CHANNEL_ID_OFFSET - chat_id.0)
+}
+
+async fn resolve_peer_for_chat(
+    extra_client: &GramClient,
+    chat_id: ChatId,
+    username: Option<&str>,
+) -> Result<GramPeer> {
+    if let Some(username) = username {
+        match waiting_out_floods(|| extra_client.resolve_username(username)).await? {
+            Some(peer) => {
+                tracing::info!(
+                    "Resolved username {} to peer {}",
+                    username,
+                    peer.id().bot_api_dialog_id()
+                );
+                return Ok(peer);
+            }
+            None => {
+                tracing::info!("Secondary client could not resolve username {}", username);
+            }
+        }
+    }
+
+    let mut dialogs = extra_client.iter_dialogs();
+    while let Some(dialog) = dialogs.next().await? {
+        let peer = dialog.peer().clone();
+        if peer.id().bot_api_dialog_id() == chat_id.0 {
+            return Ok(peer);
+        }
+    }
+
+    Err(anyhow!(
+        "Peer with chat_id {} not found in secondary client dialogs",
+        chat_id.0
+    ))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ReceivedImage {
+    Document,
+    /// A compressed photo; Telegram strips most of its metadata.
+    Photo,
+}
+
+/// The largest file the Bot API downloads; anything above it needs the secondary client.
+/// A local Bot API server serves files of up to 2000 MB.
+fn inline_size_limit(config: &Config) -> u64 {
+    if config.bot_api_url.is_some() {
+        LOCAL_SERVER_SIZE_LIMIT
+    } else {
+        MAX_INLINE_SIZE
+    }
+}
+
+fn image_file_id(msg: &Message, config: &Config) -> Option<ImageSelection> {
+    if let MessageKind::Common(common) = &msg.kind {
+        match &common.media_kind {
+            MediaKind::Photo(photo) => {
+                // Sizes are listed smallest first, but pick by area to be safe.
+                let largest = photo
+                    .photo
+                    .iter()
+                    .max_by_key(|size| u64::from(size.width) * u64::from(size.height))?;
+
+                Some(select_image(
+                    largest.file.id.clone(),
+                    largest.file.unique_id.clone(),
+                    ReceivedImage::Photo,
+                    file_meta_size_bytes(&largest.file),
+                    inline_size_limit(config),
+                ))
+            }
+            MediaKind::Document(doc) => {
+                let is_image = doc
+                    .document
+                    .mime_type
+                    .as_ref()
+                    .map(|mime| mime.essence_str().starts_with("image/"))
+                    .unwrap_or(false);
+
+                if !is_image {
+                    return None;
+                }
+
+                let file_id = doc.document.file.id.clone();
+                let unique_id = doc.document.file.unique_id.clone();
+                let size = document_size_bytes(&doc.document);
+                Some(select_image(
+                    file_id,
+                    unique_id,
+                    ReceivedImage::Document,
+                    size,
+                    inline_size_limit(config),
+                ))
+            }
+            _ => None,
+        }
+    } else {
+        None
+    }
+}
+
+fn select_image(
+    file_id: String,
+    unique_id: String,
+    media_kind: ReceivedImage,
+    size: Option<u64>,
+    limit: u64,
+) -> ImageSelection {
+    if let Some(size) = size {
+        if size > limit {
+            return ImageSelection::TooLarge {
+                file_id,
+                unique_id,
+                media_kind,
+                size,
+            };
+        }
+    }
+
+    ImageSelection::Inline {
+        file_id,
+        unique_id,
+        media_kind,
+    }
+}
+
+fn file_meta_size_bytes(meta: &FileMeta) -> Option<u64> {
+    Some(meta.size as u64)
+}
+
+fn document_size_bytes(document: &teloxide::types::Document) -> Option<u64> {
+    file_meta_size_bytes(&document.file)
+}
+
+/// Where answers to a message go: the same chat and, in forums, the same topic.
+#[derive(Clone, Copy)]
+struct ReplyTarget {
+    chat_id: ChatId,
+    thread_id: Option<i32>,
+    /// The message answers quote, so busy chats show which image a result belongs to.
+    reply_to: Option<MessageId>,
+}
+
+impl ReplyTarget {
+    fn for_message(msg: &Message) -> Self {
+        Self {
+            chat_id: msg.chat.id,
+            // Messages in the General topic carry no thread id and need none on replies.
+            thread_id: msg.thread_id.filter(|_| msg.is_topic_message),
+            reply_to: Some(msg.id),
+        }
+    }
+
+    /// The same chat and topic, answering `message` instead.
+    fn replying_to(self, message: MessageId) -> Self {
+        Self {
+            reply_to: Some(message),
+            ..self
+        }
+    }
+
+    /// Sends `request` into the topic, quoting the message answered; the answer still
+    /// goes out when that message was deleted in the meantime.
+    fn apply<R: Addressed>(self, mut request: R) -> R {
+        if let Some(thread_id) = self.thread_id {
+            request = request.in_thread(thread_id);
+        }
+        if let Some(reply_to) = self.reply_to {
+            request = request.quoting(reply_to);
+        }
+        request
+    }
+}
+
+/// Send requests that can go to a forum topic as a reply, for [`ReplyTarget::apply`].
+trait Addressed: Sized {
+    fn in_thread(self, thread_id: i32) -> Self;
+    fn quoting(self, message: MessageId) -> Self;
+}
+
+macro_rules! addressed {
+    ($($request:ident),* $(,)?) => {
+        $(
+            impl Addressed for <Bot as Requester>::$request {
+                fn in_thread(self, thread_id: i32) -> Self {
+                    self.message_thread_id(thread_id)
+                }
+
+                fn quoting(self, message: MessageId) -> Self {
+                    self.reply_to_message_id(message)
+                        .allow_sending_without_reply(true)
+                }
+            }
+        )*
+    };
+}
+
+addressed!(
+    SendMessage,
+    SendPhoto,
+    SendDocument,
+    SendMediaGroup,
+    SendLocation,
+    SendVenue,
+    SendInvoice,
+);
+
+async fn send_text(
+    bot: &Bot,
+    reply: ReplyTarget,
+    text: impl Into<String>,
+) -> Result<Message, teloxide::RequestError> {
+    let request = reply.apply(bot.send_message(reply.chat_id, text));
+    retry::send(request).await
+}
+
+/// Sends a caption rendered as HTML as a text message, without link previews.
+async fn send_html(
+    bot: &Bot,
+    reply: ReplyTarget,
+    text: String,
+) -> Result<Message, teloxide::RequestError> {
+    let request = reply.apply(
+        bot.send_message(reply.chat_id, text)
+            .parse_mode(ParseMode::Html)
+            .disable_web_page_preview(true),
+    );
+    retry::send(request).await
+}
+
+/// Sends the rest of an overlong report as replies to the message carrying its caption.
+///
+/// Failures are only logged: the caption with the essentials already went out.
+async fn send_continuation(bot: &Bot, reply: ReplyTarget, caption: &Message, parts: &[String]) {
+    for part in parts {
+        let request = reply.replying_to(caption.id).apply(
+            bot.send_message(reply.chat_id, part.clone())
+                .parse_mode(ParseMode::Html)
+                .disable_web_page_preview(true),
+        );
+        if let Err(err) = retry::send(request).await {
+            tracing::warn!("Failed to send the rest of the report: {err:?}");
+            return;
+        }
+    }
+}
+
+async fn send_album_captions(
+    bot: &Bot,
+    reply: ReplyTarget,
+    images: &[AnalyzedImage],
+) -> Result<Vec<Message>> {
+    let media: Vec<InputMedia> = images.iter().map(input_media_for).collect();
+
+    let request = reply.apply(bot.send_media_group(reply.chat_id, media));
+    let sent = retry::send(request)
+        .await
+        .context("Failed to send EXIF summary album")?;
+
+    Ok(sent)
+}
+
+/// The action buttons, plus the "send as file" help for compressed photos.
+fn result_keyboard(media_kind: ReceivedImage, locale: &str) -> InlineKeyboardMarkup {
+    let keyboard = actions::keyboard(locale);
+    match media_kind {
+        ReceivedImage::Document => keyboard,
+        ReceivedImage::Photo => keyboard.append_row(resend_help::buttons(locale)),
+    }
+}
+
+fn input_media_for(image: &AnalyzedImage) -> InputMedia {
+    let file = InputFile::file_id(image.file_id.clone());
+    match image.media_kind {
+        ReceivedImage::Document => InputMedia::Document(
+            InputMediaDocument::new(file)
+                .caption(image.caption.clone())
+                .parse_mode(ParseMode::Html),
+        ),
+        ReceivedImage::Photo => InputMedia::Photo(
+            InputMediaPhoto::new(file)
+                .caption(image.caption.clone())
+                .parse_mode(ParseMode::Html),
+        ),
+    }
+}
+
+async fn send_caption_for_media(
+    bot: &Bot,
+    reply: ReplyTarget,
+    file_id: &str,
+    media_kind: ReceivedImage,
+    caption: String,
+    locale: &str,
+) -> Result<Message> {
+    let sent = match media_kind {
+        ReceivedImage::Document => {
+            let request = reply.apply(
+                bot.send_document(reply.chat_id, InputFile::file_id(file_id.to_owned()))
+                    .caption(caption)
+                    .parse_mode(ParseMode::Html)
+                    .reply_markup(result_keyboard(media_kind, locale)),
+            );
+            retry::send(request)
+                .await
+                .context("Failed to send EXIF summary document")?
+        }
+        ReceivedImage::Photo => {
+            let request = reply.apply(
+                bot.send_photo(reply.chat_id, InputFile::file_id(file_id.to_owned()))
+                    .caption(caption)
+                    .parse_mode(ParseMode::Html)
+                    .reply_markup(result_keyboard(media_kind, locale)),
+            );
+            retry::send(request)
+                .await
+                .context("Failed to send EXIF summary photo")?
+        }
+    };
+
+    Ok(sent)
+}
+
+#[cfg(test)]
+mod tests;
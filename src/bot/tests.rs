@@ -0,0 +1,456 @@
+//! Drives `handle_message` against a mock Bot API server, so the dispatcher logic is
+//! covered without a live token. Every method answers with a plain sent message, and
+//! the tests look at which methods were called with what text.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde_json::{Value as JsonValue, json};
+use wiremock::matchers::{body_string_contains, method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use super::*;
+use crate::config::{GeocoderConfig, LookupConfig, ProviderConfig};
+
+const TOKEN: &str = "123456:TEST";
+const CHAT_ID: i64 = 42;
+
+fn ok(result: JsonValue) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({ "ok": true, "result": result }))
+}
+
+fn user() -> JsonValue {
+    json!({ "id": CHAT_ID, "is_bot": false, "first_name": "Ada", "language_code": "en" })
+}
+
+fn chat() -> JsonValue {
+    json!({ "id": CHAT_ID, "type": "private", "first_name": "Ada" })
+}
+
+fn sent_message() -> JsonValue {
+    json!({ "message_id": 100, "date": 1_700_000_000, "chat": chat(), "text": "ok" })
+}
+
+fn incoming(media: JsonValue) -> Message {
+    let mut message = json!({
+        "message_id": 7,
+        "date": 1_700_000_000,
+        "chat": chat(),
+        "from": user(),
+    });
+    for (key, value) in media.as_object().unwrap() {
+        message[key] = value.clone();
+    }
+    serde_json::from_value(message).unwrap()
+}
+
+fn document(file_id: &str, size: u64) -> Message {
+    incoming(json!({
+        "document": {
+            "file_id": file_id,
+            "file_unique_id": format!("unique-{file_id}"),
+            "file_name": "image.jpg",
+            "mime_type": "image/jpeg",
+            "file_size": size,
+        }
+    }))
+}
+
+fn photo(file_id: &str, size: u64) -> Message {
+    incoming(json!({
+        "photo": [{
+            "file_id": file_id,
+            "file_unique_id": format!("unique-{file_id}"),
+            "width": 8,
+            "height": 8,
+            "file_size": size,
+        }]
+    }))
+}
+
+fn fixture(name: &str) -> Vec<u8> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    std::fs::read(path).unwrap()
+}
+
+/// Each test gets a directory of its own for the database and the stores.
+fn scratch_dir() -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "fotobot-test-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn state() -> Arc<AppState> {
+    let dir = scratch_dir();
+    let storage = Storage::open(&dir.join("fotobot.db")).unwrap();
+    // Nothing is looked up over the network.
+    let lookup_config = LookupConfig {
+        geocoder: GeocoderConfig {
+            primary: ProviderConfig {
+                provider: Some("off".to_owned()),
+                ..ProviderConfig::default()
+            },
+            ..GeocoderConfig::default()
+        },
+        elevation_url: Some("off".to_owned()),
+        overpass_url: Some("off".to_owned()),
+        ..LookupConfig::default()
+    };
+    let config = Arc::new(Config {
+        bot_token: TOKEN.to_owned(),
+        secondary_client: None,
+        bot_api_url: None,
+        data_dir: dir.clone(),
+        admin_ids: Vec::new(),
+        error_chat: None,
+        sentry_dsn: None,
+        proxy: None,
+        default_locale: "en",
+        mode: UpdateMode::Polling,
+        extra_bots: Vec::new(),
+        cache_dir: dir.join("cache"),
+        lookups: lookup_config,
+        redis_url: None,
+        metrics_addr: None,
+        static_map_url: None,
+        static_map_zoom: None,
+        telegraph_token: None,
+        ocr: None,
+        ocr_token: None,
+        tesseract: None,
+        ocr_languages: None,
+        premium_stars: None,
+        premium_days: None,
+        daily_quota: None,
+        premium_quota: None,
+        max_concurrent: None,
+        cache_ttl_minutes: None,
+        cache_max_mb: None,
+        font: None,
+        locales_dir: None,
+    });
+    let http = crate::http::client(None);
+    let lookups = Arc::new(Lookups::new(&config.lookups, &dir, http.clone()));
+    let me = serde_json::from_value(json!({
+        "id": 123_456,
+        "is_bot": true,
+        "first_name": "Fotobot",
+        "username": "fotobot_test_bot",
+        "can_join_groups": true,
+        "can_read_all_group_messages": false,
+        "supports_inline_queries": true,
+    }))
+    .unwrap();
+    Arc::new(AppState {
+        me,
+        extra_client: None,
+        recent: RecentResults::new(),
+        settings: SettingsStore::open(storage.clone(), &dir.join("settings.json")).unwrap(),
+        moderation: Moderation::open(storage.clone()).unwrap(),
+        known_chats: KnownChats::new(storage.clone()),
+        quota: Quota::new(storage.clone(), None, None),
+        premium: Premium::open(storage.clone(), None).unwrap(),
+        storage,
+        config,
+        stats: StatsStore::open(dir.join("stats.json")).unwrap(),
+        history: HistoryStore::open(dir.join("history.json")).unwrap(),
+        albums: AlbumCollector::new(UserId(123_456)),
+        replies: ReplyIndex::new(),
+        http,
+        links: crate::remote::client(None),
+        lookups,
+        static_map: None,
+        compare: ComparePairings::new(),
+        telegraph: None,
+        batches: BatchCollector::new(),
+        typeface: None,
+        #[cfg(feature = "ocr")]
+        ocr: None,
+        in_flight: InFlight::default(),
+        error_reports: ErrorReports::new(None),
+        queue: Arc::new(WorkQueue::new(1)),
+    })
+}
+
+/// A Bot API server that accepts every message the bot sends.
+struct MockTelegram {
+    server: MockServer,
+    bot: Bot,
+}
+
+impl MockTelegram {
+    async fn start() -> Self {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path_regex(r"(?i)/bot[^/]+/deletemessage$"))
+            .respond_with(ok(json!(true)))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path_regex(r"(?i)/bot[^/]+/(send|edit)\w*$"))
+            .respond_with(ok(sent_message()))
+            .mount(&server)
+            .await;
+        let bot = Bot::new(TOKEN).set_api_url(Url::parse(&server.uri()).unwrap());
+        Self { server, bot }
+    }
+
+    /// Answers `getFile` for `file_id`, and serves `bytes` as its content when given;
+    /// without them the download fails with `404`.
+    async fn file(&self, file_id: &str, size: usize, bytes: Option<Vec<u8>>) {
+        let file_path = format!("documents/{file_id}.jpg");
+        Mock::given(method("POST"))
+            .and(path_regex(r"(?i)/bot[^/]+/getfile$"))
+            .and(body_string_contains(file_id))
+            .respond_with(ok(json!({
+                "file_id": file_id,
+                "file_unique_id": format!("unique-{file_id}"),
+                "file_size": size,
+                "file_path": file_path,
+            })))
+            .mount(&self.server)
+            .await;
+        if let Some(bytes) = bytes {
+            Mock::given(method("GET"))
+                .and(path(format!("/file/bot{TOKEN}/{file_path}")))
+                .respond_with(ResponseTemplate::new(200).set_body_bytes(bytes))
+                .mount(&self.server)
+                .await;
+        }
+    }
+
+    /// The Bot API methods called, lowercased, with the text or caption they sent.
+    async fn calls(&self) -> Vec<(String, String)> {
+        let requests = self.server.received_requests().await.unwrap_or_default();
+        requests
+            .into_iter()
+            .filter_map(|request| {
+                let name = request.url.path().strip_prefix("/bot")?;
+                let name = name.rsplit('/').next()?.to_ascii_lowercase();
+                let body = serde_json::from_slice::<JsonValue>(&request.body).ok();
+                let text = body
+                    .as_ref()
+                    .and_then(|body| body.get("text").or_else(|| body.get("caption")))
+                    .and_then(JsonValue::as_str)
+                    .map_or_else(
+                        || String::from_utf8_lossy(&request.body).into_owned(),
+                        str::to_owned,
+                    );
+                Some((name, text))
+            })
+            .collect()
+    }
+
+    async fn sent(&self, name: &str) -> Vec<String> {
+        self.calls()
+            .await
+            .into_iter()
+            .filter(|(called, _)| called == name)
+            .map(|(_, text)| text)
+            .collect()
+    }
+}
+
+#[tokio::test]
+async fn document_is_answered_with_its_caption() {
+    let telegram = MockTelegram::start().await;
+    let bytes = fixture("canon_eos_r5.jpg");
+    telegram
+        .file("doc-1", bytes.len(), Some(bytes.clone()))
+        .await;
+
+    let msg = document("doc-1", bytes.len() as u64);
+    handle_message(telegram.bot.clone(), state(), msg)
+        .await
+        .unwrap();
+
+    let captions = telegram.sent("senddocument").await;
+    assert_eq!(captions.len(), 1, "{:?}", telegram.calls().await);
+    assert!(captions[0].contains("Canon EOS R5"), "{}", captions[0]);
+    assert!(captions[0].contains("1/250"), "{}", captions[0]);
+}
+
+#[tokio::test]
+async fn compressed_photo_is_answered_with_the_resend_note() {
+    let telegram = MockTelegram::start().await;
+    let bytes = fixture("no_exif.jpg");
+    telegram
+        .file("photo-1", bytes.len(), Some(bytes.clone()))
+        .await;
+
+    let msg = photo("photo-1", bytes.len() as u64);
+    handle_message(telegram.bot.clone(), state(), msg)
+        .await
+        .unwrap();
+
+    let note = rust_i18n::t!("messages.compressed_photo_note", locale = "en");
+    let captions = telegram.sent("sendphoto").await;
+    assert_eq!(captions.len(), 1, "{:?}", telegram.calls().await);
+    assert!(captions[0].contains(note.as_ref()), "{}", captions[0]);
+    assert!(telegram.sent("senddocument").await.is_empty());
+}
+
+#[tokio::test]
+async fn unreadable_document_gets_an_error_reply() {
+    let telegram = MockTelegram::start().await;
+    telegram.file("doc-2", 2048, None).await;
+
+    let msg = document("doc-2", 2048);
+    handle_message(telegram.bot.clone(), state(), msg)
+        .await
+        .unwrap();
+
+    let error = rust_i18n::t!("messages.process_error", locale = "en");
+    assert!(telegram.sent("senddocument").await.is_empty());
+    assert_eq!(telegram.sent("sendmessage").await, [error.into_owned()]);
+}
+
+#[tokio::test]
+async fn large_file_without_secondary_client_gets_an_error_reply() {
+    let telegram = MockTelegram::start().await;
+    let size = 50 * 1024 * 1024;
+
+    let msg = document("doc-3", size);
+    handle_message(telegram.bot.clone(), state(), msg)
+        .await
+        .unwrap();
+
+    let calls = telegram.calls().await;
+    // The Bot API cannot serve the file, so it is never asked for.
+    assert!(calls.iter().all(|(name, _)| name != "getfile"), "{calls:?}");
+    // The progress message goes first and is removed once the read failed.
+    assert!(
+        calls.iter().any(|(name, _)| name == "deletemessage"),
+        "{calls:?}"
+    );
+    let error = rust_i18n::t!("messages.process_error", locale = "en");
+    let messages = telegram.sent("sendmessage").await;
+    assert_eq!(messages.last(), Some(&error.into_owned()), "{calls:?}");
+}
+
+#[tokio::test]
+async fn text_without_an_image_asks_for_one() {
+    let telegram = MockTelegram::start().await;
+
+    let msg = incoming(json!({ "text": "hello" }));
+    handle_message(telegram.bot.clone(), state(), msg)
+        .await
+        .unwrap();
+
+    let request = rust_i18n::t!("messages.request_image", locale = "en");
+    assert_eq!(telegram.sent("sendmessage").await, [request.into_owned()]);
+}
+
+#[tokio::test]
+async fn exif_command_without_a_reply_asks_for_one() {
+    let telegram = MockTelegram::start().await;
+
+    let msg = incoming(json!({
+        "text": "/exif",
+        "entities": [{ "type": "bot_command", "offset": 0, "length": 5 }],
+    }));
+    handle_message(telegram.bot.clone(), state(), msg)
+        .await
+        .unwrap();
+
+    let hint = rust_i18n::t!("messages.reply_to_image", locale = "en");
+    assert_eq!(telegram.sent("sendmessage").await, [hint.into_owned()]);
+}
+
+#[tokio::test]
+async fn batch_leaves_out_a_hidden_position() {
+    let telegram = MockTelegram::start().await;
+    let bytes = fixture("iphone_15_pro_gps.jpg");
+    telegram
+        .file("doc-4", bytes.len(), Some(bytes.clone()))
+        .await;
+    let state = state();
+    state
+        .settings
+        .update_user(UserId(CHAT_ID as u64), |user| {
+            user.gps = GpsPrivacy::HideLocation;
+        })
+        .await
+        .unwrap();
+
+    let msg = document("doc-4", bytes.len() as u64);
+    handle_message(telegram.bot.clone(), state.clone(), msg)
+        .await
+        .unwrap();
+
+    let photos = state
+        .batches
+        .photos(ChatId(CHAT_ID), UserId(CHAT_ID as u64));
+    assert_eq!(photos.len(), 1, "{:?}", telegram.calls().await);
+    assert!(photos[0].camera.contains("iPhone"), "{}", photos[0].camera);
+    assert_eq!(photos[0].gps, None);
+}
+
+#[tokio::test]
+async fn exif_reply_follows_the_image_senders_privacy() {
+    let telegram = MockTelegram::start().await;
+    let bytes = fixture("iphone_15_pro_gps.jpg");
+    telegram
+        .file("doc-5", bytes.len(), Some(bytes.clone()))
+        .await;
+    let state = state();
+    state
+        .settings
+        .update_user(UserId(CHAT_ID as u64), |user| {
+            user.gps = GpsPrivacy::HideLocation;
+        })
+        .await
+        .unwrap();
+
+    let msg = incoming(json!({
+        "from": { "id": CHAT_ID + 1, "is_bot": false, "first_name": "Bo", "language_code": "en" },
+        "text": "/exif",
+        "entities": [{ "type": "bot_command", "offset": 0, "length": 5 }],
+        "reply_to_message": {
+            "message_id": 6,
+            "date": 1_700_000_000,
+            "chat": chat(),
+            "from": user(),
+            "document": {
+                "file_id": "doc-5",
+                "file_unique_id": "unique-doc-5",
+                "file_name": "image.jpg",
+                "mime_type": "image/jpeg",
+                "file_size": bytes.len(),
+            },
+        },
+    }));
+    handle_message(telegram.bot.clone(), state, msg)
+        .await
+        .unwrap();
+
+    let captions = telegram.sent("senddocument").await;
+    assert_eq!(captions.len(), 1, "{:?}", telegram.calls().await);
+    assert!(captions[0].contains("iPhone"), "{}", captions[0]);
+    assert!(!captions[0].contains("📍"), "{}", captions[0]);
+}
+
+#[test]
+fn only_a_whole_mention_of_the_bot_counts() {
+    let mention = |text: &str, length: usize| {
+        incoming(json!({
+            "text": text,
+            "entities": [{ "type": "mention", "offset": 0, "length": length }],
+        }))
+    };
+    let username = "fotobot_test_bot";
+
+    assert!(mentions_bot(&mention("@FotoBot_Test_Bot hi", 17), username));
+    assert!(!mentions_bot(
+        &mention("@fotobot_test_bot2 hi", 18),
+        username
+    ));
+    let email = incoming(json!({ "text": "ada@fotobot_test_bot.com" }));
+    assert!(!mentions_bot(&email, username));
+}
@@ -0,0 +1,134 @@
+//! Announcements from the operators to every chat the bot has talked in.
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use teloxide::prelude::*;
+use teloxide::types::ChatId;
+use teloxide::{ApiError, RequestError};
+
+use crate::geo::ratelimit::RateLimiter;
+use crate::storage::Storage;
+
+/// Telegram allows about 30 messages a second in total; this stays well below.
+const SEND_INTERVAL: Duration = Duration::from_millis(50);
+/// How many chats are sent to between two progress updates.
+const PROGRESS_EVERY: usize = 25;
+
+/// The chats the bot has seen, stored once per chat and process.
+pub struct KnownChats {
+    storage: Storage,
+    seen: Mutex<HashSet<i64>>,
+}
+
+/// How a broadcast went so far.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Progress {
+    pub total: usize,
+    pub sent: usize,
+    pub failed: usize,
+}
+
+impl KnownChats {
+    pub fn new(storage: Storage) -> Self {
+        Self {
+            storage,
+            seen: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Remembers the chat for broadcasts; only the first call per chat reaches the database.
+    pub async fn note(&self, chat_id: ChatId) {
+        let first = {
+            let mut seen = self.seen.lock().unwrap_or_else(|err| err.into_inner());
+            seen.insert(chat_id.0)
+        };
+        if first && let Err(err) = self.storage.remember_chat(chat_id).await {
+            tracing::warn!("Failed to remember chat {}: {err:?}", chat_id.0);
+        }
+    }
+
+    /// Sends `text` to every known chat, spaced by [`SEND_INTERVAL`], calling `report`
+    /// every [`PROGRESS_EVERY`] chats and at the end. Chats that blocked or removed the
+    /// bot are forgotten.
+    pub async fn broadcast<F, Fut>(&self, bot: &Bot, text: &str, mut report: F) -> Progress
+    where
+        F: FnMut(Progress) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        let chats = match self.storage.known_chats().await {
+            Ok(chats) => chats,
+            Err(err) => {
+                tracing::error!("Failed to list known chats: {err:?}");
+                Vec::new()
+            }
+        };
+        let limiter = RateLimiter::new(SEND_INTERVAL);
+        let mut progress = Progress {
+            total: chats.len(),
+            ..Progress::default()
+        };
+
+        for (index, chat_id) in chats.into_iter().enumerate() {
+            let chat_id = ChatId(chat_id);
+            match send(bot, &limiter, chat_id, text).await {
+                Ok(()) => progress.sent += 1,
+                Err(err) => {
+                    progress.failed += 1;
+                    tracing::warn!("Broadcast to chat {} failed: {err:?}", chat_id.0);
+                    if is_gone(&err) {
+                        self.forget(chat_id).await;
+                    }
+                }
+            }
+            if (index + 1) % PROGRESS_EVERY == 0 {
+                report(progress).await;
+            }
+        }
+        report(progress).await;
+        progress
+    }
+
+    async fn forget(&self, chat_id: ChatId) {
+        {
+            let mut seen = self.seen.lock().unwrap_or_else(|err| err.into_inner());
+            seen.remove(&chat_id.0);
+        }
+        if let Err(err) = self.storage.forget_chat(chat_id).await {
+            tracing::warn!("Failed to forget chat {}: {err:?}", chat_id.0);
+        }
+    }
+}
+
+/// Sends one message, waiting out a flood limit once.
+async fn send(
+    bot: &Bot,
+    limiter: &RateLimiter,
+    chat_id: ChatId,
+    text: &str,
+) -> Result<(), RequestError> {
+    limiter.acquire().await;
+    match bot.send_message(chat_id, text).await {
+        Err(RequestError::RetryAfter(delay)) => {
+            limiter.back_off(delay.duration());
+            limiter.acquire().await;
+            bot.send_message(chat_id, text).await.map(|_| ())
+        }
+        result => result.map(|_| ()),
+    }
+}
+
+/// Whether the chat will never take messages from the bot again.
+fn is_gone(err: &RequestError) -> bool {
+    matches!(
+        err,
+        RequestError::Api(
+            ApiError::BotBlocked
+                | ApiError::BotKicked
+                | ApiError::BotKickedFromSupergroup
+                | ApiError::ChatNotFound
+                | ApiError::UserDeactivated
+        )
+    )
+}
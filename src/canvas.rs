@@ -0,0 +1,165 @@
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use ab_glyph::{Font, FontVec, PxScale, ScaleFont, point};
+use anyhow::{Context, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, Rgb, RgbImage};
+
+use crate::config::Config;
+
+/// DejaVu Sans as installed by Alpine's `font-dejavu` package.
+const DEFAULT_FONT_PATH: &str = "/usr/share/fonts/dejavu/DejaVuSans.ttf";
+const JPEG_QUALITY: u8 = 90;
+
+/// The font images are annotated with.
+#[derive(Clone)]
+pub struct Typeface {
+    /// Shared so rendering can move a handle onto a blocking thread.
+    font: Arc<FontVec>,
+}
+
+impl Typeface {
+    /// Loads the font from `font`, falling back to DejaVu Sans.
+    ///
+    /// `off`, or a font that cannot be loaded, turns image rendering off.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let path = match config.font.as_deref() {
+            Some(font) if font.eq_ignore_ascii_case("off") => return None,
+            Some(font) => PathBuf::from(font),
+            None => PathBuf::from(DEFAULT_FONT_PATH),
+        };
+
+        let font = std::fs::read(&path)
+            .with_context(|| format!("Failed to read font at `{}`", path.display()))
+            .and_then(|bytes| FontVec::try_from_vec(bytes).context("Failed to parse font"));
+        match font {
+            Ok(font) => Some(Self {
+                font: Arc::new(font),
+            }),
+            Err(err) => {
+                tracing::warn!("Watermarks and frames are disabled: {err:?}");
+                None
+            }
+        }
+    }
+
+    /// Distance from the baseline to the top of the tallest glyph at `size` pixels.
+    pub fn ascent(&self, size: f32) -> f32 {
+        self.font.as_scaled(PxScale::from(size)).ascent()
+    }
+
+    /// Distance between the baselines of two lines at `size` pixels.
+    pub fn line_height(&self, size: f32) -> f32 {
+        let font = self.font.as_scaled(PxScale::from(size));
+        font.height() + font.line_gap()
+    }
+
+    pub fn text_width(&self, size: f32, text: &str) -> f32 {
+        let font = self.font.as_scaled(PxScale::from(size));
+        let mut width = 0.0;
+        let mut previous = None;
+        for ch in text.chars() {
+            let glyph_id = font.glyph_id(ch);
+            if let Some(previous) = previous {
+                width += font.kern(previous, glyph_id);
+            }
+            width += font.h_advance(glyph_id);
+            previous = Some(glyph_id);
+        }
+        width
+    }
+
+    /// Draws `text` starting at `x` on the `baseline`; whatever falls outside is clipped.
+    pub fn draw_text(
+        &self,
+        image: &mut RgbImage,
+        size: f32,
+        (x, baseline): (f32, f32),
+        text: &str,
+        color: Rgb<u8>,
+    ) {
+        let scale = PxScale::from(size);
+        let font = self.font.as_scaled(scale);
+        let mut caret = x;
+        let mut previous = None;
+        for ch in text.chars() {
+            let glyph_id = font.glyph_id(ch);
+            if let Some(previous) = previous {
+                caret += font.kern(previous, glyph_id);
+            }
+            let glyph = glyph_id.with_scale_and_position(scale, point(caret, baseline));
+            caret += font.h_advance(glyph_id);
+            previous = Some(glyph_id);
+
+            let Some(outlined) = self.font.outline_glyph(glyph) else {
+                continue;
+            };
+            let bounds = outlined.px_bounds();
+            outlined.draw(|x, y, coverage| {
+                let x = bounds.min.x as i64 + i64::from(x);
+                let y = bounds.min.y as i64 + i64::from(y);
+                if x >= 0 && y >= 0 && x < i64::from(image.width()) && y < i64::from(image.height())
+                {
+                    blend(image, x as u32, y as u32, color, coverage);
+                }
+            });
+        }
+    }
+}
+
+/// Decodes an image with the camera's rotation baked into the pixels, since the
+/// rendered copy goes out without EXIF data.
+pub fn decode(bytes: &[u8]) -> Result<RgbImage> {
+    let mut decoder = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .context("Failed to detect image format")?
+        .into_decoder()
+        .context("Unsupported image format")?;
+    let orientation = decoder
+        .orientation()
+        .context("Failed to read orientation")?;
+    let mut image = DynamicImage::from_decoder(decoder).context("Failed to decode image")?;
+    image.apply_orientation(orientation);
+    Ok(image.into_rgb8())
+}
+
+pub fn encode_jpeg(image: &RgbImage) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    JpegEncoder::new_with_quality(&mut output, JPEG_QUALITY)
+        .encode_image(image)
+        .context("Failed to encode image")?;
+    Ok(output)
+}
+
+pub fn encode_png(image: &RgbImage) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut output), ImageFormat::Png)
+        .context("Failed to encode image")?;
+    Ok(output)
+}
+
+/// Mixes `color` over the rectangle with the given opacity, clipped to the image.
+pub fn fill_rect(
+    image: &mut RgbImage,
+    (left, top): (u32, u32),
+    (width, height): (u32, u32),
+    color: Rgb<u8>,
+    alpha: f32,
+) {
+    for y in top..top.saturating_add(height).min(image.height()) {
+        for x in left..left.saturating_add(width).min(image.width()) {
+            blend(image, x, y, color, alpha);
+        }
+    }
+}
+
+fn blend(image: &mut RgbImage, x: u32, y: u32, color: Rgb<u8>, alpha: f32) {
+    let alpha = alpha.clamp(0.0, 1.0);
+    let pixel = image.get_pixel_mut(x, y);
+    for (channel, target) in pixel.0.iter_mut().zip(color.0) {
+        *channel = (f32::from(*channel) * (1.0 - alpha) + f32::from(target) * alpha).round() as u8;
+    }
+}
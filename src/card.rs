@@ -0,0 +1,269 @@
+use anyhow::Result;
+use image::{Rgb, RgbImage};
+
+use crate::canvas::{self, Typeface};
+use crate::exif::{CaptionOptions, ParsedExif};
+use crate::formats;
+use crate::styles::{self, Section};
+
+const WIDTH: u32 = 1080;
+const PADDING: f32 = 72.0;
+const ACCENT_HEIGHT: u32 = 12;
+const TITLE_SIZE: f32 = 52.0;
+const LABEL_SIZE: f32 = 24.0;
+const VALUE_SIZE: f32 = 36.0;
+const ROW_GAP: f32 = 28.0;
+
+/// The colour scheme of a card, picked with `/card dark` or `/card light`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "" | "dark" | "night" => Some(Self::Dark),
+            "light" | "day" => Some(Self::Light),
+            _ => None,
+        }
+    }
+
+    fn palette(self) -> Palette {
+        match self {
+            Self::Dark => Palette {
+                background: Rgb([24, 24, 27]),
+                text: Rgb([244, 244, 245]),
+                muted: Rgb([161, 161, 170]),
+                accent: Rgb([245, 158, 11]),
+            },
+            Self::Light => Palette {
+                background: Rgb([250, 250, 249]),
+                text: Rgb([28, 25, 23]),
+                muted: Rgb([120, 113, 108]),
+                accent: Rgb([217, 119, 6]),
+            },
+        }
+    }
+}
+
+struct Palette {
+    background: Rgb<u8>,
+    text: Rgb<u8>,
+    muted: Rgb<u8>,
+    accent: Rgb<u8>,
+}
+
+/// What goes on a card: a localized label and the value, in reading order.
+pub struct CardContent {
+    title: Option<String>,
+    gear: Vec<(String, String)>,
+    /// Exposure values, laid out side by side.
+    exposure: Vec<(String, String)>,
+    details: Vec<(String, String)>,
+}
+
+impl CardContent {
+    /// Picks the fields the caption would show under the same options.
+    pub fn new(data: &ParsedExif, options: CaptionOptions, locale: &str) -> Self {
+        let label = |key: &str| rust_i18n::t!(key, locale = locale).to_uppercase();
+
+        let title = data
+            .title
+            .clone()
+            .filter(|title| !title.trim().is_empty() && options.shows(Section::Title));
+
+        let mut gear = vec![(
+            label("labels.camera"),
+            styles::camera_name(&data.camera, locale),
+        )];
+        if options.shows(Section::Lens) {
+            gear.push((label("labels.lens"), styles::lens_name(&data.lens, locale)));
+        }
+
+        let focal_length =
+            styles::equivalent_focal_length(data, locale).or_else(|| data.focal_length.clone());
+        let exposure = [
+            ("card.focal_length", focal_length),
+            ("card.aperture", data.aperture.clone()),
+            ("card.shutter", data.shutter.clone()),
+            (
+                "card.iso",
+                data.iso
+                    .as_deref()
+                    .map(|iso| styles::iso_speed(iso, locale)),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some((label(key), formats::decimals(&value?, locale))))
+        .collect();
+
+        let mut details = Vec::new();
+        if let Some(datetime) = data
+            .datetime
+            .clone()
+            .filter(|_| options.shows(Section::Date))
+        {
+            details.push((
+                label("labels.date"),
+                formats::datetime(&datetime, locale, options.units.clock),
+            ));
+        }
+        if !options.hide_location {
+            let location = if options.coarse_location {
+                data.city.as_deref().or(data.region.as_deref())
+            } else {
+                data.location.as_deref()
+            };
+            let place = match (location, data.country.as_deref()) {
+                (Some(location), Some(country)) if !location.contains(country) => {
+                    Some(format!("{location}, {country}"))
+                }
+                (Some(location), _) => Some(location.to_string()),
+                (None, country) => country.map(str::to_string),
+            };
+            if let Some(place) = place {
+                details.push((label("labels.place"), place));
+            }
+        }
+        if let Some((_, gps)) = data
+            .position(options.precision)
+            .filter(|_| !options.hide_coordinates)
+        {
+            details.push((label("labels.gps"), gps));
+        }
+
+        Self {
+            title,
+            gear,
+            exposure,
+            details,
+        }
+    }
+}
+
+/// Lays the content out on a card and returns it as PNG.
+pub fn render(typeface: &Typeface, content: &CardContent, theme: Theme) -> Result<Vec<u8>> {
+    let palette = theme.palette();
+    // Measure first, then draw the same layout onto a canvas of the right height.
+    let height = paint(typeface, content, &palette, None);
+    let mut image = RgbImage::from_pixel(WIDTH, height, palette.background);
+    paint(typeface, content, &palette, Some(&mut image));
+    canvas::encode_png(&image)
+}
+
+/// Walks the layout top to bottom, drawing when given an image, and returns the height.
+fn paint(
+    typeface: &Typeface,
+    content: &CardContent,
+    palette: &Palette,
+    mut image: Option<&mut RgbImage>,
+) -> u32 {
+    let width = WIDTH as f32 - 2.0 * PADDING;
+    let mut y = ACCENT_HEIGHT as f32 + PADDING;
+
+    if let Some(image) = image.as_deref_mut() {
+        canvas::fill_rect(image, (0, 0), (WIDTH, ACCENT_HEIGHT), palette.accent, 1.0);
+    }
+
+    if let Some(title) = &content.title {
+        if let Some(image) = image.as_deref_mut() {
+            let title = fit(typeface, TITLE_SIZE, title, width);
+            let baseline = y + typeface.ascent(TITLE_SIZE);
+            typeface.draw_text(image, TITLE_SIZE, (PADDING, baseline), &title, palette.text);
+        }
+        y += typeface.line_height(TITLE_SIZE) + ROW_GAP;
+    }
+
+    for (label, value) in &content.gear {
+        y = field(
+            typeface,
+            palette,
+            image.as_deref_mut(),
+            (PADDING, y),
+            width,
+            label,
+            value,
+        );
+    }
+
+    if !content.exposure.is_empty() {
+        // Leave room for the full set of four, so cards line up with each other.
+        let column = width / content.exposure.len().max(4) as f32;
+        let mut bottom = y;
+        for (index, (label, value)) in content.exposure.iter().enumerate() {
+            let x = PADDING + column * index as f32;
+            let width = column - ROW_GAP;
+            bottom = field(
+                typeface,
+                palette,
+                image.as_deref_mut(),
+                (x, y),
+                width,
+                label,
+                value,
+            );
+        }
+        y = bottom;
+    }
+
+    for (label, value) in &content.details {
+        y = field(
+            typeface,
+            palette,
+            image.as_deref_mut(),
+            (PADDING, y),
+            width,
+            label,
+            value,
+        );
+    }
+
+    (y - ROW_GAP + PADDING).ceil() as u32
+}
+
+/// Draws a muted label over its value and returns where the next field starts.
+fn field(
+    typeface: &Typeface,
+    palette: &Palette,
+    image: Option<&mut RgbImage>,
+    (x, y): (f32, f32),
+    width: f32,
+    label: &str,
+    value: &str,
+) -> f32 {
+    let value_top = y + typeface.line_height(LABEL_SIZE);
+    if let Some(image) = image {
+        let label = fit(typeface, LABEL_SIZE, label, width);
+        let value = fit(typeface, VALUE_SIZE, value, width);
+        let label_baseline = y + typeface.ascent(LABEL_SIZE);
+        let value_baseline = value_top + typeface.ascent(VALUE_SIZE);
+        typeface.draw_text(
+            image,
+            LABEL_SIZE,
+            (x, label_baseline),
+            &label,
+            palette.muted,
+        );
+        typeface.draw_text(image, VALUE_SIZE, (x, value_baseline), &value, palette.text);
+    }
+    value_top + typeface.line_height(VALUE_SIZE) + ROW_GAP
+}
+
+/// Shortens `text` with an ellipsis until it fits into `width`.
+fn fit(typeface: &Typeface, size: f32, text: &str, width: f32) -> String {
+    if typeface.text_width(size, text) <= width {
+        return text.to_string();
+    }
+    let mut chars: Vec<char> = text.chars().collect();
+    while !chars.is_empty() {
+        chars.pop();
+        let candidate: String = chars.iter().collect::<String>().trim_end().to_string() + "…";
+        if typeface.text_width(size, &candidate) <= width {
+            return candidate;
+        }
+    }
+    String::from("…")
+}
@@ -0,0 +1,177 @@
+//! `fotobot exif <path-or-url>`: the bot's pipeline run on one image, printed to the
+//! terminal, to see why a file fails without going through Telegram. `fotobot scan
+//! <dir>` reports on every image in a directory.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result, bail};
+use tokio::task;
+
+use crate::config::LookupSettings;
+use crate::exif::{self, CaptionOptions, ExifSummary};
+use crate::geo::Lookups;
+use crate::markup;
+use crate::scan::{self, ReportFormat};
+
+const USAGE: &str = "Usage: fotobot exif [--json | --dump] [--lang <code>] <path-or-url>
+       fotobot scan [--format csv|json] [--output <file>] [--jobs <n>] <dir>";
+
+/// What to print about the image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Output {
+    /// The caption the bot would send, as plain text.
+    Caption,
+    /// The `/json` document.
+    Json,
+    /// Every raw field, one per line.
+    Dump,
+}
+
+/// Runs the subcommand in `args`, the command line after the program name. `None` when
+/// `args` holds no subcommand and the bot should start instead.
+pub async fn run(args: &[String]) -> Option<Result<()>> {
+    match args.first().map(String::as_str) {
+        Some("exif") => Some(exif_command(&args[1..]).await),
+        Some("scan") => {
+            let args = args[1..].to_vec();
+            let scanned = task::spawn_blocking(move || scan_command(&args))
+                .await
+                .context("Failed to join scan task");
+            Some(scanned.and_then(|result| result))
+        }
+        Some("help" | "--help" | "-h") => {
+            println!("{USAGE}");
+            Some(Ok(()))
+        }
+        _ => None,
+    }
+}
+
+async fn exif_command(args: &[String]) -> Result<()> {
+    let mut output = Output::Caption;
+    let mut language = None;
+    let mut source = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => output = Output::Json,
+            "--dump" => output = Output::Dump,
+            "--lang" => language = Some(args.next().context(USAGE)?.clone()),
+            _ if source.is_none() && !arg.starts_with("--") => source = Some(arg.clone()),
+            _ => bail!("{USAGE}"),
+        }
+    }
+    let Some(source) = source else {
+        bail!("{USAGE}");
+    };
+    let language = language.as_deref();
+    let settings = LookupSettings::load()?;
+    rust_i18n::set_locale(settings.default_locale);
+    let client = &crate::http::client(settings.proxy.as_ref());
+    let lookups = Arc::new(Lookups::new(
+        &settings.lookups,
+        &settings.data_dir,
+        client.clone(),
+    ));
+
+    match output {
+        Output::Caption => {
+            let metadata = match remote_url(&source) {
+                Some(url) => exif::read_metadata_from_url(client, url).await?,
+                None => read_local(source, exif::read_metadata_from_file).await?,
+            };
+            let summary =
+                ExifSummary::build(&lookups, metadata, language, CaptionOptions::default()).await;
+            println!("{}", markup::to_plain(&summary.caption));
+        }
+        Output::Json | Output::Dump => {
+            let name = source.clone();
+            let exif = match remote_url(&source) {
+                Some(url) => exif::read_exif_from_url(client, url).await?,
+                None => read_local(source, exif::read_exif_from_file).await?,
+            };
+            let Some(exif) = exif else {
+                bail!("`{name}` has no EXIF data");
+            };
+            if output == Output::Dump {
+                println!("{}", exif::dump_fields(&exif, CaptionOptions::default()));
+                return Ok(());
+            }
+            let address = exif::geocode(&lookups, &exif, language, CaptionOptions::default()).await;
+            let document = exif::exif_json(&exif, address.as_ref(), CaptionOptions::default());
+            let json = serde_json::to_string_pretty(&document)
+                .context("Failed to serialize EXIF fields")?;
+            println!("{json}");
+        }
+    }
+    lookups.flush().await;
+    Ok(())
+}
+
+/// Parses the image at `path` on the blocking pool.
+async fn read_local<T: Send + 'static>(path: String, read: fn(&Path) -> Result<T>) -> Result<T> {
+    task::spawn_blocking(move || read(Path::new(&path)))
+        .await
+        .context("Failed to join EXIF parsing task")?
+}
+
+fn scan_command(args: &[String]) -> Result<()> {
+    let mut format = ReportFormat::Csv;
+    let mut output = None;
+    let mut jobs = std::thread::available_parallelism().map_or(1, usize::from);
+    let mut dir = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                let value = args.next().context(USAGE)?;
+                format = ReportFormat::parse(value).context(USAGE)?;
+            }
+            "--output" => output = Some(PathBuf::from(args.next().context(USAGE)?)),
+            "--jobs" => {
+                jobs = args
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .filter(|jobs| *jobs > 0)
+                    .context(USAGE)?;
+            }
+            _ if dir.is_none() && !arg.starts_with("--") => dir = Some(PathBuf::from(arg)),
+            _ => bail!("{USAGE}"),
+        }
+    }
+    let Some(dir) = dir else {
+        bail!("{USAGE}");
+    };
+
+    let images = scan::find_images(&dir)?;
+    let entries = scan::scan(&images, jobs);
+    let report = scan::report(&entries, &dir, format);
+    write_report(output.as_deref(), &report)?;
+
+    let totals = scan::totals(&entries);
+    eprintln!(
+        "Scanned {} images: {} with EXIF data, {} without, {} failed",
+        entries.len(),
+        totals.read,
+        totals.no_exif,
+        totals.failed
+    );
+    Ok(())
+}
+
+/// Writes to `path`, or to standard output without one.
+fn write_report(path: Option<&Path>, report: &str) -> Result<()> {
+    match path {
+        Some(path) => std::fs::write(path, report)
+            .with_context(|| format!("Failed to write `{}`", path.display())),
+        None => {
+            println!("{report}");
+            Ok(())
+        }
+    }
+}
+
+fn remote_url(source: &str) -> Option<&str> {
+    (source.starts_with("http://") || source.starts_with("https://")).then_some(source)
+}
@@ -0,0 +1,78 @@
+use teloxide::utils::command::BotCommands;
+
+#[derive(BotCommands, Clone, Debug, PartialEq)]
+#[command(rename_rule = "lowercase", description = "Supported commands:")]
+pub enum Command {
+    #[command(description = "reply to an image document to read its EXIF data")]
+    Exif,
+    #[command(description = "group admins: `all` or `explicit` to choose which images I react to")]
+    Trigger(String),
+    #[command(description = "group and channel admins: a caption style for everyone, or `default`")]
+    ChatStyle(String),
+    #[command(
+        description = "group and channel admins: `show`, `coordinates`, `location`, `spoiler` or `default`: what captions here hide at least"
+    )]
+    ChatPrivacy(String),
+    #[command(description = "reply to an image to get it back without any metadata")]
+    Strip,
+    #[command(description = "reply to a geotagged image to get its location as a map pin")]
+    Map,
+    #[command(description = "reply to an image to get its EXIF data as a JSON file")]
+    Json,
+    #[command(
+        description = "reply to an image to get a copy with its EXIF data drawn on, e.g. `tl 50%`"
+    )]
+    Watermark(String),
+    #[command(description = "reply to an image to get it framed with its camera and settings")]
+    Frame,
+    #[command(
+        description = "`dark` or `light`: reply to an image to get its EXIF summary as a card"
+    )]
+    Card(String),
+    #[command(description = "compare two images: reply to one and attach the other, or send both")]
+    Compare,
+    #[command(
+        description = "`show`, `coordinates`, `location` or `spoiler`: what my captions hide"
+    )]
+    Privacy(String),
+    #[command(description = "`exact`, `100m`, `1km` or `city`: how precise my coordinates are")]
+    Precision(String),
+    #[command(description = "`emoji`, `minimal`, `detailed` or `gear`: how my captions look")]
+    Style(String),
+    #[command(description = "`emoji` or `text`: how my caption lines are labelled")]
+    Labels(String),
+    #[command(
+        description = "`metric`, `imperial`, `12h` or `24h`: the units and clock of my captions"
+    )]
+    Units(String),
+    #[command(description = "`on` or `off`: add the text found in photos to my captions")]
+    Ocr(String),
+    #[command(description = "`on` or `off`: name a landmark near the spot in my captions")]
+    Nearby(String),
+    #[command(
+        description = "`add home 35.66,139.75 500m` or `remove home`: hide the location of photos taken there"
+    )]
+    PrivateZone(String),
+    #[command(description = "choose which parts my captions show")]
+    Sections,
+    #[command(description = "your cameras, lenses and focal lengths so far")]
+    Stats,
+    #[command(description = "bot operators: images, failures and geocoder calls of the last days")]
+    AdminStats,
+    #[command(description = "bot operators: send an announcement to every chat I am in")]
+    Broadcast(String),
+    #[command(
+        description = "bot operators: `allow`, `block` or `remove` a user id, or `allowlist on`"
+    )]
+    Access(String),
+    #[command(description = "unlock a higher daily limit, /frame and exports with Telegram Stars")]
+    Premium,
+    #[command(description = "your latest analyses with links back to the images")]
+    History,
+    #[command(description = "delete your settings, stats and history from the bot")]
+    DeleteMyData(String),
+    #[command(description = "get the geotagged images you just sent as a GPX file")]
+    Gpx,
+    #[command(description = "`gpx`, `kml`, `geojson` or `csv`: export the images you just sent")]
+    Export(String),
+}
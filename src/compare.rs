@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use teloxide::types::{ChatId, Message, UserId};
+
+use crate::exif::ShotFacts;
+use crate::formats::{self, Units};
+use crate::geo::haversine_km;
+use crate::styles;
+
+/// How long `/compare` waits for the images before the pairing is dropped.
+const PAIRING_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Where a user's `/compare` pairing stands after an image arrived.
+pub enum PairingStep {
+    /// The first image is stored; the next one completes the pair.
+    AwaitingSecond,
+    /// Both images are known.
+    Complete(Message, Message),
+}
+
+struct Pairing {
+    first: Option<Message>,
+    started: Instant,
+}
+
+/// Pairings started with `/compare`, per user and chat.
+#[derive(Default)]
+pub struct ComparePairings {
+    pending: Mutex<HashMap<(ChatId, UserId), Pairing>>,
+}
+
+impl ComparePairings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts a pairing, optionally with its first image already known.
+    pub fn start(&self, chat_id: ChatId, user_id: UserId, first: Option<Message>) {
+        let mut pending = self.pending.lock().unwrap_or_else(|err| err.into_inner());
+        pending.retain(|_, pairing| pairing.started.elapsed() < PAIRING_TIMEOUT);
+        pending.insert(
+            (chat_id, user_id),
+            Pairing {
+                first,
+                started: Instant::now(),
+            },
+        );
+    }
+
+    /// Feeds an image into the user's pairing; `None` when no pairing is running.
+    pub fn push(&self, chat_id: ChatId, user_id: UserId, image: Message) -> Option<PairingStep> {
+        let mut pending = self.pending.lock().unwrap_or_else(|err| err.into_inner());
+        let key = (chat_id, user_id);
+
+        let pairing = pending.get_mut(&key)?;
+        if pairing.started.elapsed() >= PAIRING_TIMEOUT {
+            pending.remove(&key);
+            return None;
+        }
+
+        match pairing.first.take() {
+            None => {
+                pairing.first = Some(image);
+                Some(PairingStep::AwaitingSecond)
+            }
+            Some(first) => {
+                pending.remove(&key);
+                Some(PairingStep::Complete(first, image))
+            }
+        }
+    }
+
+    /// Cancels the user's pairings in every chat.
+    pub fn forget(&self, user_id: UserId) {
+        let mut pending = self.pending.lock().unwrap_or_else(|err| err.into_inner());
+        pending.retain(|(_, owner), _| *owner != user_id);
+    }
+}
+
+/// Lists what the two images have in common and where they differ, measured in `units`.
+pub fn format_comparison(
+    first: &ShotFacts,
+    second: &ShotFacts,
+    locale: &str,
+    units: Units,
+) -> String {
+    let mut output = String::new();
+    writeln!(
+        output,
+        "🆚 {}",
+        rust_i18n::t!("compare.title", locale = locale)
+    )
+    .ok();
+
+    write_row(
+        &mut output,
+        "📸",
+        &styles::camera_name(&first.camera, locale),
+        &styles::camera_name(&second.camera, locale),
+    );
+    write_row(
+        &mut output,
+        "🔭",
+        &styles::lens_name(&first.lens, locale),
+        &styles::lens_name(&second.lens, locale),
+    );
+    write_row(
+        &mut output,
+        "📝",
+        &exposure(first, locale),
+        &exposure(second, locale),
+    );
+
+    match (
+        time_delta(first, second),
+        first.datetime.as_deref(),
+        second.datetime.as_deref(),
+    ) {
+        (Some(delta), _, _) => {
+            writeln!(
+                output,
+                "⏱️: {} ({})",
+                format_duration(delta),
+                rust_i18n::t!("compare.time_apart", locale = locale)
+            )
+            .ok();
+        }
+        (None, first_time, second_time) => {
+            let unknown = rust_i18n::t!("caption.unknown_date", locale = locale);
+            let taken = |time: Option<&str>| {
+                time.map_or_else(
+                    || unknown.to_string(),
+                    |time| formats::datetime(time, locale, units.clock),
+                )
+            };
+            write_row(&mut output, "📅", &taken(first_time), &taken(second_time));
+        }
+    }
+
+    match (first.gps, second.gps) {
+        (Some(a), Some(b)) => {
+            writeln!(
+                output,
+                "📏: {} ({})",
+                units.distance(haversine_km(a, b), locale),
+                rust_i18n::t!("compare.distance_apart", locale = locale)
+            )
+            .ok();
+        }
+        _ => {
+            writeln!(
+                output,
+                "📏: {}",
+                rust_i18n::t!("compare.distance_unknown", locale = locale)
+            )
+            .ok();
+        }
+    }
+
+    while output.ends_with('\n') {
+        output.pop();
+    }
+    output
+}
+
+fn write_row(output: &mut String, label: &str, first: &str, second: &str) {
+    if first == second {
+        writeln!(output, "{label}: {first} (=)").ok();
+    } else {
+        writeln!(output, "{label}: {first} ↔ {second}").ok();
+    }
+}
+
+fn exposure(facts: &ShotFacts, locale: &str) -> String {
+    let parts: Vec<String> = [
+        facts.focal_length.clone(),
+        facts.aperture.clone(),
+        facts.shutter.clone(),
+        facts
+            .iso
+            .as_deref()
+            .map(|iso| styles::iso_speed(iso, locale)),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if parts.is_empty() {
+        rust_i18n::t!("caption.parameters_unknown", locale = locale).into_owned()
+    } else {
+        formats::decimals(&parts.join(", "), locale)
+    }
+}
+
+fn time_delta(first: &ShotFacts, second: &ShotFacts) -> Option<u64> {
+    let first = parse_timestamp(first.datetime.as_deref()?)?;
+    let second = parse_timestamp(second.datetime.as_deref()?)?;
+    Some(first.abs_diff(second))
+}
+
+/// Seconds since the epoch for `YYYY-MM-DD HH:MM:SS`; EXIF times carry no zone, so both
+/// images are assumed to share one.
+fn parse_timestamp(value: &str) -> Option<i64> {
+    let (date, time) = value.split_once(' ')?;
+    let mut date = date.split('-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    let mut time = time.split(':').map(|part| part.parse::<i64>().ok());
+    let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    Some(days_from_civil(year, month, day) * 86_400 + hour * 3_600 + minute * 60 + second)
+}
+
+/// Days since 1970-01-01 in the proleptic Gregorian calendar (Howard Hinnant's algorithm).
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn format_duration(seconds: u64) -> String {
+    let days = seconds / 86_400;
+    let hours = seconds % 86_400 / 3_600;
+    let minutes = seconds % 3_600 / 60;
+    let seconds = seconds % 60;
+
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{seconds}s"),
+        (0, 0, _) => format!("{minutes}m {seconds}s"),
+        (0, _, _) => format!("{hours}h {minutes}m"),
+        _ => format!("{days}d {hours}h"),
+    }
+}
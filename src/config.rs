@@ -0,0 +1,693 @@
+//! The bot's configuration: `fotobot.toml`, with environment variables taking precedence
+//! over the file.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{Context, Result, anyhow, bail};
+use reqwest::Url;
+use serde::Deserialize;
+use teloxide::types::{ChatId, UserId};
+
+/// Read from the working directory unless `FOTOBOT_CONFIG` names another file.
+const DEFAULT_FILE: &str = "fotobot.toml";
+const DEFAULT_CACHE_DIR: &str = "cache";
+/// The port webhooks listen on unless `port`, FOTOBOT_WEBHOOK_PORT or PORT say otherwise.
+const DEFAULT_WEBHOOK_PORT: u16 = 8443;
+const BOT_TOKEN_VARS: [&str; 4] = [
+    "TELEGRAM_BOT_TOKEN",
+    "BOT_TOKEN",
+    "TELEGRAM_TOKEN",
+    "TELOXIDE_TOKEN",
+];
+
+/// The file as written; everything is optional there.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    bot_token: Option<String>,
+    api_id: Option<i32>,
+    api_hash: Option<String>,
+    /// A self-hosted Bot API server, e.g. `http://localhost:8081`.
+    bot_api_url: Option<String>,
+    data_dir: Option<PathBuf>,
+    admin_ids: Vec<u64>,
+    error_chat: Option<i64>,
+    sentry_dsn: Option<String>,
+    /// `http://`, `https://`, `socks5://` or `socks5h://`, with credentials if needed.
+    proxy: Option<String>,
+    default_locale: Option<String>,
+    /// `polling` (the default) or `webhook`.
+    mode: Option<String>,
+    webhook: WebhookFile,
+    geocoder: GeocoderConfig,
+    bots: Vec<ExtraBot>,
+    /// Where files above the Bot API limit are downloaded to.
+    cache_dir: Option<PathBuf>,
+    geocode_cache_days: Option<u64>,
+    cities_file: Option<PathBuf>,
+    weather_key: Option<String>,
+    weather_url: Option<String>,
+    what3words_key: Option<String>,
+    elevation_url: Option<String>,
+    overpass_url: Option<String>,
+    redis_url: Option<String>,
+    metrics_addr: Option<SocketAddr>,
+    static_map_url: Option<String>,
+    static_map_zoom: Option<u8>,
+    telegraph_token: Option<String>,
+    ocr: Option<String>,
+    ocr_token: Option<String>,
+    tesseract: Option<PathBuf>,
+    ocr_languages: Option<String>,
+    premium_stars: Option<u32>,
+    premium_days: Option<u64>,
+    daily_quota: Option<u32>,
+    premium_quota: Option<u32>,
+    max_concurrent: Option<usize>,
+    cache_ttl_minutes: Option<u64>,
+    cache_max_mb: Option<u64>,
+    font: Option<String>,
+    locales_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct WebhookFile {
+    url: Option<String>,
+    listen: Option<IpAddr>,
+    port: Option<u16>,
+    path: Option<String>,
+    secret_token: Option<String>,
+}
+
+/// Where reverse geocoding goes; see the `[geocoder]` table in the README.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct GeocoderConfig {
+    #[serde(flatten)]
+    pub primary: ProviderConfig,
+    pub fallback: Option<ProviderConfig>,
+    /// Appended to the user agent, as Nominatim's usage policy asks.
+    pub contact: Option<String>,
+    pub interval_ms: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ProviderConfig {
+    pub provider: Option<String>,
+    pub key: Option<String>,
+    pub url: Option<String>,
+}
+
+/// The services a caption's lookups go to; see [`crate::geo::Lookups`]. Each is left to
+/// its default where unset, and most take `off` to turn them off.
+#[derive(Clone, Debug, Default)]
+pub struct LookupConfig {
+    pub geocoder: GeocoderConfig,
+    /// How many days geocoding answers are cached; `0` turns the caches off.
+    pub geocode_cache_days: Option<u64>,
+    /// A GeoNames `cities*.txt` for the offline fallback, instead of the data directory's.
+    pub cities_file: Option<PathBuf>,
+    pub weather_key: Option<String>,
+    pub weather_url: Option<String>,
+    pub what3words_key: Option<String>,
+    pub elevation_url: Option<String>,
+    pub overpass_url: Option<String>,
+}
+
+/// Another bot answered by the same process, e.g. a private one with a higher quota. It
+/// shares the caches and geocoder limits, but keeps its own database and session.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExtraBot {
+    pub token: String,
+    /// Replaces `daily_quota` for this bot; 0 is unlimited.
+    pub daily_quota: Option<u32>,
+    /// Replaces `premium_quota` for this bot; 0 is unlimited.
+    pub premium_quota: Option<u32>,
+}
+
+/// How updates reach the bot.
+#[derive(Debug)]
+pub enum UpdateMode {
+    /// The bot asks Telegram for updates, which works from behind any NAT.
+    Polling,
+    /// Telegram posts updates to the bot's own HTTPS endpoint.
+    Webhook(WebhookConfig),
+}
+
+#[derive(Debug)]
+pub struct WebhookConfig {
+    /// The public address Telegram posts to, usually at a reverse proxy.
+    pub url: Url,
+    /// Where the bot's own HTTP server listens.
+    pub address: SocketAddr,
+    /// The path the server answers on, when the proxy rewrites the one in `url`.
+    pub path: Option<String>,
+    /// Sent back by Telegram with every update, so others cannot post fake ones.
+    pub secret_token: Option<String>,
+}
+
+/// The `api_id` and `api_hash` from https://my.telegram.org.
+#[derive(Debug)]
+pub struct ApiCredentials {
+    pub api_id: i32,
+    pub api_hash: String,
+}
+
+/// Everything the bot needs to start, resolved from the file and the environment.
+#[derive(Debug)]
+pub struct Config {
+    pub bot_token: String,
+    /// The credentials of the secondary client that downloads large files; only optional
+    /// with a local Bot API server.
+    pub secondary_client: Option<ApiCredentials>,
+    /// Where the Bot API is asked instead of `api.telegram.org`.
+    pub bot_api_url: Option<Url>,
+    /// Where the session, database and caches live.
+    pub data_dir: PathBuf,
+    /// The operators, who may run `/adminstats`, `/access` and `/broadcast`.
+    pub admin_ids: Vec<UserId>,
+    /// Where failures to read an image are reported, e.g. a private group of the operators.
+    pub error_chat: Option<ChatId>,
+    /// Where crashes are reported, on builds with the `sentry` feature.
+    pub sentry_dsn: Option<String>,
+    /// Every outgoing connection goes through this proxy: both Telegram clients, image
+    /// downloads and the geocoders.
+    pub proxy: Option<Url>,
+    /// The language for users whose Telegram language has no translation.
+    pub default_locale: &'static str,
+    pub mode: UpdateMode,
+    /// The bots answered next to the one of `bot_token`.
+    pub extra_bots: Vec<ExtraBot>,
+    /// Where files above the Bot API limit are downloaded to, `cache` by default.
+    pub cache_dir: PathBuf,
+    pub lookups: LookupConfig,
+    /// The Redis server replicas coordinate through.
+    pub redis_url: Option<String>,
+    /// Where `/metrics` and the health checks are served, e.g. `127.0.0.1:9184`.
+    pub metrics_addr: Option<SocketAddr>,
+    /// A URL template for map thumbnails, or `off`.
+    pub static_map_url: Option<String>,
+    pub static_map_zoom: Option<u8>,
+    /// The Telegraph account long reports are published with, or `off`.
+    pub telegraph_token: Option<String>,
+    /// `tesseract`, the URL of a remote service, or `off`.
+    pub ocr: Option<String>,
+    pub ocr_token: Option<String>,
+    pub tesseract: Option<PathBuf>,
+    pub ocr_languages: Option<String>,
+    /// What premium costs; unset or `0` turns the tier off.
+    pub premium_stars: Option<u32>,
+    pub premium_days: Option<u64>,
+    /// Images a user may have read per day; unset or `0` means no limit.
+    pub daily_quota: Option<u32>,
+    /// Replaces `daily_quota` for premium users.
+    pub premium_quota: Option<u32>,
+    /// How many images are read at once.
+    pub max_concurrent: Option<usize>,
+    /// How long a download may stay in the cache, 60 minutes by default.
+    pub cache_ttl_minutes: Option<u64>,
+    /// How large the cache may grow, 1024 MB by default.
+    pub cache_max_mb: Option<u64>,
+    /// The font of watermarks and frames, or `off`.
+    pub font: Option<String>,
+    /// Translations reloaded while the bot runs, `locales` by default.
+    pub locales_dir: Option<PathBuf>,
+}
+
+/// What `fotobot exif` needs, resolved like [`Config`] but without the bot's settings, so
+/// the command runs without a token.
+#[derive(Debug)]
+pub struct LookupSettings {
+    pub data_dir: PathBuf,
+    pub proxy: Option<Url>,
+    pub default_locale: &'static str,
+    pub lookups: LookupConfig,
+}
+
+impl LookupSettings {
+    pub fn load() -> Result<Self> {
+        let mut file = read_configured_file()?;
+        Ok(Self {
+            lookups: LookupConfig::resolve(&mut file),
+            data_dir: resolve_data_dir(file.data_dir)?,
+            proxy: resolve_proxy(file.proxy)?,
+            default_locale: resolve_default_locale(file.default_locale),
+        })
+    }
+}
+
+impl LookupConfig {
+    /// Takes the lookup settings out of `file`, the environment taking precedence.
+    fn resolve(file: &mut ConfigFile) -> Self {
+        let mut geocoder = std::mem::take(&mut file.geocoder);
+        override_provider(&mut geocoder.primary, "FOTOBOT_GEOCODER");
+        if let Some(provider) = env_value("FOTOBOT_GEOCODER_FALLBACK") {
+            geocoder
+                .fallback
+                .get_or_insert_with(ProviderConfig::default)
+                .provider = Some(provider);
+        }
+        if let Some(fallback) = &mut geocoder.fallback {
+            override_provider(fallback, "FOTOBOT_GEOCODER_FALLBACK");
+        }
+        if let Some(contact) = env_value("FOTOBOT_GEOCODER_CONTACT") {
+            geocoder.contact = Some(contact);
+        }
+        if let Some(interval) = env_parsed("FOTOBOT_GEOCODER_INTERVAL_MS") {
+            geocoder.interval_ms = Some(interval);
+        }
+
+        Self {
+            geocoder,
+            geocode_cache_days: env_parsed("FOTOBOT_GEOCODE_CACHE_DAYS")
+                .or(file.geocode_cache_days),
+            cities_file: env_value("FOTOBOT_CITIES_FILE")
+                .map(PathBuf::from)
+                .or(file.cities_file.take()),
+            weather_key: env_value("FOTOBOT_WEATHER_KEY").or(file.weather_key.take()),
+            weather_url: env_value("FOTOBOT_WEATHER_URL").or(file.weather_url.take()),
+            what3words_key: env_value("FOTOBOT_WHAT3WORDS_KEY").or(file.what3words_key.take()),
+            elevation_url: env_value("FOTOBOT_ELEVATION_URL").or(file.elevation_url.take()),
+            overpass_url: env_value("FOTOBOT_OVERPASS_URL").or(file.overpass_url.take()),
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self> {
+        Self::resolve(read_configured_file()?)
+    }
+
+    fn resolve(mut file: ConfigFile) -> Result<Self> {
+        let lookups = LookupConfig::resolve(&mut file);
+        let bot_token = BOT_TOKEN_VARS
+            .into_iter()
+            .find_map(env_value)
+            .or(file.bot_token)
+            .ok_or_else(|| {
+                anyhow!("Telegram bot token not found: set `bot_token` or TELEGRAM_BOT_TOKEN")
+            })?;
+        let api_id = match env_value("TG_ID") {
+            Some(id) => Some(
+                id.parse()
+                    .context("`TG_ID` must be a valid 32-bit integer")?,
+            ),
+            None => file.api_id,
+        };
+        let api_hash = env_value("TG_HASH").or(file.api_hash);
+        let bot_api_url = env_value("FOTOBOT_BOT_API_URL")
+            .or(file.bot_api_url)
+            .map(|url| Url::parse(&url).with_context(|| format!("Invalid Bot API URL `{url}`")))
+            .transpose()?;
+        // A local Bot API server serves large files itself, so the secondary client is
+        // only needed against the public one.
+        let secondary_client = match (api_id, api_hash) {
+            (Some(api_id), Some(api_hash)) => Some(ApiCredentials { api_id, api_hash }),
+            (None, None) if bot_api_url.is_some() => None,
+            (None, _) => {
+                bail!("`api_id` or TG_ID is required for the secondary client")
+            }
+            (_, None) => {
+                bail!("`api_hash` or TG_HASH is required for the secondary client")
+            }
+        };
+        let data_dir = resolve_data_dir(file.data_dir)?;
+        let admin_ids = match env_value("FOTOBOT_ADMIN_IDS") {
+            Some(ids) => parse_admin_ids(&ids),
+            None => file.admin_ids.into_iter().map(UserId).collect(),
+        };
+        let error_chat = match env_value("FOTOBOT_ERROR_CHAT") {
+            Some(id) => Some(
+                id.parse()
+                    .with_context(|| format!("FOTOBOT_ERROR_CHAT `{id}` is not a chat id"))?,
+            ),
+            None => file.error_chat,
+        }
+        .map(ChatId);
+        let default_locale = resolve_default_locale(file.default_locale);
+        let proxy = resolve_proxy(file.proxy)?;
+
+        let mode = match env_value("FOTOBOT_MODE")
+            .or(file.mode)
+            .as_deref()
+            .map(str::to_ascii_lowercase)
+            .as_deref()
+        {
+            None | Some("polling") => UpdateMode::Polling,
+            Some("webhook") => UpdateMode::Webhook(resolve_webhook(file.webhook)?),
+            Some(other) => bail!("Unknown mode `{other}`: use `polling` or `webhook`"),
+        };
+
+        let extra_bots = match env_value("FOTOBOT_EXTRA_BOT_TOKENS") {
+            Some(tokens) => tokens
+                .split(',')
+                .map(str::trim)
+                .filter(|token| !token.is_empty())
+                .map(|token| ExtraBot {
+                    token: token.to_owned(),
+                    daily_quota: None,
+                    premium_quota: None,
+                })
+                .collect(),
+            None => file.bots,
+        };
+        for (index, extra) in extra_bots.iter().enumerate() {
+            if extra.token == bot_token
+                || extra_bots[..index]
+                    .iter()
+                    .any(|other| other.token == extra.token)
+            {
+                bail!("The same bot token is configured twice");
+            }
+        }
+        // Telegram posts every bot's updates to its own URL, and there is only one.
+        if !extra_bots.is_empty() && matches!(mode, UpdateMode::Webhook(_)) {
+            bail!("Additional bots are only supported in polling mode");
+        }
+
+        let metrics_addr = match env_value("FOTOBOT_METRICS_ADDR") {
+            Some(address) => Some(address.parse::<SocketAddr>().with_context(|| {
+                format!("FOTOBOT_METRICS_ADDR `{address}` is not an address and port")
+            })?),
+            None => file.metrics_addr,
+        };
+
+        Ok(Self {
+            bot_token,
+            secondary_client,
+            bot_api_url,
+            data_dir,
+            admin_ids,
+            error_chat,
+            sentry_dsn: env_value("SENTRY_DSN").or(file.sentry_dsn),
+            proxy,
+            default_locale,
+            mode,
+            extra_bots,
+            cache_dir: env_value("FOTOBOT_CACHE_DIR")
+                .map(PathBuf::from)
+                .or(file.cache_dir)
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_CACHE_DIR)),
+            lookups,
+            redis_url: env_value("FOTOBOT_REDIS_URL").or(file.redis_url),
+            metrics_addr,
+            static_map_url: env_value("FOTOBOT_STATIC_MAP_URL").or(file.static_map_url),
+            static_map_zoom: env_parsed("FOTOBOT_STATIC_MAP_ZOOM").or(file.static_map_zoom),
+            telegraph_token: env_value("FOTOBOT_TELEGRAPH_TOKEN").or(file.telegraph_token),
+            ocr: env_value("FOTOBOT_OCR").or(file.ocr),
+            ocr_token: env_value("FOTOBOT_OCR_TOKEN").or(file.ocr_token),
+            tesseract: env_value("FOTOBOT_TESSERACT")
+                .map(PathBuf::from)
+                .or(file.tesseract),
+            ocr_languages: env_value("FOTOBOT_OCR_LANGUAGES").or(file.ocr_languages),
+            premium_stars: env_parsed("FOTOBOT_PREMIUM_STARS").or(file.premium_stars),
+            premium_days: env_parsed("FOTOBOT_PREMIUM_DAYS").or(file.premium_days),
+            daily_quota: env_parsed("FOTOBOT_DAILY_QUOTA").or(file.daily_quota),
+            premium_quota: env_parsed("FOTOBOT_PREMIUM_QUOTA").or(file.premium_quota),
+            max_concurrent: env_parsed("FOTOBOT_MAX_CONCURRENT").or(file.max_concurrent),
+            cache_ttl_minutes: env_parsed("FOTOBOT_CACHE_TTL_MINUTES").or(file.cache_ttl_minutes),
+            cache_max_mb: env_parsed("FOTOBOT_CACHE_MAX_MB").or(file.cache_max_mb),
+            font: env_value("FOTOBOT_FONT").or(file.font),
+            locales_dir: env_value("FOTOBOT_LOCALES_DIR")
+                .map(PathBuf::from)
+                .or(file.locales_dir),
+        })
+    }
+}
+
+/// The file named by `FOTOBOT_CONFIG`, or `fotobot.toml` when there is one.
+fn read_configured_file() -> Result<ConfigFile> {
+    match env_value("FOTOBOT_CONFIG") {
+        Some(path) => read_file(PathBuf::from(path))?
+            .context("The file named by FOTOBOT_CONFIG does not exist"),
+        None => Ok(read_file(PathBuf::from(DEFAULT_FILE))?.unwrap_or_default()),
+    }
+}
+
+fn resolve_data_dir(file: Option<PathBuf>) -> Result<PathBuf> {
+    match env_value("FOTOBOT_DATA_DIR").map(PathBuf::from).or(file) {
+        Some(dir) => Ok(dir),
+        None => default_data_dir(),
+    }
+}
+
+fn resolve_proxy(file: Option<String>) -> Result<Option<Url>> {
+    env_value("FOTOBOT_PROXY")
+        .or(file)
+        .map(|proxy| parse_proxy(&proxy))
+        .transpose()
+}
+
+fn resolve_default_locale(file: Option<String>) -> &'static str {
+    env_value("FOTOBOT_LOCALE").or(file).map_or("en", |code| {
+        crate::locale::supported_locale(&code).unwrap_or_else(|| {
+            tracing::warn!("No translation for default locale `{code}`; using English");
+            "en"
+        })
+    })
+}
+
+fn resolve_webhook(file: WebhookFile) -> Result<WebhookConfig> {
+    let url = env_value("FOTOBOT_WEBHOOK_URL")
+        .or(file.url)
+        .context("Webhook mode needs the public `url` or FOTOBOT_WEBHOOK_URL")?;
+    let url = Url::parse(&url).with_context(|| format!("Invalid webhook URL `{url}`"))?;
+    let listen = match env_value("FOTOBOT_WEBHOOK_LISTEN") {
+        Some(listen) => listen
+            .parse()
+            .with_context(|| format!("Invalid FOTOBOT_WEBHOOK_LISTEN `{listen}`"))?,
+        None => file.listen.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+    };
+    // Hosting platforms announce the port to bind in PORT.
+    let port = match env_value("FOTOBOT_WEBHOOK_PORT").or_else(|| env_value("PORT")) {
+        Some(port) => port
+            .parse()
+            .with_context(|| format!("Invalid webhook port `{port}`"))?,
+        None => file.port.unwrap_or(DEFAULT_WEBHOOK_PORT),
+    };
+    let secret_token = env_value("FOTOBOT_WEBHOOK_SECRET").or(file.secret_token);
+    if let Some(secret) = &secret_token {
+        let valid = (1..=256).contains(&secret.len())
+            && secret
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-');
+        if !valid {
+            bail!("The webhook secret token must be 1-256 letters, digits, `_` or `-`");
+        }
+    }
+
+    Ok(WebhookConfig {
+        url,
+        address: SocketAddr::new(listen, port),
+        path: env_value("FOTOBOT_WEBHOOK_PATH").or(file.path),
+        secret_token,
+    })
+}
+
+fn parse_proxy(proxy: &str) -> Result<Url> {
+    let url = Url::parse(proxy).with_context(|| format!("Invalid proxy URL `{proxy}`"))?;
+    if !matches!(url.scheme(), "http" | "https" | "socks5" | "socks5h") {
+        bail!("Unsupported proxy `{proxy}`: use http://, https://, socks5:// or socks5h://");
+    }
+    Ok(url)
+}
+
+/// Routes an HTTP client through `proxy`, if one is configured.
+pub fn with_proxy(builder: reqwest::ClientBuilder, proxy: Option<&Url>) -> reqwest::ClientBuilder {
+    let Some(url) = proxy else {
+        return builder;
+    };
+    match reqwest::Proxy::all(url.clone()) {
+        Ok(proxy) => builder.proxy(proxy),
+        Err(err) => {
+            tracing::warn!("Not using proxy `{url}`: {err:?}");
+            builder
+        }
+    }
+}
+
+/// The parsed file, or `None` when there is none at `path`.
+fn read_file(path: PathBuf) -> Result<Option<ConfigFile>> {
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read `{}`", path.display()));
+        }
+    };
+    let file =
+        toml::from_str(&text).with_context(|| format!("Failed to parse `{}`", path.display()))?;
+    tracing::info!("Loaded configuration from `{}`", path.display());
+    Ok(Some(file))
+}
+
+/// `{prefix}`, `{prefix}_KEY` and `{prefix}_URL` replace what the file says.
+fn override_provider(provider: &mut ProviderConfig, prefix: &str) {
+    if let Some(name) = env_value(prefix) {
+        provider.provider = Some(name);
+    }
+    if let Some(key) = env_value(&format!("{prefix}_KEY")) {
+        provider.key = Some(key);
+    }
+    if let Some(url) = env_value(&format!("{prefix}_URL")) {
+        provider.url = Some(url);
+    }
+}
+
+/// Comma-separated user ids, as in `FOTOBOT_ADMIN_IDS`.
+fn parse_admin_ids(ids: &str) -> Vec<UserId> {
+    ids.split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .filter_map(|id| match id.parse() {
+            Ok(id) => Some(UserId(id)),
+            Err(_) => {
+                tracing::warn!("Ignoring `{id}` in FOTOBOT_ADMIN_IDS, which is not a user id");
+                None
+            }
+        })
+        .collect()
+}
+
+/// `~/.config/fotobot`.
+fn default_data_dir() -> Result<PathBuf> {
+    let home = std::env::var("HOME")
+        .ok()
+        .or_else(|| std::env::var("USERPROFILE").ok())
+        .map(PathBuf::from)
+        .ok_or_else(|| {
+            anyhow!("Unable to determine home directory. Set `data_dir` or FOTOBOT_DATA_DIR.")
+        })?;
+    Ok(home.join(".config").join("fotobot"))
+}
+
+fn env_value(key: &str) -> Option<String> {
+    std::env::var(key)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// The number in `key`; one that does not parse is ignored, with a warning.
+fn env_parsed<T: FromStr>(key: &str) -> Option<T> {
+    let value = env_value(key)?;
+    match value.parse() {
+        Ok(parsed) => Some(parsed),
+        Err(_) => {
+            tracing::warn!("Ignoring {key} `{value}`, which is not a valid number");
+            None
+        }
+    }
+}
+
+pub fn session_path_from_env(data_dir: &Path) -> Result<PathBuf> {
+    if let Some(path) = path_override_from_env("GRAMMERS_SESSION_FILE")? {
+        return Ok(path);
+    }
+
+    Ok(created_dir(data_dir)?.join("fotobot.session"))
+}
+
+pub fn settings_path_from_env(data_dir: &Path) -> Result<PathBuf> {
+    if let Some(path) = path_override_from_env("FOTOBOT_SETTINGS_FILE")? {
+        return Ok(path);
+    }
+
+    Ok(created_dir(data_dir)?.join("settings.json"))
+}
+
+pub fn database_path_from_env(data_dir: &Path) -> Result<PathBuf> {
+    if let Some(path) = path_override_from_env("FOTOBOT_DATABASE_FILE")? {
+        return Ok(path);
+    }
+
+    Ok(created_dir(data_dir)?.join("fotobot.db"))
+}
+
+pub fn stats_path_from_env(data_dir: &Path) -> Result<PathBuf> {
+    if let Some(path) = path_override_from_env("FOTOBOT_STATS_FILE")? {
+        return Ok(path);
+    }
+
+    Ok(created_dir(data_dir)?.join("stats.json"))
+}
+
+pub fn history_path_from_env(data_dir: &Path) -> Result<PathBuf> {
+    if let Some(path) = path_override_from_env("FOTOBOT_HISTORY_FILE")? {
+        return Ok(path);
+    }
+
+    Ok(created_dir(data_dir)?.join("history.json"))
+}
+
+pub fn geocode_cache_path_from_env(data_dir: &Path) -> Result<PathBuf> {
+    if let Some(path) = path_override_from_env("FOTOBOT_GEOCODE_CACHE_FILE")? {
+        return Ok(path);
+    }
+
+    Ok(created_dir(data_dir)?.join("geocode-cache.json"))
+}
+
+pub fn poi_cache_path_from_env(data_dir: &Path) -> Result<PathBuf> {
+    if let Some(path) = path_override_from_env("FOTOBOT_POI_CACHE_FILE")? {
+        return Ok(path);
+    }
+
+    Ok(created_dir(data_dir)?.join("poi-cache.json"))
+}
+
+/// Where the extra bot `tag` keeps what the primary bot keeps at `path`: `fotobot.db`
+/// becomes `fotobot-123.db`. The primary bot, with no tag, keeps `path`.
+pub fn tagged_path(path: PathBuf, tag: Option<UserId>) -> PathBuf {
+    let Some(UserId(id)) = tag else {
+        return path;
+    };
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(extension) => format!("{stem}-{id}.{}", extension.to_string_lossy()),
+        None => format!("{stem}-{id}"),
+    };
+    path.with_file_name(name)
+}
+
+/// Reads a file path from `key`, creating its parent directory when needed.
+pub fn path_override_from_env(key: &str) -> Result<Option<PathBuf>> {
+    if let Ok(path) = std::env::var(key) {
+        if !path.trim().is_empty() {
+            let path = PathBuf::from(path);
+            if let Some(parent) = path.parent() {
+                if !parent.as_os_str().is_empty() {
+                    std::fs::create_dir_all(parent).with_context(|| {
+                        format!(
+                            "Failed to create directory for `{key}` at `{}`",
+                            parent.display()
+                        )
+                    })?;
+                }
+            }
+            return Ok(Some(path));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Creates the data directory on first use.
+fn created_dir(data_dir: &Path) -> Result<PathBuf> {
+    std::fs::create_dir_all(data_dir).with_context(|| {
+        format!(
+            "Failed to create session directory at `{}`",
+            data_dir.display()
+        )
+    })?;
+
+    Ok(data_dir.to_path_buf())
+}
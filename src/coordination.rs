@@ -0,0 +1,247 @@
+//! Lets several replicas of the bot behave like one through Redis at `FOTOBOT_REDIS_URL`:
+//! each update is answered by one replica, albums are collected across replicas, and
+//! geocoding answers and rate limits are shared. Without Redis, or on builds without the
+//! `redis` feature, every process keeps all of this to itself.
+//!
+//! Redis failing never stops an image from being read: each call logs the error and
+//! answers so that the caller falls back to its local state.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// Every key starts with this, so the bot can share a Redis database.
+#[cfg(feature = "redis")]
+const PREFIX: &str = "fotobot:";
+
+#[cfg(feature = "redis")]
+static CONNECTION: std::sync::OnceLock<redis::aio::ConnectionManager> = std::sync::OnceLock::new();
+
+/// Hands out the next request slot of a limiter, timed by the Redis clock so replicas
+/// agree on it, and returns how many milliseconds the caller waits for it.
+#[cfg(feature = "redis")]
+static RESERVE: std::sync::LazyLock<redis::Script> = std::sync::LazyLock::new(|| {
+    redis::Script::new(
+        r"
+        local time = redis.call('TIME')
+        local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+        local slot = math.max(tonumber(redis.call('GET', KEYS[1]) or '0'), now)
+        local next = slot + tonumber(ARGV[1])
+        redis.call('SET', KEYS[1], next, 'PX', next - now + 1000)
+        return slot - now
+        ",
+    )
+});
+
+/// Holds a limiter's requests back for `ARGV[1]` milliseconds from now.
+#[cfg(feature = "redis")]
+static BACK_OFF: std::sync::LazyLock<redis::Script> = std::sync::LazyLock::new(|| {
+    redis::Script::new(
+        r"
+        local time = redis.call('TIME')
+        local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+        local resume = now + tonumber(ARGV[1])
+        if resume > tonumber(redis.call('GET', KEYS[1]) or '0') then
+            redis.call('SET', KEYS[1], resume, 'PX', tonumber(ARGV[1]) + 1000)
+        end
+        return 0
+        ",
+    )
+});
+
+/// Connects to `redis_url`, if set, for the rest of the process.
+pub async fn connect_configured(redis_url: Option<&str>) -> Result<()> {
+    let Some(url) = redis_url else {
+        return Ok(());
+    };
+    connect(url).await
+}
+
+#[cfg(feature = "redis")]
+async fn connect(url: &str) -> Result<()> {
+    use anyhow::Context;
+
+    let client = redis::Client::open(url).context("Invalid Redis URL")?;
+    let connection = redis::aio::ConnectionManager::new(client)
+        .await
+        .context("Failed to connect to Redis")?;
+    CONNECTION.set(connection).ok();
+    tracing::info!("Coordinating with other replicas through Redis");
+    Ok(())
+}
+
+#[cfg(not(feature = "redis"))]
+async fn connect(_url: &str) -> Result<()> {
+    tracing::warn!("Ignoring FOTOBOT_REDIS_URL: build with `--features redis` to use it");
+    Ok(())
+}
+
+#[cfg(feature = "redis")]
+fn connection() -> Option<redis::aio::ConnectionManager> {
+    CONNECTION.get().cloned()
+}
+
+#[cfg(feature = "redis")]
+fn key(name: &str) -> String {
+    format!("{PREFIX}{name}")
+}
+
+#[cfg(feature = "redis")]
+fn failed(what: &str, err: redis::RedisError) {
+    tracing::warn!("Redis failed to {what}, using local state: {err:?}");
+}
+
+/// Claims `name` for this replica for `ttl`. `false` when another replica claimed it
+/// first; without Redis every claim succeeds.
+pub async fn claim(name: &str, ttl: Duration) -> bool {
+    #[cfg(feature = "redis")]
+    if let Some(mut connection) = connection() {
+        let claimed = redis::cmd("SET")
+            .arg(key(name))
+            .arg(1)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis() as u64)
+            .query_async::<_, Option<String>>(&mut connection)
+            .await;
+        match claimed {
+            Ok(claimed) => return claimed.is_some(),
+            Err(err) => failed("claim an update", err),
+        }
+    }
+    let _ = (name, ttl);
+    true
+}
+
+/// The value another replica stored under `name`, if any.
+pub async fn cached<T: DeserializeOwned>(name: &str) -> Option<T> {
+    #[cfg(feature = "redis")]
+    if let Some(mut connection) = connection() {
+        let stored = redis::cmd("GET")
+            .arg(key(name))
+            .query_async::<_, Option<String>>(&mut connection)
+            .await;
+        match stored {
+            Ok(stored) => return stored.and_then(|json| serde_json::from_str(&json).ok()),
+            Err(err) => failed("read the shared cache", err),
+        }
+    }
+    let _ = name;
+    None
+}
+
+/// Shares `value` under `name` with the other replicas for `ttl`.
+pub async fn store<T: Serialize>(name: &str, value: &T, ttl: Duration) {
+    #[cfg(feature = "redis")]
+    if let Some(mut connection) = connection() {
+        let Ok(json) = serde_json::to_string(value) else {
+            return;
+        };
+        let stored = redis::cmd("SET")
+            .arg(key(name))
+            .arg(json)
+            .arg("PX")
+            .arg(ttl.as_millis() as u64)
+            .query_async::<_, ()>(&mut connection)
+            .await;
+        if let Err(err) = stored {
+            failed("write the shared cache", err);
+        }
+    }
+    let _ = (name, value, ttl);
+}
+
+/// Appends `value` to the list `name`, which expires `ttl` after the last append, and
+/// returns the list's length; `None` without Redis.
+pub async fn push<T: Serialize>(name: &str, value: &T, ttl: Duration) -> Option<usize> {
+    #[cfg(feature = "redis")]
+    if let Some(mut connection) = connection() {
+        let json = serde_json::to_string(value).ok()?;
+        let pushed = redis::pipe()
+            .atomic()
+            .cmd("RPUSH")
+            .arg(key(name))
+            .arg(json)
+            .cmd("PEXPIRE")
+            .arg(key(name))
+            .arg(ttl.as_millis() as u64)
+            .ignore()
+            .query_async::<_, (usize,)>(&mut connection)
+            .await;
+        match pushed {
+            Ok((length,)) => return Some(length),
+            Err(err) => failed("collect an album", err),
+        }
+    }
+    let _ = (name, value, ttl);
+    None
+}
+
+/// Removes the list `name` and returns what every replica appended to it; `None`
+/// without Redis.
+pub async fn take<T: DeserializeOwned>(name: &str) -> Option<Vec<T>> {
+    #[cfg(feature = "redis")]
+    if let Some(mut connection) = connection() {
+        let taken = redis::pipe()
+            .atomic()
+            .cmd("LRANGE")
+            .arg(key(name))
+            .arg(0)
+            .arg(-1)
+            .cmd("DEL")
+            .arg(key(name))
+            .ignore()
+            .query_async::<_, (Vec<String>,)>(&mut connection)
+            .await;
+        match taken {
+            Ok((values,)) => {
+                return Some(
+                    values
+                        .iter()
+                        .filter_map(|json| serde_json::from_str(json).ok())
+                        .collect(),
+                );
+            }
+            Err(err) => failed("collect an album", err),
+        }
+    }
+    let _ = name;
+    None
+}
+
+/// Reserves the next slot of the limiter `name`, shared by every replica, and returns how
+/// long until it comes; `None` without Redis.
+pub async fn reserve(name: &str, interval: Duration) -> Option<Duration> {
+    #[cfg(feature = "redis")]
+    if let Some(mut connection) = connection() {
+        let reserved = RESERVE
+            .key(key(name))
+            .arg(interval.as_millis() as u64)
+            .invoke_async::<_, u64>(&mut connection)
+            .await;
+        match reserved {
+            Ok(delay) => return Some(Duration::from_millis(delay)),
+            Err(err) => failed("reserve a request slot", err),
+        }
+    }
+    let _ = (name, interval);
+    None
+}
+
+/// Holds back every replica's requests of the limiter `name` for `delay`.
+pub async fn back_off(name: &str, delay: Duration) {
+    #[cfg(feature = "redis")]
+    if let Some(mut connection) = connection() {
+        let held = BACK_OFF
+            .key(key(name))
+            .arg(delay.as_millis() as u64)
+            .invoke_async::<_, ()>(&mut connection)
+            .await;
+        if let Err(err) = held {
+            failed("back off", err);
+        }
+    }
+    let _ = (name, delay);
+}
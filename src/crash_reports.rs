@@ -0,0 +1,105 @@
+//! Crash and error reports to Sentry, on builds with the `sentry` feature.
+//!
+//! Panics are captured wherever they happen, the blocking EXIF tasks and the grammers
+//! runner included. `tracing` errors become events, and the info lines of each update,
+//! such as the download, parse and geocode timings, the breadcrumbs leading up to them.
+//! Bot tokens, which download errors carry in their file URLs, are replaced before any
+//! of it is sent.
+
+use std::sync::Arc;
+
+use sentry::protocol::{Breadcrumb, Event, Map, Value};
+
+use crate::config::Config;
+
+/// Starts the Sentry client when `sentry_dsn` or `SENTRY_DSN` is set. Reports are sent
+/// until the guard is dropped.
+pub fn init(config: &Config) -> Option<sentry::ClientInitGuard> {
+    let dsn = config.sentry_dsn.as_deref()?;
+    let tokens: Arc<Vec<String>> = Arc::new(
+        std::iter::once(&config.bot_token)
+            .chain(config.extra_bots.iter().map(|extra| &extra.token))
+            .filter(|token| !token.is_empty())
+            .cloned()
+            .collect(),
+    );
+    let breadcrumb_tokens = Arc::clone(&tokens);
+    let guard = sentry::init((
+        dsn,
+        sentry::ClientOptions {
+            release: sentry::release_name!(),
+            attach_stacktrace: true,
+            before_send: Some(Arc::new(move |mut event| {
+                redact_event(&mut event, &tokens);
+                Some(event)
+            })),
+            before_breadcrumb: Some(Arc::new(move |mut breadcrumb| {
+                redact_breadcrumb(&mut breadcrumb, &breadcrumb_tokens);
+                Some(breadcrumb)
+            })),
+            ..Default::default()
+        },
+    ));
+    if guard.is_enabled() {
+        tracing::info!("Reporting errors to Sentry");
+    } else {
+        tracing::warn!("Sentry is disabled: the DSN could not be used");
+    }
+    Some(guard)
+}
+
+fn redact_event(event: &mut Event<'static>, tokens: &[String]) {
+    redact_option(&mut event.message, tokens);
+    if let Some(entry) = &mut event.logentry {
+        redact(&mut entry.message, tokens);
+        entry
+            .params
+            .iter_mut()
+            .for_each(|param| redact_value(param, tokens));
+    }
+    for exception in &mut event.exception.values {
+        redact_option(&mut exception.value, tokens);
+    }
+    redact_map(&mut event.extra, tokens);
+    event.tags.values_mut().for_each(|tag| redact(tag, tokens));
+    for breadcrumb in &mut event.breadcrumbs.values {
+        redact_breadcrumb(breadcrumb, tokens);
+    }
+}
+
+fn redact_breadcrumb(breadcrumb: &mut Breadcrumb, tokens: &[String]) {
+    redact_option(&mut breadcrumb.message, tokens);
+    redact_map(&mut breadcrumb.data, tokens);
+}
+
+fn redact_map(map: &mut Map<String, Value>, tokens: &[String]) {
+    map.values_mut()
+        .for_each(|value| redact_value(value, tokens));
+}
+
+fn redact_value(value: &mut Value, tokens: &[String]) {
+    match value {
+        Value::String(text) => redact(text, tokens),
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| redact_value(value, tokens)),
+        Value::Object(map) => map
+            .values_mut()
+            .for_each(|value| redact_value(value, tokens)),
+        _ => {}
+    }
+}
+
+fn redact_option(text: &mut Option<String>, tokens: &[String]) {
+    if let Some(text) = text {
+        redact(text, tokens);
+    }
+}
+
+fn redact(text: &mut String, tokens: &[String]) {
+    for token in tokens {
+        if text.contains(token.as_str()) {
+            *text = text.replace(token.as_str(), "<bot token>");
+        }
+    }
+}
@@ -0,0 +1,392 @@
+//! At-rest encryption for operators on shared hosts: the secondary client's session and
+//! the downloads in the cache directory. The key is 32 random bytes written as hex, e.g.
+//! from `openssl rand -hex 32`, in `FOTOBOT_ENCRYPTION_KEY`, in the file named by
+//! `FOTOBOT_ENCRYPTION_KEY_FILE` or, on builds with the `keyring` feature, in the system
+//! keyring. Without a key both are stored as before.
+//!
+//! The session is sealed with ChaCha20-Poly1305. SQLite needs a plain file while the bot
+//! runs, so it is opened from a copy in a fresh private directory, which is sealed again
+//! after signing in and on shutdown. Downloads are encrypted with ChaCha20, which can
+//! seek, so the EXIF parser reads them without decrypting the whole file first.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result, anyhow, bail};
+use chacha20::ChaCha20;
+use chacha20::cipher::{KeyIvInit, StreamCipher, StreamCipherSeek};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, KeyInit, Nonce};
+use tokio::io::AsyncWriteExt;
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+/// Added to the session file's name for its sealed copy.
+const SEALED_EXTENSION: &str = "enc";
+
+static KEY: OnceLock<Option<Key>> = OnceLock::new();
+
+pub struct Key([u8; KEY_LEN]);
+
+impl Key {
+    /// The key from the environment or the keyring; `None` when encryption is off.
+    pub fn from_env() -> Result<Option<Self>> {
+        if let Some(value) = env_value("FOTOBOT_ENCRYPTION_KEY") {
+            return Self::parse(&value)
+                .context("FOTOBOT_ENCRYPTION_KEY is not 64 hex digits")
+                .map(Some);
+        }
+        if let Some(path) = env_value("FOTOBOT_ENCRYPTION_KEY_FILE") {
+            let value = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read the encryption key from `{path}`"))?;
+            return Self::parse(value.trim())
+                .with_context(|| format!("`{path}` does not hold 64 hex digits"))
+                .map(Some);
+        }
+        #[cfg(feature = "keyring")]
+        {
+            let entry = keyring::Entry::new("fotobot", "encryption-key")
+                .context("Failed to open the system keyring")?;
+            match entry.get_password() {
+                Ok(value) => {
+                    return Self::parse(value.trim())
+                        .context("The keyring's fotobot encryption key is not 64 hex digits")
+                        .map(Some);
+                }
+                Err(keyring::Error::NoEntry) => {}
+                Err(err) => return Err(err).context("Failed to read the system keyring"),
+            }
+        }
+        Ok(None)
+    }
+
+    fn parse(hex: &str) -> Result<Self> {
+        if hex.len() != KEY_LEN * 2 || !hex.is_ascii() {
+            bail!("expected {} hex digits, found {}", KEY_LEN * 2, hex.len());
+        }
+        let mut key = [0; KEY_LEN];
+        for (byte, pair) in key.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            let pair = std::str::from_utf8(pair).unwrap_or_default();
+            *byte = u8::from_str_radix(pair, 16).map_err(|_| anyhow!("`{pair}` is not hex"))?;
+        }
+        Ok(Self(key))
+    }
+
+    /// Makes the key available to the session and cache code; `None` turns encryption off.
+    pub fn install(key: Option<Self>) {
+        if KEY.set(key).is_err() {
+            tracing::warn!("The encryption key was already installed");
+        }
+    }
+
+    /// A random nonce followed by the ciphertext and its tag.
+    fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let mut nonce = [0; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+        let ciphertext = ChaCha20Poly1305::new(&self.0.into())
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .map_err(|_| anyhow!("Failed to encrypt"))?;
+        Ok([nonce.as_slice(), &ciphertext].concat())
+    }
+
+    fn open(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        if sealed.len() < NONCE_LEN {
+            bail!("The sealed file is truncated");
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        ChaCha20Poly1305::new(&self.0.into())
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow!("Wrong key, or the file was changed"))
+    }
+
+    fn stream(&self, nonce: &[u8; NONCE_LEN]) -> ChaCha20 {
+        ChaCha20::new(&self.0.into(), nonce.into())
+    }
+}
+
+fn key() -> Option<&'static Key> {
+    KEY.get().and_then(Option::as_ref)
+}
+
+/// Where SQLite keeps the session: the session file itself, or with a key, a plain copy
+/// of its sealed version in a private directory of its own.
+pub struct SessionFile {
+    path: PathBuf,
+    /// The sealed copy and the key, when the session is encrypted.
+    sealed: Option<(PathBuf, &'static Key)>,
+    /// A plain session from before encryption was turned on, removed once sealed.
+    legacy: Option<PathBuf>,
+    /// The private directory holding the plain copy, removed on close.
+    working_dir: Option<PathBuf>,
+}
+
+impl SessionFile {
+    pub fn open(session_path: PathBuf) -> Result<Self> {
+        let Some(key) = key() else {
+            return Ok(Self {
+                path: session_path,
+                sealed: None,
+                legacy: None,
+                working_dir: None,
+            });
+        };
+
+        let sealed_path = with_extension(&session_path, SEALED_EXTENSION);
+        let name = session_path
+            .file_name()
+            .map(|name| name.to_os_string())
+            .unwrap_or_else(|| "fotobot.session".into());
+        let working_dir = private_dir()?;
+        let working = working_dir.join(name);
+        let (plaintext, legacy) = if sealed_path.exists() {
+            let sealed = std::fs::read(&sealed_path)
+                .with_context(|| format!("Failed to read `{}`", sealed_path.display()))?;
+            let plaintext = key
+                .open(&sealed)
+                .with_context(|| format!("Failed to decrypt `{}`", sealed_path.display()))?;
+            (plaintext, None)
+        } else if session_path.exists() {
+            tracing::info!(
+                "Encrypting the session at `{}`; the plain file is removed once sealed",
+                session_path.display()
+            );
+            let plaintext = std::fs::read(&session_path)
+                .with_context(|| format!("Failed to read `{}`", session_path.display()))?;
+            (plaintext, Some(session_path))
+        } else {
+            (Vec::new(), None)
+        };
+        // Written even when empty, so SQLite creates no file anyone else can read.
+        write_new_private(&working, &plaintext)?;
+
+        Ok(Self {
+            path: working,
+            sealed: Some((sealed_path, key)),
+            legacy,
+            working_dir: Some(working_dir),
+        })
+    }
+
+    /// The file to open the SQLite session from.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Seals the working copy, so a crash after this loses no sign-in. Does nothing for
+    /// an unencrypted session.
+    pub fn save(&mut self) -> Result<()> {
+        let Some((sealed_path, key)) = &self.sealed else {
+            return Ok(());
+        };
+        let plaintext = std::fs::read(&self.path)
+            .with_context(|| format!("Failed to read `{}`", self.path.display()))?;
+        let partial = with_extension(sealed_path, "tmp");
+        write_private(&partial, &key.seal(&plaintext)?)?;
+        std::fs::rename(&partial, sealed_path)
+            .with_context(|| format!("Failed to replace `{}`", sealed_path.display()))?;
+        if let Some(legacy) = self.legacy.take() {
+            std::fs::remove_file(&legacy)
+                .with_context(|| format!("Failed to remove `{}`", legacy.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Seals the session one last time and removes the plain copy.
+    pub fn close(mut self) -> Result<()> {
+        if self.sealed.is_none() {
+            return Ok(());
+        }
+        self.save()?;
+        for suffix in ["", "-journal", "-wal", "-shm"] {
+            let path = PathBuf::from(format!("{}{suffix}", self.path.display()));
+            if let Err(err) = std::fs::remove_file(&path)
+                && err.kind() != io::ErrorKind::NotFound
+            {
+                tracing::warn!("Failed to remove `{}`: {err:?}", path.display());
+            }
+        }
+        if let Some(dir) = &self.working_dir
+            && let Err(err) = std::fs::remove_dir(dir)
+        {
+            tracing::warn!("Failed to remove `{}`: {err:?}", dir.display());
+        }
+        Ok(())
+    }
+}
+
+/// A download being written to the cache, encrypted when a key is installed.
+pub struct CacheWriter {
+    file: tokio::fs::File,
+    cipher: Option<ChaCha20>,
+}
+
+impl CacheWriter {
+    pub async fn create(path: &Path) -> Result<Self> {
+        let mut file = tokio::fs::File::create(path)
+            .await
+            .with_context(|| format!("Failed to create `{}`", path.display()))?;
+        let cipher = match key() {
+            Some(key) => {
+                let mut nonce = [0; NONCE_LEN];
+                OsRng.fill_bytes(&mut nonce);
+                file.write_all(&nonce)
+                    .await
+                    .context("Failed to write the cache file's nonce")?;
+                Some(key.stream(&nonce))
+            }
+            None => None,
+        };
+        Ok(Self { file, cipher })
+    }
+
+    pub async fn write(&mut self, chunk: &[u8]) -> Result<()> {
+        let written = match &mut self.cipher {
+            Some(cipher) => {
+                let mut chunk = chunk.to_vec();
+                cipher.apply_keystream(&mut chunk);
+                self.file.write_all(&chunk).await
+            }
+            None => self.file.write_all(chunk).await,
+        };
+        written.context("Failed to persist downloaded media to cache")
+    }
+
+    pub async fn finish(mut self) -> Result<()> {
+        self.file
+            .flush()
+            .await
+            .context("Failed to persist downloaded media to cache")
+    }
+}
+
+/// A download in the cache, decrypted as it is read when a key is installed.
+pub enum CacheReader {
+    Plain(File),
+    Encrypted { file: File, cipher: ChaCha20 },
+}
+
+impl CacheReader {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut file = File::open(path)
+            .with_context(|| format!("Failed to open local image at `{}`", path.display()))?;
+        let Some(key) = key() else {
+            return Ok(Self::Plain(file));
+        };
+        let mut nonce = [0; NONCE_LEN];
+        file.read_exact(&mut nonce)
+            .with_context(|| format!("`{}` is too short to be encrypted", path.display()))?;
+        Ok(Self::Encrypted {
+            file,
+            cipher: key.stream(&nonce),
+        })
+    }
+}
+
+impl Read for CacheReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(file) => file.read(buf),
+            Self::Encrypted { file, cipher } => {
+                let read = file.read(buf)?;
+                cipher.apply_keystream(&mut buf[..read]);
+                Ok(read)
+            }
+        }
+    }
+}
+
+impl Seek for CacheReader {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        match self {
+            Self::Plain(file) => file.seek(position),
+            Self::Encrypted { file, cipher } => {
+                // The nonce comes first, so plaintext offsets are shifted by its length.
+                let offset = NONCE_LEN as u64;
+                let target = match position {
+                    SeekFrom::Start(position) => SeekFrom::Start(position + offset),
+                    other => other,
+                };
+                let position = file.seek(target)?;
+                let Some(position) = position.checked_sub(offset) else {
+                    file.seek(SeekFrom::Start(offset))?;
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "seek before the start",
+                    ));
+                };
+                cipher.seek(position);
+                Ok(position)
+            }
+        }
+    }
+}
+
+/// Writes `bytes` to `path`, readable by the owner only.
+fn write_private(path: &Path, bytes: &[u8]) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to create `{}`", path.display()))?;
+    file.write_all(bytes)
+        .and_then(|()| file.sync_all())
+        .with_context(|| format!("Failed to write `{}`", path.display()))
+}
+
+/// Creates `path` for `bytes`, readable by the owner only, refusing a file or symlink
+/// that is already there.
+fn write_new_private(path: &Path, bytes: &[u8]) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600).custom_flags(libc::O_NOFOLLOW);
+    }
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to create `{}`", path.display()))?;
+    file.write_all(bytes)
+        .and_then(|()| file.sync_all())
+        .with_context(|| format!("Failed to write `{}`", path.display()))
+}
+
+/// Creates a directory with a random name that only the owner can enter, in
+/// `XDG_RUNTIME_DIR`, which is usually in memory and private to the user, or else in the
+/// temporary directory. Nobody can have put a file in it beforehand.
+fn private_dir() -> Result<PathBuf> {
+    let parent = env_value("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    let mut suffix = [0; 8];
+    OsRng.fill_bytes(&mut suffix);
+    let suffix: String = suffix.iter().map(|byte| format!("{byte:02x}")).collect();
+    let path = parent.join(format!("fotobot-{suffix}"));
+
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder
+        .create(&path)
+        .with_context(|| format!("Failed to create `{}`", path.display()))?;
+    Ok(path)
+}
+
+fn with_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(extension);
+    PathBuf::from(name)
+}
+
+fn env_value(key: &str) -> Option<String> {
+    std::env::var(key)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
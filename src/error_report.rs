@@ -0,0 +1,102 @@
+//! Failure reports for the operators, sent to the chat in `error_chat` so new file formats
+//! and API breakage show up without reading the logs.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use teloxide::prelude::*;
+use teloxide::types::{ChatId, UserId};
+
+/// The same failure is reported once in this window, so an outage does not flood the chat.
+const REPEAT_WINDOW: Duration = Duration::from_secs(10 * 60);
+const TELEGRAM_MESSAGE_LIMIT: usize = 4096;
+
+/// An image the bot failed to read, with what is needed to find it again.
+pub struct Failure<'a> {
+    /// `inline` or `large_file`.
+    pub path: &'static str,
+    pub chat_id: ChatId,
+    pub message_id: i32,
+    pub user_id: Option<UserId>,
+    pub file_id: &'a str,
+    pub file_unique_id: &'a str,
+    /// Known for files above the Bot API limit.
+    pub size: Option<u64>,
+    pub file_name: Option<&'a str>,
+    pub mime_type: Option<String>,
+    pub error: &'a anyhow::Error,
+}
+
+pub struct ErrorReports {
+    chat: Option<ChatId>,
+    /// When each failure was last reported.
+    sent: Mutex<HashMap<String, Instant>>,
+}
+
+impl ErrorReports {
+    /// Reports to `chat`; `None` turns reports off.
+    pub fn new(chat: Option<ChatId>) -> Self {
+        Self {
+            chat,
+            sent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sends the report unless the same failure was reported in the last
+    /// [`REPEAT_WINDOW`]. Failing to send is only logged.
+    pub async fn send(&self, bot: &Bot, failure: Failure<'_>) {
+        let Some(chat) = self.chat else {
+            return;
+        };
+        let key = format!("{}: {}", failure.path, failure.error.root_cause());
+        {
+            let mut sent = self.sent.lock().unwrap_or_else(|err| err.into_inner());
+            let now = Instant::now();
+            sent.retain(|_, at| now.duration_since(*at) < REPEAT_WINDOW);
+            if sent.contains_key(&key) {
+                return;
+            }
+            sent.insert(key, now);
+        }
+
+        // Errors can carry the file URL, which has the bot token in it.
+        let text = describe(&failure).replace(bot.token(), "<bot token>");
+        if let Err(err) = bot.send_message(chat, truncate(text)).await {
+            tracing::warn!("Failed to send error report to chat {}: {err:?}", chat.0);
+        }
+    }
+}
+
+fn describe(failure: &Failure<'_>) -> String {
+    let mut text = String::from("⚠️ Failed to read an image\n\n");
+    writeln!(text, "path: {}", failure.path).ok();
+    writeln!(text, "chat: {}", failure.chat_id.0).ok();
+    writeln!(text, "message: {}", failure.message_id).ok();
+    if let Some(user_id) = failure.user_id {
+        writeln!(text, "user: {}", user_id.0).ok();
+    }
+    writeln!(text, "file_id: {}", failure.file_id).ok();
+    writeln!(text, "file_unique_id: {}", failure.file_unique_id).ok();
+    if let Some(size) = failure.size {
+        writeln!(text, "size: {size} bytes").ok();
+    }
+    if let Some(name) = failure.file_name {
+        writeln!(text, "file name: {name}").ok();
+    }
+    if let Some(mime_type) = &failure.mime_type {
+        writeln!(text, "mime type: {mime_type}").ok();
+    }
+    // The debug form lists every context with its cause.
+    write!(text, "\n{:?}", failure.error).ok();
+    text
+}
+
+fn truncate(mut text: String) -> String {
+    if text.chars().count() > TELEGRAM_MESSAGE_LIMIT {
+        text = text.chars().take(TELEGRAM_MESSAGE_LIMIT - 1).collect();
+        text.push('…');
+    }
+    text
+}
@@ -1,70 +1,524 @@
-use std::fmt::Write;
 use std::fs::File;
-use std::io::{BufReader, Seek, SeekFrom};
+use std::io::{BufRead, BufReader, Cursor, Seek, SeekFrom};
 use std::path::Path;
+use std::time::Instant;
 
 use anyhow::{Context, Result};
+use chrono::{FixedOffset, NaiveDate, NaiveDateTime};
+use chrono_tz::Tz;
 use exif::{Error as ExifError, Exif, Field, In, Reader as ExifReader, Tag, Value};
-use http_range_client::HttpReader;
-use log::warn;
-use reqwest::blocking::Client;
+use serde::Serialize;
 use serde_json::Value as JsonValue;
 
-/// Downloads the image from the given URL and returns a textual summary of the
-/// leading bytes and EXIF metadata.
-pub fn summarize_exif(url: &str, accept_language: Option<&str>) -> Result<String> {
-    let mut reader = HttpReader::new(url);
-    reader.set_min_req_size(500 * 1024);
+use crate::formats::Units;
+use crate::geo::Lookups;
+use crate::geo::geocoder::Address;
+use crate::geo::moon::{self, Moon};
+use crate::geo::sun::{self, Light};
+use crate::geo::weather::Weather;
+use crate::geo::{pluscode, timezone};
+use crate::provenance::{self, Marker};
+use crate::ranged;
+pub use crate::settings::{CoordinatePrecision, ZoneMode, ZoneSet};
+use crate::styles::{self, CaptionStyle, LabelStyle, Section, Sections};
+
+/// How a caption is laid out and which parts of the location it may show.
+#[derive(Clone, Copy, Default)]
+pub struct CaptionOptions {
+    /// Leaves out the "📍" coordinates line.
+    pub hide_coordinates: bool,
+    /// Leaves out the "🗺️" place line; the coordinates are not geocoded at all.
+    pub hide_location: bool,
+    /// Wraps the place and coordinates in a spoiler instead of showing them openly.
+    pub spoiler_location: bool,
+    pub style: CaptionStyle,
+    pub labels: LabelStyle,
+    /// Appends the text recognized in the photo, on builds with the `ocr` feature.
+    pub detect_text: bool,
+    /// Names a landmark near the position, for users who opted in with `/nearby on`.
+    pub nearby: bool,
+    /// Sections left out for layout reasons; location and GPS go through the flags above.
+    pub hidden: Sections,
+    /// Names only the city and country, for photos taken in a coarse private zone.
+    pub coarse_location: bool,
+    /// Rounds the coordinates and map links; only exact ones get a Plus Code and words.
+    pub precision: CoordinatePrecision,
+    pub private_zones: ZoneSet,
+    /// Units of the altitude, speed and distances, and the clock of the date line.
+    pub units: Units,
+}
 
-    reader
-        .seek(SeekFrom::Start(0))
-        .context("Failed to seek to start of HTTP stream")?;
+impl CaptionOptions {
+    /// Tightens the options for a photo taken at `position`, when that falls in one of
+    /// the user's private zones.
+    pub fn at_position(mut self, position: Option<(f64, f64)>) -> Self {
+        let Some((latitude, longitude)) = position else {
+            return self;
+        };
+        match self.private_zones.mode_at(latitude, longitude) {
+            Some(ZoneMode::Hide) => {
+                self.hide_coordinates = true;
+                self.hide_location = true;
+            }
+            Some(ZoneMode::Coarse) => {
+                self.hide_coordinates = true;
+                self.coarse_location = true;
+            }
+            None => {}
+        }
+        self
+    }
 
-    let mut buf_reader = BufReader::new(reader);
-    let exif_reader = ExifReader::new();
+    /// The part of `position` a reply may reveal: nothing when the options or a private
+    /// zone hide the coordinates, otherwise the position rounded to the precision.
+    pub fn shown_position(self, position: Option<(f64, f64)>) -> Option<(f64, f64)> {
+        let options = self.at_position(position);
+        position
+            .filter(|_| !options.hide_coordinates)
+            .map(|gps| options.precision.round(gps))
+    }
 
-    let exif = match exif_reader.read_from_container(&mut buf_reader) {
-        Ok(exif) => exif,
-        Err(ExifError::NotFound(_)) => return Ok(build_empty_caption()),
-        Err(err) => return Err(err.into()),
-    };
+    pub fn shows(self, section: Section) -> bool {
+        !self.hidden.contains(section)
+    }
+}
 
-    let summary = ParsedExif::from_exif(&exif, accept_language);
-    Ok(build_caption(&summary))
+/// The caption for an image, plus what follow-up replies need from its EXIF data.
+pub struct ExifSummary {
+    pub caption: String,
+    /// Signed decimal latitude and longitude, unless the caption options hide them.
+    pub gps: Option<(f64, f64)>,
+    pub facts: ShotFacts,
 }
 
-/// Reads EXIF data from a local file and returns the formatted summary.
-pub fn summarize_exif_from_file(path: &Path, accept_language: Option<&str>) -> Result<String> {
+impl ExifSummary {
+    fn empty(accept_language: Option<&str>, options: CaptionOptions) -> Self {
+        Self {
+            caption: build_empty_caption(accept_language, options),
+            gps: None,
+            facts: ShotFacts::unknown(),
+        }
+    }
+
+    fn from_parsed(
+        exif: &Exif,
+        parsed: &ParsedExif,
+        accept_language: Option<&str>,
+        options: CaptionOptions,
+    ) -> Self {
+        Self {
+            caption: styles::render(
+                parsed,
+                options,
+                crate::locale::locale_from_language_code(accept_language),
+            ),
+            gps: options.shown_position(gps_location(exif)),
+            facts: ShotFacts::from_exif(exif),
+        }
+    }
+
+    /// Geocodes the image, looks up its what3words address, nearby landmark, weather
+    /// and, when the GPS recorded no altitude, the terrain elevation, and builds its
+    /// summary, noting when it looks AI-generated.
+    pub async fn build(
+        lookups: &Lookups,
+        metadata: ImageMetadata,
+        accept_language: Option<&str>,
+        options: CaptionOptions,
+    ) -> Self {
+        let ImageMetadata { exif, markers } = metadata;
+        let mut summary = match &exif {
+            Some(exif) => {
+                let options = options.at_position(gps_location(exif));
+                let address = geocode(lookups, exif, accept_language, options).await;
+                let mut parsed = ParsedExif::from_exif(exif, address.as_ref());
+                if !options.hide_coordinates && options.precision == CoordinatePrecision::Exact {
+                    parsed.what3words = three_words(lookups, &parsed, accept_language).await;
+                }
+                if options.nearby && !options.hide_location && !options.coarse_location {
+                    parsed.nearby =
+                        landmark(lookups, &parsed, address.as_ref(), accept_language).await;
+                }
+                if parsed.altitude.is_none() && !options.hide_location {
+                    parsed.terrain_elevation = terrain_elevation(lookups, &parsed).await;
+                }
+                if options.shows(Section::Weather) && !options.hide_location {
+                    parsed.weather =
+                        weather_at_capture(lookups, exif, &parsed, accept_language).await;
+                }
+                Self::from_parsed(exif, &parsed, accept_language, options)
+            }
+            None => Self::empty(accept_language, options),
+        };
+        summary.note_provenance(markers, exif.as_ref(), accept_language);
+        summary
+    }
+
+    /// Builds the summary from the header alone, without geocoding or any other lookup,
+    /// so the caption only depends on the image.
+    pub fn without_lookups(
+        metadata: ImageMetadata,
+        accept_language: Option<&str>,
+        options: CaptionOptions,
+    ) -> Self {
+        let ImageMetadata { exif, markers } = metadata;
+        let mut summary = match &exif {
+            Some(exif) => {
+                let options = options.at_position(gps_location(exif));
+                let parsed = ParsedExif::from_exif(exif, None);
+                Self::from_parsed(exif, &parsed, accept_language, options)
+            }
+            None => Self::empty(accept_language, options),
+        };
+        summary.note_provenance(markers, exif.as_ref(), accept_language);
+        summary
+    }
+
+    /// Appends the note on signs that the image was generated, if there are any.
+    fn note_provenance(
+        &mut self,
+        markers: Vec<Marker>,
+        exif: Option<&Exif>,
+        accept_language: Option<&str>,
+    ) {
+        let markers = provenance::conclude(markers, exif);
+        let locale = crate::locale::locale_from_language_code(accept_language);
+        if let Some(note) = provenance::note(&markers, locale) {
+            self.caption.push_str("\n\n");
+            self.caption.push_str(&note);
+        }
+    }
+}
+
+/// What an image's header gives away, read without any lookups.
+pub struct ImageMetadata {
+    exif: Option<Exif>,
+    markers: Vec<Marker>,
+}
+
+impl ImageMetadata {
+    pub fn has_exif(&self) -> bool {
+        self.exif.is_some()
+    }
+
+    fn read<R: BufRead + Seek>(mut reader: R) -> Result<Self> {
+        let markers = provenance::scan(&mut reader);
+        reader
+            .seek(SeekFrom::Start(0))
+            .context("Failed to seek back to the start of the image")?;
+        let exif = read_container(reader)?;
+        Ok(Self { exif, markers })
+    }
+}
+
+/// Reads the header of the image at `url` in range requests.
+pub async fn read_metadata_from_url(client: &reqwest::Client, url: &str) -> Result<ImageMetadata> {
+    ranged::read(client, url, |blocks| {
+        ImageMetadata::read(BufReader::new(blocks))
+    })
+    .await
+}
+
+/// Reads the header of an image from `reader`.
+pub fn read_metadata<R: BufRead + Seek>(reader: R) -> Result<ImageMetadata> {
+    ImageMetadata::read(reader)
+}
+
+/// Reads the header of a local image.
+pub fn read_metadata_from_file(path: &Path) -> Result<ImageMetadata> {
     let file = File::open(path)
         .with_context(|| format!("Failed to open local image at `{}`", path.display()))?;
-    let mut buf_reader = BufReader::new(file);
-    let exif_reader = ExifReader::new();
 
-    let exif = match exif_reader.read_from_container(&mut buf_reader) {
-        Ok(exif) => exif,
-        Err(ExifError::NotFound(_)) => return Ok(build_empty_caption()),
-        Err(err) => return Err(err.into()),
-    };
+    ImageMetadata::read(BufReader::new(file))
+}
+
+/// The what3words address of the parsed position, when a key is configured.
+async fn three_words(
+    lookups: &Lookups,
+    parsed: &ParsedExif,
+    accept_language: Option<&str>,
+) -> Option<String> {
+    let (latitude, longitude) = parsed.latitude.zip(parsed.longitude)?;
+    // what3words only knows the primary language subtag, such as `zh` for `zh-hans`.
+    let language = accept_language
+        .and_then(|code| code.split(['-', '_']).next())
+        .map(str::trim)
+        .filter(|code| !code.is_empty())
+        .unwrap_or("en");
+    lookups
+        .words(latitude, longitude, &language.to_ascii_lowercase())
+        .await
+}
+
+/// The landmark closest to the parsed position, unless the geocoder already named it.
+async fn landmark(
+    lookups: &Lookups,
+    parsed: &ParsedExif,
+    address: Option<&Address>,
+    accept_language: Option<&str>,
+) -> Option<String> {
+    let (latitude, longitude) = parsed.latitude.zip(parsed.longitude)?;
+    let name = lookups
+        .nearby(latitude, longitude, accept_language.unwrap_or("en"))
+        .await?;
+    let named = address.and_then(|address| address.name.as_deref());
+    (named != Some(name.as_str())).then_some(name)
+}
+
+/// The ground elevation at the parsed position.
+async fn terrain_elevation(lookups: &Lookups, parsed: &ParsedExif) -> Option<f64> {
+    let (latitude, longitude) = parsed.latitude.zip(parsed.longitude)?;
+    lookups.terrain(latitude, longitude).await
+}
+
+/// The weather at the parsed position when the photo was taken.
+async fn weather_at_capture(
+    lookups: &Lookups,
+    exif: &Exif,
+    parsed: &ParsedExif,
+    accept_language: Option<&str>,
+) -> Option<Weather> {
+    if !lookups.weather_available() {
+        return None;
+    }
+    let (latitude, longitude) = parsed.latitude.zip(parsed.longitude)?;
+    let zone = parsed
+        .timezone
+        .as_deref()
+        .and_then(|zone| zone.parse().ok());
+    let utc = capture_utc(exif, zone)?;
+    lookups
+        .weather(latitude, longitude, utc, accept_language.unwrap_or("en"))
+        .await
+}
+
+/// Geocodes the image's GPS position, unless the options hide the location.
+pub async fn geocode(
+    lookups: &Lookups,
+    exif: &Exif,
+    accept_language: Option<&str>,
+    options: CaptionOptions,
+) -> Option<Address> {
+    if options.hide_location {
+        return None;
+    }
+    let gps = gps_coordinates(exif)?;
+    reverse_geocode(lookups, gps.latitude, gps.longitude, accept_language).await
+}
+
+/// Reads the EXIF block of the image at `url` in range requests; `None` when the image
+/// has none.
+pub async fn read_exif_from_url(client: &reqwest::Client, url: &str) -> Result<Option<Exif>> {
+    ranged::read(client, url, |blocks| read_container(BufReader::new(blocks))).await
+}
+
+/// Reads the EXIF block of a local image; `None` when the image has none.
+pub fn read_exif_from_file(path: &Path) -> Result<Option<Exif>> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open local image at `{}`", path.display()))?;
+
+    read_container(BufReader::new(file))
+}
+
+/// Reads the EXIF block of an image from `reader`; `None` when the image has none.
+pub fn read_exif<R: BufRead + Seek>(reader: R) -> Result<Option<Exif>> {
+    read_container(reader)
+}
+
+/// Reads the EXIF block of an image already in memory; `None` when the image has none.
+pub fn read_exif_from_bytes(bytes: &[u8]) -> Result<Option<Exif>> {
+    read_container(Cursor::new(bytes))
+}
+
+fn read_container<R: BufRead + Seek>(mut reader: R) -> Result<Option<Exif>> {
+    match ExifReader::new().read_from_container(&mut reader) {
+        Ok(exif) => Ok(Some(exif)),
+        Err(ExifError::NotFound(_)) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Signed decimal latitude and longitude, if the image is geotagged.
+pub fn gps_location(exif: &Exif) -> Option<(f64, f64)> {
+    gps_coordinates(exif).map(|gps| (gps.latitude, gps.longitude))
+}
+
+/// A geotagged position and, when reverse geocoding succeeded, what is there.
+pub struct Place {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Short name for the spot, e.g. a landmark or the first part of the address.
+    pub title: Option<String>,
+    /// Full address as returned by the geocoder.
+    pub address: Option<String>,
+    /// The city, town or village the point lies in.
+    pub city: Option<String>,
+}
+
+/// Looks up the image's GPS position and geocodes it.
+pub async fn locate(
+    lookups: &Lookups,
+    exif: &Exif,
+    accept_language: Option<&str>,
+) -> Option<Place> {
+    let gps = gps_coordinates(exif)?;
+    Some(locate_at(lookups, gps.latitude, gps.longitude, accept_language).await)
+}
+
+/// Geocodes the position at `latitude` and `longitude`.
+pub async fn locate_at(
+    lookups: &Lookups,
+    latitude: f64,
+    longitude: f64,
+    accept_language: Option<&str>,
+) -> Place {
+    let geocoded = reverse_geocode(lookups, latitude, longitude, accept_language).await;
+
+    let title = geocoded.as_ref().and_then(|geocoded| {
+        geocoded.name.clone().or_else(|| {
+            geocoded
+                .display_name
+                .split(',')
+                .map(str::trim)
+                .find(|part| !part.is_empty())
+                .map(str::to_string)
+        })
+    });
+
+    Place {
+        latitude,
+        longitude,
+        title,
+        city: geocoded.as_ref().and_then(|geocoded| geocoded.city.clone()),
+        address: geocoded.map(|geocoded| geocoded.display_name),
+    }
+}
+
+/// Every field as one `Tag (IFD): value` line, in file order.
+///
+/// The GPS fields are left out when `options` round or hide the position.
+pub fn dump_fields(exif: &Exif, options: CaptionOptions) -> String {
+    let exact_gps = shows_exact_gps(exif, options);
+    exif.fields()
+        .filter(|field| exact_gps || field.tag.context() != exif::Context::Gps)
+        .map(|field| {
+            format!(
+                "{} ({}): {}",
+                field.tag,
+                field.ifd_num,
+                field.display_value().with_unit(exif)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The `/json` document: the parsed summary next to every raw field, revealing no more
+/// of the position than `options` let the caption show.
+pub fn exif_json(exif: &Exif, address: Option<&Address>, options: CaptionOptions) -> JsonValue {
+    let mut document = fields_json(exif, options);
+    let mut parsed = ParsedExif::from_exif(exif, address);
+    parsed.restrict(options);
+    let summary = serde_json::to_value(parsed).unwrap_or(JsonValue::Null);
+    if let JsonValue::Object(root) = &mut document {
+        root.insert(String::from("summary"), summary);
+    }
+    document
+}
+
+/// All fields grouped by IFD (`primary`, `thumbnail`), plus decoded GPS coordinates. When
+/// `options` round or hide the position, the GPS fields are left out and the coordinates
+/// are the ones the caption shows.
+pub fn fields_json(exif: &Exif, options: CaptionOptions) -> JsonValue {
+    let exact_gps = shows_exact_gps(exif, options);
+    let mut ifds = serde_json::Map::new();
+    for field in exif
+        .fields()
+        .filter(|field| exact_gps || field.tag.context() != exif::Context::Gps)
+    {
+        let entry = ifds
+            .entry(field.ifd_num.to_string())
+            .or_insert_with(|| JsonValue::Object(serde_json::Map::new()));
+        if let JsonValue::Object(fields) = entry {
+            fields.insert(
+                field.tag.to_string(),
+                JsonValue::String(field.display_value().with_unit(exif).to_string()),
+            );
+        }
+    }
+
+    let mut root = serde_json::Map::new();
+    root.insert(String::from("fields"), JsonValue::Object(ifds));
+    if let Some((latitude, longitude)) = options.shown_position(gps_location(exif)) {
+        root.insert(
+            String::from("gps"),
+            serde_json::json!({ "latitude": latitude, "longitude": longitude }),
+        );
+    }
 
-    let summary = ParsedExif::from_exif(&exif, accept_language);
-    Ok(build_caption(&summary))
+    JsonValue::Object(root)
 }
 
-struct ParsedExif {
-    title: Option<String>,
-    camera: String,
-    lens: String,
-    focal_length: Option<String>,
-    focal_length_val: Option<f64>,
-    focal_length_35mm: Option<String>,
-    focal_length_35mm_val: Option<f64>,
-    aperture: Option<String>,
-    shutter: Option<String>,
-    iso: Option<String>,
-    datetime: Option<String>,
-    location: Option<String>,
-    country: Option<String>,
-    gps: Option<String>,
+/// Whether `options` let the exact GPS position through, so the raw fields may be shown.
+fn shows_exact_gps(exif: &Exif, options: CaptionOptions) -> bool {
+    let position = gps_location(exif);
+    options.shown_position(position) == position
+}
+
+/// Everything the caption is built from, as shown in `/json` output.
+#[derive(Serialize)]
+pub struct ParsedExif {
+    pub title: Option<String>,
+    pub camera: String,
+    pub lens: String,
+    pub focal_length: Option<String>,
+    /// Focal length in millimeters.
+    pub focal_length_value: Option<f64>,
+    pub focal_length_35mm: Option<String>,
+    pub focal_length_35mm_value: Option<f64>,
+    pub aperture: Option<String>,
+    pub shutter: Option<String>,
+    pub iso: Option<String>,
+    pub datetime: Option<String>,
+    /// Capture time at the photo's location, when the camera recorded no UTC offset but
+    /// the GPS clock tells the time.
+    pub local_time: Option<String>,
+    /// IANA time zone at the GPS position.
+    pub timezone: Option<String>,
+    /// Where the sun stood at the position when the photo was taken.
+    pub light: Option<Light>,
+    /// The moon's phase, for photos taken at night.
+    pub moon: Option<Moon>,
+    /// The weather at the position when the photo was taken, when the bot has a key.
+    pub weather: Option<Weather>,
+    /// Geocoded city and region, or the location tags when geocoding is unavailable.
+    pub location: Option<String>,
+    /// Full address as returned by the geocoder.
+    pub address: Option<String>,
+    pub city: Option<String>,
+    /// The state, province or prefecture.
+    pub region: Option<String>,
+    pub country: Option<String>,
+    /// ISO 3166-1 alpha-2 code of the country.
+    pub country_code: Option<String>,
+    /// Coordinates formatted for display.
+    pub gps: Option<String>,
+    /// Signed decimal latitude and longitude.
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// Meters above sea level, as the GPS recorded it.
+    pub altitude: Option<f64>,
+    /// How fast the GPS receiver moved, in km/h.
+    pub speed: Option<f64>,
+    /// Ground elevation at the position in meters, looked up when the GPS recorded no
+    /// altitude.
+    pub terrain_elevation: Option<f64>,
+    /// Open Location Code of the position.
+    pub plus_code: Option<String>,
+    /// what3words address of the position, when the bot has an API key.
+    pub what3words: Option<String>,
+    /// A named landmark near the position, for users who opted in.
+    pub nearby: Option<String>,
 }
 
 struct GpsData {
@@ -73,161 +527,246 @@ struct GpsData {
     longitude: f64,
 }
 
-const NOMINATIM_ENDPOINT: &str = "https://nominatim.openstreetmap.org/reverse";
-const NOMINATIM_USER_AGENT: &str = "fotobot_rs/0.1.0 (https://github.com/woolen-sheep/fotobot)";
-
 impl ParsedExif {
-    fn from_exif(exif: &Exif, accept_language: Option<&str>) -> Self {
-        let title = first_string(exif, &[Tag::ImageDescription]);
+    /// Rounds or drops the position and the place as `options` do in the caption.
+    fn restrict(&mut self, options: CaptionOptions) {
+        let position = self.latitude.zip(self.longitude);
+        let options = options.at_position(position);
+        let shown = options.shown_position(position);
+        if shown != position {
+            self.gps = shown.map(|(latitude, longitude)| format_gps(latitude, longitude, 6));
+            self.latitude = shown.map(|(latitude, _)| latitude);
+            self.longitude = shown.map(|(_, longitude)| longitude);
+            self.plus_code = None;
+            self.what3words = None;
+            self.address = None;
+            self.nearby = None;
+        }
+        if options.coarse_location {
+            self.address = None;
+            self.nearby = None;
+        }
+        if options.hide_location {
+            self.location = None;
+            self.city = None;
+            self.region = None;
+            self.country = None;
+            self.country_code = None;
+        }
+    }
 
-        let make = first_string(exif, &[Tag::Make]);
-        let model = first_string(exif, &[Tag::Model]);
-        let camera = match (make, model.clone()) {
-            (Some(make), Some(model)) => format!("{make} {model}"),
-            (Some(make), None) => make,
-            (None, Some(model)) => model,
-            (None, None) => String::from("Unknown Camera"),
-        };
+    /// Parses the fields, taking the place from `address` when the image was geocoded.
+    pub fn from_exif(exif: &Exif, address: Option<&Address>) -> Self {
+        let title = first_string(exif, &[Tag::ImageDescription]);
 
-        let lens_model = first_string(exif, &[Tag::LensModel]);
-        let lens_spec = lens_specification(exif);
-        let lens = lens_model
-            .or(lens_spec)
-            .unwrap_or_else(|| String::from("Unknown Lens"));
+        let camera = camera_name(exif);
+        let lens = lens_name(exif);
 
-        let (focal_length, focal_length_val) = focal_length_values(exif);
-        let (focal_length_35mm, focal_length_35mm_val) = focal_length_35mm_values(exif);
+        let (focal_length, focal_length_value) = focal_length_values(exif);
+        let (focal_length_35mm, focal_length_35mm_value) = focal_length_35mm_values(exif);
         let aperture = aperture_value(exif);
         let shutter = shutter_value(exif);
         let iso = iso_value(exif);
         let datetime = datetime_value(exif);
         let gps_data = gps_coordinates(exif);
-        let geocoded = gps_data
+        let zone = gps_data
             .as_ref()
-            .and_then(|gps| reverse_geocode(gps.latitude, gps.longitude, accept_language));
+            .and_then(|gps| timezone::zone_at(gps.latitude, gps.longitude));
+        let local_time = zone
+            .filter(|_| first_string(exif, &[Tag::OffsetTimeOriginal, Tag::OffsetTime]).is_none())
+            .zip(gps_timestamp(exif))
+            .map(|(zone, utc)| {
+                timezone::local_time(utc, zone)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string()
+            });
+
+        let capture = gps_data.as_ref().zip(capture_utc(exif, zone));
+        let light = capture.map(|(gps, utc)| sun::light_at(utc, gps.latitude, gps.longitude));
+        let moon = capture
+            .zip(light)
+            .filter(|(_, light)| light.is_night())
+            .map(|((_, utc), _)| moon::moon_at(utc));
 
         let (fallback_location, fallback_country) = location_values(exif);
 
-        let location = geocoded.clone().or(fallback_location);
-
-        let country = geocoded
-            .as_ref()
-            .and_then(|name| extract_country(name))
+        let country = address
+            .and_then(|address| address.country.clone())
             .or(fallback_country);
 
+        let location = address.map(concise_place).or(fallback_location);
+
         let gps = gps_data.as_ref().map(|gps| gps.display.clone());
+        let latitude = gps_data.as_ref().map(|gps| gps.latitude);
+        let longitude = gps_data.as_ref().map(|gps| gps.longitude);
+        let plus_code = gps_data
+            .as_ref()
+            .map(|gps| pluscode::encode(gps.latitude, gps.longitude));
 
         Self {
             title,
             camera,
             lens,
             focal_length,
-            focal_length_val,
+            focal_length_value,
             focal_length_35mm,
-            focal_length_35mm_val,
+            focal_length_35mm_value,
             aperture,
             shutter,
             iso,
             datetime,
+            local_time,
+            timezone: zone.map(|zone| zone.name().to_string()),
+            light,
+            moon,
+            weather: None,
             location,
+            address: address.map(|address| address.display_name.clone()),
+            city: address.and_then(|address| address.city.clone()),
+            region: address.and_then(|address| address.region.clone()),
             country,
+            country_code: address.and_then(|address| address.country_code.clone()),
             gps,
+            latitude,
+            longitude,
+            altitude: altitude_value(exif),
+            speed: speed_value(exif),
+            terrain_elevation: None,
+            plus_code,
+            what3words: None,
+            nearby: None,
         }
     }
-}
 
-fn build_caption(data: &ParsedExif) -> String {
-    let mut output = String::new();
-
-    // Emoji formatting follows the style requested by the user template.
-    writeln!(output, "💭: {}", data.title.as_deref().unwrap_or("")).ok();
-    writeln!(output, "——————————").ok();
-    writeln!(output, "📸: {} / {}", data.camera, data.lens).ok();
-
-    let use_full_frame = match (data.focal_length_val, data.focal_length_35mm_val) {
-        (_, None) => true,
-        (Some(f), Some(f35)) => (f - f35).abs() < 0.5,
-        (None, Some(_)) => false,
-    };
-
-    let mut metrics: Vec<String> = Vec::new();
-
-    if use_full_frame {
-        if let Some(value) = data.focal_length.clone() {
-            metrics.push(value);
-        }
-    } else if let Some(value) = data.focal_length_35mm.clone() {
-        metrics.push(value);
-    } else if let Some(value) = data.focal_length.clone() {
-        metrics.push(value);
-    }
-
-    if let Some(value) = data.aperture.clone() {
-        metrics.push(value);
-    }
-    if let Some(value) = data.shutter.clone() {
-        metrics.push(value);
-    }
-    if let Some(value) = data.iso.clone() {
-        metrics.push(value);
-    }
-
-    if metrics.is_empty() {
-        writeln!(output, "📝: Parameters Unknown").ok();
-    } else {
-        writeln!(output, "📝: {}", metrics.join(", ")).ok();
+    /// The position rounded to `precision`, with its text written like `gps`.
+    pub fn position(&self, precision: CoordinatePrecision) -> Option<((f64, f64), String)> {
+        let (latitude, longitude) = precision.round((self.latitude?, self.longitude?));
+        let text = format_gps(latitude, longitude, precision.decimals());
+        Some(((latitude, longitude), text))
     }
+}
 
-    writeln!(
-        output,
-        "📅: {}",
-        data.datetime.as_deref().unwrap_or("Unknown")
-    )
-    .ok();
+/// The camera name of images that record neither make nor model. Captions show it in
+/// the user's language.
+pub const UNKNOWN_CAMERA: &str = "Unknown Camera";
+/// The lens name of images that record no lens.
+pub const UNKNOWN_LENS: &str = "Unknown Lens";
+
+/// Shooting parameters of an image, read without any network lookups.
+#[derive(Clone)]
+pub struct ShotFacts {
+    /// Make and model, as one name.
+    pub camera: String,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub lens: String,
+    pub focal_length: Option<String>,
+    /// Focal length in millimeters, as 35mm equivalent when the camera records it.
+    pub focal_length_mm: Option<f64>,
+    pub aperture: Option<String>,
+    pub shutter: Option<String>,
+    pub iso: Option<String>,
+    /// Capture time as `YYYY-MM-DD HH:MM:SS`.
+    pub datetime: Option<String>,
+    pub gps: Option<(f64, f64)>,
+}
 
-    match (data.location.as_deref(), data.country.as_deref()) {
-        (Some(location), Some(country)) => {
-            writeln!(output, "🗺️: {}, {}", location, country).ok();
-        }
-        (Some(location), None) => {
-            writeln!(output, "🗺️: {}", location).ok();
-        }
-        (None, Some(country)) => {
-            writeln!(output, "🗺️: {}", country).ok();
+impl ShotFacts {
+    /// Facts of an image without EXIF data: everything unknown.
+    pub fn unknown() -> Self {
+        Self {
+            camera: String::from(UNKNOWN_CAMERA),
+            make: None,
+            model: None,
+            lens: String::from(UNKNOWN_LENS),
+            focal_length: None,
+            focal_length_mm: None,
+            aperture: None,
+            shutter: None,
+            iso: None,
+            datetime: None,
+            gps: None,
         }
-        (None, None) => {}
     }
 
-    if let Some(gps) = data.gps.as_deref() {
-        writeln!(output, "📍: {}", gps).ok();
+    pub fn from_exif(exif: &Exif) -> Self {
+        let (focal_length, actual_mm) = focal_length_values(exif);
+        let equivalent_mm = focal_length_35mm_values(exif)
+            .1
+            .filter(|value| *value > 0.0);
+        Self {
+            camera: camera_name(exif),
+            make: first_string(exif, &[Tag::Make]),
+            model: first_string(exif, &[Tag::Model]),
+            lens: lens_name(exif),
+            focal_length,
+            focal_length_mm: equivalent_mm.or(actual_mm),
+            aperture: aperture_value(exif),
+            shutter: shutter_value(exif),
+            iso: iso_value(exif),
+            datetime: datetime_value(exif),
+            gps: gps_location(exif),
+        }
     }
+}
 
-    while output.ends_with('\n') {
-        output.pop();
+fn camera_name(exif: &Exif) -> String {
+    let make = first_string(exif, &[Tag::Make]);
+    let model = first_string(exif, &[Tag::Model]);
+    match (make, model) {
+        (Some(make), Some(model)) => format!("{make} {model}"),
+        (Some(make), None) => make,
+        (None, Some(model)) => model,
+        (None, None) => String::from(UNKNOWN_CAMERA),
     }
+}
 
-    output
+fn lens_name(exif: &Exif) -> String {
+    first_string(exif, &[Tag::LensModel])
+        .or_else(|| lens_specification(exif))
+        .unwrap_or_else(|| String::from(UNKNOWN_LENS))
 }
 
-fn build_empty_caption() -> String {
+fn build_empty_caption(accept_language: Option<&str>, options: CaptionOptions) -> String {
     let data = ParsedExif {
         title: None,
-        camera: String::from("Unknown Camera"),
-        lens: String::from("Unknown Lens"),
+        camera: String::from(UNKNOWN_CAMERA),
+        lens: String::from(UNKNOWN_LENS),
         focal_length: None,
-        focal_length_val: None,
+        focal_length_value: None,
         focal_length_35mm: None,
-        focal_length_35mm_val: None,
+        focal_length_35mm_value: None,
         aperture: None,
         shutter: None,
         iso: None,
         datetime: None,
+        local_time: None,
+        timezone: None,
+        light: None,
+        moon: None,
+        weather: None,
         location: None,
+        address: None,
+        city: None,
+        region: None,
         country: None,
+        country_code: None,
         gps: None,
+        latitude: None,
+        longitude: None,
+        altitude: None,
+        speed: None,
+        terrain_elevation: None,
+        plus_code: None,
+        what3words: None,
+        nearby: None,
     };
 
-    build_caption(&data)
+    styles::render(
+        &data,
+        options,
+        crate::locale::locale_from_language_code(accept_language),
+    )
 }
 
 fn first_string(exif: &Exif, tags: &[Tag]) -> Option<String> {
@@ -391,6 +930,43 @@ fn datetime_value(exif: &Exif) -> Option<String> {
     None
 }
 
+/// The UTC time of the GPS fix, from `GPSDateStamp` and `GPSTimeStamp`.
+fn gps_timestamp(exif: &Exif) -> Option<NaiveDateTime> {
+    let date = first_string(exif, &[Tag::GPSDateStamp])?;
+    let date = NaiveDate::parse_from_str(date.trim_matches('\0').trim(), "%Y:%m:%d").ok()?;
+    let Value::Rational(parts) = &find_field(exif, Tag::GPSTimeStamp)?.value else {
+        return None;
+    };
+    let [hour, minute, second] = [parts.first()?, parts.get(1)?, parts.get(2)?]
+        .map(|part| part.to_f64())
+        .map(|part| part.is_finite().then_some(part as u32));
+    date.and_hms_opt(hour?, minute?, second?)
+}
+
+/// The UTC time of the shot: the GPS clock, else the camera clock with its recorded
+/// offset, else the camera clock read in the time zone at the position.
+fn capture_utc(exif: &Exif, zone: Option<Tz>) -> Option<NaiveDateTime> {
+    if let Some(utc) = gps_timestamp(exif) {
+        return Some(utc);
+    }
+    let text = first_string(
+        exif,
+        &[Tag::DateTimeOriginal, Tag::DateTimeDigitized, Tag::DateTime],
+    )?;
+    let camera_clock = NaiveDateTime::parse_from_str(text.get(..19)?, "%Y:%m:%d %H:%M:%S").ok()?;
+    let offset = first_string(exif, &[Tag::OffsetTimeOriginal, Tag::OffsetTime])
+        .and_then(|offset| offset.parse::<FixedOffset>().ok());
+    match (offset, zone) {
+        (Some(offset), _) => camera_clock.and_local_timezone(offset).single(),
+        (None, Some(zone)) => camera_clock
+            .and_local_timezone(zone)
+            .earliest()
+            .map(|time| time.fixed_offset()),
+        (None, None) => None,
+    }
+    .map(|time| time.naive_utc())
+}
+
 fn location_values(exif: &Exif) -> (Option<String>, Option<String>) {
     let location = first_string(exif, &[Tag::GPSAreaInformation]);
     (location, None)
@@ -424,21 +1000,57 @@ fn gps_coordinates(exif: &Exif) -> Option<GpsData> {
         lon_value
     };
 
-    let display = format!(
-        "{:.6}° {}, {:.6}° {}",
-        lat_value.abs(),
-        lat_dir,
-        lon_value.abs(),
-        lon_dir
-    );
-
     Some(GpsData {
-        display,
+        display: format_gps(signed_lat, signed_lon, 6),
         latitude: signed_lat,
         longitude: signed_lon,
     })
 }
 
+/// A signed position as `48.858370° N, 2.294481° E`, with `decimals` places.
+pub fn format_gps(latitude: f64, longitude: f64, decimals: usize) -> String {
+    let lat_dir = if latitude < 0.0 { 'S' } else { 'N' };
+    let lon_dir = if longitude < 0.0 { 'W' } else { 'E' };
+    format!(
+        "{:.*}° {lat_dir}, {:.*}° {lon_dir}",
+        decimals,
+        latitude.abs(),
+        decimals,
+        longitude.abs()
+    )
+}
+
+/// `GPSAltitude`, negative when `GPSAltitudeRef` puts it below sea level.
+fn altitude_value(exif: &Exif) -> Option<f64> {
+    let Value::Rational(values) = &find_field(exif, Tag::GPSAltitude)?.value else {
+        return None;
+    };
+    let altitude = values.first()?.to_f64();
+    if !altitude.is_finite() {
+        return None;
+    }
+    let below_sea_level =
+        find_field(exif, Tag::GPSAltitudeRef).and_then(|field| field.value.get_uint(0)) == Some(1);
+    Some(if below_sea_level { -altitude } else { altitude })
+}
+
+/// The GPS speed in km/h, whichever of km/h, mph or knots `GPSSpeedRef` recorded it in.
+fn speed_value(exif: &Exif) -> Option<f64> {
+    let Value::Rational(values) = &find_field(exif, Tag::GPSSpeed)?.value else {
+        return None;
+    };
+    let speed = values.first()?.to_f64();
+    if !speed.is_finite() || speed < 0.0 {
+        return None;
+    }
+    let factor = match first_string(exif, &[Tag::GPSSpeedRef]).as_deref() {
+        Some("M") => 1.609_344,
+        Some("N") => 1.852,
+        _ => 1.0,
+    };
+    Some(speed * factor)
+}
+
 fn gps_coordinate(value: &Value) -> Option<f64> {
     if let Value::Rational(values) = value {
         if values.len() >= 3 {
@@ -465,85 +1077,44 @@ fn normalized_gps_ref(reference: &str, default: char) -> char {
         .unwrap_or(default)
 }
 
-fn reverse_geocode(lat: f64, lon: f64, accept_language: Option<&str>) -> Option<String> {
+async fn reverse_geocode(
+    lookups: &Lookups,
+    lat: f64,
+    lon: f64,
+    accept_language: Option<&str>,
+) -> Option<Address> {
     let language = accept_language
-        .and_then(|code| {
-            let trimmed = code.trim();
-            if trimmed.is_empty() {
-                None
-            } else {
-                Some(trimmed.replace('_', "-"))
-            }
-        })
-        .unwrap_or_else(|| String::from("en"));
-
-    let url = format!(
-        "{}?lat={:.6}&lon={:.6}&addressdetails=0&accept-language={}&format=json",
-        NOMINATIM_ENDPOINT, lat, lon, language
+        .filter(|code| !code.trim().is_empty())
+        .map_or_else(|| String::from("en"), crate::locale::geocoder_language);
+
+    let started = Instant::now();
+    let address = lookups.reverse(lat, lon, &language).await;
+    let elapsed = started.elapsed();
+    crate::telemetry::observe_geocoder(elapsed);
+    tracing::info!(
+        geocode_ms = elapsed.as_millis() as u64,
+        found = address.is_some(),
+        "Reverse geocoded"
     );
+    address
+}
 
-    let client = Client::new();
-    let response = match client
-        .get(url)
-        .header("User-Agent", NOMINATIM_USER_AGENT)
-        .send()
+/// "City, Region" for captions, dropping the region when it repeats the city; the full
+/// address when the provider names neither.
+fn concise_place(address: &Address) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in [address.city.as_deref(), address.region.as_deref()]
+        .into_iter()
+        .flatten()
     {
-        Ok(resp) => resp,
-        Err(err) => {
-            warn!(
-                "Reverse geocoding request failed for coordinates ({:.6}, {:.6}): {}",
-                lat, lon, err
-            );
-            return None;
-        }
-    };
-
-    let response = match response.error_for_status() {
-        Ok(resp) => resp,
-        Err(err) => {
-            warn!(
-                "Reverse geocoding returned error for coordinates ({:.6}, {:.6}): {}",
-                lat, lon, err
-            );
-            return None;
-        }
-    };
-
-    let body = match response.text() {
-        Ok(text) => text,
-        Err(err) => {
-            warn!(
-                "Failed to read reverse geocoding response for coordinates ({:.6}, {:.6}): {}",
-                lat, lon, err
-            );
-            return None;
+        if !parts.contains(&part) {
+            parts.push(part);
         }
-    };
-
-    let value: JsonValue = match serde_json::from_str(&body) {
-        Ok(json) => json,
-        Err(err) => {
-            warn!(
-                "Failed to parse reverse geocoding JSON for coordinates ({:.6}, {:.6}): {}",
-                lat, lon, err
-            );
-            return None;
-        }
-    };
-
-    value
-        .get("display_name")
-        .and_then(|field| field.as_str())
-        .map(|name| name.to_string())
-}
-
-fn extract_country(location: &str) -> Option<String> {
-    location
-        .split(',')
-        .map(|part| part.trim())
-        .rev()
-        .find(|part| !part.is_empty())
-        .map(|part| part.to_string())
+    }
+    if parts.is_empty() {
+        return address.display_name.clone();
+    }
+    parts.join(", ")
 }
 
 fn lens_specification(exif: &Exif) -> Option<String> {
@@ -579,12 +1150,10 @@ fn lens_specification(exif: &Exif) -> Option<String> {
 
 fn format_datetime(input: &str) -> String {
     let trimmed = input.trim_matches('\0').trim();
-    if trimmed.len() >= 19 {
-        let date = &trimmed[0..10].replace(':', "-");
-        let time = &trimmed[11..19];
-        format!("{} {}", date, time)
-    } else {
-        trimmed.to_string()
+    // Byte ranges, which a stamp with non-ASCII text may not split at.
+    match (trimmed.get(0..10), trimmed.get(11..19)) {
+        (Some(date), Some(time)) => format!("{} {}", date.replace(':', "-"), time),
+        _ => trimmed.to_string(),
     }
 }
 
@@ -0,0 +1,251 @@
+use std::fmt::Write;
+
+use serde_json::{Value as JsonValue, json};
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+use crate::exif::ShotFacts;
+
+/// Callback data of the export buttons is this prefix followed by the format name.
+const CALLBACK_PREFIX: &str = "export:";
+
+/// File formats the collected photos can be exported as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Gpx,
+    Kml,
+    GeoJson,
+    /// Every image with all its shooting parameters, geotagged or not.
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "" | "gpx" => Some(Self::Gpx),
+            "kml" => Some(Self::Kml),
+            "geojson" | "json" => Some(Self::GeoJson),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Self::Gpx => "photos.gpx",
+            Self::Kml => "photos.kml",
+            Self::GeoJson => "photos.geojson",
+            Self::Csv => "photos.csv",
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Gpx => "gpx",
+            Self::Kml => "kml",
+            Self::GeoJson => "geojson",
+            Self::Csv => "csv",
+        }
+    }
+
+    /// Whether the format only carries geotagged photos.
+    pub fn needs_location(self) -> bool {
+        self != Self::Csv
+    }
+
+    /// Serializes the photos in this format; map formats skip photos without a location.
+    pub fn write(self, photos: &[ShotFacts]) -> String {
+        match self {
+            Self::Gpx => write_gpx(&waypoints(photos)),
+            Self::Kml => write_kml(&waypoints(photos)),
+            Self::GeoJson => write_geojson(&waypoints(photos)),
+            Self::Csv => write_csv(photos),
+        }
+    }
+}
+
+/// The button offered under album answers to download the batch as CSV.
+pub fn csv_keyboard(locale: &str) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new([[InlineKeyboardButton::callback(
+        rust_i18n::t!("export.csv_button", locale = locale),
+        format!("{CALLBACK_PREFIX}{}", ExportFormat::Csv.as_str()),
+    )]])
+}
+
+/// Returns the format an export button asks for, or `None` for other callback data.
+pub fn parse_callback(data: &str) -> Option<ExportFormat> {
+    data.strip_prefix(CALLBACK_PREFIX)
+        .filter(|format| !format.is_empty())
+        .and_then(ExportFormat::parse)
+}
+
+/// A geotagged photo as every export format sees it.
+struct Waypoint {
+    latitude: f64,
+    longitude: f64,
+    /// The capture time, or a running number for undated photos.
+    name: String,
+    /// The camera that took the photo.
+    description: String,
+}
+
+/// One waypoint per geotagged photo, ordered by capture time.
+fn waypoints(photos: &[ShotFacts]) -> Vec<Waypoint> {
+    let mut located: Vec<(&ShotFacts, (f64, f64))> = photos
+        .iter()
+        .filter_map(|photo| photo.gps.map(|gps| (photo, gps)))
+        .collect();
+    // `YYYY-MM-DD HH:MM:SS` sorts chronologically as text; undated photos go last.
+    located.sort_by(|(a, _), (b, _)| match (&a.datetime, &b.datetime) {
+        (Some(a), Some(b)) => a.cmp(b),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+
+    located
+        .into_iter()
+        .enumerate()
+        .map(|(index, (photo, (latitude, longitude)))| Waypoint {
+            latitude,
+            longitude,
+            name: photo
+                .datetime
+                .clone()
+                .unwrap_or_else(|| format!("Photo {}", index + 1)),
+            description: photo.camera.clone(),
+        })
+        .collect()
+}
+
+/// GPX 1.1; EXIF times carry no zone, so they only go into the names instead of `<time>`.
+fn write_gpx(waypoints: &[Waypoint]) -> String {
+    let mut output = String::new();
+    output.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    output.push_str(
+        "<gpx version=\"1.1\" creator=\"fotobot\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n",
+    );
+    for waypoint in waypoints {
+        writeln!(
+            output,
+            "  <wpt lat=\"{:.7}\" lon=\"{:.7}\">",
+            waypoint.latitude, waypoint.longitude
+        )
+        .ok();
+        writeln!(output, "    <name>{}</name>", escape_xml(&waypoint.name)).ok();
+        writeln!(
+            output,
+            "    <desc>{}</desc>",
+            escape_xml(&waypoint.description)
+        )
+        .ok();
+        output.push_str("  </wpt>\n");
+    }
+    output.push_str("</gpx>\n");
+    output
+}
+
+/// KML 2.2 placemarks; KML puts the longitude first.
+fn write_kml(waypoints: &[Waypoint]) -> String {
+    let mut output = String::new();
+    output.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    output.push_str("<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n  <Document>\n");
+    for waypoint in waypoints {
+        output.push_str("    <Placemark>\n");
+        writeln!(output, "      <name>{}</name>", escape_xml(&waypoint.name)).ok();
+        writeln!(
+            output,
+            "      <description>{}</description>",
+            escape_xml(&waypoint.description)
+        )
+        .ok();
+        writeln!(
+            output,
+            "      <Point><coordinates>{:.7},{:.7}</coordinates></Point>",
+            waypoint.longitude, waypoint.latitude
+        )
+        .ok();
+        output.push_str("    </Placemark>\n");
+    }
+    output.push_str("  </Document>\n</kml>\n");
+    output
+}
+
+/// A GeoJSON feature collection of points; positions are `[longitude, latitude]`.
+fn write_geojson(waypoints: &[Waypoint]) -> String {
+    let features: Vec<JsonValue> = waypoints
+        .iter()
+        .map(|waypoint| {
+            json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "Point",
+                    "coordinates": [waypoint.longitude, waypoint.latitude],
+                },
+                "properties": {
+                    "name": waypoint.name,
+                    "description": waypoint.description,
+                },
+            })
+        })
+        .collect();
+
+    let collection = json!({
+        "type": "FeatureCollection",
+        "features": features,
+    });
+    serde_json::to_string_pretty(&collection).unwrap_or_default()
+}
+
+/// One row per photo, quoted per RFC 4180.
+fn write_csv(photos: &[ShotFacts]) -> String {
+    let mut output = String::from(
+        "datetime,camera,lens,focal_length,aperture,shutter,iso,latitude,longitude\r\n",
+    );
+    for photo in photos {
+        let (latitude, longitude) = match photo.gps {
+            Some((latitude, longitude)) => (format!("{latitude:.7}"), format!("{longitude:.7}")),
+            None => (String::new(), String::new()),
+        };
+        let cells = [
+            photo.datetime.as_deref().unwrap_or(""),
+            &photo.camera,
+            &photo.lens,
+            photo.focal_length.as_deref().unwrap_or(""),
+            photo.aperture.as_deref().unwrap_or(""),
+            photo.shutter.as_deref().unwrap_or(""),
+            photo.iso.as_deref().unwrap_or(""),
+            &latitude,
+            &longitude,
+        ];
+        let row: Vec<String> = cells.iter().map(|cell| escape_csv(cell)).collect();
+        output.push_str(&row.join(","));
+        output.push_str("\r\n");
+    }
+    output
+}
+
+/// Quotes a CSV cell when it holds a separator, quote or line break.
+pub fn escape_csv(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // XML 1.0 cannot carry most control characters, even escaped.
+            ch if ch.is_control() && !matches!(ch, '\t' | '\n' | '\r') => {}
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
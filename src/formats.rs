@@ -0,0 +1,194 @@
+//! Capture times, decimal numbers and measures written the way each locale writes them
+//! and in the units each user picked. The patterns, month names and unit symbols live
+//! in the `formats` section of the translations, so they can be fixed like any other
+//! string.
+
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+
+/// How the EXIF reader writes capture times.
+const PARSED_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+const FEET_PER_METER: f64 = 3.280_84;
+const MILES_PER_KILOMETER: f64 = 0.621_371;
+
+/// Metric or imperial measures, chosen with `/units`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnitSystem {
+    #[default]
+    Metric,
+    /// Feet, miles and miles per hour.
+    Imperial,
+}
+
+impl UnitSystem {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Metric => "metric",
+            Self::Imperial => "imperial",
+        }
+    }
+}
+
+/// How times of day are written, chosen with `/units 12h` or `/units 24h`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Clock {
+    #[default]
+    #[serde(rename = "24h")]
+    TwentyFourHour,
+    #[serde(rename = "12h")]
+    TwelveHour,
+}
+
+impl Clock {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::TwentyFourHour => "24h",
+            Self::TwelveHour => "12h",
+        }
+    }
+}
+
+/// The units and clock a user picked, applied wherever a reply writes a measure or a
+/// time of day.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Units {
+    pub system: UnitSystem,
+    pub clock: Clock,
+}
+
+impl Units {
+    /// The units after a `/units` argument: `metric`, `imperial`, `12h` or `24h`.
+    pub fn parse_change(self, value: &str) -> Option<Self> {
+        let mut units = self;
+        match value.trim().to_ascii_lowercase().as_str() {
+            "metric" | "si" => units.system = UnitSystem::Metric,
+            "imperial" | "us" => units.system = UnitSystem::Imperial,
+            "12h" | "12" => units.clock = Clock::TwelveHour,
+            "24h" | "24" => units.clock = Clock::TwentyFourHour,
+            _ => return None,
+        }
+        Some(units)
+    }
+
+    /// A height above sea level in whole meters or feet, like `1234 m`.
+    pub fn altitude(self, meters: f64, locale: &str) -> String {
+        match self.system {
+            UnitSystem::Metric => measure("formats.meters", format!("{meters:.0}"), locale),
+            UnitSystem::Imperial => {
+                let feet = meters * FEET_PER_METER;
+                measure("formats.feet", format!("{feet:.0}"), locale)
+            }
+        }
+    }
+
+    /// A speed in whole kilometers or miles per hour, like `52 km/h`.
+    pub fn speed(self, kilometers_per_hour: f64, locale: &str) -> String {
+        match self.system {
+            UnitSystem::Metric => measure(
+                "formats.kilometers_per_hour",
+                format!("{kilometers_per_hour:.0}"),
+                locale,
+            ),
+            UnitSystem::Imperial => {
+                let miles_per_hour = kilometers_per_hour * MILES_PER_KILOMETER;
+                measure(
+                    "formats.miles_per_hour",
+                    format!("{miles_per_hour:.0}"),
+                    locale,
+                )
+            }
+        }
+    }
+
+    /// A distance between two places, in meters or feet when they are close, like
+    /// `850 m`, `12.4 km` or `312 km`.
+    pub fn distance(self, kilometers: f64, locale: &str) -> String {
+        match self.system {
+            UnitSystem::Metric if kilometers < 1.0 => measure(
+                "formats.meters",
+                format!("{:.0}", kilometers * 1000.0),
+                locale,
+            ),
+            UnitSystem::Metric if kilometers < 100.0 => {
+                measure("formats.kilometers", format!("{kilometers:.1}"), locale)
+            }
+            UnitSystem::Metric => measure("formats.kilometers", format!("{kilometers:.0}"), locale),
+            UnitSystem::Imperial => {
+                let miles = kilometers * MILES_PER_KILOMETER;
+                if miles < 0.1 {
+                    let feet = kilometers * 1000.0 * FEET_PER_METER;
+                    measure("formats.feet", format!("{feet:.0}"), locale)
+                } else if miles < 100.0 {
+                    measure("formats.miles", format!("{miles:.1}"), locale)
+                } else {
+                    measure("formats.miles", format!("{miles:.0}"), locale)
+                }
+            }
+        }
+    }
+}
+
+/// `value` with its unit symbol from `key` and decimals as `locale` writes them.
+fn measure(key: &str, value: String, locale: &str) -> String {
+    rust_i18n::t!(key, locale = locale, value = decimals(&value, locale)).into_owned()
+}
+
+/// A capture time as `locale` writes dates, like `5 Mar 2024 14:02` or
+/// `2024年3月5日 14:02`, on `clock`. Text the reader could not turn into a time comes
+/// back as it is.
+pub fn datetime(text: &str, locale: &str, clock: Clock) -> String {
+    let Ok(datetime) = NaiveDateTime::parse_from_str(text, PARSED_FORMAT) else {
+        return text.to_string();
+    };
+    let months = rust_i18n::t!("formats.months", locale = locale);
+    let month = months
+        .split_whitespace()
+        .nth(datetime.month0() as usize)
+        .map_or_else(|| datetime.month().to_string(), str::to_string);
+    let time = match clock {
+        Clock::TwentyFourHour => format!("{:02}:{:02}", datetime.hour(), datetime.minute()),
+        Clock::TwelveHour => {
+            let (afternoon, hour) = datetime.hour12();
+            let key = if afternoon {
+                "formats.time_pm"
+            } else {
+                "formats.time_am"
+            };
+            let time = format!("{hour}:{:02}", datetime.minute());
+            rust_i18n::t!(key, locale = locale, time = time).into_owned()
+        }
+    };
+    rust_i18n::t!(
+        "formats.datetime",
+        locale = locale,
+        year = datetime.year(),
+        month = month,
+        day = datetime.day(),
+        time = time
+    )
+    .into_owned()
+}
+
+/// `text` with every decimal point between two digits written as `locale` writes it,
+/// like `f/2,8`. Coordinates keep theirs, since maps and other apps expect it.
+pub fn decimals(text: &str, locale: &str) -> String {
+    let separator = rust_i18n::t!("formats.decimal_separator", locale = locale);
+    if separator == "." {
+        return text.to_string();
+    }
+    let chars: Vec<char> = text.chars().collect();
+    let mut output = String::with_capacity(text.len());
+    for (index, ch) in chars.iter().enumerate() {
+        let between_digits = index > 0
+            && chars[index - 1].is_ascii_digit()
+            && chars.get(index + 1).is_some_and(char::is_ascii_digit);
+        if *ch == '.' && between_digits {
+            output.push_str(&separator);
+        } else {
+            output.push(*ch);
+        }
+    }
+    output
+}
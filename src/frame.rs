@@ -0,0 +1,121 @@
+use anyhow::Result;
+use image::{Rgb, RgbImage, imageops};
+
+use crate::canvas::{self, Typeface};
+use crate::exif::{self, ShotFacts};
+
+const PAPER: Rgb<u8> = Rgb([255, 255, 255]);
+const INK: Rgb<u8> = Rgb([34, 34, 34]);
+const MUTED_INK: Rgb<u8> = Rgb([136, 136, 136]);
+const DIVIDER: Rgb<u8> = Rgb([204, 204, 204]);
+
+/// Puts the photo on a white card with the gear and exposure printed underneath:
+/// model and lens on the left, the brand and the settings on the right.
+pub fn render(typeface: &Typeface, bytes: &[u8], facts: &ShotFacts) -> Result<Vec<u8>> {
+    let photo = canvas::decode(bytes)?;
+    let (width, height) = photo.dimensions();
+    let short_side = width.min(height) as f32;
+
+    // Proportions follow the photo, so every frame looks the same at any resolution.
+    let border = (short_side * 0.03).round().max(8.0) as u32;
+    let band = (short_side * 0.14).round().max(48.0) as u32;
+    let mut card = RgbImage::from_pixel(width + 2 * border, height + border + band, PAPER);
+    imageops::replace(&mut card, &photo, i64::from(border), i64::from(border));
+
+    let primary = band as f32 * 0.22;
+    let secondary = band as f32 * 0.15;
+    let band_top = (height + border) as f32;
+    let first_baseline = band_top + band as f32 * 0.45;
+    let second_baseline = band_top + band as f32 * 0.72;
+    let left = border as f32;
+    let right = (card.width() - border) as f32;
+
+    let model = facts.model.as_deref().unwrap_or(&facts.camera);
+    let lens = Some(facts.lens.as_str())
+        .filter(|lens| *lens != exif::UNKNOWN_LENS)
+        .unwrap_or_default();
+    typeface.draw_text(&mut card, primary, (left, first_baseline), model, INK);
+    typeface.draw_text(
+        &mut card,
+        secondary,
+        (left, second_baseline),
+        lens,
+        MUTED_INK,
+    );
+
+    let exposure = exposure(facts);
+    let taken = facts.datetime.as_deref().unwrap_or_default();
+    let settings_width = typeface
+        .text_width(primary, &exposure)
+        .max(typeface.text_width(secondary, taken));
+    let settings_left = right - settings_width;
+    typeface.draw_text(
+        &mut card,
+        primary,
+        (
+            right - typeface.text_width(primary, &exposure),
+            first_baseline,
+        ),
+        &exposure,
+        INK,
+    );
+    typeface.draw_text(
+        &mut card,
+        secondary,
+        (
+            right - typeface.text_width(secondary, taken),
+            second_baseline,
+        ),
+        taken,
+        MUTED_INK,
+    );
+
+    // The brand stands in for a logo, set off from the settings by a thin rule. On
+    // narrow portrait frames it is left out rather than crowding the model name.
+    if let Some(make) = facts.make.as_deref() {
+        let brand = make.to_uppercase();
+        let brand_size = primary * 1.2;
+        let gap = band as f32 * 0.2;
+        let brand_left = settings_left - 2.0 * gap - typeface.text_width(brand_size, &brand);
+        let model_right = left
+            + typeface
+                .text_width(primary, model)
+                .max(typeface.text_width(secondary, lens));
+        if brand_left > model_right + gap {
+            let middle = (first_baseline + second_baseline) / 2.0;
+            typeface.draw_text(
+                &mut card,
+                brand_size,
+                (brand_left, middle + brand_size * 0.35),
+                &brand,
+                INK,
+            );
+
+            let rule_top = first_baseline - typeface.ascent(primary);
+            let rule_width = (band / 60).max(1);
+            canvas::fill_rect(
+                &mut card,
+                ((settings_left - gap) as u32, rule_top as u32),
+                (rule_width, (second_baseline - rule_top) as u32),
+                DIVIDER,
+                1.0,
+            );
+        }
+    }
+
+    canvas::encode_jpeg(&card)
+}
+
+/// Focal length, aperture, shutter and ISO the way frames print them.
+fn exposure(facts: &ShotFacts) -> String {
+    let parts: Vec<&str> = [
+        &facts.focal_length,
+        &facts.aperture,
+        &facts.shutter,
+        &facts.iso,
+    ]
+    .into_iter()
+    .filter_map(|value| value.as_deref())
+    .collect();
+    parts.join("  ")
+}
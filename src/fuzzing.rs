@@ -0,0 +1,47 @@
+//! Entry points for the `cargo fuzz` targets in `fuzz/`, which feed arbitrary bytes to
+//! the parsers that read files sent by strangers. Only built with `--cfg fuzzing`, which
+//! `cargo fuzz` sets.
+
+use std::io::Cursor;
+
+use crate::exif::{self, CaptionOptions, ExifSummary, ParsedExif, ShotFacts};
+use crate::strip::{Categories, Category, Removal};
+
+/// Container detection and the EXIF reader, then everything a caption is made from.
+pub fn metadata(data: &[u8]) {
+    let _ = crate::remote::sniff_image(data);
+    if let Ok(Some(exif)) = exif::read_exif_from_bytes(data) {
+        let _ = ParsedExif::from_exif(&exif, None);
+        let _ = ShotFacts::from_exif(&exif);
+        let _ = exif::fields_json(&exif, CaptionOptions::default());
+        let _ = exif::dump_fields(&exif, CaptionOptions::default());
+    }
+    for language in ["en", "zh-CN"] {
+        if let Ok(metadata) = exif::read_metadata(Cursor::new(data)) {
+            let _ =
+                ExifSummary::without_lookups(metadata, Some(language), CaptionOptions::default());
+        }
+    }
+}
+
+/// The scan for generation markers in XMP packets, PNG text chunks and C2PA manifests.
+pub fn provenance(data: &[u8]) {
+    let markers = crate::provenance::scan(&mut Cursor::new(data));
+    let markers = crate::provenance::conclude(markers, None);
+    let _ = crate::provenance::note(&markers, "en");
+}
+
+/// Rewriting JPEG and PNG files without their EXIF, XMP and IPTC blocks. Whatever comes
+/// out is read again, as the bot sends it on.
+pub fn strip(data: &[u8]) {
+    let removals = [
+        Removal::All,
+        Removal::Selected(Categories::default().toggled(Category::Gps)),
+        Removal::Selected(Categories::from_bits(u8::MAX)),
+    ];
+    for removal in removals {
+        if let Ok(stripped) = crate::strip::strip_metadata(data, removal) {
+            let _ = exif::read_exif_from_bytes(&stripped);
+        }
+    }
+}
@@ -0,0 +1,176 @@
+pub mod breaker;
+pub mod cache;
+pub mod elevation;
+pub mod geocoder;
+pub mod links;
+pub mod moon;
+pub mod offline;
+pub mod pluscode;
+pub mod poi;
+pub mod ratelimit;
+pub mod sun;
+pub mod timezone;
+pub mod weather;
+pub mod what3words;
+
+use std::path::Path;
+use std::sync::Arc;
+
+use chrono::NaiveDateTime;
+use reqwest::Client;
+
+use crate::config::LookupConfig;
+use elevation::Elevation;
+use geocoder::{Address, Geocoder};
+use weather::{OpenWeather, Weather};
+use what3words::What3Words;
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// The services a caption's place, landmark, weather, words and elevation come from,
+/// built once from the configuration and shared by every bot. Each lookup is `None`
+/// where its service is off or fails; failures are logged, since captions do without.
+pub struct Lookups {
+    client: Client,
+    /// The providers to ask in turn, or `None` when geocoding is off altogether.
+    geocoder: Option<Box<dyn Geocoder>>,
+    /// Overpass behind its cache, answering with the nearest landmark's name.
+    landmarks: Option<Box<dyn Geocoder>>,
+    weather: Option<OpenWeather>,
+    what3words: Option<What3Words>,
+    elevation: Option<Elevation>,
+}
+
+impl Lookups {
+    /// Sets up every service `config` turns on; caches and the offline city data are kept
+    /// in `data_dir`.
+    pub fn new(config: &LookupConfig, data_dir: &Path, client: Client) -> Self {
+        Self {
+            client,
+            geocoder: geocoder::from_config(config, data_dir),
+            landmarks: poi::from_config(
+                config.overpass_url.as_deref(),
+                config.geocode_cache_days,
+                data_dir,
+            ),
+            weather: OpenWeather::from_config(
+                config.weather_key.as_deref(),
+                config.weather_url.as_deref(),
+            ),
+            what3words: What3Words::from_config(config.what3words_key.as_deref()),
+            elevation: Elevation::from_config(config.elevation_url.as_deref()),
+        }
+    }
+
+    /// Looks up the point with the configured provider.
+    pub async fn reverse(&self, latitude: f64, longitude: f64, language: &str) -> Option<Address> {
+        let geocoder = self.geocoder.as_deref()?;
+        match geocoder
+            .reverse(&self.client, latitude, longitude, language)
+            .await
+        {
+            Ok(address) => address,
+            Err(err) => {
+                tracing::warn!(
+                    "Reverse geocoding failed for coordinates ({latitude:.6}, {longitude:.6}): {err:?}"
+                );
+                None
+            }
+        }
+    }
+
+    /// Whether landmark lookups are configured at all.
+    pub fn landmarks_available(&self) -> bool {
+        self.landmarks.is_some()
+    }
+
+    /// The name of the landmark closest to the point, in `language` where OpenStreetMap
+    /// has it.
+    pub async fn nearby(&self, latitude: f64, longitude: f64, language: &str) -> Option<String> {
+        let landmarks = self.landmarks.as_deref()?;
+        match landmarks
+            .reverse(&self.client, latitude, longitude, language)
+            .await
+        {
+            Ok(address) => address.and_then(|address| address.name),
+            Err(err) => {
+                tracing::warn!(
+                    "Landmark lookup failed for coordinates ({latitude:.6}, {longitude:.6}): {err:?}"
+                );
+                None
+            }
+        }
+    }
+
+    /// Whether a weather key is configured, so callers can skip working out the capture
+    /// time.
+    pub fn weather_available(&self) -> bool {
+        self.weather.is_some()
+    }
+
+    pub async fn weather(
+        &self,
+        latitude: f64,
+        longitude: f64,
+        utc: NaiveDateTime,
+        language: &str,
+    ) -> Option<Weather> {
+        self.weather
+            .as_ref()?
+            .at(&self.client, latitude, longitude, utc, language)
+            .await
+    }
+
+    pub async fn words(&self, latitude: f64, longitude: f64, language: &str) -> Option<String> {
+        self.what3words
+            .as_ref()?
+            .words(&self.client, latitude, longitude, language)
+            .await
+    }
+
+    pub async fn terrain(&self, latitude: f64, longitude: f64) -> Option<f64> {
+        self.elevation
+            .as_ref()?
+            .terrain(&self.client, latitude, longitude)
+            .await
+    }
+
+    /// Writes out the answers the caches have not stored yet, before exiting.
+    pub async fn flush(self: &Arc<Self>) {
+        let lookups = Arc::clone(self);
+        let flushed = tokio::task::spawn_blocking(move || {
+            for geocoder in [&lookups.geocoder, &lookups.landmarks]
+                .into_iter()
+                .flatten()
+            {
+                geocoder.flush();
+            }
+        });
+        if let Err(err) = flushed.await {
+            tracing::warn!("Failed to flush the geocoding caches: {err:?}");
+        }
+    }
+}
+
+/// Great-circle distance between two points by the haversine formula.
+pub fn haversine_km((lat1, lon1): (f64, f64), (lat2, lon2): (f64, f64)) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let delta_phi = (lat2 - lat1).to_radians();
+    let delta_lambda = (lon2 - lon1).to_radians();
+
+    let a = (delta_phi / 2.0).sin().powi(2)
+        + phi1.cos() * phi2.cos() * (delta_lambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+/// The flag of a country as a pair of regional indicator symbols, from its ISO 3166-1
+/// alpha-2 code.
+pub fn flag_emoji(country_code: &str) -> Option<String> {
+    let code = country_code.trim();
+    if code.len() != 2 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    code.chars()
+        .map(|c| char::from_u32(0x1F1E6 + u32::from(c.to_ascii_uppercase()) - u32::from('A')))
+        .collect()
+}
@@ -0,0 +1,103 @@
+//! Stops asking a geocoder that keeps failing, for a while, so lookups go straight to
+//! the next one in the chain.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use reqwest::Client;
+
+use super::geocoder::{Geocoder, Lookup};
+
+/// Failures in a row after which the provider is skipped.
+const FAILURE_THRESHOLD: u32 = 3;
+const COOLDOWN: Duration = Duration::from_secs(60);
+
+#[derive(Default)]
+struct State {
+    failures: u32,
+    /// Set while the provider is skipped.
+    open_until: Option<Instant>,
+}
+
+/// Wraps a network geocoder. After [`FAILURE_THRESHOLD`] errors in a row it answers with
+/// an error right away for [`COOLDOWN`], then lets a single lookup through to see
+/// whether the provider is back.
+pub struct Breaker {
+    name: String,
+    inner: Box<dyn Geocoder>,
+    state: Mutex<State>,
+}
+
+impl Breaker {
+    pub fn new(name: &str, inner: Box<dyn Geocoder>) -> Self {
+        Self {
+            name: name.to_string(),
+            inner,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Whether a lookup may go to the provider. Once the cooldown is over, the first
+    /// caller gets through while the others keep being turned away until it reports back.
+    fn allow(&self) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        match state.open_until {
+            Some(until) if Instant::now() < until => false,
+            Some(_) => {
+                state.open_until = Some(Instant::now() + COOLDOWN);
+                true
+            }
+            None => true,
+        }
+    }
+
+    fn record(&self, succeeded: bool) {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        if succeeded {
+            if state.open_until.is_some() {
+                tracing::info!("Geocoder `{}` answers again", self.name);
+            }
+            *state = State::default();
+            return;
+        }
+
+        state.failures += 1;
+        if state.failures >= FAILURE_THRESHOLD {
+            if state.failures == FAILURE_THRESHOLD {
+                tracing::warn!(
+                    "Geocoder `{}` failed {} times in a row; skipping it for {}s",
+                    self.name,
+                    state.failures,
+                    COOLDOWN.as_secs()
+                );
+            }
+            state.open_until = Some(Instant::now() + COOLDOWN);
+        }
+    }
+}
+
+impl Geocoder for Breaker {
+    fn reverse<'a>(
+        &'a self,
+        client: &'a Client,
+        latitude: f64,
+        longitude: f64,
+        language: &'a str,
+    ) -> Lookup<'a> {
+        Box::pin(async move {
+            if !self.allow() {
+                bail!(
+                    "Geocoder `{}` is skipped after repeated failures",
+                    self.name
+                );
+            }
+            let answer = self
+                .inner
+                .reverse(client, latitude, longitude, language)
+                .await;
+            self.record(answer.is_ok());
+            answer
+        })
+    }
+}
@@ -0,0 +1,268 @@
+//! An on-disk cache of reverse geocoding answers, so photos from the same spot do not
+//! hit the provider again. Replicas coordinating through Redis share their answers, too.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use super::geocoder::{Address, Geocoder, Lookup};
+
+/// Coordinates are rounded to this many decimals (about 110 m) to form the key.
+const KEY_DECIMALS: i32 = 3;
+const DEFAULT_TTL_DAYS: u64 = 30;
+/// The oldest answers are dropped past this, which keeps the file around a few MB.
+const MAX_ENTRIES: usize = 10_000;
+/// New answers are written out together this long after the first of them, so a burst
+/// of lookups rewrites the file once; a crash loses at most these last answers.
+const PERSIST_DELAY: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CachedAnswer {
+    /// `None` records that the provider knows nothing at the spot.
+    address: Option<Address>,
+    /// Seconds since the UNIX epoch.
+    stored_at: u64,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct CacheData {
+    answers: HashMap<String, CachedAnswer>,
+}
+
+/// Answers persisted as a JSON file next to the settings, each kept for a limited time.
+pub struct GeocodeCache {
+    /// Keeps the answers of this cache apart from other caches' in Redis.
+    namespace: String,
+    ttl: Duration,
+    store: Arc<Store>,
+}
+
+/// The answers and their file, shared with the task that writes them out.
+struct Store {
+    path: PathBuf,
+    data: Mutex<CacheData>,
+    /// Set while a write is scheduled, so further answers join it.
+    scheduled: AtomicBool,
+    /// Held while the file is written, so two writes never share the temporary file.
+    writing: Mutex<()>,
+}
+
+impl GeocodeCache {
+    /// Opens the cache at `path`, keeping answers for `days` (30 by default); `None`,
+    /// after logging why, when that is `0` or the file cannot be used.
+    pub fn configured(path: Result<PathBuf>, days: Option<u64>) -> Option<Self> {
+        let days = days.unwrap_or(DEFAULT_TTL_DAYS);
+        if days == 0 {
+            return None;
+        }
+
+        let opened =
+            path.and_then(|path| Self::open(path, Duration::from_secs(days * 24 * 60 * 60)));
+        match opened {
+            Ok(cache) => Some(cache),
+            Err(err) => {
+                tracing::warn!("Geocoding cache is disabled: {err:?}");
+                None
+            }
+        }
+    }
+
+    /// Loads the cache file, starting empty when it does not exist yet. Expired answers
+    /// are dropped right away.
+    pub fn open(path: PathBuf, ttl: Duration) -> Result<Self> {
+        let mut data: CacheData = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).with_context(|| {
+                format!("Failed to parse geocoding cache at `{}`", path.display())
+            })?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => CacheData::default(),
+            Err(err) => {
+                return Err(err).with_context(|| {
+                    format!("Failed to read geocoding cache at `{}`", path.display())
+                });
+            }
+        };
+        let now = now_secs();
+        data.answers
+            .retain(|_, answer| now.saturating_sub(answer.stored_at) < ttl.as_secs());
+
+        let namespace = path.file_stem().map_or_else(
+            || String::from("cache"),
+            |stem| stem.to_string_lossy().into_owned(),
+        );
+        Ok(Self {
+            namespace,
+            ttl,
+            store: Arc::new(Store {
+                path,
+                data: Mutex::new(data),
+                scheduled: AtomicBool::new(false),
+                writing: Mutex::new(()),
+            }),
+        })
+    }
+
+    /// The stored answer for `key`, unless it has expired.
+    fn get(&self, key: &str) -> Option<Option<Address>> {
+        let data = self
+            .store
+            .data
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let answer = data.answers.get(key)?;
+        if now_secs().saturating_sub(answer.stored_at) >= self.ttl.as_secs() {
+            return None;
+        }
+        Some(answer.address.clone())
+    }
+
+    /// Stores an answer, dropping the oldest one past the limit, and schedules writing the
+    /// file back.
+    fn insert(&self, key: String, address: Option<Address>) {
+        let mut data = self
+            .store
+            .data
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        data.answers.insert(
+            key,
+            CachedAnswer {
+                address,
+                stored_at: now_secs(),
+            },
+        );
+        if data.answers.len() > MAX_ENTRIES {
+            let oldest = data
+                .answers
+                .iter()
+                .min_by_key(|(_, answer)| answer.stored_at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                data.answers.remove(&oldest);
+            }
+        }
+        drop(data);
+        self.schedule_persist();
+    }
+
+    /// Writes the file back on a blocking thread after [`PERSIST_DELAY`], unless a write
+    /// is already scheduled.
+    fn schedule_persist(&self) {
+        if self.store.scheduled.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let store = Arc::clone(&self.store);
+        tokio::spawn(async move {
+            tokio::time::sleep(PERSIST_DELAY).await;
+            if !store.scheduled.load(Ordering::Acquire) {
+                // Written by `flush` in the meantime.
+                return;
+            }
+            match tokio::task::spawn_blocking(move || store.persist()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => tracing::warn!("Failed to store geocoding answers: {err:?}"),
+                Err(err) => tracing::warn!("Geocoding cache write did not finish: {err:?}"),
+            }
+        });
+    }
+
+    /// Writes the answers of a scheduled write out right away, blocking on the file; for
+    /// shutdown.
+    pub fn flush(&self) {
+        if !self.store.scheduled.load(Ordering::Acquire) {
+            return;
+        }
+        if let Err(err) = self.store.persist() {
+            tracing::warn!("Failed to store geocoding answers: {err:?}");
+        }
+    }
+}
+
+impl Store {
+    fn persist(&self) -> Result<()> {
+        // Cleared before taking the answers, so one stored meanwhile schedules a new write.
+        self.scheduled.store(false, Ordering::Release);
+        let bytes = {
+            let data = self.data.lock().unwrap_or_else(|err| err.into_inner());
+            serde_json::to_vec(&*data).context("Failed to serialize geocoding cache")?
+        };
+
+        let _writing = self.writing.lock().unwrap_or_else(|err| err.into_inner());
+        // Write to a sibling file first so a crash never leaves a half-written cache file.
+        let tmp_path = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, bytes).with_context(|| {
+            format!(
+                "Failed to write geocoding cache at `{}`",
+                tmp_path.display()
+            )
+        })?;
+        std::fs::rename(&tmp_path, &self.path).with_context(|| {
+            format!(
+                "Failed to replace geocoding cache at `{}`",
+                self.path.display()
+            )
+        })
+    }
+}
+
+/// Answers from the cache where it can and remembers what `inner` says otherwise.
+/// Errors are not cached, so a provider outage is retried on the next lookup.
+pub struct Cached {
+    pub inner: Box<dyn Geocoder>,
+    pub cache: GeocodeCache,
+}
+
+impl Geocoder for Cached {
+    fn reverse<'a>(
+        &'a self,
+        client: &'a Client,
+        latitude: f64,
+        longitude: f64,
+        language: &'a str,
+    ) -> Lookup<'a> {
+        Box::pin(async move {
+            let key = cache_key(latitude, longitude, language);
+            if let Some(address) = self.cache.get(&key) {
+                return Ok(address);
+            }
+            let shared = format!("{}:{key}", self.cache.namespace);
+            if let Some(address) = crate::coordination::cached::<Option<Address>>(&shared).await {
+                self.cache.insert(key, address.clone());
+                return Ok(address);
+            }
+
+            let address = self
+                .inner
+                .reverse(client, latitude, longitude, language)
+                .await?;
+            crate::coordination::store(&shared, &address, self.cache.ttl).await;
+            self.cache.insert(key, address.clone());
+            Ok(address)
+        })
+    }
+
+    fn flush(&self) {
+        self.cache.flush();
+    }
+}
+
+fn cache_key(latitude: f64, longitude: f64, language: &str) -> String {
+    // Rounding to integers first keeps `-0.000` and `0.000` from becoming two keys.
+    let scale = 10f64.powi(KEY_DECIMALS);
+    let latitude = (latitude * scale).round() as i64;
+    let longitude = (longitude * scale).round() as i64;
+    format!("{latitude}:{longitude}:{language}")
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
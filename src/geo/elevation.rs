@@ -0,0 +1,64 @@
+//! Terrain elevation at a point, for photos whose GPS recorded no altitude.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use reqwest::Client;
+use serde_json::Value as JsonValue;
+
+/// Open-Elevation's public instance. Open Topo Data answers in the same shape, so a
+/// `https://api.opentopodata.org/v1/srtm90m` style URL works too.
+const DEFAULT_ENDPOINT: &str = "https://api.open-elevation.com/api/v1/lookup";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// An elevation service.
+pub struct Elevation {
+    endpoint: String,
+}
+
+impl Elevation {
+    /// The service at `url` (`elevation_url` in the configuration), or `None` when that
+    /// is `off`.
+    pub fn from_config(url: Option<&str>) -> Option<Self> {
+        let endpoint = url.unwrap_or(DEFAULT_ENDPOINT).to_string();
+        (!endpoint.eq_ignore_ascii_case("off")).then_some(Self { endpoint })
+    }
+
+    /// Meters above sea level of the ground at the point. Failures are logged, since the
+    /// elevation is extra.
+    pub async fn terrain(&self, client: &Client, latitude: f64, longitude: f64) -> Option<f64> {
+        match lookup(client, &self.endpoint, latitude, longitude).await {
+            Ok(elevation) => elevation,
+            Err(err) => {
+                tracing::warn!(
+                    "Elevation lookup failed for coordinates ({latitude:.6}, {longitude:.6}): {err:?}"
+                );
+                None
+            }
+        }
+    }
+}
+
+async fn lookup(
+    client: &Client,
+    endpoint: &str,
+    latitude: f64,
+    longitude: f64,
+) -> Result<Option<f64>> {
+    let value: JsonValue = client
+        .get(endpoint)
+        .query(&[("locations", format!("{latitude:.6},{longitude:.6}"))])
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await
+        .context("Elevation request failed")?
+        .error_for_status()
+        .context("Elevation service returned an error")?
+        .json()
+        .await
+        .context("Failed to parse elevation response")?;
+    // Open Topo Data answers `null` for points outside its dataset.
+    Ok(value
+        .pointer("/results/0/elevation")
+        .and_then(JsonValue::as_f64))
+}
@@ -0,0 +1,480 @@
+//! Reverse geocoding through a configurable provider.
+
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use reqwest::{Client, RequestBuilder, StatusCode, header};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use super::breaker::Breaker;
+use super::cache::{Cached, GeocodeCache};
+use super::offline::OfflineGeocoder;
+use super::ratelimit::RateLimiter;
+use crate::config::{GeocoderConfig, LookupConfig, ProviderConfig};
+
+const NOMINATIM_ENDPOINT: &str = "https://nominatim.openstreetmap.org/reverse";
+const LOCATIONIQ_ENDPOINT: &str = "https://us1.locationiq.com/v1/reverse";
+const MAPBOX_ENDPOINT: &str = "https://api.mapbox.com/search/geocode/v6/reverse";
+const GOOGLE_ENDPOINT: &str = "https://maps.googleapis.com/maps/api/geocode/json";
+/// Nominatim's usage policy allows one request per second.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often a rate-limited request is retried, and the longest pause worth waiting
+/// for; beyond that the lookup fails over to the next geocoder.
+const MAX_RETRIES: u32 = 2;
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(5);
+const DEFAULT_RETRY_WAIT: Duration = Duration::from_secs(2);
+
+/// A pending lookup; boxed so geocoders can sit behind `dyn Geocoder`.
+pub type Lookup<'a> = Pin<Box<dyn Future<Output = Result<Option<Address>>> + Send + 'a>>;
+
+/// What a provider knows about a point, in the same shape for every provider.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Address {
+    /// Name of the feature itself (a building, park, street...), when it has one.
+    pub name: Option<String>,
+    /// The full address on one line, most specific part first.
+    pub display_name: String,
+    /// The city, town or village the point lies in.
+    pub city: Option<String>,
+    /// The state, province or prefecture.
+    pub region: Option<String>,
+    pub country: Option<String>,
+    /// ISO 3166-1 alpha-2 code, uppercase.
+    pub country_code: Option<String>,
+}
+
+/// A reverse geocoding service.
+pub trait Geocoder: Send + Sync {
+    /// Looks up the address at the point, in `language` where the provider supports it;
+    /// `None` when the provider knows nothing there.
+    fn reverse<'a>(
+        &'a self,
+        client: &'a Client,
+        latitude: f64,
+        longitude: f64,
+        language: &'a str,
+    ) -> Lookup<'a>;
+
+    /// Writes out answers kept in memory, blocking on the file system; called before
+    /// exiting.
+    fn flush(&self) {}
+}
+
+/// The network providers behind the answer cache, followed by the offline city data
+/// when it is installed; `None` when geocoding is off altogether.
+pub fn from_config(config: &LookupConfig, data_dir: &Path) -> Option<Box<dyn Geocoder>> {
+    let network = network_from_config(&config.geocoder).map(|inner| {
+        let path = crate::config::geocode_cache_path_from_env(data_dir);
+        match GeocodeCache::configured(path, config.geocode_cache_days) {
+            Some(cache) => Box::new(Cached { inner, cache }) as Box<dyn Geocoder>,
+            None => inner,
+        }
+    });
+    let offline = OfflineGeocoder::from_config(config.cities_file.as_deref(), data_dir);
+    let chain: Vec<Box<dyn Geocoder>> = [
+        network,
+        offline.map(|offline| Box::new(offline) as Box<dyn Geocoder>),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if chain.is_empty() {
+        return None;
+    }
+    Some(Box::new(Chain(chain)))
+}
+
+/// The primary provider (Nominatim unless set) and the fallback one (none unless set),
+/// each behind a circuit breaker.
+fn network_from_config(config: &GeocoderConfig) -> Option<Box<dyn Geocoder>> {
+    let interval = config
+        .interval_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_INTERVAL);
+    // Nominatim's usage policy asks for an agent that identifies the application, and
+    // whoever runs it where a contact is configured.
+    let user_agent = match &config.contact {
+        Some(contact) => format!("{} {contact}", crate::http::USER_AGENT),
+        None => crate::http::USER_AGENT.to_string(),
+    };
+    let mut chain: Vec<Box<dyn Geocoder>> = [
+        provider_from_config(&config.primary, "FOTOBOT_GEOCODER", interval, &user_agent),
+        config.fallback.as_ref().and_then(|fallback| {
+            provider_from_config(fallback, "FOTOBOT_GEOCODER_FALLBACK", interval, &user_agent)
+        }),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    match chain.len() {
+        0 => None,
+        1 => chain.pop(),
+        _ => Some(Box::new(Chain(chain))),
+    }
+}
+
+/// Sets up the provider (`nominatim`, `locationiq`, `mapbox`, `google` or `off`), with
+/// its API key and an optional URL for self-hosted or regional endpoints. `prefix` names
+/// the environment variables for the warnings. Requests are spaced by `interval`
+/// separately for each provider.
+fn provider_from_config(
+    config: &ProviderConfig,
+    prefix: &str,
+    interval: Duration,
+    user_agent: &str,
+) -> Option<Box<dyn Geocoder>> {
+    let provider = config
+        .provider
+        .as_deref()
+        .unwrap_or("nominatim")
+        .trim()
+        .to_ascii_lowercase();
+    let key = config.key.clone();
+    let endpoint = |default: &str| config.url.clone().unwrap_or_else(|| default.to_string());
+    let limiter = || RateLimiter::shared(format!("geocoder:{provider}"), interval);
+    let user_agent = user_agent.to_string();
+
+    let geocoder: Box<dyn Geocoder> = match (provider.as_str(), key) {
+        ("off", _) => return None,
+        ("nominatim", _) => Box::new(Nominatim {
+            endpoint: endpoint(NOMINATIM_ENDPOINT),
+            key: None,
+            limiter: limiter(),
+            user_agent,
+        }),
+        ("locationiq", Some(key)) => Box::new(Nominatim {
+            endpoint: endpoint(LOCATIONIQ_ENDPOINT),
+            key: Some(key),
+            limiter: limiter(),
+            user_agent,
+        }),
+        ("mapbox", Some(token)) => Box::new(Mapbox {
+            endpoint: endpoint(MAPBOX_ENDPOINT),
+            token,
+            limiter: limiter(),
+            user_agent,
+        }),
+        ("google", Some(key)) => Box::new(Google {
+            endpoint: endpoint(GOOGLE_ENDPOINT),
+            key,
+            limiter: limiter(),
+            user_agent,
+        }),
+        ("locationiq" | "mapbox" | "google", None) => {
+            tracing::warn!(
+                "Reverse geocoding through `{provider}` is disabled: it needs a key ({prefix}_KEY)"
+            );
+            return None;
+        }
+        _ => {
+            tracing::warn!(
+                "Reverse geocoding is disabled: unknown provider `{provider}` in {prefix}"
+            );
+            return None;
+        }
+    };
+    Some(Box::new(Breaker::new(&provider, geocoder)))
+}
+
+/// Asks each geocoder in turn until one knows the point. Errors, such as an unreachable
+/// or rate-limiting provider, are logged and move on to the next; when no geocoder
+/// answered and one of them failed, the last error is returned, so the miss is not
+/// mistaken for a place nobody knows.
+struct Chain(Vec<Box<dyn Geocoder>>);
+
+impl Geocoder for Chain {
+    fn reverse<'a>(
+        &'a self,
+        client: &'a Client,
+        latitude: f64,
+        longitude: f64,
+        language: &'a str,
+    ) -> Lookup<'a> {
+        Box::pin(async move {
+            let mut failure = None;
+            for geocoder in &self.0 {
+                match geocoder
+                    .reverse(client, latitude, longitude, language)
+                    .await
+                {
+                    Ok(Some(address)) => return Ok(Some(address)),
+                    Ok(None) => {}
+                    Err(err) => {
+                        tracing::warn!(
+                            "Reverse geocoding failed for coordinates ({latitude:.6}, {longitude:.6}), trying the next provider: {err:?}"
+                        );
+                        failure = Some(err);
+                    }
+                }
+            }
+            match failure {
+                Some(err) => Err(err),
+                None => Ok(None),
+            }
+        })
+    }
+
+    fn flush(&self) {
+        for geocoder in &self.0 {
+            geocoder.flush();
+        }
+    }
+}
+
+/// Nominatim, or LocationIQ, which serves the same API behind a `key` parameter.
+struct Nominatim {
+    endpoint: String,
+    key: Option<String>,
+    limiter: RateLimiter,
+    user_agent: String,
+}
+
+impl Geocoder for Nominatim {
+    fn reverse<'a>(
+        &'a self,
+        client: &'a Client,
+        latitude: f64,
+        longitude: f64,
+        language: &'a str,
+    ) -> Lookup<'a> {
+        Box::pin(async move {
+            let mut request = client.get(&self.endpoint).query(&[
+                ("lat", format!("{latitude:.6}")),
+                ("lon", format!("{longitude:.6}")),
+                ("addressdetails", String::from("1")),
+                ("accept-language", nominatim_languages(language)),
+                ("format", String::from("json")),
+            ]);
+            if let Some(key) = &self.key {
+                request = request.query(&[("key", key)]);
+            }
+            let value = fetch_json(&self.limiter, &self.user_agent, request).await?;
+
+            let Some(display_name) = string_at(&value, "/display_name") else {
+                return Ok(None);
+            };
+            let first_of = |keys: &[&str]| {
+                keys.iter()
+                    .find_map(|key| string_at(&value, &format!("/address/{key}")))
+            };
+            Ok(Some(Address {
+                name: string_at(&value, "/name"),
+                display_name,
+                city: first_of(&["city", "town", "village", "municipality"]),
+                region: first_of(&["state", "province", "region", "county"]),
+                country: string_at(&value, "/address/country"),
+                country_code: string_at(&value, "/address/country_code")
+                    .map(|code| code.to_ascii_uppercase()),
+            }))
+        })
+    }
+}
+
+/// Nominatim's `accept-language` for `language`. OpenStreetMap keys traditional Chinese
+/// names by script as often as by region, so `zh-TW` and `zh-HK` also accept `zh-Hant`
+/// before falling back to plain `zh`.
+fn nominatim_languages(language: &str) -> String {
+    match language {
+        "zh-TW" | "zh-HK" => format!("{language},zh-Hant,zh"),
+        _ => language.to_string(),
+    }
+}
+
+/// Mapbox's v6 geocoding API.
+struct Mapbox {
+    endpoint: String,
+    token: String,
+    limiter: RateLimiter,
+    user_agent: String,
+}
+
+impl Geocoder for Mapbox {
+    fn reverse<'a>(
+        &'a self,
+        client: &'a Client,
+        latitude: f64,
+        longitude: f64,
+        language: &'a str,
+    ) -> Lookup<'a> {
+        Box::pin(async move {
+            let request = client.get(&self.endpoint).query(&[
+                ("latitude", format!("{latitude:.6}")),
+                ("longitude", format!("{longitude:.6}")),
+                ("language", language.to_string()),
+                ("limit", String::from("1")),
+                ("access_token", self.token.clone()),
+            ]);
+            let value = fetch_json(&self.limiter, &self.user_agent, request).await?;
+
+            let Some(properties) = value.pointer("/features/0/properties") else {
+                return Ok(None);
+            };
+            let Some(display_name) = string_at(properties, "/full_address")
+                .or_else(|| string_at(properties, "/place_formatted"))
+            else {
+                return Ok(None);
+            };
+            // Only points of interest carry a name of their own; for addresses it is the street.
+            let name = string_at(properties, "/name")
+                .filter(|_| string_at(properties, "/feature_type").as_deref() == Some("poi"));
+            Ok(Some(Address {
+                name,
+                display_name,
+                city: string_at(properties, "/context/place/name"),
+                region: string_at(properties, "/context/region/name"),
+                country: string_at(properties, "/context/country/name"),
+                country_code: string_at(properties, "/context/country/country_code")
+                    .map(|code| code.to_ascii_uppercase()),
+            }))
+        })
+    }
+}
+
+/// Google's Geocoding API.
+struct Google {
+    endpoint: String,
+    key: String,
+    limiter: RateLimiter,
+    user_agent: String,
+}
+
+impl Geocoder for Google {
+    fn reverse<'a>(
+        &'a self,
+        client: &'a Client,
+        latitude: f64,
+        longitude: f64,
+        language: &'a str,
+    ) -> Lookup<'a> {
+        Box::pin(async move {
+            let request = client.get(&self.endpoint).query(&[
+                ("latlng", format!("{latitude:.6},{longitude:.6}")),
+                ("language", language.to_string()),
+                ("key", self.key.clone()),
+            ]);
+            let value = fetch_json(&self.limiter, &self.user_agent, request).await?;
+
+            // Google answers errors with HTTP 200 and a status field.
+            match string_at(&value, "/status").as_deref() {
+                Some("OK") => {}
+                Some("ZERO_RESULTS") => return Ok(None),
+                status => bail!(
+                    "Google geocoding returned status {}: {}",
+                    status.unwrap_or("(none)"),
+                    string_at(&value, "/error_message").unwrap_or_default()
+                ),
+            }
+
+            let Some(result) = value.pointer("/results/0") else {
+                return Ok(None);
+            };
+            let Some(display_name) = string_at(result, "/formatted_address") else {
+                return Ok(None);
+            };
+            let component = |kinds: &[&str], form: &str| {
+                result
+                    .get("address_components")?
+                    .as_array()?
+                    .iter()
+                    .find(|component| {
+                        component
+                            .get("types")
+                            .and_then(JsonValue::as_array)
+                            .is_some_and(|types| {
+                                types.iter().any(|kind| {
+                                    kind.as_str().is_some_and(|kind| kinds.contains(&kind))
+                                })
+                            })
+                    })
+                    .and_then(|component| string_at(component, form))
+            };
+            Ok(Some(Address {
+                name: component(
+                    &["point_of_interest", "establishment", "premise"],
+                    "/long_name",
+                ),
+                display_name,
+                city: component(&["locality", "postal_town"], "/long_name"),
+                region: component(&["administrative_area_level_1"], "/long_name"),
+                country: component(&["country"], "/long_name"),
+                country_code: component(&["country"], "/short_name"),
+            }))
+        })
+    }
+}
+
+/// Sends the request through the provider's rate limiter. A `429` or `503` pauses every
+/// request to it for as long as `Retry-After` asks, and is retried when that pause is short.
+async fn fetch_json(
+    limiter: &RateLimiter,
+    user_agent: &str,
+    request: RequestBuilder,
+) -> Result<JsonValue> {
+    let request = request
+        .header(header::USER_AGENT, user_agent)
+        .timeout(REQUEST_TIMEOUT);
+    let mut attempt = 0;
+    loop {
+        let retry = request
+            .try_clone()
+            .context("Reverse geocoding request cannot be retried")?;
+        limiter.acquire().await;
+        crate::metrics::record(crate::metrics::Metric::GeocoderCall);
+        let response = retry
+            .send()
+            .await
+            .context("Reverse geocoding request failed")?;
+
+        if matches!(
+            response.status(),
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+        ) {
+            let delay = retry_after(&response).unwrap_or(DEFAULT_RETRY_WAIT);
+            limiter.back_off(delay);
+            if attempt < MAX_RETRIES && delay <= MAX_RETRY_WAIT {
+                attempt += 1;
+                continue;
+            }
+            bail!(
+                "Reverse geocoding is rate-limited for {}s ({})",
+                delay.as_secs(),
+                response.status()
+            );
+        }
+
+        return response
+            .error_for_status()
+            .context("Reverse geocoding returned an error")?
+            .json()
+            .await
+            .context("Failed to parse reverse geocoding JSON");
+    }
+}
+
+/// The pause a `Retry-After` header asks for, when given in seconds.
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// The trimmed, non-empty string at the JSON pointer.
+fn string_at(value: &JsonValue, pointer: &str) -> Option<String> {
+    value
+        .pointer(pointer)
+        .and_then(JsonValue::as_str)
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .map(str::to_string)
+}
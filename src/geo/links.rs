@@ -0,0 +1,82 @@
+//! Deep links that open a GPS point in the common map apps.
+
+/// Opens a pin at the point in Google Maps.
+pub fn google_maps(latitude: f64, longitude: f64) -> String {
+    format!(
+        "https://www.google.com/maps/search/?api=1&query={},{}",
+        format_coordinate(latitude),
+        format_coordinate(longitude)
+    )
+}
+
+/// Opens a marker at the point on openstreetmap.org, zoomed to street level.
+pub fn openstreetmap(latitude: f64, longitude: f64) -> String {
+    let (lat, lon) = (format_coordinate(latitude), format_coordinate(longitude));
+    format!("https://www.openstreetmap.org/?mlat={lat}&mlon={lon}#map=16/{lat}/{lon}")
+}
+
+/// Opens a pin at the point in Apple Maps (falls back to the web version off Apple devices).
+pub fn apple_maps(latitude: f64, longitude: f64) -> String {
+    let (lat, lon) = (format_coordinate(latitude), format_coordinate(longitude));
+    format!("https://maps.apple.com/?ll={lat},{lon}&q={lat},{lon}")
+}
+
+/// A `geo:` URI (RFC 5870), which phones hand to whatever maps app is installed.
+pub fn geo_uri(latitude: f64, longitude: f64) -> String {
+    format!(
+        "geo:{},{}",
+        format_coordinate(latitude),
+        format_coordinate(longitude)
+    )
+}
+
+/// Six decimals (about 10 cm) without trailing zeros, and never a negative zero.
+fn format_coordinate(value: f64) -> String {
+    let formatted = format!("{value:.6}");
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+    match trimmed {
+        "-0" => String::from("0"),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coordinates_are_rounded_to_six_decimals() {
+        assert_eq!(format_coordinate(35.689_487_654), "35.689488");
+        assert_eq!(format_coordinate(-122.419_415_7), "-122.419416");
+    }
+
+    #[test]
+    fn trailing_zeros_are_trimmed() {
+        assert_eq!(format_coordinate(48.85), "48.85");
+        assert_eq!(format_coordinate(10.0), "10");
+        assert_eq!(format_coordinate(0.0), "0");
+    }
+
+    #[test]
+    fn negative_zero_is_formatted_as_zero() {
+        assert_eq!(format_coordinate(-0.0), "0");
+        assert_eq!(format_coordinate(-0.000_000_1), "0");
+    }
+
+    #[test]
+    fn provider_links_embed_the_point() {
+        assert_eq!(
+            google_maps(51.5, -0.125),
+            "https://www.google.com/maps/search/?api=1&query=51.5,-0.125"
+        );
+        assert_eq!(
+            openstreetmap(51.5, -0.125),
+            "https://www.openstreetmap.org/?mlat=51.5&mlon=-0.125#map=16/51.5/-0.125"
+        );
+        assert_eq!(
+            apple_maps(51.5, -0.125),
+            "https://maps.apple.com/?ll=51.5,-0.125&q=51.5,-0.125"
+        );
+        assert_eq!(geo_uri(51.5, -0.125), "geo:51.5,-0.125");
+    }
+}
@@ -0,0 +1,83 @@
+//! The moon's phase at a moment, from the mean length of the synodic month.
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+const SYNODIC_MONTH_DAYS: f64 = 29.530_588_853;
+/// A new moon, 2000-01-06 18:14 UTC, as a Julian day.
+const REFERENCE_NEW_MOON_JD: f64 = 2_451_550.26;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MoonPhase {
+    New,
+    WaxingCrescent,
+    FirstQuarter,
+    WaxingGibbous,
+    Full,
+    WaningGibbous,
+    LastQuarter,
+    WaningCrescent,
+}
+
+impl MoonPhase {
+    pub fn emoji(self) -> &'static str {
+        match self {
+            Self::New => "🌑",
+            Self::WaxingCrescent => "🌒",
+            Self::FirstQuarter => "🌓",
+            Self::WaxingGibbous => "🌔",
+            Self::Full => "🌕",
+            Self::WaningGibbous => "🌖",
+            Self::LastQuarter => "🌗",
+            Self::WaningCrescent => "🌘",
+        }
+    }
+
+    /// The key of the phase's name in the locale files.
+    pub fn locale_key(self) -> &'static str {
+        match self {
+            Self::New => "moon.new",
+            Self::WaxingCrescent => "moon.waxing_crescent",
+            Self::FirstQuarter => "moon.first_quarter",
+            Self::WaxingGibbous => "moon.waxing_gibbous",
+            Self::Full => "moon.full",
+            Self::WaningGibbous => "moon.waning_gibbous",
+            Self::LastQuarter => "moon.last_quarter",
+            Self::WaningCrescent => "moon.waning_crescent",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Moon {
+    pub phase: MoonPhase,
+    /// Share of the disc that is lit, from 0 to 1.
+    pub illumination: f64,
+}
+
+/// The moon at `utc`. Good to within a few hours of the true phase, which is well
+/// below what the eye or a caption can tell apart.
+pub fn moon_at(utc: NaiveDateTime) -> Moon {
+    let julian_day = utc.and_utc().timestamp() as f64 / 86_400.0 + 2_440_587.5;
+    let age = (julian_day - REFERENCE_NEW_MOON_JD).rem_euclid(SYNODIC_MONTH_DAYS);
+    let fraction = age / SYNODIC_MONTH_DAYS;
+    let illumination = (1.0 - (fraction * std::f64::consts::TAU).cos()) / 2.0;
+
+    // The quarters and the full and new moon get a day's width each; the rest is shared
+    // out evenly between them.
+    let phase = match age {
+        age if age < 1.0 || age >= SYNODIC_MONTH_DAYS - 1.0 => MoonPhase::New,
+        age if age < SYNODIC_MONTH_DAYS / 4.0 - 0.5 => MoonPhase::WaxingCrescent,
+        age if age < SYNODIC_MONTH_DAYS / 4.0 + 0.5 => MoonPhase::FirstQuarter,
+        age if age < SYNODIC_MONTH_DAYS / 2.0 - 0.5 => MoonPhase::WaxingGibbous,
+        age if age < SYNODIC_MONTH_DAYS / 2.0 + 0.5 => MoonPhase::Full,
+        age if age < SYNODIC_MONTH_DAYS * 0.75 - 0.5 => MoonPhase::WaningGibbous,
+        age if age < SYNODIC_MONTH_DAYS * 0.75 + 0.5 => MoonPhase::LastQuarter,
+        _ => MoonPhase::WaningCrescent,
+    };
+    Moon {
+        phase,
+        illumination,
+    }
+}
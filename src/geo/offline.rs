@@ -0,0 +1,161 @@
+//! City-level reverse geocoding from a local GeoNames extract, for when no network
+//! geocoder answers.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result};
+use reqwest::Client;
+
+use super::geocoder::{Address, Geocoder, Lookup};
+use super::haversine_km;
+
+/// Points farther than this from any known city are left without a name.
+const MAX_DISTANCE_KM: f64 = 50.0;
+
+struct City {
+    name: String,
+    country: String,
+    country_code: String,
+    latitude: f64,
+    longitude: f64,
+}
+
+/// Finds the nearest city in a GeoNames `cities*.txt` dump, such as `cities15000.txt`.
+///
+/// The file is only read on the first lookup. Country codes are spelled out from a
+/// `countryInfo.txt` next to it, when there is one.
+pub struct OfflineGeocoder {
+    path: PathBuf,
+    cities: OnceLock<Vec<City>>,
+}
+
+impl OfflineGeocoder {
+    /// Uses `file` (`cities_file` in the configuration), or `cities15000.txt` in the data
+    /// directory; `None` when that file does not exist.
+    pub fn from_config(file: Option<&Path>, data_dir: &Path) -> Option<Self> {
+        let path = file.map_or_else(|| data_dir.join("cities15000.txt"), Path::to_path_buf);
+        if !path.is_file() {
+            tracing::debug!(
+                "No offline geocoding data at `{}`; offline fallback is off",
+                path.display()
+            );
+            return None;
+        }
+
+        Some(Self {
+            path,
+            cities: OnceLock::new(),
+        })
+    }
+
+    fn cities(&self) -> &[City] {
+        self.cities.get_or_init(|| match load_cities(&self.path) {
+            Ok(cities) => {
+                tracing::info!("Loaded {} cities for offline geocoding", cities.len());
+                cities
+            }
+            Err(err) => {
+                tracing::warn!("Offline geocoding is unavailable: {err:?}");
+                Vec::new()
+            }
+        })
+    }
+}
+
+impl Geocoder for OfflineGeocoder {
+    fn reverse<'a>(
+        &'a self,
+        _client: &'a Client,
+        latitude: f64,
+        longitude: f64,
+        language: &'a str,
+    ) -> Lookup<'a> {
+        Box::pin(async move {
+            let nearest = self
+                .cities()
+                .iter()
+                .map(|city| {
+                    let distance =
+                        haversine_km((latitude, longitude), (city.latitude, city.longitude));
+                    (distance, city)
+                })
+                .min_by(|(a, _), (b, _)| a.total_cmp(b));
+            let Some((distance, city)) =
+                nearest.filter(|(distance, _)| *distance <= MAX_DISTANCE_KM)
+            else {
+                return Ok(None);
+            };
+            tracing::debug!("Nearest offline city is {} at {distance:.1} km", city.name);
+
+            let locale = crate::locale::locale_from_language_code(Some(language));
+            let display_name = rust_i18n::t!(
+                "geocoder.near",
+                locale = locale,
+                city = &city.name,
+                country = &city.country
+            )
+            .into_owned();
+            Ok(Some(Address {
+                name: None,
+                display_name,
+                city: Some(city.name.clone()),
+                // GeoNames only has codes for regions, which need yet another file to name.
+                region: None,
+                country: Some(city.country.clone()),
+                country_code: Some(city.country_code.clone()).filter(|code| !code.is_empty()),
+            }))
+        })
+    }
+}
+
+fn load_cities(path: &Path) -> Result<Vec<City>> {
+    let data = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read city data at `{}`", path.display()))?;
+    let countries = path
+        .parent()
+        .map(|dir| load_countries(&dir.join("countryInfo.txt")))
+        .unwrap_or_default();
+
+    // Tab separated: id, name, ASCII name, alternate names, latitude, longitude, feature
+    // class, feature code, country code, and more we do not need.
+    let cities = data
+        .lines()
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split('\t').collect();
+            let name = columns.get(1)?.trim();
+            let latitude = columns.get(4)?.parse().ok()?;
+            let longitude = columns.get(5)?.parse().ok()?;
+            let code = columns.get(8)?.trim();
+            if name.is_empty() {
+                return None;
+            }
+            Some(City {
+                name: name.to_string(),
+                country: countries
+                    .get(code)
+                    .cloned()
+                    .unwrap_or_else(|| code.to_string()),
+                country_code: code.to_ascii_uppercase(),
+                latitude,
+                longitude,
+            })
+        })
+        .collect();
+    Ok(cities)
+}
+
+/// Country names by ISO code from GeoNames' `countryInfo.txt`; empty when it is missing.
+fn load_countries(path: &Path) -> HashMap<String, String> {
+    let Ok(data) = std::fs::read_to_string(path) else {
+        return HashMap::new();
+    };
+    data.lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split('\t').collect();
+            Some((columns.first()?.to_string(), columns.get(4)?.to_string()))
+        })
+        .collect()
+}
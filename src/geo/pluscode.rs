@@ -0,0 +1,51 @@
+//! Open Location Codes ("Plus Codes"), which name a spot without a maps link.
+
+const ALPHABET: &[u8; 20] = b"23456789CFGHJMPQRVWX";
+/// Ten digits, five pairs of latitude and longitude, narrow a code down to 1/8000 of a
+/// degree, about 14 m.
+const PAIRS: usize = 5;
+const STEPS_PER_DEGREE: f64 = 8000.0;
+
+/// The ten-digit code of the point, such as `8FVC9G8F+6X`.
+pub fn encode(latitude: f64, longitude: f64) -> String {
+    let latitude = latitude.clamp(-90.0, 90.0);
+    let longitude = (longitude + 180.0).rem_euclid(360.0) - 180.0;
+
+    // Rounding away float noise first keeps points on a cell edge in the right cell, as
+    // the reference implementation does.
+    let steps = |degrees: f64| ((degrees * STEPS_PER_DEGREE * 1e6).round() / 1e6).floor() as i64;
+    let max_latitude = (180.0 * STEPS_PER_DEGREE) as i64 - 1;
+    let mut latitude = steps(latitude + 90.0).min(max_latitude);
+    let mut longitude = steps(longitude + 180.0).rem_euclid((360.0 * STEPS_PER_DEGREE) as i64);
+
+    let mut digits = Vec::with_capacity(PAIRS * 2);
+    for _ in 0..PAIRS {
+        digits.push(ALPHABET[(longitude % 20) as usize]);
+        digits.push(ALPHABET[(latitude % 20) as usize]);
+        latitude /= 20;
+        longitude /= 20;
+    }
+    digits.reverse();
+
+    let code = String::from_utf8(digits).unwrap_or_default();
+    format!("{}+{}", &code[..8], &code[8..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_reference_points() {
+        assert_eq!(encode(20.370_062_5, 2.782_187_5), "7FG49QCJ+2V");
+        assert_eq!(encode(47.000_062_5, 8.000_062_5), "8FVC2222+22");
+        assert_eq!(encode(-41.273_062_5, 174.785_937_5), "4VCPPQGP+Q9");
+    }
+
+    #[test]
+    fn clips_the_poles_and_wraps_the_antimeridian() {
+        assert_eq!(encode(90.0, 1.0), "CFX3X2X2+X2");
+        assert_eq!(encode(1.0, 180.0), "62H22222+22");
+        assert_eq!(encode(1.0, -180.0), "62H22222+22");
+    }
+}
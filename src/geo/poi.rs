@@ -0,0 +1,141 @@
+//! Named landmarks near a point, from OpenStreetMap's Overpass API, for spots a street
+//! address says little about, such as mountaintops and parks.
+
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Context;
+use reqwest::Client;
+use serde_json::Value as JsonValue;
+
+use super::cache::{Cached, GeocodeCache};
+use super::geocoder::{Address, Geocoder, Lookup};
+use super::haversine_km;
+use super::ratelimit::RateLimiter;
+
+const OVERPASS_ENDPOINT: &str = "https://overpass-api.de/api/interpreter";
+/// How far from the point landmarks are searched, in meters.
+const SEARCH_RADIUS_M: u32 = 300;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+/// The public Overpass instances ask for a gentle pace.
+const REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+/// Kinds of features worth naming, as `key`, `value regex` pairs.
+const LANDMARK_TAGS: &[(&str, &str)] = &[
+    ("tourism", "^(viewpoint|attraction|museum|zoo|theme_park)$"),
+    (
+        "natural",
+        "^(peak|volcano|waterfall|beach|glacier|cave_entrance)$",
+    ),
+    ("leisure", "^(park|nature_reserve|garden)$"),
+    ("man_made", "^(tower|lighthouse|bridge)$"),
+    ("historic", "."),
+];
+
+/// Overpass at `url` (`overpass_url` in the configuration), behind an answer cache of its
+/// own in `poi-cache.json` (or `FOTOBOT_POI_CACHE_FILE`); `None` when `url` is `off`. The
+/// landmark is the `name` of the answer.
+pub fn from_config(
+    url: Option<&str>,
+    cache_days: Option<u64>,
+    data_dir: &Path,
+) -> Option<Box<dyn Geocoder>> {
+    let endpoint = url.unwrap_or(OVERPASS_ENDPOINT).to_string();
+    if endpoint.eq_ignore_ascii_case("off") {
+        return None;
+    }
+
+    let inner: Box<dyn Geocoder> = Box::new(Overpass {
+        endpoint,
+        limiter: RateLimiter::shared("overpass", REQUEST_INTERVAL),
+    });
+    let path = crate::config::poi_cache_path_from_env(data_dir);
+    Some(match GeocodeCache::configured(path, cache_days) {
+        Some(cache) => Box::new(Cached { inner, cache }) as Box<dyn Geocoder>,
+        None => inner,
+    })
+}
+
+/// Answers with the nearest landmark's name as both `name` and `display_name`.
+struct Overpass {
+    endpoint: String,
+    limiter: RateLimiter,
+}
+
+impl Geocoder for Overpass {
+    fn reverse<'a>(
+        &'a self,
+        client: &'a Client,
+        latitude: f64,
+        longitude: f64,
+        language: &'a str,
+    ) -> Lookup<'a> {
+        Box::pin(async move {
+            self.limiter.acquire().await;
+            let value: JsonValue = client
+                .post(&self.endpoint)
+                .form(&[("data", query(latitude, longitude))])
+                .timeout(REQUEST_TIMEOUT)
+                .send()
+                .await
+                .context("Overpass request failed")?
+                .error_for_status()
+                .context("Overpass returned an error")?
+                .json()
+                .await
+                .context("Failed to parse Overpass response")?;
+
+            let name_key = format!("name:{}", primary_subtag(language));
+            let nearest = value
+                .get("elements")
+                .and_then(JsonValue::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(|element| {
+                    let position = element.get("center").unwrap_or(element);
+                    let point = (
+                        position.get("lat")?.as_f64()?,
+                        position.get("lon")?.as_f64()?,
+                    );
+                    let tags = element.get("tags")?;
+                    let name = tags
+                        .get(&name_key)
+                        .or_else(|| tags.get("name"))?
+                        .as_str()?
+                        .trim();
+                    (!name.is_empty())
+                        .then(|| (haversine_km((latitude, longitude), point), name.to_string()))
+                })
+                .min_by(|(a, _), (b, _)| a.total_cmp(b));
+
+            Ok(nearest.map(|(_, name)| Address {
+                name: Some(name.clone()),
+                display_name: name,
+                city: None,
+                region: None,
+                country: None,
+                country_code: None,
+            }))
+        })
+    }
+}
+
+/// Everything named of the [`LANDMARK_TAGS`] kinds around the point; ways and relations
+/// come with their center.
+fn query(latitude: f64, longitude: f64) -> String {
+    let around = format!("(around:{SEARCH_RADIUS_M},{latitude:.6},{longitude:.6})");
+    let clauses: String = LANDMARK_TAGS
+        .iter()
+        .map(|(key, pattern)| format!("nwr{around}[\"{key}\"~\"{pattern}\"][\"name\"];"))
+        .collect();
+    format!("[out:json][timeout:10];({clauses});out center tags 50;")
+}
+
+/// `zh` for `zh-hans`, the way OpenStreetMap's `name:*` tags are keyed.
+fn primary_subtag(language: &str) -> String {
+    language
+        .split(['-', '_'])
+        .next()
+        .unwrap_or(language)
+        .trim()
+        .to_ascii_lowercase()
+}
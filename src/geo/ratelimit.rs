@@ -0,0 +1,59 @@
+//! Spaces out requests to a rate-limited service, across every task of the bot, and
+//! across replicas when they coordinate through Redis.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Hands out request slots at a fixed interval. Callers reserve the next free slot and
+/// sleep until it comes, so bursts queue up instead of going out together.
+pub struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+    /// The name the slots are shared under with other replicas, if they are.
+    shared: Option<String>,
+}
+
+impl RateLimiter {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next: Mutex::new(Instant::now()),
+            shared: None,
+        }
+    }
+
+    /// A limiter whose slots every replica takes from, for services whose limit counts
+    /// all of them together.
+    pub fn shared(name: impl Into<String>, interval: Duration) -> Self {
+        Self {
+            shared: Some(format!("limit:{}", name.into())),
+            ..Self::new(interval)
+        }
+    }
+
+    /// Waits until the caller may send its request.
+    pub async fn acquire(&self) {
+        if let Some(name) = &self.shared
+            && let Some(delay) = crate::coordination::reserve(name, self.interval).await
+        {
+            tokio::time::sleep(delay).await;
+            return;
+        }
+        let slot = {
+            let mut next = self.next.lock().unwrap_or_else(|err| err.into_inner());
+            let slot = (*next).max(Instant::now());
+            *next = slot + self.interval;
+            slot
+        };
+        tokio::time::sleep_until(slot.into()).await;
+    }
+
+    /// Holds every request back for `delay`, after the service asked for a pause.
+    pub fn back_off(&self, delay: Duration) {
+        if let Some(name) = self.shared.clone() {
+            tokio::spawn(async move { crate::coordination::back_off(&name, delay).await });
+        }
+        let mut next = self.next.lock().unwrap_or_else(|err| err.into_inner());
+        *next = (*next).max(Instant::now() + delay);
+    }
+}
@@ -0,0 +1,123 @@
+//! Where the sun stood when a photo was taken, by NOAA's solar position formulas.
+
+use chrono::{Duration, NaiveDateTime};
+use serde::Serialize;
+
+/// The sun's upper edge touches the horizon here, refraction included.
+const HORIZON_DEG: f64 = -0.833;
+/// Golden hour runs from 4° below to 6° above the horizon, blue hour from 6° to 4° below.
+const GOLDEN_HOUR_TOP_DEG: f64 = 6.0;
+const BLUE_HOUR_TOP_DEG: f64 = -4.0;
+const BLUE_HOUR_BOTTOM_DEG: f64 = -6.0;
+/// Sunrise and sunset are searched this many minutes either side of the shot.
+const SEARCH_MINUTES: i64 = 12 * 60;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    GoldenHour,
+    BlueHour,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    Sunrise,
+    Sunset,
+}
+
+/// The light at the moment of a shot.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Light {
+    /// Degrees of the sun's center above the horizon.
+    pub elevation: f64,
+    pub phase: Option<Phase>,
+    /// The sunrise or sunset closest to the shot, unless the sun neither rises nor sets
+    /// that day, as in polar summer.
+    pub event: Option<Event>,
+    /// Minutes from that event to the shot, negative when the shot came first.
+    pub minutes_from_event: i64,
+}
+
+impl Light {
+    /// Whether the sun was below the blue hour, the sky dark enough for stars.
+    pub fn is_night(&self) -> bool {
+        self.elevation < BLUE_HOUR_BOTTOM_DEG
+    }
+}
+
+/// The light at `utc` at the point.
+pub fn light_at(utc: NaiveDateTime, latitude: f64, longitude: f64) -> Light {
+    let elevation = solar_elevation(utc, latitude, longitude);
+    let phase = if (BLUE_HOUR_TOP_DEG..=GOLDEN_HOUR_TOP_DEG).contains(&elevation) {
+        Some(Phase::GoldenHour)
+    } else if (BLUE_HOUR_BOTTOM_DEG..BLUE_HOUR_TOP_DEG).contains(&elevation) {
+        Some(Phase::BlueHour)
+    } else {
+        None
+    };
+
+    // A minute-by-minute walk is plenty precise and cheap next to a network lookup.
+    let above: Vec<bool> = (-SEARCH_MINUTES..=SEARCH_MINUTES)
+        .map(|minute| {
+            solar_elevation(utc + Duration::minutes(minute), latitude, longitude) > HORIZON_DEG
+        })
+        .collect();
+    let nearest = above
+        .windows(2)
+        .zip(-SEARCH_MINUTES + 1..)
+        .filter_map(|(pair, minute)| match pair {
+            [false, true] => Some((Event::Sunrise, minute)),
+            [true, false] => Some((Event::Sunset, minute)),
+            _ => None,
+        })
+        .min_by_key(|(_, minute)| minute.abs());
+
+    Light {
+        elevation,
+        phase,
+        event: nearest.map(|(event, _)| event),
+        minutes_from_event: nearest.map_or(0, |(_, minute)| -minute),
+    }
+}
+
+/// Degrees of the sun's center above the horizon, without refraction.
+fn solar_elevation(utc: NaiveDateTime, latitude: f64, longitude: f64) -> f64 {
+    let seconds = utc.and_utc().timestamp() as f64;
+    let julian_day = seconds / 86_400.0 + 2_440_587.5;
+    let t = (julian_day - 2_451_545.0) / 36_525.0;
+
+    let mean_longitude = (280.46646 + t * (36_000.76983 + t * 0.000_303_2)).rem_euclid(360.0);
+    let mean_anomaly = 357.52911 + t * (35_999.05029 - 0.000_153_7 * t);
+    let eccentricity = 0.016_708_634 - t * (0.000_042_037 + 0.000_000_126_7 * t);
+    let m = mean_anomaly.to_radians();
+    let center = m.sin() * (1.914602 - t * (0.004817 + 0.000014 * t))
+        + (2.0 * m).sin() * (0.019993 - 0.000101 * t)
+        + (3.0 * m).sin() * 0.000289;
+
+    let omega = (125.04 - 1934.136 * t).to_radians();
+    let apparent_longitude =
+        (mean_longitude + center - 0.00569 - 0.00478 * omega.sin()).to_radians();
+    let mean_obliquity =
+        23.0 + (26.0 + (21.448 - t * (46.815 + t * (0.00059 - t * 0.001813))) / 60.0) / 60.0;
+    let obliquity = (mean_obliquity + 0.00256 * omega.cos()).to_radians();
+    let declination = (obliquity.sin() * apparent_longitude.sin()).asin();
+
+    let y = (obliquity / 2.0).tan().powi(2);
+    let l0 = mean_longitude.to_radians();
+    let equation_of_time = 4.0
+        * (y * (2.0 * l0).sin() - 2.0 * eccentricity * m.sin()
+            + 4.0 * eccentricity * y * m.sin() * (2.0 * l0).cos()
+            - 0.5 * y * y * (4.0 * l0).sin()
+            - 1.25 * eccentricity * eccentricity * (2.0 * m).sin())
+        .to_degrees();
+
+    let minutes_of_day = seconds.rem_euclid(86_400.0) / 60.0;
+    let true_solar_time = (minutes_of_day + equation_of_time + 4.0 * longitude).rem_euclid(1440.0);
+    let hour_angle = (true_solar_time / 4.0 - 180.0).to_radians();
+
+    let latitude = latitude.to_radians();
+    let cos_zenith =
+        latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos();
+    90.0 - cos_zenith.clamp(-1.0, 1.0).acos().to_degrees()
+}
@@ -0,0 +1,23 @@
+//! Time zones by location, from the boundary data bundled with `tzf-rs`.
+
+use std::sync::LazyLock;
+
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use tzf_rs::DefaultFinder;
+
+/// Loading the boundaries takes a moment, so it happens on the first lookup only.
+static FINDER: LazyLock<DefaultFinder> = LazyLock::new(DefaultFinder::new);
+
+/// The IANA time zone at the point, such as `Asia/Tokyo`. Open sea gets one of the
+/// `Etc/GMT` zones.
+pub fn zone_at(latitude: f64, longitude: f64) -> Option<Tz> {
+    FINDER.get_tz_name(longitude, latitude).parse().ok()
+}
+
+/// The wall-clock time in `zone` at the given UTC time.
+pub fn local_time(utc: NaiveDateTime, zone: Tz) -> NaiveDateTime {
+    Utc.from_utc_datetime(&utc)
+        .with_timezone(&zone)
+        .naive_local()
+}
@@ -0,0 +1,114 @@
+//! Historical weather at a place and time, from OpenWeather's One Call API.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::NaiveDateTime;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_ENDPOINT: &str = "https://api.openweathermap.org/data/3.0/onecall/timemachine";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The conditions at the time of a shot.
+#[derive(Clone, Debug, Serialize)]
+pub struct Weather {
+    /// Degrees Celsius.
+    pub temperature: f64,
+    /// Short description in the requested language, such as "light rain".
+    pub conditions: String,
+}
+
+#[derive(Deserialize)]
+struct TimeMachine {
+    data: Vec<Reading>,
+}
+
+#[derive(Deserialize)]
+struct Reading {
+    temp: f64,
+    #[serde(default)]
+    weather: Vec<Condition>,
+}
+
+#[derive(Deserialize)]
+struct Condition {
+    description: String,
+}
+
+/// The One Call API at `endpoint`, with the key it is called with.
+pub struct OpenWeather {
+    key: String,
+    endpoint: String,
+}
+
+impl OpenWeather {
+    /// `None` without a key (`weather_key` in the configuration): no weather is looked up
+    /// then. `url` points elsewhere than OpenWeather's own endpoint.
+    pub fn from_config(key: Option<&str>, url: Option<&str>) -> Option<Self> {
+        Some(Self {
+            key: key?.to_string(),
+            endpoint: url.unwrap_or(DEFAULT_ENDPOINT).to_string(),
+        })
+    }
+
+    /// The weather at the point at `utc`, described in `language`. Failures are logged,
+    /// since the weather is extra.
+    pub async fn at(
+        &self,
+        client: &Client,
+        latitude: f64,
+        longitude: f64,
+        utc: NaiveDateTime,
+        language: &str,
+    ) -> Option<Weather> {
+        match self.fetch(client, latitude, longitude, utc, language).await {
+            Ok(weather) => weather,
+            Err(err) => {
+                tracing::warn!(
+                    "Weather lookup failed for coordinates ({latitude:.6}, {longitude:.6}): {err:?}"
+                );
+                None
+            }
+        }
+    }
+
+    async fn fetch(
+        &self,
+        client: &Client,
+        latitude: f64,
+        longitude: f64,
+        utc: NaiveDateTime,
+        language: &str,
+    ) -> Result<Option<Weather>> {
+        let answer: TimeMachine = client
+            .get(&self.endpoint)
+            .timeout(REQUEST_TIMEOUT)
+            .query(&[
+                ("lat", format!("{latitude:.6}")),
+                ("lon", format!("{longitude:.6}")),
+                ("dt", utc.and_utc().timestamp().to_string()),
+                ("units", String::from("metric")),
+                ("lang", language.replace('-', "_")),
+                ("appid", self.key.clone()),
+            ])
+            .send()
+            .await
+            .context("Weather request failed")?
+            .error_for_status()
+            .context("Weather service returned an error")?
+            .json()
+            .await
+            .context("Failed to parse weather response")?;
+
+        Ok(answer.data.into_iter().next().map(|reading| Weather {
+            temperature: reading.temp,
+            conditions: reading
+                .weather
+                .into_iter()
+                .map(|condition| condition.description)
+                .next()
+                .unwrap_or_default(),
+        }))
+    }
+}
@@ -0,0 +1,74 @@
+//! what3words addresses, through their API.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use reqwest::Client;
+use serde::Deserialize;
+
+const ENDPOINT: &str = "https://api.what3words.com/v3/convert-to-3wa";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Deserialize)]
+struct Conversion {
+    words: String,
+}
+
+/// The what3words API, with the key it is called with.
+pub struct What3Words {
+    key: String,
+}
+
+impl What3Words {
+    /// `None` without a key (`what3words_key` in the configuration): no words are looked
+    /// up then.
+    pub fn from_config(key: Option<&str>) -> Option<Self> {
+        key.map(|key| Self {
+            key: key.to_string(),
+        })
+    }
+
+    /// The three words of the 3 m square at the point, like `filled.count.soap`, in
+    /// `language` where what3words has it. Failures are logged, since the words are extra.
+    pub async fn words(
+        &self,
+        client: &Client,
+        latitude: f64,
+        longitude: f64,
+        language: &str,
+    ) -> Option<String> {
+        match convert(client, &self.key, latitude, longitude, language).await {
+            Ok(words) => Some(words),
+            Err(err) => {
+                tracing::warn!("what3words lookup failed: {err:?}");
+                None
+            }
+        }
+    }
+}
+
+async fn convert(
+    client: &Client,
+    key: &str,
+    latitude: f64,
+    longitude: f64,
+    language: &str,
+) -> Result<String> {
+    let conversion: Conversion = client
+        .get(ENDPOINT)
+        .timeout(REQUEST_TIMEOUT)
+        .query(&[
+            ("coordinates", format!("{latitude:.6},{longitude:.6}")),
+            ("language", language.to_string()),
+            ("key", key.to_string()),
+        ])
+        .send()
+        .await
+        .context("what3words request failed")?
+        .error_for_status()
+        .context("what3words returned an error")?
+        .json()
+        .await
+        .context("Failed to parse what3words response")?;
+    Ok(conversion.words)
+}
@@ -0,0 +1,100 @@
+use crate::exif::{self, ShotFacts};
+
+/// Instagram ignores every hashtag past the 30th.
+const INSTAGRAM_MAX_TAGS: usize = 30;
+
+/// Where the hashtags are pasted; each platform links a different set of characters.
+#[derive(Clone, Copy)]
+pub enum Platform {
+    /// Letters and digits of any script, plus underscores.
+    Telegram,
+    /// Only ASCII letters, digits and underscores link reliably.
+    Instagram,
+}
+
+impl Platform {
+    const ALL: [Platform; 2] = [Platform::Telegram, Platform::Instagram];
+
+    fn name(self) -> &'static str {
+        match self {
+            Platform::Telegram => "Telegram",
+            Platform::Instagram => "Instagram",
+        }
+    }
+
+    fn keeps(self, ch: char) -> bool {
+        match self {
+            Platform::Telegram => ch.is_alphanumeric() || ch == '_',
+            Platform::Instagram => ch.is_ascii_alphanumeric() || ch == '_',
+        }
+    }
+
+    /// Lowercases `value` and drops everything the platform would not link, so
+    /// "Sony ILCE-7M4" becomes `sonyilce7m4`.
+    fn slugify(self, value: &str) -> Option<String> {
+        let slug: String = value
+            .chars()
+            .flat_map(char::to_lowercase)
+            .filter(|ch| self.keeps(*ch))
+            .collect();
+        // A tag of digits only is not linked as a hashtag.
+        if slug.chars().all(|ch| ch.is_ascii_digit() || ch == '_') {
+            None
+        } else {
+            Some(slug)
+        }
+    }
+
+    fn hashtags(self, facts: &ShotFacts, city: Option<&str>) -> Vec<String> {
+        let mut sources: Vec<String> = Vec::new();
+        if facts.camera != exif::UNKNOWN_CAMERA {
+            sources.push(facts.camera.clone());
+        }
+        if facts.lens != exif::UNKNOWN_LENS {
+            sources.push(facts.lens.clone());
+        }
+        if let Some(focal) = facts.focal_length_mm.filter(|value| *value > 0.0) {
+            sources.push(format!("{focal:.0}mm"));
+        }
+        if let Some(aperture) = facts.aperture.as_deref() {
+            // `f/1.8` → `f1p8`: the dot would end the hashtag.
+            sources.push(aperture.replace('/', "").replace('.', "p"));
+        }
+        if let Some(city) = city {
+            sources.push(city.to_string());
+        }
+
+        let mut tags: Vec<String> = Vec::new();
+        for tag in sources.iter().filter_map(|source| self.slugify(source)) {
+            let tag = format!("#{tag}");
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        if let Platform::Instagram = self {
+            tags.truncate(INSTAGRAM_MAX_TAGS);
+        }
+        tags
+    }
+}
+
+/// One line of hashtags per platform, ready to copy; `None` when nothing is known.
+pub fn format_hashtags(facts: &ShotFacts, city: Option<&str>) -> Option<String> {
+    let blocks: Vec<String> = Platform::ALL
+        .into_iter()
+        .filter_map(|platform| {
+            let tags = platform.hashtags(facts, city);
+            if tags.is_empty() {
+                None
+            } else {
+                Some(format!("{}:\n{}", platform.name(), tags.join(" ")))
+            }
+        })
+        .collect();
+
+    if blocks.is_empty() {
+        None
+    } else {
+        Some(blocks.join("\n\n"))
+    }
+}
@@ -0,0 +1,124 @@
+//! `/healthz` and `/readyz` for orchestrators, served next to `/metrics`.
+//!
+//! `/healthz` fails when restarting would help: a secondary client lost its session or
+//! the cache directory cannot be written. `/readyz` also asks Telegram whether every bot
+//! token still works, which a network outage fails without the process being at fault.
+
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow};
+use axum::Router;
+use axum::http::StatusCode;
+use axum::routing::get;
+use grammers_client::Client as GramClient;
+use teloxide::prelude::*;
+
+/// How long one check may take before it counts as failed.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What the checks need: the Telegram clients of every bot and the directory large files
+/// go to.
+pub struct Health {
+    pub bots: Vec<Bot>,
+    /// Empty when the bots run without secondary clients.
+    pub extra_clients: Vec<GramClient>,
+    pub cache_dir: PathBuf,
+}
+
+impl Health {
+    /// The `/healthz` and `/readyz` routes.
+    pub fn routes(self) -> Router {
+        let health = Arc::new(self);
+        let live = Arc::clone(&health);
+        Router::new()
+            .route(
+                "/healthz",
+                get(move || {
+                    let health = Arc::clone(&live);
+                    async move { health.respond(false).await }
+                }),
+            )
+            .route(
+                "/readyz",
+                get(move || {
+                    let health = Arc::clone(&health);
+                    async move { health.respond(true).await }
+                }),
+            )
+    }
+
+    /// One `name: ok` or `name: error` line per check, with `503` when any failed.
+    async fn respond(&self, ready: bool) -> (StatusCode, String) {
+        let mut checks = vec![
+            ("session", timed(self.session()).await),
+            ("cache", timed(self.cache()).await),
+        ];
+        if ready {
+            checks.insert(0, ("token", timed(self.token()).await));
+        }
+
+        let mut body = String::new();
+        let mut healthy = true;
+        for (name, outcome) in checks {
+            match outcome {
+                Ok(()) => writeln!(body, "{name}: ok").ok(),
+                Err(err) => {
+                    healthy = false;
+                    tracing::warn!("Health check `{name}` failed: {err:?}");
+                    writeln!(body, "{name}: {err:#}").ok()
+                }
+            };
+        }
+        let status = if healthy {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        };
+        (status, body)
+    }
+
+    /// Every bot token is accepted by `getMe`.
+    async fn token(&self) -> Result<()> {
+        for bot in &self.bots {
+            bot.get_me().await.context("getMe failed")?;
+        }
+        Ok(())
+    }
+
+    /// The secondary clients are still signed in.
+    async fn session(&self) -> Result<()> {
+        for extra_client in &self.extra_clients {
+            let authorized = extra_client
+                .is_authorized()
+                .await
+                .context("Failed to ask for the authorization state")?;
+            if !authorized {
+                return Err(anyhow!("a secondary client is not signed in"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Large files can still be written to the cache directory.
+    async fn cache(&self) -> Result<()> {
+        let probe = self.cache_dir.join(".healthz");
+        tokio::fs::create_dir_all(&self.cache_dir)
+            .await
+            .with_context(|| format!("Failed to create `{}`", self.cache_dir.display()))?;
+        tokio::fs::write(&probe, b"ok")
+            .await
+            .with_context(|| format!("Failed to write `{}`", probe.display()))?;
+        tokio::fs::remove_file(&probe)
+            .await
+            .with_context(|| format!("Failed to remove `{}`", probe.display()))
+    }
+}
+
+async fn timed(check: impl Future<Output = Result<()>>) -> Result<()> {
+    tokio::time::timeout(CHECK_TIMEOUT, check)
+        .await
+        .map_err(|_| anyhow!("timed out after {}s", CHECK_TIMEOUT.as_secs()))?
+}
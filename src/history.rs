@@ -0,0 +1,138 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use teloxide::types::UserId;
+
+use crate::formats::{self, Clock};
+use crate::styles;
+
+/// How many analyses `/history` keeps for every user.
+const MAX_HISTORY_PER_USER: usize = 20;
+
+/// One image the bot analyzed for a user.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// When the analysis was requested, as `YYYY-MM-DD HH:MM UTC`.
+    pub date: String,
+    pub camera: String,
+    /// Capture time from the EXIF data.
+    pub taken: Option<String>,
+    /// Link back to the original message or image, when Telegram offers one.
+    pub link: Option<String>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct HistoryData {
+    users: HashMap<u64, VecDeque<HistoryEntry>>,
+}
+
+/// The latest analyses per user, persisted as a JSON file next to the settings.
+pub struct HistoryStore {
+    path: PathBuf,
+    data: Mutex<HistoryData>,
+}
+
+impl HistoryStore {
+    /// Loads the history file, starting empty when it does not exist yet.
+    pub fn open(path: PathBuf) -> Result<Self> {
+        let data = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("Failed to parse history file at `{}`", path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => HistoryData::default(),
+            Err(err) => {
+                return Err(err).with_context(|| {
+                    format!("Failed to read history file at `{}`", path.display())
+                });
+            }
+        };
+
+        Ok(Self {
+            path,
+            data: Mutex::new(data),
+        })
+    }
+
+    /// Returns the user's analyses, newest first.
+    pub fn list(&self, user_id: UserId) -> Vec<HistoryEntry> {
+        let data = self.data.lock().unwrap_or_else(|err| err.into_inner());
+        data.users
+            .get(&user_id.0)
+            .map(|entries| entries.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Adds an analysis, dropping the oldest one past the limit, and writes the file back.
+    pub fn record(&self, user_id: UserId, entry: HistoryEntry) -> Result<()> {
+        let mut data = self.data.lock().unwrap_or_else(|err| err.into_inner());
+        let entries = data.users.entry(user_id.0).or_default();
+        entries.push_front(entry);
+        entries.truncate(MAX_HISTORY_PER_USER);
+        self.persist(&data)
+    }
+
+    /// Drops the user's analyses and writes the file back to disk.
+    pub fn forget(&self, user_id: UserId) -> Result<()> {
+        let mut data = self.data.lock().unwrap_or_else(|err| err.into_inner());
+        if data.users.remove(&user_id.0).is_none() {
+            return Ok(());
+        }
+        self.persist(&data)
+    }
+
+    fn persist(&self, data: &HistoryData) -> Result<()> {
+        let bytes = serde_json::to_vec(data).context("Failed to serialize history")?;
+
+        // Write to a sibling file first so a crash never leaves a half-written history file.
+        let tmp_path = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, bytes)
+            .with_context(|| format!("Failed to write history file at `{}`", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, &self.path).with_context(|| {
+            format!(
+                "Failed to replace history file at `{}`",
+                self.path.display()
+            )
+        })
+    }
+}
+
+/// The `/history` answer: one numbered line per analysis, newest first.
+pub fn format_history(entries: &[HistoryEntry], locale: &str, clock: Clock) -> String {
+    if entries.is_empty() {
+        return rust_i18n::t!("history.empty", locale = locale).into_owned();
+    }
+
+    let mut output = String::new();
+    writeln!(
+        output,
+        "🗂️ {}",
+        rust_i18n::t!("history.title", locale = locale)
+    )
+    .ok();
+
+    for (index, entry) in entries.iter().enumerate() {
+        let camera = styles::camera_name(&entry.camera, locale);
+        write!(output, "\n{}. {} · {camera}", index + 1, entry.date).ok();
+        if let Some(taken) = &entry.taken {
+            write!(
+                output,
+                " ({})",
+                rust_i18n::t!(
+                    "history.taken",
+                    locale = locale,
+                    date = formats::datetime(taken, locale, clock)
+                )
+            )
+            .ok();
+        }
+        if let Some(link) = &entry.link {
+            write!(output, "\n   🔗 {link}").ok();
+        }
+    }
+
+    output
+}
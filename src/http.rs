@@ -0,0 +1,42 @@
+//! The HTTP client shared by every request outside the Bot API: the geocoders and other
+//! lookups, map images, Telegraph and image links. Sharing it pools their connections
+//! and keeps them alive between requests.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use reqwest::{Client, ClientBuilder, Url};
+
+pub const USER_AGENT: &str = concat!(
+    "fotobot_rs/",
+    env!("CARGO_PKG_VERSION"),
+    " (https://github.com/woolen-sheep/fotobot)"
+);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Idle connections are closed after this long.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The client to share, going through `proxy` when one is configured; requests set their
+/// own overall timeout. Clones share the connection pool.
+pub fn client(proxy: Option<&Url>) -> Client {
+    build(proxy).unwrap_or_else(|err| {
+        tracing::warn!("Falling back to a default HTTP client: {err:?}");
+        Client::new()
+    })
+}
+
+fn build(proxy: Option<&Url>) -> Result<Client> {
+    builder(proxy)
+        .build()
+        .context("Failed to build HTTP client")
+}
+
+/// The settings every client outside the Bot API starts from.
+pub fn builder(proxy: Option<&Url>) -> ClientBuilder {
+    crate::config::with_proxy(Client::builder(), proxy)
+        .user_agent(USER_AGENT)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .pool_idle_timeout(IDLE_TIMEOUT)
+        .tcp_keepalive(KEEPALIVE_INTERVAL)
+}
@@ -0,0 +1,157 @@
+//! Keeps the cache directory that large files are downloaded to from filling the disk.
+//! Files are removed once read; a background task removes what a crash or failed
+//! download left behind, files older than `cache_ttl_minutes` and the oldest files beyond
+//! `cache_max_mb`.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use tokio::fs;
+
+use crate::config::Config;
+
+/// Downloads are named `tmp-…`; nothing else in the directory is touched.
+const DOWNLOAD_PREFIX: &str = "tmp-";
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+const DEFAULT_MAX_BYTES: u64 = 1024 * 1024 * 1024;
+/// Files this young may still be read, so the size cap leaves them alone.
+const IN_USE_GRACE: Duration = Duration::from_secs(2 * 60);
+
+pub struct Janitor {
+    dir: PathBuf,
+    ttl: Duration,
+    max_bytes: u64,
+}
+
+struct Download {
+    path: PathBuf,
+    size: u64,
+    age: Duration,
+}
+
+impl Janitor {
+    /// Cleans `cache_dir` within `cache_ttl_minutes` and `cache_max_mb`.
+    pub fn from_config(config: &Config) -> Self {
+        let ttl = config.cache_ttl_minutes.map_or(DEFAULT_TTL, |minutes| {
+            Duration::from_secs(minutes.saturating_mul(60))
+        });
+        let max_bytes = config
+            .cache_max_mb
+            .map_or(DEFAULT_MAX_BYTES, |mb| mb.saturating_mul(1024 * 1024));
+        Self {
+            dir: config.cache_dir.clone(),
+            ttl,
+            max_bytes,
+        }
+    }
+
+    /// Removes every download left from an earlier run, before any new one starts, then
+    /// sweeps the directory every [`SWEEP_INTERVAL`] in the background.
+    pub async fn start(self) {
+        match self.sweep(true).await {
+            Ok(0) => {}
+            Ok(removed) => tracing::info!("Removed {removed} orphaned downloads from the cache"),
+            Err(err) => tracing::warn!("Failed to clean the cache directory: {err:?}"),
+        }
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                match self.sweep(false).await {
+                    Ok(0) => {}
+                    Ok(removed) => {
+                        tracing::info!("Removed {removed} stale downloads from the cache")
+                    }
+                    Err(err) => tracing::warn!("Failed to sweep the cache directory: {err:?}"),
+                }
+            }
+        });
+    }
+
+    /// Removes expired downloads, or all of them with `everything`, then the oldest ones
+    /// until the rest fit under the cap. Returns how many were removed.
+    async fn sweep(&self, everything: bool) -> Result<usize> {
+        let mut removed = 0;
+        let mut kept = Vec::new();
+        for download in self.downloads().await? {
+            if everything || download.age >= self.ttl {
+                remove(&download.path).await;
+                removed += 1;
+            } else {
+                kept.push(download);
+            }
+        }
+
+        // Oldest first.
+        kept.sort_by(|a, b| b.age.cmp(&a.age));
+        let mut total: u64 = kept.iter().map(|download| download.size).sum();
+        for download in kept {
+            if total <= self.max_bytes {
+                break;
+            }
+            if download.age < IN_USE_GRACE {
+                continue;
+            }
+            remove(&download.path).await;
+            total -= download.size;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    async fn downloads(&self) -> Result<Vec<Download>> {
+        let mut entries = match fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Failed to list `{}`", self.dir.display()));
+            }
+        };
+        let now = SystemTime::now();
+        let mut downloads = Vec::new();
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .with_context(|| format!("Failed to list `{}`", self.dir.display()))?
+        {
+            if !entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with(DOWNLOAD_PREFIX)
+            {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata().await else {
+                continue;
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+            let age = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .unwrap_or_default();
+            downloads.push(Download {
+                path: entry.path(),
+                size: metadata.len(),
+                age,
+            });
+        }
+        Ok(downloads)
+    }
+}
+
+/// Removes a download once it has been read. Failing to is only logged, since the
+/// janitor gets to it later.
+pub async fn remove(path: &Path) {
+    if let Err(err) = fs::remove_file(path).await
+        && err.kind() != std::io::ErrorKind::NotFound
+    {
+        tracing::warn!("Failed to remove `{}`: {err:?}", path.display());
+    }
+}
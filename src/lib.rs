@@ -0,0 +1,69 @@
+//! Reads the EXIF data of photos and turns it into captions, with the Telegram bot
+//! built on top.
+//!
+//! [`exif::ParsedExif`] holds what a photo's metadata says, [`styles::CaptionRenderer`]
+//! writes it out as a caption and [`geo::geocoder::Geocoder`] names the place it was
+//! taken. [`bot::run`] starts the bot itself, and [`cli::run`] the `fotobot exif`
+//! and `fotobot scan` commands.
+
+mod actions;
+mod album;
+mod batch;
+pub mod bot;
+mod broadcast;
+mod canvas;
+mod card;
+pub mod cli;
+mod commands;
+mod compare;
+mod config;
+mod coordination;
+#[cfg(feature = "sentry")]
+mod crash_reports;
+mod crypto;
+mod error_report;
+pub mod exif;
+mod export;
+mod formats;
+mod frame;
+#[cfg(fuzzing)]
+#[doc(hidden)]
+pub mod fuzzing;
+pub mod geo;
+mod hashtags;
+mod health;
+mod history;
+mod http;
+mod janitor;
+mod locale;
+mod markup;
+mod metrics;
+mod moderation;
+#[cfg(feature = "ocr")]
+mod ocr;
+mod premium;
+mod provenance;
+mod qr;
+mod queue;
+mod quota;
+mod ranged;
+mod recent;
+mod remote;
+mod replies;
+mod resend_help;
+mod retry;
+mod scan;
+mod settings;
+mod shutdown;
+mod state;
+mod staticmap;
+mod stats;
+mod storage;
+mod strip;
+pub mod styles;
+mod telegraph;
+mod telemetry;
+mod translations;
+mod watermark;
+
+rust_i18n::i18n!("locales", backend = crate::translations::LiveTranslations);
@@ -0,0 +1,72 @@
+//! Which of the bundled translations to answer in.
+
+/// The translation to answer in for a Telegram language code, falling back to the
+/// configured default, which startup sets as `rust_i18n`'s locale.
+pub fn locale_from_language_code(language_code: Option<&str>) -> &'static str {
+    language_code
+        .and_then(supported_locale)
+        .or_else(|| supported_locale(&rust_i18n::locale()))
+        .unwrap_or("en")
+}
+
+/// The translation for a Telegram language code, if there is one.
+pub fn supported_locale(code: &str) -> Option<&'static str> {
+    let code = code.trim();
+    if code.is_empty() {
+        return None;
+    }
+
+    let normalized = code.replace('_', "-").to_ascii_lowercase();
+    // Chinese goes by script rather than region: `zh-SG` reads simplified characters,
+    // `zh-HK` traditional ones.
+    let wanted = if is_traditional_chinese_code(&normalized) {
+        String::from("zh-tw")
+    } else if is_simplified_chinese_code(&normalized) {
+        String::from("zh-cn")
+    } else {
+        normalized
+    };
+
+    // The code itself, then without its last subtag each time, so `pt-BR` gets `pt`.
+    let available = rust_i18n::available_locales!();
+    let mut candidate = wanted.as_str();
+    loop {
+        if let Some(locale) = available
+            .iter()
+            .find(|locale| locale.eq_ignore_ascii_case(candidate))
+        {
+            return Some(*locale);
+        }
+        candidate = candidate.rsplit_once('-')?.0;
+    }
+}
+
+/// Whether `code` is `language`, with or without a region or script subtag.
+fn has_language(code: &str, language: &str) -> bool {
+    code.strip_prefix(language)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('-'))
+}
+
+/// The language to ask geocoders for place names in. Traditional Chinese codes become
+/// `zh-TW`, or `zh-HK` for Hong Kong and Macau, which the providers know, where
+/// `zh-hant` would get simplified names or none in Chinese at all.
+pub fn geocoder_language(code: &str) -> String {
+    let code = code.trim().replace('_', "-");
+    let normalized = code.to_ascii_lowercase();
+    if !is_traditional_chinese_code(&normalized) {
+        code
+    } else if normalized.ends_with("-hk") || normalized.ends_with("-mo") {
+        String::from("zh-HK")
+    } else {
+        String::from("zh-TW")
+    }
+}
+
+fn is_simplified_chinese_code(code: &str) -> bool {
+    matches!(code, "zh") || code.starts_with("zh-")
+}
+
+/// Taiwan, Hong Kong and Macau, or the script subtag, as in `zh-hant-hk`.
+fn is_traditional_chinese_code(code: &str) -> bool {
+    matches!(code, "zh-tw" | "zh-hk" | "zh-mo") || has_language(code, "zh-hant")
+}
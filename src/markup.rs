@@ -0,0 +1,179 @@
+/// Escapes text for Telegram's HTML parse mode.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+pub fn bold(text: &str) -> String {
+    format!("<b>{}</b>", escape(text))
+}
+
+pub fn code(text: &str) -> String {
+    format!("<code>{}</code>", escape(text))
+}
+
+pub fn link(text: &str, url: &str) -> String {
+    format!("<a href=\"{}\">{}</a>", escape(url), escape(text))
+}
+
+/// Hides already formatted `html` behind a spoiler until it is tapped.
+pub fn spoiler(html: &str) -> String {
+    format!("<tg-spoiler>{html}</tg-spoiler>")
+}
+
+/// The visible text of a caption: tags dropped, entities decoded.
+pub fn to_plain(html: &str) -> String {
+    let mut plain = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for ch in html.chars() {
+        match ch {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            ch if !in_tag => plain.push(ch),
+            _ => {}
+        }
+    }
+    plain
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+/// Cuts a caption to at most `limit` bytes without splitting a tag or an entity.
+pub fn truncate(html: &str, limit: usize) -> String {
+    if html.len() <= limit {
+        return html.to_string();
+    }
+
+    // Every line is rendered with balanced tags, so a line break is always a safe cut.
+    let head = &html[..floor_char_boundary(html, limit)];
+    if let Some(cut) = head.rfind('\n') {
+        return html[..cut].to_string();
+    }
+
+    // A single overlong line: keep its text and drop the markup.
+    let escaped = escape(&to_plain(html));
+    escaped[..entity_boundary(&escaped, limit)].to_string()
+}
+
+/// The last index at most `limit` that splits neither a character nor an entity of
+/// `escaped`, but always past its first one so cutting goes on.
+fn entity_boundary(escaped: &str, limit: usize) -> usize {
+    let mut cut = floor_char_boundary(escaped, limit);
+    if let Some(amp) = escaped[..cut].rfind('&') {
+        if !escaped[amp..cut].contains(';') {
+            cut = amp;
+        }
+    }
+    if cut == 0 {
+        let first = if escaped.starts_with('&') {
+            escaped.find(';').map(|end| end + 1)
+        } else {
+            escaped.chars().next().map(char::len_utf8)
+        };
+        cut = first.unwrap_or(escaped.len());
+    }
+    cut
+}
+
+/// Splits a report into chunks of at most `first_limit` bytes for the first one and
+/// `limit` for the rest, breaking between lines. A line too long for a chunk of its own
+/// loses its markup and goes on in the next chunks.
+pub fn split(html: &str, first_limit: usize, limit: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    for line in html.split('\n') {
+        if current.is_empty() && line.trim().is_empty() {
+            // Blank lines at the start of a chunk would only add padding.
+            continue;
+        }
+
+        let chunk_limit = if chunks.is_empty() {
+            first_limit
+        } else {
+            limit
+        };
+        if !current.is_empty() && current.len() + 1 + line.len() > chunk_limit {
+            chunks.push(std::mem::take(&mut current));
+        }
+
+        let chunk_limit = if chunks.is_empty() {
+            first_limit
+        } else {
+            limit
+        };
+        if current.is_empty() && line.len() > chunk_limit {
+            let mut rest = escape(&to_plain(line));
+            loop {
+                let chunk_limit = if chunks.is_empty() {
+                    first_limit
+                } else {
+                    limit
+                };
+                if rest.len() <= chunk_limit {
+                    current = rest;
+                    break;
+                }
+                let cut = entity_boundary(&rest, chunk_limit);
+                let tail = rest.split_off(cut);
+                chunks.push(rest);
+                rest = tail;
+            }
+        } else if current.is_empty() {
+            current = line.to_string();
+        } else {
+            current.push('\n');
+            current.push_str(line);
+        }
+    }
+
+    while current.ends_with('\n') {
+        current.pop();
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    // Emoji labels and localized text are multi-byte.
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlong_line_continues_in_the_next_chunks() {
+        let line = format!("<b>{}</b>", "a&amp;b ".repeat(40));
+        let chunks = split(&format!("head\n{line}\ntail"), 50, 30);
+        assert!(chunks[0].len() <= 50, "{chunks:?}");
+        assert!(
+            chunks[1..].iter().all(|chunk| chunk.len() <= 30),
+            "{chunks:?}"
+        );
+        // Nothing is dropped, and no entity is cut in half.
+        let text: String = chunks.iter().map(|chunk| to_plain(chunk)).collect();
+        assert_eq!(
+            text.replace('\n', ""),
+            format!("head{}tail", "a&b ".repeat(40))
+        );
+    }
+}
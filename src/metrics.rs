@@ -0,0 +1,172 @@
+//! Per-day usage counters behind the admins' `/adminstats` report.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use chrono::DateTime;
+
+use crate::storage::Storage;
+
+/// How often counts kept in memory are added to the database.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+/// Days `/adminstats` lists, today included.
+const REPORT_DAYS: u64 = 7;
+
+/// Counts not yet written to the database; recorded from anywhere without a handle.
+static PENDING: LazyLock<Mutex<HashMap<Metric, u64>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Metric {
+    /// An image that got a caption.
+    Processed,
+    /// An image that could not be read or answered.
+    Failed,
+    /// An image too large for the Bot API, downloaded through the user client.
+    LargeFile,
+    /// A request sent to a network geocoder.
+    GeocoderCall,
+}
+
+impl Metric {
+    const ALL: [Self; 4] = [
+        Self::Processed,
+        Self::Failed,
+        Self::LargeFile,
+        Self::GeocoderCall,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Processed => "processed",
+            Self::Failed => "failed",
+            Self::LargeFile => "large_file",
+            Self::GeocoderCall => "geocoder_call",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|metric| metric.as_str() == value)
+    }
+}
+
+/// Counts one `metric` for today.
+pub fn record(metric: Metric) {
+    let mut pending = PENDING.lock().unwrap_or_else(|err| err.into_inner());
+    *pending.entry(metric).or_default() += 1;
+}
+
+/// Writes the recorded counts to the database every [`FLUSH_INTERVAL`].
+pub fn spawn_flusher(storage: Storage) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = flush(&storage).await {
+                tracing::warn!("Failed to store usage metrics: {err:?}");
+            }
+        }
+    });
+}
+
+/// Adds the pending counts to today's row. Counts that fail to store are put back.
+pub async fn flush(storage: &Storage) -> Result<()> {
+    let counts: Vec<(Metric, u64)> = {
+        let mut pending = PENDING.lock().unwrap_or_else(|err| err.into_inner());
+        pending.drain().collect()
+    };
+    if counts.is_empty() {
+        return Ok(());
+    }
+
+    let rows = counts
+        .iter()
+        .map(|(metric, count)| (metric.as_str(), *count))
+        .collect();
+    if let Err(err) = storage.add_metrics(today(), rows).await {
+        let mut pending = PENDING.lock().unwrap_or_else(|err| err.into_inner());
+        for (metric, count) in counts {
+            *pending.entry(metric).or_default() += count;
+        }
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// The `/adminstats` text: one line per day with anything recorded, newest first, and
+/// the totals.
+pub async fn report(storage: &Storage, locale: &str) -> Result<String> {
+    flush(storage).await?;
+    let since = day_string(now_secs() - (REPORT_DAYS - 1) * 24 * 60 * 60);
+    let rows = storage.metrics_since(since).await?;
+
+    let mut days: BTreeMap<String, HashMap<Metric, u64>> = BTreeMap::new();
+    for (day, metric, count) in rows {
+        if let Some(metric) = Metric::parse(&metric) {
+            *days.entry(day).or_default().entry(metric).or_default() += count;
+        }
+    }
+    if days.is_empty() {
+        return Ok(rust_i18n::t!("adminstats.empty", locale = locale).into_owned());
+    }
+
+    let mut totals: HashMap<Metric, u64> = HashMap::new();
+    let mut output = String::new();
+    writeln!(
+        output,
+        "📈 {}",
+        rust_i18n::t!("adminstats.title", locale = locale, days = REPORT_DAYS)
+    )
+    .ok();
+    for (day, counts) in days.iter().rev() {
+        writeln!(output, "{day}: {}", describe(counts, locale)).ok();
+        for (metric, count) in counts {
+            *totals.entry(*metric).or_default() += count;
+        }
+    }
+    writeln!(
+        output,
+        "\n{}: {}",
+        rust_i18n::t!("adminstats.total", locale = locale),
+        describe(&totals, locale)
+    )
+    .ok();
+    Ok(output)
+}
+
+fn describe(counts: &HashMap<Metric, u64>, locale: &str) -> String {
+    let count = |metric| counts.get(&metric).copied().unwrap_or_default();
+    rust_i18n::t!(
+        "adminstats.day",
+        locale = locale,
+        processed = count(Metric::Processed),
+        failed = count(Metric::Failed),
+        large = count(Metric::LargeFile),
+        geocoder = count(Metric::GeocoderCall)
+    )
+    .into_owned()
+}
+
+/// Today in UTC, as `YYYY-MM-DD`.
+fn today() -> String {
+    day_string(now_secs())
+}
+
+fn day_string(secs: u64) -> String {
+    let Some(time) = DateTime::from_timestamp(secs as i64, 0) else {
+        return String::new();
+    };
+    time.date_naive().format("%Y-%m-%d").to_string()
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -0,0 +1,122 @@
+//! Who may use the bot: operators can ban users, or restrict the bot to an allowlist.
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use teloxide::types::UserId;
+
+use crate::storage::Storage;
+
+/// Which list a user is on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Allowed,
+    Blocked,
+}
+
+impl Access {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Allowed => "allowed",
+            Self::Blocked => "blocked",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "allowed" => Some(Self::Allowed),
+            "blocked" => Some(Self::Blocked),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Lists {
+    allowlist_only: bool,
+    allowed: HashSet<u64>,
+    blocked: HashSet<u64>,
+}
+
+/// The allowlist and blocklist from the database, with a copy in memory so every update
+/// can be checked before any work starts.
+pub struct Moderation {
+    storage: Storage,
+    lists: Mutex<Lists>,
+}
+
+impl Moderation {
+    pub fn open(storage: Storage) -> Result<Self> {
+        let mut lists = Lists {
+            allowlist_only: storage.allowlist_only()?,
+            ..Lists::default()
+        };
+        for (user_id, access) in storage.load_access()? {
+            match access {
+                Access::Allowed => lists.allowed.insert(user_id),
+                Access::Blocked => lists.blocked.insert(user_id),
+            };
+        }
+
+        Ok(Self {
+            storage,
+            lists: Mutex::new(lists),
+        })
+    }
+
+    /// Whether the bot answers `user_id`: never when blocked, and only when allowed while
+    /// the allowlist is on.
+    pub fn permits(&self, user_id: UserId) -> bool {
+        let lists = self.lists.lock().unwrap_or_else(|err| err.into_inner());
+        if lists.blocked.contains(&user_id.0) {
+            return false;
+        }
+        !lists.allowlist_only || lists.allowed.contains(&user_id.0)
+    }
+
+    pub fn is_blocked(&self, user_id: UserId) -> bool {
+        let lists = self.lists.lock().unwrap_or_else(|err| err.into_inner());
+        lists.blocked.contains(&user_id.0)
+    }
+
+    pub fn allowlist_only(&self) -> bool {
+        let lists = self.lists.lock().unwrap_or_else(|err| err.into_inner());
+        lists.allowlist_only
+    }
+
+    /// `(allowed, blocked)` user ids, sorted.
+    pub fn lists(&self) -> (Vec<u64>, Vec<u64>) {
+        let lists = self.lists.lock().unwrap_or_else(|err| err.into_inner());
+        let sorted = |ids: &HashSet<u64>| {
+            let mut ids: Vec<u64> = ids.iter().copied().collect();
+            ids.sort_unstable();
+            ids
+        };
+        (sorted(&lists.allowed), sorted(&lists.blocked))
+    }
+
+    /// Puts the user on one of the lists, taking them off the other; `None` takes them
+    /// off both.
+    pub async fn set(&self, user_id: UserId, access: Option<Access>) -> Result<()> {
+        {
+            let mut lists = self.lists.lock().unwrap_or_else(|err| err.into_inner());
+            lists.allowed.remove(&user_id.0);
+            lists.blocked.remove(&user_id.0);
+            match access {
+                Some(Access::Allowed) => lists.allowed.insert(user_id.0),
+                Some(Access::Blocked) => lists.blocked.insert(user_id.0),
+                None => false,
+            };
+        }
+        self.storage.set_access(user_id, access).await
+    }
+
+    pub async fn set_allowlist_only(&self, enabled: bool) -> Result<()> {
+        {
+            let mut lists = self.lists.lock().unwrap_or_else(|err| err.into_inner());
+            lists.allowlist_only = enabled;
+        }
+        self.storage.set_allowlist_only(enabled).await
+    }
+}
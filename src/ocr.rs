@@ -0,0 +1,129 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use reqwest::{Client, Url, header};
+use serde::Deserialize;
+use tokio::task;
+
+use crate::config::Config;
+
+const DEFAULT_TESSERACT: &str = "tesseract";
+const DEFAULT_LANGUAGES: &str = "eng";
+const REMOTE_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Where text in a photo gets recognized.
+pub enum OcrProvider {
+    /// A local `tesseract` binary, fed the image on stdin.
+    Tesseract { binary: PathBuf, languages: String },
+    /// An HTTP service that takes the image bytes and answers `{"text": "..."}`.
+    Remote { url: Url, token: Option<String> },
+}
+
+#[derive(Deserialize)]
+struct RemoteResponse {
+    text: String,
+}
+
+impl OcrProvider {
+    /// Configures the provider from `ocr`: `tesseract` (the default), the URL of a remote
+    /// service, or `off`.
+    ///
+    /// Tesseract runs the `tesseract` binary with `ocr_languages`; the remote service is
+    /// called with `ocr_token` as a bearer token when it is set.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let provider = config.ocr.as_deref().unwrap_or("tesseract");
+        if provider.eq_ignore_ascii_case("off") {
+            return None;
+        }
+        if provider.eq_ignore_ascii_case("tesseract") {
+            return Some(Self::Tesseract {
+                binary: config
+                    .tesseract
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from(DEFAULT_TESSERACT)),
+                languages: config
+                    .ocr_languages
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_LANGUAGES.to_string()),
+            });
+        }
+
+        match Url::parse(provider) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => Some(Self::Remote {
+                url,
+                token: config.ocr_token.clone(),
+            }),
+            _ => {
+                tracing::warn!("OCR is disabled: `{provider}` is neither `tesseract` nor a URL");
+                None
+            }
+        }
+    }
+
+    /// Returns the text found in the image, trimmed; empty when there is none.
+    pub async fn recognize(&self, client: &Client, image: Vec<u8>) -> Result<String> {
+        let text = match self {
+            Self::Tesseract { binary, languages } => {
+                let binary = binary.clone();
+                let languages = languages.clone();
+                task::spawn_blocking(move || run_tesseract(&binary, &languages, &image))
+                    .await
+                    .context("OCR task panicked")??
+            }
+            Self::Remote { url, token } => {
+                let mut request = client
+                    .post(url.clone())
+                    .timeout(REMOTE_TIMEOUT)
+                    .header(header::CONTENT_TYPE, "application/octet-stream")
+                    .body(image);
+                if let Some(token) = token {
+                    request = request.bearer_auth(token);
+                }
+                let response: RemoteResponse = request
+                    .send()
+                    .await
+                    .context("OCR request failed")?
+                    .error_for_status()
+                    .context("OCR service returned an error")?
+                    .json()
+                    .await
+                    .context("Failed to parse OCR response")?;
+                response.text
+            }
+        };
+        Ok(text.trim().to_string())
+    }
+}
+
+fn run_tesseract(binary: &Path, languages: &str, image: &[u8]) -> Result<String> {
+    let mut child = Command::new(binary)
+        .args(["stdin", "stdout", "-l", languages])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to start `{}`", binary.display()))?;
+
+    // Tesseract reads the whole image before it writes anything, so this cannot block on
+    // a full stdout pipe.
+    let mut stdin = child.stdin.take().context("Tesseract stdin is not piped")?;
+    stdin
+        .write_all(image)
+        .context("Failed to pass the image to tesseract")?;
+    drop(stdin);
+
+    let output = child
+        .wait_with_output()
+        .context("Failed to wait for tesseract")?;
+    if !output.status.success() {
+        bail!(
+            "Tesseract exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,107 @@
+//! The premium tier users buy with Telegram Stars: a higher daily quota, `/frame` and
+//! batch exports.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use teloxide::types::UserId;
+
+use crate::config::Config;
+use crate::storage::Storage;
+
+/// The currency code of Telegram Stars; Stars invoices need no payment provider.
+pub const STARS_CURRENCY: &str = "XTR";
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+const PAYLOAD_PREFIX: &str = "premium:";
+
+/// What premium costs and how long it lasts.
+#[derive(Clone, Copy, Debug)]
+pub struct Offer {
+    pub stars: u32,
+    pub days: u64,
+}
+
+impl Offer {
+    /// `premium_stars` turns the tier on; `premium_days` (30 by default) is how long one
+    /// purchase lasts.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let stars = config.premium_stars.filter(|stars| *stars > 0)?;
+        let days = config.premium_days.filter(|days| *days > 0).unwrap_or(30);
+        Some(Self { stars, days })
+    }
+}
+
+/// Who has premium and until when, kept in the database with a copy in memory.
+pub struct Premium {
+    storage: Storage,
+    offer: Option<Offer>,
+    /// Expiry in seconds since the Unix epoch, by user id.
+    expiries: Mutex<HashMap<u64, u64>>,
+}
+
+impl Premium {
+    pub fn open(storage: Storage, offer: Option<Offer>) -> Result<Self> {
+        let expiries = storage.load_entitlements()?.into_iter().collect();
+        Ok(Self {
+            storage,
+            offer,
+            expiries: Mutex::new(expiries),
+        })
+    }
+
+    /// `None` while the bot sells nothing; everything is free then.
+    pub fn offer(&self) -> Option<Offer> {
+        self.offer
+    }
+
+    /// When the user's premium ends, if it has not yet.
+    pub fn active_until(&self, user_id: UserId) -> Option<u64> {
+        let expiries = self.expiries.lock().unwrap_or_else(|err| err.into_inner());
+        expiries
+            .get(&user_id.0)
+            .copied()
+            .filter(|until| *until > now_secs())
+    }
+
+    /// Whether the user may use what premium unlocks: everyone while no tier is offered.
+    pub fn unlocked(&self, user_id: UserId) -> bool {
+        self.offer.is_none() || self.active_until(user_id).is_some()
+    }
+
+    /// Adds one purchase to the user's premium, after what they still have. Returns the
+    /// new expiry.
+    pub async fn grant(&self, user_id: UserId, charge_id: String) -> Result<u64> {
+        let days = self.offer.map_or(0, |offer| offer.days);
+        let start = self.active_until(user_id).unwrap_or_else(now_secs);
+        let until = start + days * SECS_PER_DAY;
+        self.storage
+            .grant_entitlement(user_id, until, charge_id)
+            .await?;
+        let mut expiries = self.expiries.lock().unwrap_or_else(|err| err.into_inner());
+        expiries.insert(user_id.0, until);
+        Ok(until)
+    }
+}
+
+/// The invoice payload of a premium purchase, which names the buyer.
+pub fn invoice_payload(user_id: UserId) -> String {
+    format!("{PAYLOAD_PREFIX}{}", user_id.0)
+}
+
+/// The buyer named by a payload from [`invoice_payload`].
+pub fn parse_payload(payload: &str) -> Option<UserId> {
+    payload
+        .strip_prefix(PAYLOAD_PREFIX)?
+        .parse()
+        .ok()
+        .map(UserId)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -0,0 +1,267 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use exif::{Exif, In, Tag, Value};
+
+use crate::markup;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+/// Text chunks above this are skipped rather than read, since prompts are far smaller.
+const MAX_PNG_TEXT: u32 = 1024 * 1024;
+/// IPTC digital source type that C2PA manifests and XMP use for generated media. It also
+/// matches `compositeWithTrainedAlgorithmicMedia`.
+const AI_SOURCE_TYPE: &[u8] = b"rainedAlgorithmicMedia";
+/// Lowercase names of image generators as they show up in metadata, with their spelling.
+const AI_GENERATORS: &[(&str, &str)] = &[
+    ("stable diffusion", "Stable Diffusion"),
+    ("midjourney", "Midjourney"),
+    ("dall-e", "DALL·E"),
+    ("dall·e", "DALL·E"),
+    ("novelai", "NovelAI"),
+    ("comfyui", "ComfyUI"),
+    ("automatic1111", "AUTOMATIC1111"),
+    ("invokeai", "InvokeAI"),
+    ("adobe firefly", "Adobe Firefly"),
+];
+/// PNG text keywords generation UIs store their prompt and settings under.
+const PROMPT_KEYWORDS: &[&str] = &[
+    "parameters",
+    "prompt",
+    "workflow",
+    "dream",
+    "sd-metadata",
+    "invokeai_metadata",
+];
+
+/// A sign that an image came out of a generative model rather than a camera.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Marker {
+    /// A C2PA manifest with an AI-generation assertion.
+    C2paAssertion,
+    /// XMP metadata declaring the image as algorithmically generated.
+    XmpSourceType,
+    /// A software or description field naming a known image generator.
+    Generator(String),
+    /// A PNG text chunk of the kind generation UIs store their prompt in.
+    PromptChunk(String),
+    /// No camera make, model or exposure data at all. Only listed next to another marker,
+    /// since screenshots and edited exports look the same.
+    NoCameraData,
+}
+
+/// Looks for generation markers in the metadata segments ahead of the image data.
+///
+/// Only PNG and JPEG are inspected. Reading stops at the first image data, and a stream
+/// that ends early, like a downloaded file header, just ends the scan.
+pub fn scan<R: Read + Seek>(reader: &mut R) -> Vec<Marker> {
+    let mut markers = Vec::new();
+    let mut signature = [0u8; 8];
+    let scanned = reader.read_exact(&mut signature).and_then(|()| {
+        if signature == PNG_SIGNATURE {
+            scan_png(reader, &mut markers)
+        } else if signature[..2] == [0xff, 0xd8] {
+            reader.seek(SeekFrom::Start(2))?;
+            scan_jpeg(reader, &mut markers)
+        } else {
+            Ok(())
+        }
+    });
+    if let Err(err) = scanned
+        && err.kind() != io::ErrorKind::UnexpectedEof
+    {
+        tracing::debug!("Stopped scanning for generation markers: {err}");
+    }
+    markers
+}
+
+/// Adds what the EXIF data gives away to the markers found by [`scan`].
+pub fn conclude(mut markers: Vec<Marker>, exif: Option<&Exif>) -> Vec<Marker> {
+    if let Some(exif) = exif {
+        for tag in [
+            Tag::Software,
+            Tag::Make,
+            Tag::Model,
+            Tag::ImageDescription,
+            Tag::Artist,
+        ] {
+            if let Some(field) = exif.get_field(tag, In::PRIMARY) {
+                inspect_text(&field.display_value().to_string(), &mut markers);
+            }
+        }
+
+        // Stable Diffusion UIs save the prompt and sampler settings of JPEGs here.
+        if let Some(Value::Undefined(bytes, _)) = exif
+            .get_field(Tag::UserComment, In::PRIMARY)
+            .map(|field| &field.value)
+        {
+            // Dropping the zero bytes reads ASCII out of UTF-16 in either byte order.
+            let bytes: Vec<u8> = bytes.iter().copied().filter(|&byte| byte != 0).collect();
+            let comment = String::from_utf8_lossy(&bytes).to_lowercase();
+            if comment.contains("negative prompt:")
+                || (comment.contains("steps:") && comment.contains("sampler:"))
+            {
+                push_unique(
+                    &mut markers,
+                    Marker::PromptChunk(String::from("UserComment")),
+                );
+            }
+            inspect_text(&comment, &mut markers);
+        }
+    }
+
+    if !markers.is_empty() && !has_camera_data(exif) {
+        markers.push(Marker::NoCameraData);
+    }
+    markers
+}
+
+/// The caption line listing why the image is likely AI-generated, if anything points to it.
+pub fn note(markers: &[Marker], locale: &str) -> Option<String> {
+    if markers.is_empty() {
+        return None;
+    }
+
+    let reasons: Vec<String> = markers
+        .iter()
+        .map(|marker| match marker {
+            Marker::C2paAssertion => rust_i18n::t!("ai.c2pa", locale = locale).into_owned(),
+            Marker::XmpSourceType => rust_i18n::t!("ai.xmp", locale = locale).into_owned(),
+            Marker::Generator(name) => {
+                rust_i18n::t!("ai.generator", locale = locale, name = name).into_owned()
+            }
+            Marker::PromptChunk(keyword) => {
+                rust_i18n::t!("ai.prompt", locale = locale, keyword = keyword).into_owned()
+            }
+            Marker::NoCameraData => rust_i18n::t!("ai.no_camera", locale = locale).into_owned(),
+        })
+        .collect();
+    let heading = rust_i18n::t!("ai.heading", locale = locale);
+    Some(format!(
+        "🤖 {} {}",
+        markup::bold(&heading),
+        markup::escape(&reasons.join("; "))
+    ))
+}
+
+fn scan_png<R: Read + Seek>(reader: &mut R, markers: &mut Vec<Marker>) -> io::Result<()> {
+    loop {
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let kind = &header[4..];
+        if kind == b"IDAT" || kind == b"IEND" {
+            return Ok(());
+        }
+
+        let is_text = matches!(kind, b"tEXt" | b"iTXt" | b"zTXt");
+        if (is_text || kind == b"caBX") && length <= MAX_PNG_TEXT {
+            let mut data = vec![0u8; length as usize];
+            reader.read_exact(&mut data)?;
+            if kind == b"caBX" {
+                if contains(&data, AI_SOURCE_TYPE) {
+                    push_unique(markers, Marker::C2paAssertion);
+                }
+            } else {
+                inspect_png_text(&data, markers);
+            }
+            reader.seek(SeekFrom::Current(4))?;
+        } else {
+            reader.seek(SeekFrom::Current(i64::from(length) + 4))?;
+        }
+    }
+}
+
+fn inspect_png_text(data: &[u8], markers: &mut Vec<Marker>) {
+    let keyword_end = data
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(data.len());
+    let keyword = String::from_utf8_lossy(&data[..keyword_end]).to_lowercase();
+    let text = &data[keyword_end.min(data.len())..];
+
+    if keyword == "xml:com.adobe.xmp" {
+        if contains(text, AI_SOURCE_TYPE) {
+            push_unique(markers, Marker::XmpSourceType);
+        }
+    } else if PROMPT_KEYWORDS.contains(&keyword.as_str()) {
+        push_unique(markers, Marker::PromptChunk(keyword.clone()));
+    }
+    inspect_text(&String::from_utf8_lossy(text), markers);
+}
+
+fn scan_jpeg<R: Read + Seek>(reader: &mut R, markers: &mut Vec<Marker>) -> io::Result<()> {
+    loop {
+        let mut marker = [0u8; 2];
+        reader.read_exact(&mut marker)?;
+        if marker[0] != 0xff {
+            return Ok(());
+        }
+        // Start of scan or end of image: the metadata is behind us.
+        if marker[1] == 0xda || marker[1] == 0xd9 {
+            return Ok(());
+        }
+        if marker[1] == 0xff || (0xd0..=0xd7).contains(&marker[1]) || marker[1] == 0x01 {
+            continue;
+        }
+
+        let mut length = [0u8; 2];
+        reader.read_exact(&mut length)?;
+        let length = usize::from(u16::from_be_bytes(length)).saturating_sub(2);
+        let mut data = vec![0u8; length];
+        reader.read_exact(&mut data)?;
+
+        match marker[1] {
+            // APP1 carries XMP next to EXIF.
+            0xe1 if data.starts_with(b"http://ns.adobe.com/xap/1.0/") => {
+                if contains(&data, AI_SOURCE_TYPE) {
+                    push_unique(markers, Marker::XmpSourceType);
+                }
+                inspect_text(&String::from_utf8_lossy(&data), markers);
+            }
+            // APP11 holds the JUMBF boxes a C2PA manifest is stored in.
+            0xeb if contains(&data, b"c2pa") && contains(&data, AI_SOURCE_TYPE) => {
+                push_unique(markers, Marker::C2paAssertion);
+            }
+            // Comment segment.
+            0xfe => inspect_text(&String::from_utf8_lossy(&data), markers),
+            _ => {}
+        }
+    }
+}
+
+fn has_camera_data(exif: Option<&Exif>) -> bool {
+    let Some(exif) = exif else {
+        return false;
+    };
+    [
+        Tag::Make,
+        Tag::Model,
+        Tag::ExposureTime,
+        Tag::FNumber,
+        Tag::PhotographicSensitivity,
+    ]
+    .into_iter()
+    .any(|tag| exif.get_field(tag, In::PRIMARY).is_some())
+}
+
+/// Records the first known generator `text` mentions.
+fn inspect_text(text: &str, markers: &mut Vec<Marker>) {
+    let text = text.to_lowercase();
+    if let Some((_, name)) = AI_GENERATORS
+        .iter()
+        .find(|(needle, _)| text.contains(needle))
+    {
+        push_unique(markers, Marker::Generator(name.to_string()));
+    }
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+fn push_unique(markers: &mut Vec<Marker>, marker: Marker) {
+    if !markers.contains(&marker) {
+        markers.push(marker);
+    }
+}
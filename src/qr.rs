@@ -0,0 +1,23 @@
+use std::io::Cursor;
+
+use anyhow::{Context, Result};
+use image::{DynamicImage, ImageFormat, Luma};
+use qrcode::QrCode;
+
+/// Large enough to scan off another phone's screen from arm's length.
+const MIN_SIZE: u32 = 480;
+
+/// Encodes `text` as a black-on-white QR code PNG.
+pub fn render_png(text: &str) -> Result<Vec<u8>> {
+    let code = QrCode::new(text.as_bytes()).context("Failed to encode QR code")?;
+    let image = code
+        .render::<Luma<u8>>()
+        .min_dimensions(MIN_SIZE, MIN_SIZE)
+        .build();
+
+    let mut png = Vec::new();
+    DynamicImage::ImageLuma8(image)
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .context("Failed to encode QR code image")?;
+    Ok(png)
+}
@@ -0,0 +1,115 @@
+//! Limits how many images are read at once, and reads each chat's images in the order they
+//! arrived, so a burst of uploads waits its turn instead of overloading the host.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use teloxide::types::ChatId;
+use tokio::sync::{OwnedMutexGuard, OwnedSemaphorePermit, Semaphore};
+
+use crate::config::Config;
+
+/// Images read at once when `max_concurrent` is unset.
+const DEFAULT_CONCURRENCY: usize = 4;
+
+pub struct WorkQueue {
+    permits: Arc<Semaphore>,
+    chats: Mutex<HashMap<ChatId, Arc<ChatQueue>>>,
+}
+
+#[derive(Default)]
+struct ChatQueue {
+    /// Tokio's mutex is fair, so waiters get their turn first come, first served.
+    turn: Arc<tokio::sync::Mutex<()>>,
+    /// Images of the chat waiting or being read.
+    queued: AtomicUsize,
+}
+
+/// A place in the queue of a chat, taken before waiting for the turn.
+pub struct Ticket {
+    queued: Queued,
+    permits: Arc<Semaphore>,
+    ahead: usize,
+}
+
+/// Held while an image is read; dropping it lets the next one go.
+pub struct Turn {
+    _chat: OwnedMutexGuard<()>,
+    _permit: OwnedSemaphorePermit,
+    _queued: Queued,
+}
+
+/// Counts the image out of its chat's queue when dropped, even if it never got its turn.
+struct Queued(Arc<ChatQueue>);
+
+impl Drop for Queued {
+    fn drop(&mut self) {
+        self.0.queued.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl WorkQueue {
+    pub fn new(concurrency: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(concurrency.max(1))),
+            chats: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reads at most `max_concurrent` images at once.
+    pub fn from_config(config: &Config) -> Self {
+        let concurrency = match config.max_concurrent {
+            Some(0) => {
+                tracing::warn!("Ignoring a `max_concurrent` of 0, which is not a positive number");
+                DEFAULT_CONCURRENCY
+            }
+            Some(limit) => limit,
+            None => DEFAULT_CONCURRENCY,
+        };
+        Self::new(concurrency)
+    }
+
+    /// Joins the end of the queue of `chat_id`.
+    pub fn enter(&self, chat_id: ChatId) -> Ticket {
+        let chat = {
+            let mut chats = self.chats.lock().unwrap_or_else(|err| err.into_inner());
+            // Queues nobody holds a ticket for anymore are only referenced from the map.
+            chats.retain(|_, chat| Arc::strong_count(chat) > 1);
+            Arc::clone(chats.entry(chat_id).or_default())
+        };
+        let ahead = chat.queued.fetch_add(1, Ordering::AcqRel);
+        Ticket {
+            queued: Queued(chat),
+            permits: Arc::clone(&self.permits),
+            ahead,
+        }
+    }
+}
+
+impl Ticket {
+    /// The place in the queue, counting from 1; `None` when the image can be read right
+    /// away.
+    pub fn position(&self) -> Option<usize> {
+        if self.ahead == 0 && self.permits.available_permits() > 0 {
+            None
+        } else {
+            Some(self.ahead + 1)
+        }
+    }
+
+    /// Waits until the images of the chat before this one are done and a slot is free.
+    pub async fn turn(self) -> Turn {
+        let chat = Arc::clone(&self.queued.0.turn).lock_owned().await;
+        let permit = self
+            .permits
+            .acquire_owned()
+            .await
+            .expect("the semaphore is never closed");
+        Turn {
+            _chat: chat,
+            _permit: permit,
+            _queued: self.queued,
+        }
+    }
+}
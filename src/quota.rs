@@ -0,0 +1,73 @@
+//! How many images each user may have read per day, so one user cannot take up a shared
+//! bot.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use teloxide::types::UserId;
+
+use crate::storage::Storage;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// What the quota says about a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Admission {
+    Allowed,
+    /// Today's images are used up until `resets_at`, in seconds since the Unix epoch.
+    Exceeded {
+        limit: u32,
+        resets_at: u64,
+    },
+}
+
+/// The per-user daily limit, counted in the database so restarts do not reset it. Days
+/// start at midnight UTC.
+pub struct Quota {
+    storage: Storage,
+    limit: Option<u32>,
+    premium_limit: Option<u32>,
+}
+
+impl Quota {
+    /// `None` lets everyone, or everyone with premium, have any number of images read.
+    pub fn new(storage: Storage, limit: Option<u32>, premium_limit: Option<u32>) -> Self {
+        Self {
+            storage,
+            limit,
+            premium_limit,
+        }
+    }
+
+    /// Counts `images` against the user's quota for today, unless that would go over it.
+    /// An album is taken as a whole or not at all.
+    pub async fn admit(&self, user_id: UserId, images: u32, premium: bool) -> Result<Admission> {
+        let limit = if premium {
+            self.premium_limit
+        } else {
+            self.limit
+        };
+        let Some(limit) = limit else {
+            return Ok(Admission::Allowed);
+        };
+        let day = now_secs() / SECS_PER_DAY;
+        if self
+            .storage
+            .take_quota(user_id, day as i64, images, limit)
+            .await?
+        {
+            return Ok(Admission::Allowed);
+        }
+        Ok(Admission::Exceeded {
+            limit,
+            resets_at: (day + 1) * SECS_PER_DAY,
+        })
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
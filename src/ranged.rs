@@ -0,0 +1,162 @@
+//! Reads images over HTTP with an async client, fetching only the blocks the parser asks
+//! for. The EXIF parsers want `Read + Seek`, so they run on the blocking pool over the
+//! blocks fetched so far; when one reads past them, the async side fetches the missing
+//! block and the parse runs again.
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+use reqwest::header::{CONTENT_RANGE, RANGE};
+use reqwest::{Client, StatusCode};
+use tokio::task;
+
+use crate::remote::MAX_REMOTE_IMAGE_SIZE;
+
+/// Bytes asked for with each range request.
+const BLOCK_SIZE: u64 = 512 * 1024;
+/// Range requests for one image before the parse has to make do with what it has.
+const MAX_REQUESTS: usize = 48;
+
+/// The blocks of a remote file fetched so far, read like the file itself.
+pub struct Blocks {
+    /// Keyed by block number.
+    blocks: BTreeMap<u64, Vec<u8>>,
+    len: u64,
+    position: u64,
+    /// The first block a read needed that was not fetched yet.
+    missing: Option<u64>,
+}
+
+impl Blocks {
+    fn new() -> Self {
+        Self {
+            blocks: BTreeMap::new(),
+            len: 0,
+            position: 0,
+            missing: None,
+        }
+    }
+
+    /// Fetches block `number`, returning how many bytes came in.
+    async fn fetch(&mut self, client: &Client, url: &str, number: u64) -> Result<u64> {
+        let start = number * BLOCK_SIZE;
+        let mut response = client
+            .get(url)
+            .header(RANGE, format!("bytes={}-{}", start, start + BLOCK_SIZE - 1))
+            .send()
+            .await
+            .context("Failed to request the image")?
+            .error_for_status()
+            .context("The image could not be fetched")?;
+        let partial = response.status() == StatusCode::PARTIAL_CONTENT;
+        let total = response
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit('/').next())
+            .and_then(|total| total.parse().ok());
+        // A server ignoring the range sends the whole file, so stop reading before it gets
+        // out of hand instead of buffering whatever it sends.
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.chunk().await.context("Failed to read the image")? {
+            bytes.extend_from_slice(&chunk);
+            if bytes.len() as u64 > MAX_REMOTE_IMAGE_SIZE {
+                bail!("The image is larger than {MAX_REMOTE_IMAGE_SIZE} bytes");
+            }
+        }
+        let received = bytes.len() as u64;
+
+        if partial {
+            self.len = total.unwrap_or(self.len.max(start + received));
+            self.blocks.insert(number, bytes);
+        } else {
+            // The server ignored the range and sent the whole file.
+            self.len = received;
+            for (number, block) in bytes.chunks(BLOCK_SIZE as usize).enumerate() {
+                self.blocks.insert(number as u64, block.to_vec());
+            }
+        }
+        Ok(received)
+    }
+}
+
+impl Read for Blocks {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.position >= self.len {
+            return Ok(0);
+        }
+        let number = self.position / BLOCK_SIZE;
+        let Some(block) = self.blocks.get(&number) else {
+            self.missing.get_or_insert(number);
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "this part of the image is not fetched yet",
+            ));
+        };
+        let offset = ((self.position - number * BLOCK_SIZE) as usize).min(block.len());
+        let available = &block[offset..];
+        let read = available.len().min(buf.len());
+        buf[..read].copy_from_slice(&available[..read]);
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for Blocks {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let target = match position {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+        };
+        self.position = target
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "seek before the start"))?;
+        Ok(self.position)
+    }
+}
+
+/// Runs `parse` over the image at `url`, fetching the blocks it reads as it goes.
+pub async fn read<T: Send + 'static>(
+    client: &Client,
+    url: &str,
+    parse: fn(&mut Blocks) -> Result<T>,
+) -> Result<T> {
+    let mut blocks = Blocks::new();
+    let mut waited = Duration::ZERO;
+    let mut downloaded = 0;
+    let mut requests = 0;
+    let mut next = 0;
+    let parsed = loop {
+        let started = Instant::now();
+        downloaded += blocks.fetch(client, url, next).await?;
+        waited += started.elapsed();
+        requests += 1;
+
+        blocks.position = 0;
+        blocks.missing = None;
+        let span = tracing::Span::current();
+        let (returned, parsed) = task::spawn_blocking(move || {
+            let parsed = span.in_scope(|| parse(&mut blocks));
+            (blocks, parsed)
+        })
+        .await
+        .context("Failed to join EXIF parsing task")?;
+        blocks = returned;
+
+        match blocks.missing {
+            Some(missing) if requests < MAX_REQUESTS => next = missing,
+            _ => break parsed,
+        }
+    };
+
+    crate::telemetry::downloaded(downloaded);
+    tracing::info!(
+        download_ms = waited.as_millis() as u64,
+        download_bytes = downloaded,
+        requests,
+        "Streamed image header"
+    );
+    parsed
+}
@@ -0,0 +1,51 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use teloxide::types::UserId;
+
+/// How many processed images are remembered for every user.
+const MAX_RECENT_PER_USER: usize = 20;
+
+/// A caption the bot produced for one of the user's images.
+#[derive(Clone)]
+pub struct RecentResult {
+    pub file_id: String,
+    pub file_unique_id: String,
+    pub caption: String,
+}
+
+/// Keeps the most recent results per user so they can be offered in inline mode.
+#[derive(Default)]
+pub struct RecentResults {
+    entries: Mutex<HashMap<UserId, VecDeque<RecentResult>>>,
+}
+
+impl RecentResults {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remembers a result, replacing an older caption for the same file.
+    pub fn record(&self, user_id: UserId, result: RecentResult) {
+        let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        let list = entries.entry(user_id).or_default();
+
+        list.retain(|entry| entry.file_unique_id != result.file_unique_id);
+        list.push_front(result);
+        list.truncate(MAX_RECENT_PER_USER);
+    }
+
+    /// Returns the user's results, newest first.
+    pub fn list(&self, user_id: UserId) -> Vec<RecentResult> {
+        let entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        entries
+            .get(&user_id)
+            .map(|list| list.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn forget(&self, user_id: UserId) {
+        let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        entries.remove(&user_id);
+    }
+}
@@ -0,0 +1,270 @@
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::{Client, StatusCode, Url, header, redirect};
+
+/// Upper bound for the whole EXIF extraction of a remote image.
+pub const REMOTE_FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+/// The largest image read from a link, or from a server that ignores range requests.
+pub const MAX_REMOTE_IMAGE_SIZE: u64 = 200 * 1024 * 1024;
+/// Enough leading bytes to recognize every supported container.
+const SNIFF_LEN: usize = 32;
+const MAX_REDIRECTS: usize = 5;
+
+/// A remote file that passed validation and looks like an image.
+pub struct RemoteImage {
+    pub url: Url,
+    pub size: Option<u64>,
+}
+
+#[derive(Debug)]
+pub enum ProbeError {
+    /// The URL points at a private, loopback or otherwise internal address.
+    Blocked,
+    NotImage,
+    TooLarge(u64),
+    Request(reqwest::Error),
+}
+
+impl fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Blocked => write!(f, "URL points to a non-public address"),
+            Self::NotImage => write!(f, "URL does not point to an image"),
+            Self::TooLarge(size) => write!(f, "remote image is too large ({size} bytes)"),
+            Self::Request(err) => write!(f, "failed to probe remote image: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ProbeError {}
+
+impl From<reqwest::Error> for ProbeError {
+    fn from(err: reqwest::Error) -> Self {
+        Self::Request(err)
+    }
+}
+
+/// The client for image links. Every redirect hop has to pass [`is_allowed_url`], and
+/// every host name is resolved through [`PublicResolver`], so neither a redirect nor a
+/// name that changes its address between two lookups reaches an internal address.
+pub fn client(proxy: Option<&Url>) -> Client {
+    let policy = redirect::Policy::custom(|attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            attempt.error("too many redirects")
+        } else if is_allowed_url(attempt.url()) {
+            attempt.follow()
+        } else {
+            attempt.error(ProbeError::Blocked)
+        }
+    });
+    crate::http::builder(proxy)
+        .redirect(policy)
+        .dns_resolver(Arc::new(PublicResolver))
+        .build()
+        .unwrap_or_else(|err| {
+            tracing::warn!(
+                "Falling back to an HTTP client that does not follow redirects: {err:?}"
+            );
+            Client::builder()
+                .redirect(redirect::Policy::none())
+                .build()
+                .unwrap_or_default()
+        })
+}
+
+/// Resolves host names for [`client`], failing when any address is not public. The
+/// connection is made to the addresses checked here, so there is no second lookup for a
+/// rebinding name to answer differently.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addresses: Vec<SocketAddr> =
+                tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            if addresses.is_empty() || addresses.iter().any(|address| !is_public_ip(address.ip())) {
+                return Err(ProbeError::Blocked.into());
+            }
+            let addresses: Addrs = Box::new(addresses.into_iter());
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(addresses)
+        })
+    }
+}
+
+/// Returns the first http(s) URL in `text` that points to a public host.
+pub fn find_image_url(text: &str) -> Option<Url> {
+    text.split_whitespace()
+        .map(|word| word.trim_matches(|c| matches!(c, '<' | '>' | '(' | ')' | '"' | '\'')))
+        .filter_map(|word| Url::parse(word).ok())
+        .find(is_allowed_url)
+}
+
+/// Checks that `url` is reachable and serves an image, without downloading it. `client`
+/// is the one from [`client`], which keeps redirects and lookups on public hosts.
+pub async fn probe_image(client: &Client, url: Url) -> Result<RemoteImage, ProbeError> {
+    // A proxy resolves the host itself, so check it up front as well.
+    ensure_public_host(&url).await?;
+
+    let mut response = client
+        .get(url)
+        .header(header::RANGE, format!("bytes=0-{}", SNIFF_LEN - 1))
+        .timeout(PROBE_TIMEOUT)
+        .send()
+        .await
+        .map_err(blocked_or_request)?
+        .error_for_status()?;
+    let final_url = response.url().clone();
+
+    let size = if response.status() == StatusCode::PARTIAL_CONTENT {
+        response
+            .headers()
+            .get(header::CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit('/').next())
+            .and_then(|total| total.parse().ok())
+    } else {
+        response.content_length()
+    };
+
+    if let Some(size) = size {
+        if size > MAX_REMOTE_IMAGE_SIZE {
+            return Err(ProbeError::TooLarge(size));
+        }
+    }
+
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_ascii_lowercase());
+
+    // Servers ignoring the range header send the whole file; one chunk is enough.
+    let head = response.chunk().await?.unwrap_or_default();
+
+    // Trust the bytes first; fall back to the declared type for formats we cannot sniff.
+    let sniffed = sniff_image(&head);
+    let declared = content_type
+        .as_deref()
+        .map(|value| value.starts_with("image/"))
+        .unwrap_or(false);
+
+    if !sniffed && !declared {
+        return Err(ProbeError::NotImage);
+    }
+
+    Ok(RemoteImage {
+        url: final_url,
+        size,
+    })
+}
+
+/// Tells the redirects and lookups [`client`] refused apart from other failures.
+fn blocked_or_request(err: reqwest::Error) -> ProbeError {
+    let mut source = std::error::Error::source(&err);
+    while let Some(cause) = source {
+        if matches!(
+            cause.downcast_ref::<ProbeError>(),
+            Some(ProbeError::Blocked)
+        ) {
+            return ProbeError::Blocked;
+        }
+        source = cause.source();
+    }
+    ProbeError::Request(err)
+}
+
+/// Recognizes the magic bytes of the containers kamadak-exif can read.
+pub fn sniff_image(head: &[u8]) -> bool {
+    head.starts_with(&[0xFF, 0xD8, 0xFF])
+        || head.starts_with(b"\x89PNG\r\n\x1a\n")
+        || head.starts_with(b"II*\0")
+        || head.starts_with(b"MM\0*")
+        || (head.len() >= 12 && &head[0..4] == b"RIFF" && &head[8..12] == b"WEBP")
+        || (head.len() >= 12
+            && &head[4..8] == b"ftyp"
+            && matches!(
+                &head[8..12],
+                b"heic" | b"heix" | b"mif1" | b"msf1" | b"avif"
+            ))
+}
+
+fn is_allowed_url(url: &Url) -> bool {
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return is_public_ip(ip);
+    }
+
+    let host = host.to_ascii_lowercase();
+    host.contains('.')
+        && host != "localhost"
+        && !host.ends_with(".localhost")
+        && !host.ends_with(".local")
+        && !host.ends_with(".internal")
+}
+
+/// Resolves the host so names pointing at internal addresses are rejected as well.
+async fn ensure_public_host(url: &Url) -> Result<(), ProbeError> {
+    let host = url
+        .host_str()
+        .ok_or(ProbeError::Blocked)?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    let port = url.port_or_known_default().unwrap_or(443);
+
+    let addresses = tokio::net::lookup_host((host.as_str(), port))
+        .await
+        .map_err(|_| ProbeError::Blocked)?;
+
+    let mut any = false;
+    for address in addresses {
+        any = true;
+        if !is_public_ip(address.ip()) {
+            return Err(ProbeError::Blocked);
+        }
+    }
+
+    if any {
+        Ok(())
+    } else {
+        Err(ProbeError::Blocked)
+    }
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_unspecified()
+                // Carrier-grade NAT range 100.64.0.0/10.
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(mapped) = ip.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(mapped));
+            }
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_unique_local()
+                || ip.is_unicast_link_local())
+        }
+    }
+}
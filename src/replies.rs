@@ -0,0 +1,52 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use teloxide::types::{ChatId, MessageId};
+
+/// Upper bound on remembered replies; the oldest entries are forgotten first.
+const MAX_TRACKED_REPLIES: usize = 10_000;
+
+/// The bot's answer to an image message.
+#[derive(Clone)]
+pub struct TrackedReply {
+    pub reply_id: MessageId,
+    pub file_unique_id: String,
+}
+
+/// Remembers which bot message answered which image, so edits can update the answer.
+#[derive(Default)]
+pub struct ReplyIndex {
+    inner: Mutex<ReplyIndexInner>,
+}
+
+#[derive(Default)]
+struct ReplyIndexInner {
+    replies: HashMap<(ChatId, MessageId), TrackedReply>,
+    order: VecDeque<(ChatId, MessageId)>,
+}
+
+impl ReplyIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, chat_id: ChatId, source_id: MessageId, reply: TrackedReply) {
+        let mut inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        let key = (chat_id, source_id);
+
+        if inner.replies.insert(key, reply).is_none() {
+            inner.order.push_back(key);
+        }
+
+        while inner.order.len() > MAX_TRACKED_REPLIES {
+            if let Some(oldest) = inner.order.pop_front() {
+                inner.replies.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn get(&self, chat_id: ChatId, source_id: MessageId) -> Option<TrackedReply> {
+        let inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        inner.replies.get(&(chat_id, source_id)).cloned()
+    }
+}
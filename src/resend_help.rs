@@ -0,0 +1,72 @@
+use std::borrow::Cow;
+
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+/// Callback data of the help buttons is this prefix followed by the platform name.
+const CALLBACK_PREFIX: &str = "resend_help:";
+
+/// The Telegram apps whose "send as file" steps differ.
+#[derive(Clone, Copy)]
+pub enum Platform {
+    Ios,
+    Android,
+    Desktop,
+}
+
+impl Platform {
+    const ALL: [Platform; 3] = [Platform::Ios, Platform::Android, Platform::Desktop];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Platform::Ios => "ios",
+            Platform::Android => "android",
+            Platform::Desktop => "desktop",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|platform| platform.as_str() == value)
+    }
+
+    fn label(self, locale: &str) -> Cow<'static, str> {
+        match self {
+            Platform::Ios => rust_i18n::t!("resend_help.ios_button", locale = locale),
+            Platform::Android => rust_i18n::t!("resend_help.android_button", locale = locale),
+            Platform::Desktop => rust_i18n::t!("resend_help.desktop_button", locale = locale),
+        }
+    }
+
+    /// Step-by-step instructions; kept short enough for a callback alert (200 chars).
+    pub fn steps(self, locale: &str) -> Cow<'static, str> {
+        match self {
+            Platform::Ios => rust_i18n::t!("resend_help.ios_steps", locale = locale),
+            Platform::Android => rust_i18n::t!("resend_help.android_steps", locale = locale),
+            Platform::Desktop => rust_i18n::t!("resend_help.desktop_steps", locale = locale),
+        }
+    }
+}
+
+/// One button per platform, attached to answers for compressed photos.
+pub fn keyboard(locale: &str) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new([buttons(locale)])
+}
+
+/// The platform buttons as a single keyboard row.
+pub fn buttons(locale: &str) -> Vec<InlineKeyboardButton> {
+    Platform::ALL
+        .into_iter()
+        .map(|platform| {
+            InlineKeyboardButton::callback(
+                platform.label(locale),
+                format!("{CALLBACK_PREFIX}{}", platform.as_str()),
+            )
+        })
+        .collect()
+}
+
+/// Returns the platform a help button stands for, or `None` for other callback data.
+pub fn parse_callback(data: &str) -> Option<Platform> {
+    data.strip_prefix(CALLBACK_PREFIX).and_then(Platform::parse)
+}
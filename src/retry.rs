@@ -0,0 +1,63 @@
+//! Retries Telegram requests on flood waits and failed connections, so a hiccup does not
+//! turn into an error message for the user. Only failures that mean Telegram never took
+//! the request are retried, since sending a message twice is worse than not at all.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use teloxide::RequestError;
+use teloxide::requests::{Output, Request};
+
+/// Tries after the first one.
+const MAX_RETRIES: u32 = 3;
+/// The wait before the first retry, doubled for each one after it.
+const BASE_DELAY: Duration = Duration::from_millis(500);
+/// Flood waits longer than this are given up on rather than waited out in a handler.
+const MAX_FLOOD_WAIT: Duration = Duration::from_secs(30);
+
+/// Sends `request`, sending it again when Telegram asks to wait or the connection could
+/// not be made, with exponential backoff and jitter between tries.
+pub async fn send<R>(request: R) -> Result<Output<R>, RequestError>
+where
+    R: Request<Err = RequestError>,
+{
+    let mut retries = 0;
+    loop {
+        let err = match request.send_ref().await {
+            Ok(output) => return Ok(output),
+            Err(err) => err,
+        };
+        let delay = match delay_for(&err, retries) {
+            Some(delay) if retries < MAX_RETRIES => delay,
+            _ => return Err(err),
+        };
+        retries += 1;
+        tracing::info!(
+            "Telegram request failed ({err}); retrying in {} ms",
+            delay.as_millis()
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// How long to wait before retry number `retries + 1`; `None` when the error will not go
+/// away by itself, or when the request may have been delivered: a timeout or a broken
+/// connection can come after Telegram already sent the message.
+fn delay_for(err: &RequestError, retries: u32) -> Option<Duration> {
+    match err {
+        RequestError::RetryAfter(wait) => {
+            Some(wait.duration()).filter(|wait| *wait <= MAX_FLOOD_WAIT)
+        }
+        RequestError::Network(err) if err.is_connect() => Some(backoff(retries)),
+        _ => None,
+    }
+}
+
+/// [`BASE_DELAY`] doubled `retries` times, plus up to half of that again, so requests
+/// that failed together do not all come back at once.
+fn backoff(retries: u32) -> Duration {
+    let delay = BASE_DELAY * 2u32.pow(retries);
+    let jitter = RandomState::new().build_hasher().finish() % (delay.as_millis() as u64 / 2 + 1);
+    delay + Duration::from_millis(jitter)
+}
@@ -0,0 +1,232 @@
+//! `fotobot scan <dir>`: every image below a directory through the same extraction the
+//! bot uses, written out as one CSV or JSON report.
+
+use std::fmt::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{Context, Result};
+use serde_json::{Value as JsonValue, json};
+
+use crate::exif::{self, ParsedExif, ShotFacts};
+use crate::export::escape_csv;
+
+/// File extensions the EXIF reader understands, lowercase.
+const IMAGE_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "jpe", "png", "webp", "tif", "tiff", "heic", "heif", "avif", "dng", "nef",
+    "arw", "cr2", "orf", "rw2", "pef", "srw",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    Csv,
+    Json,
+}
+
+impl ReportFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "csv" => Some(Self::Csv),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+/// What reading one image gave.
+enum Outcome {
+    Read {
+        facts: ShotFacts,
+        summary: JsonValue,
+    },
+    NoExif,
+    /// The reader returned an error, or panicked.
+    Failed(String),
+}
+
+pub struct Entry {
+    path: PathBuf,
+    outcome: Outcome,
+}
+
+/// How many images had EXIF data, none, or could not be read.
+#[derive(Default)]
+pub struct Totals {
+    pub read: usize,
+    pub no_exif: usize,
+    pub failed: usize,
+}
+
+/// Every image file below `dir`, sorted. Symlinks to directories are not followed, so
+/// loops cannot keep the walk going.
+pub fn find_images(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut images = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = std::fs::read_dir(&dir)
+            .with_context(|| format!("Failed to list `{}`", dir.display()))?;
+        for entry in entries {
+            let entry = entry.with_context(|| format!("Failed to list `{}`", dir.display()))?;
+            let file_type = entry.file_type()?;
+            let path = entry.path();
+            if file_type.is_dir() {
+                pending.push(path);
+            } else if is_image(&path) {
+                images.push(path);
+            }
+        }
+    }
+    images.sort();
+    Ok(images)
+}
+
+/// Reads every image on `jobs` threads. Entries keep the order of `images`.
+pub fn scan(images: &[PathBuf], jobs: usize) -> Vec<Entry> {
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<Entry>>> =
+        Mutex::new(std::iter::repeat_with(|| None).take(images.len()).collect());
+
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, images.len().max(1)) {
+            scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(path) = images.get(index) else {
+                        break;
+                    };
+                    let entry = Entry {
+                        path: path.clone(),
+                        outcome: read(path),
+                    };
+                    let mut results = results.lock().unwrap_or_else(|err| err.into_inner());
+                    results[index] = Some(entry);
+                }
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap_or_else(|err| err.into_inner())
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+fn read(path: &Path) -> Outcome {
+    // A panic in the parser is a finding about that file, not a reason to stop the scan.
+    let read = panic::catch_unwind(AssertUnwindSafe(|| {
+        exif::read_exif_from_file(path).map(|exif| {
+            exif.map(|exif| {
+                let summary = serde_json::to_value(ParsedExif::from_exif(&exif, None))
+                    .unwrap_or(JsonValue::Null);
+                (ShotFacts::from_exif(&exif), summary)
+            })
+        })
+    }));
+    match read {
+        Ok(Ok(Some((facts, summary)))) => Outcome::Read { facts, summary },
+        Ok(Ok(None)) => Outcome::NoExif,
+        Ok(Err(err)) => Outcome::Failed(format!("{err:#}")),
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            Outcome::Failed(format!("the parser panicked: {message}"))
+        }
+    }
+}
+
+pub fn totals(entries: &[Entry]) -> Totals {
+    let mut totals = Totals::default();
+    for entry in entries {
+        match entry.outcome {
+            Outcome::Read { .. } => totals.read += 1,
+            Outcome::NoExif => totals.no_exif += 1,
+            Outcome::Failed(_) => totals.failed += 1,
+        }
+    }
+    totals
+}
+
+/// The report, with paths relative to `root`.
+pub fn report(entries: &[Entry], root: &Path, format: ReportFormat) -> String {
+    match format {
+        ReportFormat::Csv => write_csv(entries, root),
+        ReportFormat::Json => write_json(entries, root),
+    }
+}
+
+fn write_csv(entries: &[Entry], root: &Path) -> String {
+    let mut output = String::from(
+        "path,status,datetime,camera,lens,focal_length,aperture,shutter,iso,latitude,longitude\r\n",
+    );
+    for entry in entries {
+        let path = relative(&entry.path, root);
+        let row = match &entry.outcome {
+            Outcome::Read { facts, .. } => {
+                let (latitude, longitude) = match facts.gps {
+                    Some((latitude, longitude)) => {
+                        (format!("{latitude:.7}"), format!("{longitude:.7}"))
+                    }
+                    None => (String::new(), String::new()),
+                };
+                [
+                    path.as_str(),
+                    "ok",
+                    facts.datetime.as_deref().unwrap_or(""),
+                    &facts.camera,
+                    &facts.lens,
+                    facts.focal_length.as_deref().unwrap_or(""),
+                    facts.aperture.as_deref().unwrap_or(""),
+                    facts.shutter.as_deref().unwrap_or(""),
+                    facts.iso.as_deref().unwrap_or(""),
+                    &latitude,
+                    &longitude,
+                ]
+                .map(escape_csv)
+                .join(",")
+            }
+            Outcome::NoExif => format!("{},no exif,,,,,,,,,", escape_csv(&path)),
+            Outcome::Failed(err) => {
+                format!("{},{},,,,,,,,,", escape_csv(&path), escape_csv(err))
+            }
+        };
+        writeln!(output, "{row}\r").ok();
+    }
+    output
+}
+
+fn write_json(entries: &[Entry], root: &Path) -> String {
+    let entries: Vec<JsonValue> = entries
+        .iter()
+        .map(|entry| {
+            let path = relative(&entry.path, root);
+            match &entry.outcome {
+                Outcome::Read { summary, .. } => json!({ "path": path, "summary": summary }),
+                Outcome::NoExif => json!({ "path": path, "summary": null }),
+                Outcome::Failed(err) => json!({ "path": path, "error": err }),
+            }
+        })
+        .collect();
+    serde_json::to_string_pretty(&entries).unwrap_or_default()
+}
+
+fn relative(path: &Path, root: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            IMAGE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+        })
+}
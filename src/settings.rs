@@ -0,0 +1,419 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use teloxide::types::{ChatId, UserId};
+
+use crate::exif::CaptionOptions;
+use crate::formats::Units;
+use crate::geo::haversine_km;
+use crate::storage::Storage;
+use crate::styles::{CaptionStyle, LabelStyle, Section, Sections};
+
+/// Decides which image documents the bot reacts to in group chats.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerMode {
+    /// Only images that mention the bot, or `/exif` replies to an image.
+    #[default]
+    Explicit,
+    /// Every image document posted to the chat.
+    All,
+}
+
+impl TriggerMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "explicit" | "mention" => Some(Self::Explicit),
+            "all" | "always" => Some(Self::All),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Explicit => "explicit",
+            Self::All => "all",
+        }
+    }
+}
+
+/// How much of an image's location a user's captions reveal.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GpsPrivacy {
+    /// Coordinates and the geocoded place name.
+    #[default]
+    Show,
+    /// The place name, but not the exact coordinates.
+    HideCoordinates,
+    /// Neither coordinates nor place name.
+    HideLocation,
+    /// Both, behind a spoiler so they are not readable at a glance.
+    Spoiler,
+}
+
+impl GpsPrivacy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "show" | "off" => Some(Self::Show),
+            "coordinates" | "coords" => Some(Self::HideCoordinates),
+            "location" | "all" => Some(Self::HideLocation),
+            "spoiler" | "blur" => Some(Self::Spoiler),
+            _ => None,
+        }
+    }
+
+    /// Whichever of the two reveals less; a spoiler counts as less than showing openly.
+    pub fn stricter(self, other: Self) -> Self {
+        let rank = |privacy: Self| match privacy {
+            Self::Show => 0,
+            Self::Spoiler => 1,
+            Self::HideCoordinates => 2,
+            Self::HideLocation => 3,
+        };
+        if rank(other) > rank(self) {
+            other
+        } else {
+            self
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Show => "show",
+            Self::HideCoordinates => "coordinates",
+            Self::HideLocation => "location",
+            Self::Spoiler => "spoiler",
+        }
+    }
+}
+
+/// How many decimals of the GPS position captions, map links and exports keep.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoordinatePrecision {
+    /// Six decimals, as the camera recorded them.
+    #[default]
+    Exact,
+    /// Three decimals, about 100 m.
+    Block,
+    /// Two decimals, about 1 km.
+    Area,
+    /// No coordinates in captions, only the place name; exports keep about 10 km.
+    City,
+}
+
+impl CoordinatePrecision {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "exact" | "full" => Some(Self::Exact),
+            "100m" | "block" | "street" => Some(Self::Block),
+            "1km" | "area" => Some(Self::Area),
+            "city" => Some(Self::City),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Exact => "exact",
+            Self::Block => "100m",
+            Self::Area => "1km",
+            Self::City => "city",
+        }
+    }
+
+    pub fn decimals(self) -> usize {
+        match self {
+            Self::Exact => 6,
+            Self::Block => 3,
+            Self::Area => 2,
+            Self::City => 1,
+        }
+    }
+
+    /// Rounds a `(latitude, longitude)` pair to [`Self::decimals`].
+    pub fn round(self, (latitude, longitude): (f64, f64)) -> (f64, f64) {
+        let scale = 10f64.powi(self.decimals() as i32);
+        (
+            (latitude * scale).round() / scale,
+            (longitude * scale).round() / scale,
+        )
+    }
+}
+
+/// Most private zones a user can keep, so they fit in the copyable [`ZoneSet`].
+pub const MAX_PRIVATE_ZONES: usize = 5;
+const DEFAULT_ZONE_RADIUS_M: f64 = 500.0;
+const MAX_ZONE_RADIUS_M: f64 = 50_000.0;
+
+/// What captions of photos taken inside a private zone give away.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ZoneMode {
+    /// Neither coordinates nor place name.
+    #[default]
+    Hide,
+    /// Only the city and country.
+    Coarse,
+}
+
+/// An area, like home, whose photos get their location left out of captions.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PrivateZone {
+    pub name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub radius_m: f64,
+    #[serde(default)]
+    pub mode: ZoneMode,
+}
+
+impl PrivateZone {
+    /// Parses `<name> [<lat>,<lon>] [<radius>m|km] [coarse]`. The coordinates may be left
+    /// out when `fallback` gives them, as for a reply to a shared location.
+    pub fn parse(args: &str, fallback: Option<(f64, f64)>) -> Option<Self> {
+        let mut words = args.split_whitespace();
+        let name = words.next()?.to_string();
+        let mut position = None;
+        let mut radius_m = DEFAULT_ZONE_RADIUS_M;
+        let mut mode = ZoneMode::Hide;
+        for word in words {
+            let word = word.to_ascii_lowercase();
+            if word == "coarse" {
+                mode = ZoneMode::Coarse;
+            } else if let Some((latitude, longitude)) = word.split_once(',') {
+                let latitude: f64 = latitude.trim().parse().ok()?;
+                let longitude: f64 = longitude.trim().parse().ok()?;
+                if latitude.abs() > 90.0 || longitude.abs() > 180.0 {
+                    return None;
+                }
+                position = Some((latitude, longitude));
+            } else if let Some(km) = word.strip_suffix("km") {
+                radius_m = km.parse::<f64>().ok()? * 1000.0;
+            } else {
+                radius_m = word.trim_end_matches('m').parse().ok()?;
+            }
+        }
+
+        let (latitude, longitude) = position.or(fallback)?;
+        if !(radius_m > 0.0 && radius_m <= MAX_ZONE_RADIUS_M) {
+            return None;
+        }
+        Some(Self {
+            name,
+            latitude,
+            longitude,
+            radius_m,
+            mode,
+        })
+    }
+}
+
+/// The user's private zones without their names, small enough to copy around with the
+/// caption options.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ZoneSet([Option<(f64, f64, f64, ZoneMode)>; MAX_PRIVATE_ZONES]);
+
+impl ZoneSet {
+    fn new(zones: &[PrivateZone]) -> Self {
+        let mut set = Self::default();
+        for (slot, zone) in set.0.iter_mut().zip(zones) {
+            *slot = Some((zone.latitude, zone.longitude, zone.radius_m, zone.mode));
+        }
+        set
+    }
+
+    /// The strictest mode of the zones the point falls in.
+    pub fn mode_at(&self, latitude: f64, longitude: f64) -> Option<ZoneMode> {
+        self.0
+            .iter()
+            .flatten()
+            .filter(|(zone_latitude, zone_longitude, radius_m, _)| {
+                haversine_km((*zone_latitude, *zone_longitude), (latitude, longitude)) * 1000.0
+                    <= *radius_m
+            })
+            .map(|(.., mode)| *mode)
+            .min_by_key(|mode| *mode != ZoneMode::Hide)
+    }
+}
+
+/// Settings group and channel admins can change for their chat.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChatSettings {
+    pub trigger: TriggerMode,
+    /// Set with `/chatstyle`; everyone's own style applies otherwise.
+    pub style: Option<CaptionStyle>,
+    /// Set with `/chatprivacy`; members can still hide more for themselves.
+    pub gps: Option<GpsPrivacy>,
+}
+
+impl ChatSettings {
+    /// `user`'s settings as they apply in this chat.
+    pub fn apply(&self, mut user: UserSettings) -> UserSettings {
+        if let Some(style) = self.style {
+            user.style = style;
+        }
+        if let Some(gps) = self.gps {
+            user.gps = user.gps.stricter(gps);
+        }
+        user
+    }
+}
+
+/// Settings every user can change for the captions generated for them.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserSettings {
+    pub gps: GpsPrivacy,
+    pub style: CaptionStyle,
+    pub labels: LabelStyle,
+    pub hidden_sections: Sections,
+    /// Opted in to text recognition with `/ocr on`.
+    pub ocr: bool,
+    /// Opted in to naming nearby landmarks with `/nearby on`.
+    pub nearby: bool,
+    /// Set with `/privatezone`; at most [`MAX_PRIVATE_ZONES`].
+    pub private_zones: Vec<PrivateZone>,
+    pub precision: CoordinatePrecision,
+    /// Set with `/units`.
+    pub units: Units,
+}
+
+impl UserSettings {
+    pub fn caption_options(&self) -> CaptionOptions {
+        let hidden = self.hidden_sections;
+        CaptionOptions {
+            hide_coordinates: matches!(
+                self.gps,
+                GpsPrivacy::HideCoordinates | GpsPrivacy::HideLocation
+            ) || hidden.contains(Section::Gps)
+                || self.precision == CoordinatePrecision::City,
+            hide_location: self.gps == GpsPrivacy::HideLocation
+                || hidden.contains(Section::Location),
+            spoiler_location: self.gps == GpsPrivacy::Spoiler,
+            style: self.style,
+            labels: self.labels,
+            detect_text: self.ocr,
+            nearby: self.nearby,
+            coarse_location: false,
+            precision: self.precision,
+            private_zones: ZoneSet::new(&self.private_zones),
+            units: self.units,
+            hidden,
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SettingsData {
+    chats: HashMap<i64, ChatSettings>,
+    users: HashMap<u64, UserSettings>,
+}
+
+/// Settings kept in the [`Storage`] database, with a copy in memory so captions can be
+/// laid out without waiting on it.
+pub struct SettingsStore {
+    storage: Storage,
+    data: Mutex<SettingsData>,
+}
+
+impl SettingsStore {
+    /// Loads every stored preference. On the first start with an empty database, the
+    /// `settings.json` of older versions at `legacy_path` is imported and renamed to
+    /// `settings.json.imported`.
+    pub fn open(storage: Storage, legacy_path: &Path) -> Result<Self> {
+        let mut data = SettingsData {
+            chats: storage.load_chat_preferences()?,
+            users: storage.load_user_preferences()?,
+        };
+        if data.chats.is_empty()
+            && data.users.is_empty()
+            && let Some(legacy) = read_legacy_file(legacy_path)?
+        {
+            storage.import_preferences(&legacy.users, &legacy.chats)?;
+            tracing::info!(
+                "Imported the settings of {} users and {} chats from `{}`",
+                legacy.users.len(),
+                legacy.chats.len(),
+                legacy_path.display()
+            );
+            let imported = legacy_path.with_extension("json.imported");
+            if let Err(err) = std::fs::rename(legacy_path, &imported) {
+                tracing::warn!("Failed to rename the imported settings file: {err}");
+            }
+            data = legacy;
+        }
+
+        Ok(Self {
+            storage,
+            data: Mutex::new(data),
+        })
+    }
+
+    pub fn chat(&self, chat_id: ChatId) -> ChatSettings {
+        let data = self.data.lock().unwrap_or_else(|err| err.into_inner());
+        data.chats.get(&chat_id.0).cloned().unwrap_or_default()
+    }
+
+    /// Applies `update` to the chat's settings and stores them.
+    pub async fn update_chat(
+        &self,
+        chat_id: ChatId,
+        update: impl FnOnce(&mut ChatSettings),
+    ) -> Result<()> {
+        let settings = {
+            let mut data = self.data.lock().unwrap_or_else(|err| err.into_inner());
+            let settings = data.chats.entry(chat_id.0).or_default();
+            update(settings);
+            settings.clone()
+        };
+        self.storage.save_chat_preferences(chat_id, &settings).await
+    }
+
+    pub fn user(&self, user_id: UserId) -> UserSettings {
+        let data = self.data.lock().unwrap_or_else(|err| err.into_inner());
+        data.users.get(&user_id.0).cloned().unwrap_or_default()
+    }
+
+    /// Applies `update` to the user's settings and stores them.
+    pub async fn update_user(
+        &self,
+        user_id: UserId,
+        update: impl FnOnce(&mut UserSettings),
+    ) -> Result<()> {
+        let settings = {
+            let mut data = self.data.lock().unwrap_or_else(|err| err.into_inner());
+            let settings = data.users.entry(user_id.0).or_default();
+            update(settings);
+            settings.clone()
+        };
+        self.storage.save_user_preferences(user_id, &settings).await
+    }
+
+    /// Drops the user's settings, so they are back to the defaults.
+    pub async fn forget_user(&self, user_id: UserId) -> Result<()> {
+        {
+            let mut data = self.data.lock().unwrap_or_else(|err| err.into_inner());
+            data.users.remove(&user_id.0);
+        }
+        self.storage.delete_user_preferences(user_id).await
+    }
+}
+
+/// The settings file older versions kept, or `None` when there is none.
+fn read_legacy_file(path: &Path) -> Result<Option<SettingsData>> {
+    match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .map(Some)
+            .with_context(|| format!("Failed to parse settings file at `{}`", path.display())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err)
+            .with_context(|| format!("Failed to read settings file at `{}`", path.display())),
+    }
+}
@@ -0,0 +1,105 @@
+//! Stopping on SIGTERM or SIGINT without dropping images that are being worked on.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+
+/// How long shutdown waits for in-flight work before giving up on it.
+pub const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Counts the detached handler tasks still running, so shutdown can wait for them.
+#[derive(Clone, Default)]
+pub struct InFlight {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    running: AtomicUsize,
+    idle: Notify,
+}
+
+/// Held by a running task; dropping it, even on a panic, counts the task as done.
+struct Running(Arc<Inner>);
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        if self.0.running.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+impl InFlight {
+    /// Spawns `task` on the runtime and tracks it until it finishes.
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.inner.running.fetch_add(1, Ordering::AcqRel);
+        let running = Running(Arc::clone(&self.inner));
+        // Each task collects its own Sentry breadcrumbs.
+        #[cfg(feature = "sentry")]
+        let task = sentry::SentryFutureExt::bind_hub(
+            task,
+            sentry::Hub::new_from_top(sentry::Hub::current()),
+        );
+        tokio::spawn(async move {
+            let _running = running;
+            task.await;
+        });
+    }
+
+    pub fn running(&self) -> usize {
+        self.inner.running.load(Ordering::Acquire)
+    }
+
+    /// Waits until no tracked task is running, or `timeout` passes; `false` when tasks
+    /// were still running at the end.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        let idle = async {
+            loop {
+                // Created before the check, so a task finishing in between still wakes it.
+                let notified = self.inner.idle.notified();
+                if self.running() == 0 {
+                    return;
+                }
+                notified.await;
+            }
+        };
+        tokio::time::timeout(timeout, idle).await.is_ok()
+    }
+}
+
+/// Resolves on the first SIGINT (Ctrl+C) or, on Unix, SIGTERM.
+pub async fn signal() {
+    let interrupt = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            tracing::warn!("Failed to listen for Ctrl+C: {err:?}");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = interrupt => {}
+                    _ = terminate.recv() => {}
+                }
+            }
+            Err(err) => {
+                tracing::warn!("Failed to listen for SIGTERM: {err:?}");
+                interrupt.await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    interrupt.await;
+}
@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use grammers_client::Client as GramClient;
+use teloxide::types::Me;
+
+use crate::album::AlbumCollector;
+use crate::batch::BatchCollector;
+use crate::broadcast::KnownChats;
+use crate::canvas::Typeface;
+use crate::compare::ComparePairings;
+use crate::config::Config;
+use crate::error_report::ErrorReports;
+use crate::geo::Lookups;
+use crate::history::HistoryStore;
+use crate::moderation::Moderation;
+#[cfg(feature = "ocr")]
+use crate::ocr::OcrProvider;
+use crate::premium::Premium;
+use crate::queue::WorkQueue;
+use crate::quota::Quota;
+use crate::recent::RecentResults;
+use crate::replies::ReplyIndex;
+use crate::settings::SettingsStore;
+use crate::shutdown::InFlight;
+use crate::staticmap::StaticMapProvider;
+use crate::stats::StatsStore;
+use crate::storage::Storage;
+use crate::telegraph::Telegraph;
+
+/// Everything the update handlers share, injected once through dptree dependencies.
+pub struct AppState {
+    pub me: Me,
+    /// `None` with a local Bot API server and no credentials for the secondary client.
+    pub extra_client: Option<GramClient>,
+    pub recent: RecentResults,
+    pub settings: SettingsStore,
+    /// The database behind the settings, for features that keep their own tables.
+    pub storage: Storage,
+    /// The configuration loaded at startup, operators included.
+    pub config: Arc<Config>,
+    pub moderation: Moderation,
+    /// How many images each user may still have read today.
+    pub quota: Quota,
+    /// Who bought premium with Telegram Stars, and what it costs.
+    pub premium: Premium,
+    /// Every chat the bot has answered in, for `/broadcast`.
+    pub known_chats: KnownChats,
+    pub stats: StatsStore,
+    pub history: HistoryStore,
+    pub albums: AlbumCollector,
+    pub replies: ReplyIndex,
+    pub http: reqwest::Client,
+    /// Fetches the images users link to; see [`crate::remote::client`].
+    pub links: reqwest::Client,
+    /// Geocoding and the caption's other lookups; shared by every bot the process answers
+    /// as.
+    pub lookups: Arc<Lookups>,
+    /// `None` when map thumbnails are turned off.
+    pub static_map: Option<StaticMapProvider>,
+    pub compare: ComparePairings,
+    /// `None` when long reports are cut off instead of published.
+    pub telegraph: Option<Telegraph>,
+    pub batches: BatchCollector,
+    /// `None` when no font could be loaded, which turns watermarks and frames off.
+    pub typeface: Option<Typeface>,
+    /// `None` when text recognition is turned off.
+    #[cfg(feature = "ocr")]
+    pub ocr: Option<OcrProvider>,
+    /// Handler tasks still running, which shutdown waits for.
+    pub in_flight: InFlight,
+    /// Where images the bot failed to read are reported, if anywhere.
+    pub error_reports: ErrorReports,
+    /// Limits how many images are read at once, in each chat's order; shared by every bot
+    /// the process answers as.
+    pub queue: Arc<WorkQueue>,
+}
@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use reqwest::{Client, Url, header};
+
+use crate::config::Config;
+
+/// OpenStreetMap's public static map renderer.
+const DEFAULT_TEMPLATE: &str = "https://staticmap.openstreetmap.de/staticmap.php?center={lat},{lon}&zoom={zoom}&size=600x400&markers={lat},{lon},red-pushpin";
+const DEFAULT_ZOOM: u8 = 14;
+/// Maps are a nice-to-have; never hold up the reply for long.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_MAP_SIZE: usize = 5 * 1024 * 1024;
+
+/// A static map service addressed through a URL template.
+///
+/// The template may use the `{lat}`, `{lon}` and `{zoom}` placeholders.
+pub struct StaticMapProvider {
+    template: String,
+    zoom: u8,
+}
+
+impl StaticMapProvider {
+    /// Configures the provider from `static_map_url` and `static_map_zoom`.
+    ///
+    /// Falls back to OpenStreetMap; `off` turns map thumbnails off entirely.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let template = match config.static_map_url.as_deref() {
+            Some(url) if url.eq_ignore_ascii_case("off") => return None,
+            Some(url) => url.to_string(),
+            None => DEFAULT_TEMPLATE.to_string(),
+        };
+        let zoom = config.static_map_zoom.unwrap_or(DEFAULT_ZOOM);
+
+        Some(Self { template, zoom })
+    }
+
+    fn url(&self, latitude: f64, longitude: f64) -> Result<Url> {
+        let url = self
+            .template
+            .replace("{lat}", &format!("{latitude:.6}"))
+            .replace("{lon}", &format!("{longitude:.6}"))
+            .replace("{zoom}", &self.zoom.to_string());
+        Url::parse(&url).with_context(|| format!("Invalid static map URL `{url}`"))
+    }
+
+    /// Downloads the map image centered on the point.
+    pub async fn fetch(&self, client: &Client, latitude: f64, longitude: f64) -> Result<Vec<u8>> {
+        let response = client
+            .get(self.url(latitude, longitude)?)
+            .timeout(FETCH_TIMEOUT)
+            .send()
+            .await
+            .context("Static map request failed")?
+            .error_for_status()
+            .context("Static map provider returned an error")?;
+
+        let is_image = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.starts_with("image/"))
+            .unwrap_or(false);
+        if !is_image {
+            bail!("Static map provider did not return an image");
+        }
+
+        let bytes = response
+            .bytes()
+            .await
+            .context("Failed to read static map image")?;
+        if bytes.len() > MAX_MAP_SIZE {
+            bail!("Static map image is too large ({} bytes)", bytes.len());
+        }
+
+        Ok(bytes.to_vec())
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use teloxide::types::UserId;
+
+use crate::exif::{self, ShotFacts};
+
+/// How many cameras and lenses `/stats` lists.
+const TOP_ENTRIES: usize = 3;
+
+/// Focal length buckets, by 35mm equivalent where the camera records it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FocalRange {
+    UltraWide,
+    Wide,
+    Standard,
+    ShortTele,
+    Telephoto,
+    SuperTele,
+}
+
+impl FocalRange {
+    fn from_mm(value: f64) -> Self {
+        match value {
+            value if value < 24.0 => Self::UltraWide,
+            value if value <= 35.0 => Self::Wide,
+            value if value <= 70.0 => Self::Standard,
+            value if value <= 135.0 => Self::ShortTele,
+            value if value <= 300.0 => Self::Telephoto,
+            _ => Self::SuperTele,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::UltraWide => "<24mm",
+            Self::Wide => "24–35mm",
+            Self::Standard => "36–70mm",
+            Self::ShortTele => "71–135mm",
+            Self::Telephoto => "136–300mm",
+            Self::SuperTele => ">300mm",
+        }
+    }
+}
+
+/// What one user has sent the bot so far.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserStats {
+    pub images: u64,
+    pub cameras: HashMap<String, u64>,
+    pub lenses: HashMap<String, u64>,
+    pub focal_ranges: BTreeMap<FocalRange, u64>,
+}
+
+impl UserStats {
+    fn record(&mut self, facts: &ShotFacts) {
+        self.images += 1;
+        if facts.camera != exif::UNKNOWN_CAMERA {
+            *self.cameras.entry(facts.camera.clone()).or_default() += 1;
+        }
+        if facts.lens != exif::UNKNOWN_LENS {
+            *self.lenses.entry(facts.lens.clone()).or_default() += 1;
+        }
+        if let Some(value) = facts.focal_length_mm.filter(|value| *value > 0.0) {
+            *self
+                .focal_ranges
+                .entry(FocalRange::from_mm(value))
+                .or_default() += 1;
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct StatsData {
+    users: HashMap<u64, UserStats>,
+}
+
+/// Per-user usage counters persisted as a JSON file next to the settings.
+pub struct StatsStore {
+    path: PathBuf,
+    data: Mutex<StatsData>,
+}
+
+impl StatsStore {
+    /// Loads the stats file, starting empty when it does not exist yet.
+    pub fn open(path: PathBuf) -> Result<Self> {
+        let data = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("Failed to parse stats file at `{}`", path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => StatsData::default(),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Failed to read stats file at `{}`", path.display()));
+            }
+        };
+
+        Ok(Self {
+            path,
+            data: Mutex::new(data),
+        })
+    }
+
+    pub fn user(&self, user_id: UserId) -> UserStats {
+        let data = self.data.lock().unwrap_or_else(|err| err.into_inner());
+        data.users.get(&user_id.0).cloned().unwrap_or_default()
+    }
+
+    /// Counts one processed image for the user and writes the file back to disk.
+    pub fn record(&self, user_id: UserId, facts: &ShotFacts) -> Result<()> {
+        let mut data = self.data.lock().unwrap_or_else(|err| err.into_inner());
+        data.users.entry(user_id.0).or_default().record(facts);
+        self.persist(&data)
+    }
+
+    /// Drops the user's counters and writes the file back to disk.
+    pub fn forget(&self, user_id: UserId) -> Result<()> {
+        let mut data = self.data.lock().unwrap_or_else(|err| err.into_inner());
+        if data.users.remove(&user_id.0).is_none() {
+            return Ok(());
+        }
+        self.persist(&data)
+    }
+
+    fn persist(&self, data: &StatsData) -> Result<()> {
+        let bytes = serde_json::to_vec(data).context("Failed to serialize stats")?;
+
+        // Write to a sibling file first so a crash never leaves a half-written stats file.
+        let tmp_path = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, bytes)
+            .with_context(|| format!("Failed to write stats file at `{}`", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, &self.path)
+            .with_context(|| format!("Failed to replace stats file at `{}`", self.path.display()))
+    }
+}
+
+/// The `/stats` answer: totals, favourite gear and the focal length distribution.
+pub fn format_stats(stats: &UserStats, locale: &str) -> String {
+    if stats.images == 0 {
+        return rust_i18n::t!("stats.empty", locale = locale).into_owned();
+    }
+
+    let mut output = String::new();
+    writeln!(
+        output,
+        "📊 {}",
+        rust_i18n::t!("stats.title", locale = locale)
+    )
+    .ok();
+    writeln!(
+        output,
+        "🖼️ {}",
+        rust_i18n::t!("stats.images", locale = locale, count = stats.images)
+    )
+    .ok();
+
+    write_top(
+        &mut output,
+        "📸",
+        &rust_i18n::t!("stats.cameras", locale = locale),
+        &stats.cameras,
+    );
+    write_top(
+        &mut output,
+        "🔭",
+        &rust_i18n::t!("stats.lenses", locale = locale),
+        &stats.lenses,
+    );
+
+    let with_focal: u64 = stats.focal_ranges.values().sum();
+    if with_focal > 0 {
+        writeln!(
+            output,
+            "\n🎯 {}:",
+            rust_i18n::t!("stats.focal_lengths", locale = locale)
+        )
+        .ok();
+        for (range, count) in &stats.focal_ranges {
+            let percent = *count as f64 * 100.0 / with_focal as f64;
+            writeln!(output, "  {}: {count} ({percent:.0}%)", range.label()).ok();
+        }
+    }
+
+    while output.ends_with('\n') {
+        output.pop();
+    }
+    output
+}
+
+fn write_top(output: &mut String, icon: &str, heading: &str, counts: &HashMap<String, u64>) {
+    if counts.is_empty() {
+        return;
+    }
+
+    let mut entries: Vec<(&String, &u64)> = counts.iter().collect();
+    // Most used first; ties in name order so the answer does not shuffle between calls.
+    entries.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+
+    writeln!(output, "\n{icon} {heading}:").ok();
+    for (name, count) in entries.into_iter().take(TOP_ENTRIES) {
+        writeln!(output, "  {name}: {count}").ok();
+    }
+}
@@ -0,0 +1,477 @@
+//! The SQLite database holding what users and chats have set up with the bot.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OptionalExtension, params};
+use teloxide::types::{ChatId, UserId};
+use tokio::task;
+
+use crate::moderation::Access;
+use crate::settings::{ChatSettings, UserSettings};
+
+/// Run in order on every start; each statement must be safe to repeat.
+const SCHEMA: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS user_preferences (
+        user_id INTEGER PRIMARY KEY,
+        settings TEXT NOT NULL,
+        updated_at INTEGER NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS chat_preferences (
+        chat_id INTEGER PRIMARY KEY,
+        settings TEXT NOT NULL,
+        updated_at INTEGER NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS user_access (
+        user_id INTEGER PRIMARY KEY,
+        access TEXT NOT NULL,
+        updated_at INTEGER NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS bot_settings (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS entitlements (
+        user_id INTEGER PRIMARY KEY,
+        premium_until INTEGER NOT NULL,
+        charge_id TEXT NOT NULL,
+        updated_at INTEGER NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS known_chats (
+        chat_id INTEGER PRIMARY KEY,
+        first_seen INTEGER NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS daily_usage (
+        user_id INTEGER NOT NULL,
+        day INTEGER NOT NULL,
+        count INTEGER NOT NULL,
+        PRIMARY KEY (user_id, day)
+    )",
+    "CREATE TABLE IF NOT EXISTS daily_metrics (
+        day TEXT NOT NULL,
+        metric TEXT NOT NULL,
+        count INTEGER NOT NULL,
+        PRIMARY KEY (day, metric)
+    )",
+];
+
+/// A handle to the database, cheap to clone. Preferences are stored as JSON, so new
+/// settings only need a serde default rather than a migration.
+///
+/// Queries run on the blocking thread pool; the startup loaders run inline.
+#[derive(Clone)]
+pub struct Storage {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl Storage {
+    /// Opens the database at `path`, creating it and its tables when needed.
+    pub fn open(path: &Path) -> Result<Self> {
+        let connection = Connection::open(path)
+            .with_context(|| format!("Failed to open database at `{}`", path.display()))?;
+        connection
+            .pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))
+            .context("Failed to enable the database's write-ahead log")?;
+        for statement in SCHEMA {
+            connection
+                .execute(statement, [])
+                .context("Failed to set up the database schema")?;
+        }
+
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    /// Every user's preferences. Rows that no longer parse are skipped with a warning.
+    pub fn load_user_preferences(&self) -> Result<HashMap<u64, UserSettings>> {
+        let connection = self.lock();
+        load_rows(
+            &connection,
+            "SELECT user_id, settings FROM user_preferences",
+        )
+        .map(|rows| rows.into_iter().map(|(id, row)| (id as u64, row)).collect())
+    }
+
+    /// Every chat's preferences. Rows that no longer parse are skipped with a warning.
+    pub fn load_chat_preferences(&self) -> Result<HashMap<i64, ChatSettings>> {
+        let connection = self.lock();
+        load_rows(
+            &connection,
+            "SELECT chat_id, settings FROM chat_preferences",
+        )
+        .map(|rows| rows.into_iter().collect())
+    }
+
+    pub async fn save_user_preferences(
+        &self,
+        user_id: UserId,
+        settings: &UserSettings,
+    ) -> Result<()> {
+        let json = serde_json::to_string(settings).context("Failed to serialize settings")?;
+        // Telegram user ids fit in 52 bits, so they survive SQLite's signed integers.
+        let id = user_id.0 as i64;
+        self.run(move |connection| {
+            connection
+                .execute(
+                    "INSERT INTO user_preferences (user_id, settings, updated_at)
+                     VALUES (?1, ?2, ?3)
+                     ON CONFLICT (user_id) DO UPDATE
+                     SET settings = excluded.settings, updated_at = excluded.updated_at",
+                    params![id, json, now_secs()],
+                )
+                .context("Failed to store user preferences")?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn save_chat_preferences(
+        &self,
+        chat_id: ChatId,
+        settings: &ChatSettings,
+    ) -> Result<()> {
+        let json = serde_json::to_string(settings).context("Failed to serialize settings")?;
+        let id = chat_id.0;
+        self.run(move |connection| {
+            connection
+                .execute(
+                    "INSERT INTO chat_preferences (chat_id, settings, updated_at)
+                     VALUES (?1, ?2, ?3)
+                     ON CONFLICT (chat_id) DO UPDATE
+                     SET settings = excluded.settings, updated_at = excluded.updated_at",
+                    params![id, json, now_secs()],
+                )
+                .context("Failed to store chat preferences")?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn delete_user_preferences(&self, user_id: UserId) -> Result<()> {
+        let id = user_id.0 as i64;
+        self.run(move |connection| {
+            connection
+                .execute(
+                    "DELETE FROM user_preferences WHERE user_id = ?1",
+                    params![id],
+                )
+                .context("Failed to delete user preferences")?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Every user on the allowlist or blocklist.
+    pub fn load_access(&self) -> Result<Vec<(u64, Access)>> {
+        let connection = self.lock();
+        let mut statement = connection
+            .prepare("SELECT user_id, access FROM user_access")
+            .context("Failed to read the access lists")?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })
+            .context("Failed to read the access lists")?;
+
+        let mut access = Vec::new();
+        for row in rows {
+            let (id, value) = row.context("Failed to read the access lists")?;
+            match Access::parse(&value) {
+                Some(entry) => access.push((id as u64, entry)),
+                None => tracing::warn!("Skipping unknown access `{value}` of user {id}"),
+            }
+        }
+        Ok(access)
+    }
+
+    pub fn allowlist_only(&self) -> Result<bool> {
+        let connection = self.lock();
+        let value: Option<String> = connection
+            .query_row(
+                "SELECT value FROM bot_settings WHERE key = 'allowlist_only'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Failed to read the allowlist mode")?;
+        Ok(value.as_deref() == Some("1"))
+    }
+
+    /// Puts the user on a list, or with `None` takes them off.
+    pub async fn set_access(&self, user_id: UserId, access: Option<Access>) -> Result<()> {
+        let id = user_id.0 as i64;
+        self.run(move |connection| {
+            match access {
+                Some(access) => connection.execute(
+                    "INSERT INTO user_access (user_id, access, updated_at) VALUES (?1, ?2, ?3)
+                     ON CONFLICT (user_id) DO UPDATE
+                     SET access = excluded.access, updated_at = excluded.updated_at",
+                    params![id, access.as_str(), now_secs()],
+                ),
+                None => {
+                    connection.execute("DELETE FROM user_access WHERE user_id = ?1", params![id])
+                }
+            }
+            .context("Failed to store the access lists")?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// `(user id, premium expiry)` of everyone who ever bought premium.
+    pub fn load_entitlements(&self) -> Result<Vec<(u64, u64)>> {
+        let connection = self.lock();
+        let mut statement = connection
+            .prepare("SELECT user_id, premium_until FROM entitlements")
+            .context("Failed to read entitlements")?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((row.get::<_, i64>(0)? as u64, row.get::<_, i64>(1)? as u64))
+            })
+            .context("Failed to read entitlements")?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+            .context("Failed to read entitlements")
+    }
+
+    /// Stores the user's new premium expiry with the Telegram charge that paid for it.
+    pub async fn grant_entitlement(
+        &self,
+        user_id: UserId,
+        until: u64,
+        charge_id: String,
+    ) -> Result<()> {
+        let id = user_id.0 as i64;
+        self.run(move |connection| {
+            connection
+                .execute(
+                    "INSERT INTO entitlements (user_id, premium_until, charge_id, updated_at)
+                     VALUES (?1, ?2, ?3, ?4)
+                     ON CONFLICT (user_id) DO UPDATE SET premium_until = excluded.premium_until,
+                     charge_id = excluded.charge_id, updated_at = excluded.updated_at",
+                    params![id, until as i64, charge_id, now_secs()],
+                )
+                .context("Failed to store entitlement")?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn set_allowlist_only(&self, enabled: bool) -> Result<()> {
+        let value = if enabled { "1" } else { "0" };
+        self.run(move |connection| {
+            connection
+                .execute(
+                    "INSERT INTO bot_settings (key, value) VALUES ('allowlist_only', ?1)
+                     ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+                    params![value],
+                )
+                .context("Failed to store the allowlist mode")?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Adds `images` to the user's count for `day`, days since the Unix epoch, unless the
+    /// count would go over `limit`. Returns whether they were counted.
+    pub async fn take_quota(
+        &self,
+        user_id: UserId,
+        day: i64,
+        images: u32,
+        limit: u32,
+    ) -> Result<bool> {
+        let id = user_id.0 as i64;
+        self.run(move |connection| {
+            connection
+                .execute(
+                    "DELETE FROM daily_usage WHERE user_id = ?1 AND day < ?2",
+                    params![id, day],
+                )
+                .context("Failed to prune the daily usage")?;
+            if images > limit {
+                return Ok(false);
+            }
+            let changed = connection
+                .execute(
+                    "INSERT INTO daily_usage (user_id, day, count) VALUES (?1, ?2, ?3)
+                     ON CONFLICT (user_id, day) DO UPDATE SET count = count + excluded.count
+                     WHERE count + excluded.count <= ?4",
+                    params![id, day, images, limit],
+                )
+                .context("Failed to store the daily usage")?;
+            Ok(changed > 0)
+        })
+        .await
+    }
+
+    pub async fn remember_chat(&self, chat_id: ChatId) -> Result<()> {
+        let id = chat_id.0;
+        self.run(move |connection| {
+            connection
+                .execute(
+                    "INSERT OR IGNORE INTO known_chats (chat_id, first_seen) VALUES (?1, ?2)",
+                    params![id, now_secs()],
+                )
+                .context("Failed to store known chat")?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn forget_chat(&self, chat_id: ChatId) -> Result<()> {
+        let id = chat_id.0;
+        self.run(move |connection| {
+            connection
+                .execute("DELETE FROM known_chats WHERE chat_id = ?1", params![id])
+                .context("Failed to delete known chat")?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Every chat the bot has seen, oldest first.
+    pub async fn known_chats(&self) -> Result<Vec<i64>> {
+        self.run(|connection| {
+            let mut statement = connection
+                .prepare("SELECT chat_id FROM known_chats ORDER BY first_seen")
+                .context("Failed to read known chats")?;
+            let rows = statement
+                .query_map([], |row| row.get::<_, i64>(0))
+                .context("Failed to read known chats")?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()
+                .context("Failed to read known chats")
+        })
+        .await
+    }
+
+    /// Adds `counts` to the `day`'s totals, a `YYYY-MM-DD` date.
+    pub async fn add_metrics(&self, day: String, counts: Vec<(&'static str, u64)>) -> Result<()> {
+        self.run(move |connection| {
+            for (metric, count) in counts {
+                connection
+                    .execute(
+                        "INSERT INTO daily_metrics (day, metric, count) VALUES (?1, ?2, ?3)
+                         ON CONFLICT (day, metric) DO UPDATE SET count = count + excluded.count",
+                        params![day, metric, count as i64],
+                    )
+                    .context("Failed to store usage metrics")?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// `(day, metric, count)` rows from `since` on, a `YYYY-MM-DD` date.
+    pub async fn metrics_since(&self, since: String) -> Result<Vec<(String, String, u64)>> {
+        self.run(move |connection| {
+            let mut statement = connection
+                .prepare("SELECT day, metric, count FROM daily_metrics WHERE day >= ?1")
+                .context("Failed to read usage metrics")?;
+            let rows = statement
+                .query_map(params![since], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, i64>(2)? as u64,
+                    ))
+                })
+                .context("Failed to read usage metrics")?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()
+                .context("Failed to read usage metrics")
+        })
+        .await
+    }
+
+    /// Stores preferences read from an older settings file in one transaction, so a
+    /// failed import can simply be retried on the next start.
+    pub fn import_preferences(
+        &self,
+        users: &HashMap<u64, UserSettings>,
+        chats: &HashMap<i64, ChatSettings>,
+    ) -> Result<()> {
+        let mut connection = self.lock();
+        let transaction = connection
+            .transaction()
+            .context("Failed to start the settings import")?;
+        let now = now_secs();
+        for (id, settings) in users {
+            let json = serde_json::to_string(settings).context("Failed to serialize settings")?;
+            transaction
+                .execute(
+                    "INSERT OR REPLACE INTO user_preferences (user_id, settings, updated_at)
+                     VALUES (?1, ?2, ?3)",
+                    params![*id as i64, json, now],
+                )
+                .context("Failed to import user preferences")?;
+        }
+        for (id, settings) in chats {
+            let json = serde_json::to_string(settings).context("Failed to serialize settings")?;
+            transaction
+                .execute(
+                    "INSERT OR REPLACE INTO chat_preferences (chat_id, settings, updated_at)
+                     VALUES (?1, ?2, ?3)",
+                    params![id, json, now],
+                )
+                .context("Failed to import chat preferences")?;
+        }
+        transaction
+            .commit()
+            .context("Failed to finish the settings import")
+    }
+
+    /// Runs `query` on the blocking thread pool.
+    async fn run<T: Send + 'static>(
+        &self,
+        query: impl FnOnce(&Connection) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let connection = Arc::clone(&self.connection);
+        task::spawn_blocking(move || {
+            let connection = connection.lock().unwrap_or_else(|err| err.into_inner());
+            query(&connection)
+        })
+        .await
+        .context("Database task panicked")?
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.connection
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// `(id, JSON)` rows, parsed.
+fn load_rows<T: serde::de::DeserializeOwned>(
+    connection: &Connection,
+    query: &str,
+) -> Result<Vec<(i64, T)>> {
+    let mut statement = connection
+        .prepare(query)
+        .context("Failed to read preferences")?;
+    let rows = statement
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })
+        .context("Failed to read preferences")?;
+
+    let mut parsed = Vec::new();
+    for row in rows {
+        let (id, json) = row.context("Failed to read preferences")?;
+        match serde_json::from_str(&json) {
+            Ok(settings) => parsed.push((id, settings)),
+            Err(err) => tracing::warn!("Skipping unreadable preferences of {id}: {err}"),
+        }
+    }
+    Ok(parsed)
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,393 @@
+use std::fmt;
+
+const JPEG_APP1: u8 = 0xE1;
+const JPEG_APP13: u8 = 0xED;
+const JPEG_EXIF_HEADER: &[u8] = b"Exif\0\0";
+/// PNG chunks holding free-form text such as comments, authors and XMP packets.
+const PNG_TEXT_CHUNKS: [&[u8; 4]; 3] = [b"tEXt", b"zTXt", b"iTXt"];
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Pointer tags from IFD0 to the Exif and GPS sub-IFDs.
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+
+#[derive(Debug)]
+pub enum StripError {
+    /// Only JPEG and PNG files can be rewritten.
+    Unsupported,
+    /// The file ended in the middle of a segment or chunk.
+    Malformed,
+}
+
+impl fmt::Display for StripError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported => write!(f, "image format is not supported for stripping"),
+            Self::Malformed => write!(f, "image file is truncated or malformed"),
+        }
+    }
+}
+
+impl std::error::Error for StripError {}
+
+/// A group of EXIF tags that can be removed on its own.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Gps,
+    Serials,
+    Author,
+}
+
+impl Category {
+    pub const ALL: [Category; 3] = [Category::Gps, Category::Serials, Category::Author];
+
+    fn bit(self) -> u8 {
+        match self {
+            Category::Gps => 1,
+            Category::Serials => 2,
+            Category::Author => 4,
+        }
+    }
+
+    /// Tags of this category stored directly in IFD0/IFD1 or the Exif IFD.
+    ///
+    /// GPS data lives in its own IFD, which is dropped as a whole instead.
+    fn tags(self) -> &'static [u16] {
+        match self {
+            Category::Gps => &[],
+            // BodySerialNumber, LensSerialNumber, ImageUniqueID, CameraSerialNumber (DNG),
+            // and the maker note, where most vendors hide the body serial as well.
+            Category::Serials => &[0xA431, 0xA435, 0xA420, 0xC62F, 0x927C],
+            // Artist, Copyright, XPAuthor, CameraOwnerName.
+            Category::Author => &[0x013B, 0x8298, 0x9C9D, 0xA430],
+        }
+    }
+}
+
+/// A set of [`Category`] values, small enough to travel in callback data.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct Categories(u8);
+
+impl Categories {
+    pub fn from_bits(bits: u8) -> Self {
+        let known = Category::ALL
+            .iter()
+            .fold(0, |acc, category| acc | category.bit());
+        Self(bits & known)
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn contains(self, category: Category) -> bool {
+        self.0 & category.bit() != 0
+    }
+
+    pub fn toggled(self, category: Category) -> Self {
+        Self(self.0 ^ category.bit())
+    }
+
+    fn removes_tag(self, tag: u16) -> bool {
+        Category::ALL
+            .into_iter()
+            .filter(|category| self.contains(*category))
+            .any(|category| category.tags().contains(&tag))
+    }
+}
+
+/// What to take out of an image.
+#[derive(Clone, Copy)]
+pub enum Removal {
+    /// Every EXIF, XMP and IPTC block.
+    All,
+    /// Only the chosen EXIF tags. XMP and IPTC packets mix every category in free-form
+    /// text, so they are dropped as soon as anything is removed.
+    Selected(Categories),
+}
+
+/// Returns a copy of the image with the chosen metadata removed.
+///
+/// Pixel data is copied as is, so the image is not re-encoded. Selected tags are removed
+/// from their IFDs in place and their values zeroed, so every other offset in the EXIF
+/// block stays valid.
+pub fn strip_metadata(bytes: &[u8], removal: Removal) -> Result<Vec<u8>, StripError> {
+    if bytes.starts_with(&[0xFF, 0xD8]) {
+        rewrite_jpeg(bytes, removal)
+    } else if bytes.starts_with(PNG_SIGNATURE) {
+        rewrite_png(bytes, removal)
+    } else {
+        Err(StripError::Unsupported)
+    }
+}
+
+fn rewrite_jpeg(bytes: &[u8], removal: Removal) -> Result<Vec<u8>, StripError> {
+    let mut output = Vec::with_capacity(bytes.len());
+    output.extend_from_slice(&bytes[..2]);
+
+    let mut pos = 2;
+    loop {
+        if pos + 4 > bytes.len() || bytes[pos] != 0xFF {
+            return Err(StripError::Malformed);
+        }
+
+        let marker = bytes[pos + 1];
+        // Start of scan: everything after it is entropy-coded image data.
+        if marker == 0xDA {
+            output.extend_from_slice(&bytes[pos..]);
+            return Ok(output);
+        }
+
+        let length = usize::from(u16::from_be_bytes([bytes[pos + 2], bytes[pos + 3]]));
+        let end = pos + 2 + length;
+        if length < 2 || end > bytes.len() {
+            return Err(StripError::Malformed);
+        }
+
+        let payload = &bytes[pos + 4..end];
+        match (marker, removal) {
+            (JPEG_APP1, Removal::Selected(categories)) if payload.starts_with(JPEG_EXIF_HEADER) => {
+                let mut segment = bytes[pos..end].to_vec();
+                remove_tiff_tags(&mut segment[4 + JPEG_EXIF_HEADER.len()..], categories)?;
+                output.extend_from_slice(&segment);
+            }
+            // APP1 also carries XMP, APP13 carries IPTC.
+            (JPEG_APP1 | JPEG_APP13, _) => {}
+            _ => output.extend_from_slice(&bytes[pos..end]),
+        }
+        pos = end;
+    }
+}
+
+fn rewrite_png(bytes: &[u8], removal: Removal) -> Result<Vec<u8>, StripError> {
+    let mut output = Vec::with_capacity(bytes.len());
+    output.extend_from_slice(PNG_SIGNATURE);
+
+    let mut pos = PNG_SIGNATURE.len();
+    while pos < bytes.len() {
+        if pos + 8 > bytes.len() {
+            return Err(StripError::Malformed);
+        }
+
+        let length =
+            u32::from_be_bytes([bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]]);
+        let chunk_type = &bytes[pos + 4..pos + 8];
+        // Length, type, data and CRC.
+        let end = pos + 12 + length as usize;
+        if end > bytes.len() {
+            return Err(StripError::Malformed);
+        }
+
+        let is_text = PNG_TEXT_CHUNKS
+            .iter()
+            .any(|name| name.as_slice() == chunk_type);
+        match removal {
+            _ if is_text => {}
+            Removal::All if matches!(chunk_type, b"eXIf" | b"tIME") => {}
+            Removal::Selected(categories) if chunk_type == b"eXIf" => {
+                let mut chunk = bytes[pos..end].to_vec();
+                let data_end = chunk.len() - 4;
+                remove_tiff_tags(&mut chunk[8..data_end], categories)?;
+                let crc = crc32(&chunk[4..data_end]);
+                chunk[data_end..].copy_from_slice(&crc.to_be_bytes());
+                output.extend_from_slice(&chunk);
+            }
+            _ => output.extend_from_slice(&bytes[pos..end]),
+        }
+        pos = end;
+
+        if chunk_type == b"IEND" {
+            break;
+        }
+    }
+
+    Ok(output)
+}
+
+/// Byte order of a TIFF structure.
+#[derive(Clone, Copy)]
+enum Endian {
+    Little,
+    Big,
+}
+
+/// A TIFF block (the body of an EXIF segment) edited in place.
+struct Tiff<'a> {
+    data: &'a mut [u8],
+    endian: Endian,
+}
+
+impl Tiff<'_> {
+    fn u16_at(&self, offset: usize) -> Result<u16, StripError> {
+        let bytes = self
+            .data
+            .get(offset..offset + 2)
+            .ok_or(StripError::Malformed)?;
+        let bytes = [bytes[0], bytes[1]];
+        Ok(match self.endian {
+            Endian::Little => u16::from_le_bytes(bytes),
+            Endian::Big => u16::from_be_bytes(bytes),
+        })
+    }
+
+    fn u32_at(&self, offset: usize) -> Result<u32, StripError> {
+        let bytes = self
+            .data
+            .get(offset..offset + 4)
+            .ok_or(StripError::Malformed)?;
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        Ok(match self.endian {
+            Endian::Little => u32::from_le_bytes(bytes),
+            Endian::Big => u32::from_be_bytes(bytes),
+        })
+    }
+
+    fn set_u16(&mut self, offset: usize, value: u16) {
+        let bytes = match self.endian {
+            Endian::Little => value.to_le_bytes(),
+            Endian::Big => value.to_be_bytes(),
+        };
+        self.data[offset..offset + 2].copy_from_slice(&bytes);
+    }
+
+    fn zero(&mut self, start: usize, len: usize) -> Result<(), StripError> {
+        self.data
+            .get_mut(start..start + len)
+            .ok_or(StripError::Malformed)?
+            .fill(0);
+        Ok(())
+    }
+
+    /// Byte length of the IFD at `offset`, including the count and next-IFD pointer.
+    fn ifd_len(&self, offset: usize) -> Result<usize, StripError> {
+        Ok(2 + usize::from(self.u16_at(offset)?) * 12 + 4)
+    }
+
+    /// Value of the first entry tagged `tag`, read as an offset.
+    fn pointer(&self, ifd: usize, tag: u16) -> Result<Option<usize>, StripError> {
+        let count = usize::from(self.u16_at(ifd)?);
+        for index in 0..count {
+            let entry = ifd + 2 + index * 12;
+            if self.u16_at(entry)? == tag {
+                return Ok(Some(self.u32_at(entry + 8)? as usize));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Zeroes the out-of-line value of the entry at `entry`.
+    fn zero_value(&mut self, entry: usize) -> Result<(), StripError> {
+        let unit = match self.u16_at(entry + 2)? {
+            1 | 2 | 6 | 7 => 1,
+            3 | 8 => 2,
+            4 | 9 | 11 => 4,
+            5 | 10 | 12 => 8,
+            _ => return Ok(()),
+        };
+        let len = unit * self.u32_at(entry + 4)? as usize;
+        if len > 4 {
+            let offset = self.u32_at(entry + 8)? as usize;
+            self.zero(offset, len)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the entries matching `remove` from the IFD at `ifd`.
+    ///
+    /// Later entries move up and the freed slots at the end are zeroed, so the IFD keeps
+    /// its position and size.
+    fn remove_entries(
+        &mut self,
+        ifd: usize,
+        remove: impl Fn(u16) -> bool,
+    ) -> Result<(), StripError> {
+        let count = usize::from(self.u16_at(ifd)?);
+        let old_len = self.ifd_len(ifd)?;
+        self.data
+            .get(ifd..ifd + old_len)
+            .ok_or(StripError::Malformed)?;
+
+        let next_ifd = ifd + 2 + count * 12;
+        let next_pointer = self.data[next_ifd..next_ifd + 4].to_vec();
+
+        let mut kept = 0;
+        for index in 0..count {
+            let entry = ifd + 2 + index * 12;
+            if remove(self.u16_at(entry)?) {
+                self.zero_value(entry)?;
+                continue;
+            }
+            if kept != index {
+                let target = ifd + 2 + kept * 12;
+                self.data.copy_within(entry..entry + 12, target);
+            }
+            kept += 1;
+        }
+
+        // `kept` fits because it never exceeds the original u16 count.
+        self.set_u16(ifd, kept as u16);
+        let pointer_at = ifd + 2 + kept * 12;
+        self.data[pointer_at..pointer_at + 4].copy_from_slice(&next_pointer);
+        self.zero(pointer_at + 4, old_len - (pointer_at + 4 - ifd))
+    }
+
+    /// Zeroes the IFD at `ifd` together with all out-of-line values of its entries.
+    fn erase_ifd(&mut self, ifd: usize) -> Result<(), StripError> {
+        let count = usize::from(self.u16_at(ifd)?);
+        for index in 0..count {
+            self.zero_value(ifd + 2 + index * 12)?;
+        }
+        let len = self.ifd_len(ifd)?;
+        self.zero(ifd, len)
+    }
+}
+
+fn remove_tiff_tags(data: &mut [u8], categories: Categories) -> Result<(), StripError> {
+    let endian = match data.get(0..2) {
+        Some(b"II") => Endian::Little,
+        Some(b"MM") => Endian::Big,
+        _ => return Err(StripError::Malformed),
+    };
+    let mut tiff = Tiff { data, endian };
+    let ifd0 = tiff.u32_at(4)? as usize;
+
+    if categories.contains(Category::Gps) {
+        if let Some(gps_ifd) = tiff.pointer(ifd0, TAG_GPS_IFD)? {
+            tiff.erase_ifd(gps_ifd)?;
+        }
+    }
+
+    let exif_ifd = tiff.pointer(ifd0, TAG_EXIF_IFD)?;
+    tiff.remove_entries(ifd0, |tag| {
+        (tag == TAG_GPS_IFD && categories.contains(Category::Gps)) || categories.removes_tag(tag)
+    })?;
+    if let Some(exif_ifd) = exif_ifd {
+        tiff.remove_entries(exif_ifd, |tag| categories.removes_tag(tag))?;
+    }
+
+    // IFD1 describes the embedded thumbnail and may repeat the author tags.
+    let ifd0_len = tiff.ifd_len(ifd0)?;
+    let ifd1 = tiff.u32_at(ifd0 + ifd0_len - 4)? as usize;
+    if ifd1 != 0 {
+        tiff.remove_entries(ifd1, |tag| categories.removes_tag(tag))?;
+    }
+
+    Ok(())
+}
+
+/// CRC-32 as used by PNG chunks (ISO-HDLC polynomial).
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
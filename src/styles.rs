@@ -0,0 +1,783 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+use crate::exif::{self, CaptionOptions, ParsedExif};
+use crate::formats;
+use crate::geo::sun::{Event, Phase};
+use crate::geo::{self, links};
+use crate::markup;
+use crate::settings::CoordinatePrecision;
+
+/// Callback data of the section toggles is this prefix followed by the section name.
+const SECTIONS_CALLBACK_PREFIX: &str = "sections:";
+
+/// The built-in caption layouts users pick from with `/style`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptionStyle {
+    /// One emoji-labelled line per field.
+    #[default]
+    Emoji,
+    /// Camera and exposure on a single line.
+    Minimal,
+    /// Labelled fields grouped into sections.
+    Detailed,
+    /// Gear and settings the way photographers list them under a post.
+    GearList,
+}
+
+impl CaptionStyle {
+    pub const ALL: [CaptionStyle; 4] = [
+        CaptionStyle::Emoji,
+        CaptionStyle::Minimal,
+        CaptionStyle::Detailed,
+        CaptionStyle::GearList,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "emoji" | "default" => Some(Self::Emoji),
+            "minimal" | "short" => Some(Self::Minimal),
+            "detailed" | "full" => Some(Self::Detailed),
+            "gear" | "gearlist" | "instagram" => Some(Self::GearList),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Emoji => "emoji",
+            Self::Minimal => "minimal",
+            Self::Detailed => "detailed",
+            Self::GearList => "gear",
+        }
+    }
+}
+
+/// How caption lines are labelled, chosen with `/labels`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LabelStyle {
+    #[default]
+    Emoji,
+    /// Localized words, for clients that render emoji poorly and for copy-pasting.
+    Text,
+}
+
+impl LabelStyle {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "emoji" | "icons" => Some(Self::Emoji),
+            "text" | "plain" | "words" => Some(Self::Text),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Emoji => "emoji",
+            Self::Text => "text",
+        }
+    }
+}
+
+/// Sunrise or sunset is only mentioned when the shot is this close to it.
+const SUN_EVENT_MINUTES: i64 = 90;
+
+/// A labelled caption line.
+#[derive(Clone, Copy)]
+enum Label {
+    Title,
+    Camera,
+    Lens,
+    Settings,
+    Date,
+    Light,
+    Moon,
+    Weather,
+    Place,
+    Near,
+    Altitude,
+    Speed,
+    Gps,
+}
+
+impl Label {
+    fn word(self, locale: &str) -> Cow<'static, str> {
+        match self {
+            Label::Title => rust_i18n::t!("labels.title", locale = locale),
+            Label::Camera => rust_i18n::t!("labels.camera", locale = locale),
+            Label::Lens => rust_i18n::t!("labels.lens", locale = locale),
+            Label::Settings => rust_i18n::t!("labels.settings", locale = locale),
+            Label::Date => rust_i18n::t!("labels.date", locale = locale),
+            Label::Light => rust_i18n::t!("labels.light", locale = locale),
+            Label::Moon => rust_i18n::t!("labels.moon", locale = locale),
+            Label::Weather => rust_i18n::t!("labels.weather", locale = locale),
+            Label::Place => rust_i18n::t!("labels.place", locale = locale),
+            Label::Near => rust_i18n::t!("labels.near", locale = locale),
+            Label::Altitude => rust_i18n::t!("labels.altitude", locale = locale),
+            Label::Speed => rust_i18n::t!("labels.speed", locale = locale),
+            Label::Gps => rust_i18n::t!("labels.gps", locale = locale),
+        }
+    }
+}
+
+/// A part of the caption users can switch off with `/sections`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Title,
+    Lens,
+    Date,
+    /// The place name; hiding it also skips geocoding.
+    Location,
+    Gps,
+    /// Golden and blue hour, the time to sunrise or sunset, and the moon at night.
+    Light,
+    /// The weather at the time of the shot; hiding it also skips the lookup.
+    Weather,
+}
+
+impl Section {
+    pub const ALL: [Section; 7] = [
+        Section::Title,
+        Section::Lens,
+        Section::Date,
+        Section::Location,
+        Section::Gps,
+        Section::Light,
+        Section::Weather,
+    ];
+
+    fn bit(self) -> u8 {
+        match self {
+            Section::Title => 1,
+            Section::Lens => 2,
+            Section::Date => 4,
+            Section::Location => 8,
+            Section::Gps => 16,
+            Section::Light => 32,
+            Section::Weather => 64,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Section::Title => "title",
+            Section::Lens => "lens",
+            Section::Date => "date",
+            Section::Location => "location",
+            Section::Gps => "gps",
+            Section::Light => "light",
+            Section::Weather => "weather",
+        }
+    }
+
+    fn label(self, locale: &str) -> Cow<'static, str> {
+        match self {
+            Section::Title => rust_i18n::t!("sections.title", locale = locale),
+            Section::Lens => rust_i18n::t!("sections.lens", locale = locale),
+            Section::Date => rust_i18n::t!("sections.date", locale = locale),
+            Section::Location => rust_i18n::t!("sections.location", locale = locale),
+            Section::Gps => rust_i18n::t!("sections.gps", locale = locale),
+            Section::Light => rust_i18n::t!("sections.light", locale = locale),
+            Section::Weather => rust_i18n::t!("sections.weather", locale = locale),
+        }
+    }
+}
+
+/// A set of [`Section`] values, stored as bits in the settings file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Sections(u8);
+
+impl Sections {
+    pub fn contains(self, section: Section) -> bool {
+        self.0 & section.bit() != 0
+    }
+
+    pub fn toggled(self, section: Section) -> Self {
+        Self(self.0 ^ section.bit())
+    }
+}
+
+/// One toggle per section; `hidden` are shown unchecked.
+pub fn sections_keyboard(hidden: Sections, locale: &str) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(Section::ALL.into_iter().map(|section| {
+        let mark = if hidden.contains(section) {
+            "⬜"
+        } else {
+            "✅"
+        };
+        vec![InlineKeyboardButton::callback(
+            format!("{mark} {}", section.label(locale)),
+            format!("{SECTIONS_CALLBACK_PREFIX}{}", section.as_str()),
+        )]
+    }))
+}
+
+/// Returns the section a toggle stands for, or `None` for other callback data.
+pub fn parse_sections_callback(data: &str) -> Option<Section> {
+    let name = data.strip_prefix(SECTIONS_CALLBACK_PREFIX)?;
+    Section::ALL
+        .into_iter()
+        .find(|section| section.as_str() == name)
+}
+
+/// Writes captions with the same options and language every time, for projects that use
+/// the summarizer without the bot.
+#[derive(Clone)]
+pub struct CaptionRenderer {
+    options: CaptionOptions,
+    locale: String,
+}
+
+impl CaptionRenderer {
+    /// `locale` picks the bundled translation, such as `en`, `zh-CN` or `pt`.
+    pub fn new(options: CaptionOptions, locale: &str) -> Self {
+        Self {
+            options,
+            locale: locale.to_string(),
+        }
+    }
+
+    /// The caption for `data`, as Telegram HTML; see [`render`].
+    pub fn render(&self, data: &ParsedExif) -> String {
+        render(data, self.options, &self.locale)
+    }
+}
+
+/// Renders the caption in the style the options ask for, as Telegram HTML.
+///
+/// Every line keeps its tags balanced, so captions can be cut at line breaks. Text
+/// labels are worded in `locale`.
+pub fn render(data: &ParsedExif, options: CaptionOptions, locale: &str) -> String {
+    let mut output = match options.style {
+        CaptionStyle::Emoji => render_emoji(data, options, locale),
+        CaptionStyle::Minimal => render_minimal(data, locale),
+        CaptionStyle::Detailed => render_detailed(data, options, locale),
+        CaptionStyle::GearList => render_gear_list(data, options, locale),
+    };
+
+    while output.ends_with('\n') {
+        output.pop();
+    }
+    output
+}
+
+fn render_emoji(data: &ParsedExif, options: CaptionOptions, locale: &str) -> String {
+    let mut output = String::new();
+    let label = |emoji: &str, kind: Label| match options.labels {
+        LabelStyle::Emoji => emoji.to_string(),
+        LabelStyle::Text => kind.word(locale).into_owned(),
+    };
+
+    // Emoji formatting follows the style requested by the user template.
+    if options.shows(Section::Title) {
+        writeln!(
+            output,
+            "{}: {}",
+            label("💭", Label::Title),
+            markup::escape(data.title.as_deref().unwrap_or(""))
+        )
+        .ok();
+        writeln!(output, "——————————").ok();
+    }
+    if options.shows(Section::Lens) {
+        writeln!(
+            output,
+            "{}: {} / {}",
+            label("📸", Label::Camera),
+            markup::escape(&camera_name(&data.camera, locale)),
+            markup::escape(&lens_name(&data.lens, locale))
+        )
+        .ok();
+    } else {
+        writeln!(
+            output,
+            "{}: {}",
+            label("📸", Label::Camera),
+            markup::escape(&camera_name(&data.camera, locale))
+        )
+        .ok();
+    }
+
+    let metrics = metrics(data, locale);
+    let settings = label("📝", Label::Settings);
+    if metrics.is_empty() {
+        let unknown = rust_i18n::t!("caption.parameters_unknown", locale = locale);
+        writeln!(output, "{settings}: {}", markup::escape(&unknown)).ok();
+    } else {
+        writeln!(output, "{settings}: {}", metrics.join(", ")).ok();
+    }
+
+    if options.shows(Section::Date) {
+        let mut date = markup::escape(&date_taken(data, options, locale));
+        if let Some((time, zone)) = local_time(data, options) {
+            let local = rust_i18n::t!(
+                "labels.local_time",
+                locale = locale,
+                time = formats::datetime(time, locale, options.units.clock),
+                zone = zone
+            );
+            date = format!(
+                "{date} · {}",
+                hide_in_spoiler(markup::escape(&local), options)
+            );
+        }
+        writeln!(output, "{}: {date}", label("📅", Label::Date)).ok();
+    }
+    if let Some(light) = sunlight(data, options, locale) {
+        writeln!(output, "{}: {light}", label("🌅", Label::Light)).ok();
+    }
+    if let Some(moon) = moon(data, options, locale) {
+        writeln!(output, "{}: {moon}", label("🌙", Label::Moon)).ok();
+    }
+    if let Some(weather) = weather(data, options) {
+        writeln!(output, "{}: {weather}", label("🌤️", Label::Weather)).ok();
+    }
+
+    if let Some(place) = place(data, options) {
+        writeln!(output, "{}: {place}", label("🗺️", Label::Place)).ok();
+    }
+    if let Some(near) = nearby(data, options) {
+        writeln!(output, "{}: {near}", label("🏞️", Label::Near)).ok();
+    }
+    if let Some(altitude) = altitude(data, options, locale) {
+        writeln!(output, "{}: {altitude}", label("⛰️", Label::Altitude)).ok();
+    }
+    if let Some(speed) = speed(data, options, locale) {
+        writeln!(output, "{}: {speed}", label("💨", Label::Speed)).ok();
+    }
+    if let Some(gps) = coordinates(data, options, locale) {
+        writeln!(output, "{}: {gps}", label("📍", Label::Gps)).ok();
+    }
+
+    output
+}
+
+fn render_minimal(data: &ParsedExif, locale: &str) -> String {
+    let mut parts = vec![markup::escape(&camera_name(&data.camera, locale))];
+    parts.extend(metrics(data, locale));
+    parts.join(" · ")
+}
+
+fn render_detailed(data: &ParsedExif, options: CaptionOptions, locale: &str) -> String {
+    let mut output = String::new();
+    let word = |key: &str| rust_i18n::t!(key, locale = locale);
+    let number = |text: &str| markup::escape(&formats::decimals(text, locale));
+
+    let title = data.title.as_deref().filter(|title| !title.is_empty());
+    if let Some(title) = title.filter(|_| options.shows(Section::Title)) {
+        writeln!(output, "{}{}\n", icon("💭", options), markup::escape(title)).ok();
+    }
+
+    writeln!(
+        output,
+        "{}{}",
+        icon("📷", options),
+        markup::bold(&word("labels.camera"))
+    )
+    .ok();
+    write_row(
+        &mut output,
+        &word("caption.body"),
+        &markup::escape(&camera_name(&data.camera, locale)),
+    );
+    if options.shows(Section::Lens) {
+        write_row(
+            &mut output,
+            &word("labels.lens"),
+            &markup::escape(&lens_name(&data.lens, locale)),
+        );
+    }
+
+    writeln!(
+        output,
+        "\n{}{}",
+        icon("⚙️", options),
+        markup::bold(&word("caption.exposure"))
+    )
+    .ok();
+    let focal_length = word("card.focal_length");
+    match (&data.focal_length, equivalent_focal_length(data, locale)) {
+        (Some(focal), Some(equivalent)) if !same_focal_length(data) => write_row(
+            &mut output,
+            &focal_length,
+            &number(&format!("{focal} / {equivalent}")),
+        ),
+        (Some(focal), _) => write_row(&mut output, &focal_length, &number(focal)),
+        (None, Some(equivalent)) => {
+            write_row(&mut output, &focal_length, &number(&equivalent));
+        }
+        (None, None) => {}
+    }
+    let rows = [
+        ("card.aperture", data.aperture.clone()),
+        ("card.shutter", data.shutter.clone()),
+        (
+            "caption.sensitivity",
+            data.iso.as_deref().map(|iso| iso_speed(iso, locale)),
+        ),
+    ];
+    for (key, value) in rows {
+        if let Some(value) = value {
+            write_row(&mut output, &word(key), &number(&value));
+        }
+    }
+
+    if options.shows(Section::Date) {
+        writeln!(
+            output,
+            "\n{}{}",
+            icon("🕒", options),
+            markup::bold(&word("caption.time"))
+        )
+        .ok();
+        write_row(
+            &mut output,
+            &word("caption.taken"),
+            &markup::escape(&date_taken(data, options, locale)),
+        );
+        if let Some((time, zone)) = local_time(data, options) {
+            let time = formats::datetime(time, locale, options.units.clock);
+            let local = markup::escape(&format!("{time} ({zone})"));
+            write_row(
+                &mut output,
+                &word("caption.local_time"),
+                &hide_in_spoiler(local, options),
+            );
+        }
+    }
+    if let Some(light) = sunlight(data, options, locale) {
+        write_row(&mut output, &word("labels.light"), &light);
+    }
+    if let Some(moon) = moon(data, options, locale) {
+        write_row(&mut output, &word("labels.moon"), &moon);
+    }
+    if let Some(weather) = weather(data, options) {
+        write_row(&mut output, &word("labels.weather"), &weather);
+    }
+
+    let place = place(data, options);
+    let near = nearby(data, options);
+    let altitude = altitude(data, options, locale);
+    let speed = speed(data, options, locale);
+    let gps = coordinates(data, options, locale);
+    if place.is_some() || near.is_some() || altitude.is_some() || speed.is_some() || gps.is_some() {
+        writeln!(
+            output,
+            "\n{}{}",
+            icon("📍", options),
+            markup::bold(&word("caption.location"))
+        )
+        .ok();
+        if let Some(place) = place {
+            write_row(&mut output, &word("labels.place"), &place);
+        }
+        if let Some(near) = near {
+            write_row(&mut output, &word("labels.near"), &near);
+        }
+        if let Some(altitude) = altitude {
+            write_row(&mut output, &word("labels.altitude"), &altitude);
+        }
+        if let Some(speed) = speed {
+            write_row(&mut output, &word("labels.speed"), &speed);
+        }
+        if let Some(gps) = gps {
+            write_row(&mut output, &word("caption.coordinates"), &gps);
+        }
+    }
+
+    output
+}
+
+fn render_gear_list(data: &ParsedExif, options: CaptionOptions, locale: &str) -> String {
+    let mut output = String::new();
+    let mut line = |emoji: &str, label: Label, value: &str| {
+        match options.labels {
+            LabelStyle::Emoji => writeln!(output, "{emoji} {value}"),
+            LabelStyle::Text => writeln!(output, "{}: {value}", label.word(locale)),
+        }
+        .ok();
+    };
+
+    line(
+        "📷",
+        Label::Camera,
+        &markup::escape(&camera_name(&data.camera, locale)),
+    );
+    if options.shows(Section::Lens) {
+        line(
+            "🔭",
+            Label::Lens,
+            &markup::escape(&lens_name(&data.lens, locale)),
+        );
+    }
+    let metrics = metrics(data, locale);
+    if !metrics.is_empty() {
+        line("⚙️", Label::Settings, &metrics.join(" | "));
+    }
+    if let Some(place) = place(data, options) {
+        line("📍", Label::Place, &place);
+    }
+
+    output
+}
+
+/// The emoji in front of a heading, left out with text labels.
+fn icon(emoji: &str, options: CaptionOptions) -> String {
+    match options.labels {
+        LabelStyle::Emoji => format!("{emoji} "),
+        LabelStyle::Text => String::new(),
+    }
+}
+
+/// A bold label followed by an already escaped value.
+fn write_row(output: &mut String, label: &str, value: &str) {
+    writeln!(output, "{}: {value}", markup::bold(label)).ok();
+}
+
+/// Whether the recorded focal length already is the 35mm equivalent (full frame).
+fn same_focal_length(data: &ParsedExif) -> bool {
+    match (data.focal_length_value, data.focal_length_35mm_value) {
+        (_, None) => true,
+        (Some(f), Some(f35)) => (f - f35).abs() < 0.5,
+        (None, Some(_)) => false,
+    }
+}
+
+/// Focal length (35mm equivalent on crop sensors), aperture, shutter and ISO, escaped and
+/// with decimals as `locale` writes them.
+fn metrics(data: &ParsedExif, locale: &str) -> Vec<String> {
+    let focal = if same_focal_length(data) {
+        data.focal_length.clone()
+    } else {
+        equivalent_focal_length(data, locale).or_else(|| data.focal_length.clone())
+    };
+
+    [
+        focal,
+        data.aperture.clone(),
+        data.shutter.clone(),
+        data.iso.as_deref().map(|iso| iso_speed(iso, locale)),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|value| markup::escape(&formats::decimals(&value, locale)))
+    .collect()
+}
+
+/// The camera name, or the placeholder for unknown cameras in `locale`.
+pub fn camera_name(camera: &str, locale: &str) -> String {
+    if camera == exif::UNKNOWN_CAMERA {
+        rust_i18n::t!("caption.unknown_camera", locale = locale).into_owned()
+    } else {
+        camera.to_string()
+    }
+}
+
+/// The lens name, or the placeholder for unknown lenses in `locale`.
+pub fn lens_name(lens: &str, locale: &str) -> String {
+    if lens == exif::UNKNOWN_LENS {
+        rust_i18n::t!("caption.unknown_lens", locale = locale).into_owned()
+    } else {
+        lens.to_string()
+    }
+}
+
+/// The 35mm equivalent focal length, marked as such in `locale`.
+pub fn equivalent_focal_length(data: &ParsedExif, locale: &str) -> Option<String> {
+    let value = data.focal_length_35mm_value?;
+    let text = rust_i18n::t!(
+        "caption.focal_length_35mm",
+        locale = locale,
+        focal = format!("{value:.0}mm")
+    );
+    Some(text.into_owned())
+}
+
+/// The ISO speed the parser wrote as `ISO 100`, prefixed the way `locale` writes it.
+pub fn iso_speed(iso: &str, locale: &str) -> String {
+    match iso.strip_prefix("ISO ") {
+        Some(value) => rust_i18n::t!("caption.iso", locale = locale, value = value).into_owned(),
+        None => iso.to_string(),
+    }
+}
+
+/// When the photo was taken, or that it is unknown in `locale`.
+fn date_taken(data: &ParsedExif, options: CaptionOptions, locale: &str) -> String {
+    match &data.datetime {
+        Some(datetime) => formats::datetime(datetime, locale, options.units.clock),
+        None => rust_i18n::t!("caption.unknown_date", locale = locale).into_owned(),
+    }
+}
+
+/// The place name, escaped and behind a spoiler when the options ask for one, with the
+/// country's flag when the geocoder gave its code.
+fn place(data: &ParsedExif, options: CaptionOptions) -> Option<String> {
+    if options.hide_location {
+        return None;
+    }
+    let location = if options.coarse_location {
+        data.city.as_deref().or(data.region.as_deref())
+    } else {
+        data.location.as_deref()
+    };
+    let mut place = match (location, data.country.as_deref()) {
+        (Some(location), Some(country)) => format!("{location}, {country}"),
+        (Some(location), None) => location.to_string(),
+        (None, Some(country)) => country.to_string(),
+        (None, None) => return None,
+    };
+    if let Some(flag) = data.country_code.as_deref().and_then(geo::flag_emoji) {
+        place = format!("{flag} {place}");
+    }
+    Some(hide_in_spoiler(markup::escape(&place), options))
+}
+
+/// The nearby landmark, escaped and hidden like the place name.
+fn nearby(data: &ParsedExif, options: CaptionOptions) -> Option<String> {
+    if options.hide_location || options.coarse_location {
+        return None;
+    }
+    let near = data.nearby.as_deref()?;
+    Some(hide_in_spoiler(markup::escape(near), options))
+}
+
+/// The GPS altitude, or the looked up terrain elevation marked as such, in whole meters
+/// or feet.
+fn altitude(data: &ParsedExif, options: CaptionOptions, locale: &str) -> Option<String> {
+    if options.hide_location {
+        return None;
+    }
+    let text = match (data.altitude, data.terrain_elevation) {
+        (Some(altitude), _) => options.units.altitude(altitude, locale),
+        (None, Some(elevation)) => rust_i18n::t!(
+            "labels.terrain_elevation",
+            locale = locale,
+            elevation = options.units.altitude(elevation, locale)
+        )
+        .into_owned(),
+        (None, None) => return None,
+    };
+    Some(hide_in_spoiler(markup::escape(&text), options))
+}
+
+/// How fast the camera moved, hidden like the altitude. Standing still is not worth a
+/// line, and phones record that for most photos.
+fn speed(data: &ParsedExif, options: CaptionOptions, locale: &str) -> Option<String> {
+    if options.hide_location {
+        return None;
+    }
+    let speed = data.speed.filter(|speed| *speed >= 1.0)?;
+    let text = options.units.speed(speed, locale);
+    Some(hide_in_spoiler(markup::escape(&text), options))
+}
+
+/// Golden or blue hour and the minutes to the nearest sunrise or sunset, when that is
+/// within [`SUN_EVENT_MINUTES`]. Follows the place line, since it hints at the position.
+fn sunlight(data: &ParsedExif, options: CaptionOptions, locale: &str) -> Option<String> {
+    if options.hide_location || !options.shows(Section::Light) {
+        return None;
+    }
+    let light = data.light?;
+    let mut parts = Vec::new();
+    match light.phase {
+        Some(Phase::GoldenHour) => parts.push(rust_i18n::t!("sun.golden_hour", locale = locale)),
+        Some(Phase::BlueHour) => parts.push(rust_i18n::t!("sun.blue_hour", locale = locale)),
+        None => {}
+    }
+    let minutes = light.minutes_from_event;
+    if let Some(event) = light.event.filter(|_| minutes.abs() <= SUN_EVENT_MINUTES) {
+        let key = match (event, minutes < 0) {
+            (Event::Sunrise, true) => "sun.before_sunrise",
+            (Event::Sunrise, false) => "sun.after_sunrise",
+            (Event::Sunset, true) => "sun.before_sunset",
+            (Event::Sunset, false) => "sun.after_sunset",
+        };
+        parts.push(rust_i18n::t!(key, locale = locale, minutes = minutes.abs()));
+    }
+    if parts.is_empty() {
+        return None;
+    }
+    Some(hide_in_spoiler(markup::escape(&parts.join(" · ")), options))
+}
+
+/// The moon's phase and how much of it was lit, like "🌔 Waxing gibbous, 78% lit".
+fn moon(data: &ParsedExif, options: CaptionOptions, locale: &str) -> Option<String> {
+    if options.hide_location || !options.shows(Section::Light) {
+        return None;
+    }
+    let moon = data.moon?;
+    let phase = rust_i18n::t!(moon.phase.locale_key(), locale = locale);
+    let lit = rust_i18n::t!(
+        "moon.illuminated",
+        locale = locale,
+        percent = format!("{:.0}", moon.illumination * 100.0)
+    );
+    let text = format!("{} {phase}, {lit}", moon.phase.emoji());
+    Some(hide_in_spoiler(markup::escape(&text), options))
+}
+
+/// Temperature and conditions at the time of the shot, like "18°C, clear sky".
+fn weather(data: &ParsedExif, options: CaptionOptions) -> Option<String> {
+    if options.hide_location || !options.shows(Section::Weather) {
+        return None;
+    }
+    let weather = data.weather.as_ref()?;
+    let mut text = format!("{:.0}°C", weather.temperature);
+    if !weather.conditions.is_empty() {
+        text = format!("{text}, {}", weather.conditions);
+    }
+    Some(hide_in_spoiler(markup::escape(&text), options))
+}
+
+/// The capture time at the location and its zone, when it differs from the camera clock.
+/// The zone gives away roughly where the photo was taken, so it follows the place line.
+fn local_time(data: &ParsedExif, options: CaptionOptions) -> Option<(&str, &str)> {
+    if options.hide_location {
+        return None;
+    }
+    let time = data.local_time.as_deref()?;
+    if data.datetime.as_deref() == Some(time) {
+        return None;
+    }
+    Some((time, data.timezone.as_deref()?))
+}
+
+/// The coordinates in monospace at the user's precision, followed by a map link and,
+/// for exact coordinates, the Plus Code and the what3words address when there is one.
+fn coordinates(data: &ParsedExif, options: CaptionOptions, locale: &str) -> Option<String> {
+    if options.hide_coordinates {
+        return None;
+    }
+    let ((latitude, longitude), gps) = data.position(options.precision)?;
+    let mut parts = vec![
+        markup::code(&gps),
+        markup::link(
+            &rust_i18n::t!("caption.map", locale = locale),
+            &links::google_maps(latitude, longitude),
+        ),
+    ];
+    // Both pin the spot down to a few metres, which would undo any rounding.
+    if options.precision == CoordinatePrecision::Exact {
+        if let Some(plus_code) = &data.plus_code {
+            parts.push(markup::code(plus_code));
+        }
+        if let Some(words) = &data.what3words {
+            parts.push(markup::code(&format!("///{words}")));
+        }
+    }
+    Some(hide_in_spoiler(parts.join(" · "), options))
+}
+
+fn hide_in_spoiler(html: String, options: CaptionOptions) -> String {
+    if options.spoiler_location {
+        markup::spoiler(&html)
+    } else {
+        html
+    }
+}
@@ -0,0 +1,125 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{Value as JsonValue, json};
+
+use crate::config::Config;
+
+const API_ENDPOINT: &str = "https://api.telegra.ph";
+const AUTHOR_NAME: &str = "fotobot";
+/// Telegraph rejects longer page titles.
+const MAX_TITLE_CHARS: usize = 256;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize)]
+struct ApiResponse<T> {
+    ok: bool,
+    result: Option<T>,
+    error: Option<String>,
+}
+
+impl<T> ApiResponse<T> {
+    fn into_result(self) -> Result<T> {
+        match (self.ok, self.result) {
+            (true, Some(result)) => Ok(result),
+            _ => Err(anyhow!(
+                "Telegraph API error: {}",
+                self.error.as_deref().unwrap_or("unknown")
+            )),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Account {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct Page {
+    url: String,
+}
+
+/// Publishes reports too long for a caption as Telegraph pages.
+pub struct Telegraph {
+    /// Taken from `telegraph_token`, or created with a new account on first use.
+    access_token: Mutex<Option<String>>,
+}
+
+impl Telegraph {
+    /// Configures the client from `telegraph_token`; `off` turns publishing off.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let access_token = match config.telegraph_token.as_deref() {
+            Some(token) if token.eq_ignore_ascii_case("off") => return None,
+            token => token.map(str::to_string),
+        };
+
+        Some(Self {
+            access_token: Mutex::new(access_token),
+        })
+    }
+
+    /// Creates a page with one paragraph per line of `text` and returns its URL.
+    pub async fn publish(&self, client: &Client, title: &str, text: &str) -> Result<String> {
+        let access_token = self.access_token(client).await?;
+        let title: String = title.chars().take(MAX_TITLE_CHARS).collect();
+        let content: Vec<JsonValue> = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| json!({ "tag": "p", "children": [line] }))
+            .collect();
+        let content = serde_json::to_string(&content).context("Failed to serialize page")?;
+
+        let page: ApiResponse<Page> = client
+            .post(format!("{API_ENDPOINT}/createPage"))
+            .timeout(REQUEST_TIMEOUT)
+            .form(&[
+                ("access_token", access_token.as_str()),
+                ("title", title.as_str()),
+                ("author_name", AUTHOR_NAME),
+                ("content", content.as_str()),
+            ])
+            .send()
+            .await
+            .context("Telegraph page request failed")?
+            .json()
+            .await
+            .context("Failed to decode Telegraph page response")?;
+
+        Ok(page.into_result()?.url)
+    }
+
+    async fn access_token(&self, client: &Client) -> Result<String> {
+        if let Some(token) = self
+            .access_token
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+        {
+            return Ok(token);
+        }
+
+        // Two reports racing here each create an account; either token works.
+        let account: ApiResponse<Account> = client
+            .post(format!("{API_ENDPOINT}/createAccount"))
+            .timeout(REQUEST_TIMEOUT)
+            .form(&[("short_name", AUTHOR_NAME), ("author_name", AUTHOR_NAME)])
+            .send()
+            .await
+            .context("Telegraph account request failed")?
+            .json()
+            .await
+            .context("Failed to decode Telegraph account response")?;
+        let token = account.into_result()?.access_token;
+        tracing::info!("Created a Telegraph account for publishing long reports");
+
+        *self
+            .access_token
+            .lock()
+            .unwrap_or_else(|err| err.into_inner()) = Some(token.clone());
+        Ok(token)
+    }
+}
@@ -0,0 +1,155 @@
+//! Prometheus counters and histograms, served at `/metrics` (next to the health checks)
+//! when `FOTOBOT_METRICS_ADDR` is set. Unlike the daily counts behind `/adminstats`, these start over on every restart.
+
+use std::net::SocketAddr;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use axum::Router;
+use axum::http::header::CONTENT_TYPE;
+use axum::routing::get;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, Opts, Registry, TEXT_FORMAT,
+    TextEncoder,
+};
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+struct Metrics {
+    registry: Registry,
+    messages: IntCounter,
+    exif_parse: Histogram,
+    download_bytes: IntCounter,
+    large_files: IntCounter,
+    geocoder: Histogram,
+    api_errors: IntCounterVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some(String::from("fotobot")), None)
+            .expect("the metrics prefix is valid");
+        let metrics = Self {
+            messages: IntCounter::new("messages_handled_total", "Messages the bot handled")
+                .expect("valid counter"),
+            exif_parse: Histogram::with_opts(
+                HistogramOpts::new(
+                    "exif_parse_duration_seconds",
+                    "Time to read and parse an image's EXIF data, download included",
+                )
+                .buckets(vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0]),
+            )
+            .expect("valid histogram"),
+            download_bytes: IntCounter::new(
+                "download_bytes_total",
+                "Image bytes read from Telegram and image links",
+            )
+            .expect("valid counter"),
+            large_files: IntCounter::new(
+                "large_file_downloads_total",
+                "Images above the Bot API limit, downloaded through the user client",
+            )
+            .expect("valid counter"),
+            geocoder: Histogram::with_opts(
+                HistogramOpts::new(
+                    "geocoder_latency_seconds",
+                    "Time to turn coordinates into a place name, cache hits included",
+                )
+                .buckets(vec![0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0]),
+            )
+            .expect("valid histogram"),
+            api_errors: IntCounterVec::new(
+                Opts::new(
+                    "telegram_api_errors_total",
+                    "Telegram requests that failed while handling an update",
+                ),
+                &["kind"],
+            )
+            .expect("valid counter"),
+            registry,
+        };
+
+        let collectors: [Box<dyn prometheus::core::Collector>; 6] = [
+            Box::new(metrics.messages.clone()),
+            Box::new(metrics.exif_parse.clone()),
+            Box::new(metrics.download_bytes.clone()),
+            Box::new(metrics.large_files.clone()),
+            Box::new(metrics.geocoder.clone()),
+            Box::new(metrics.api_errors.clone()),
+        ];
+        for collector in collectors {
+            metrics
+                .registry
+                .register(collector)
+                .expect("metric names are unique");
+        }
+        metrics
+    }
+}
+
+pub fn message_handled() {
+    METRICS.messages.inc();
+}
+
+pub fn observe_exif_parse(elapsed: Duration) {
+    METRICS.exif_parse.observe(elapsed.as_secs_f64());
+}
+
+pub fn downloaded(bytes: u64) {
+    METRICS.download_bytes.inc_by(bytes);
+}
+
+pub fn large_file() {
+    METRICS.large_files.inc();
+}
+
+pub fn observe_geocoder(elapsed: Duration) {
+    METRICS.geocoder.observe(elapsed.as_secs_f64());
+}
+
+/// Counts a failed Telegram request by the kind of failure, such as `api` or `network`.
+pub fn telegram_error(err: &teloxide::RequestError) {
+    use teloxide::RequestError;
+
+    let kind = match err {
+        RequestError::Api(_) => "api",
+        RequestError::MigrateToChatId(_) => "migrate",
+        RequestError::RetryAfter(_) => "retry_after",
+        RequestError::Network(_) => "network",
+        RequestError::InvalidJson { .. } => "invalid_json",
+        RequestError::Io(_) => "io",
+    };
+    METRICS.api_errors.with_label_values(&[kind]).inc();
+}
+
+/// Every metric in Prometheus' text format.
+fn render() -> String {
+    let mut buffer = Vec::new();
+    if let Err(err) = TextEncoder::new().encode(&METRICS.registry.gather(), &mut buffer) {
+        tracing::warn!("Failed to encode metrics: {err:?}");
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
+
+/// Serves `/metrics` and the `routes` next to it on `address` in the background.
+pub fn spawn_server(address: SocketAddr, routes: Router) {
+    let app = Router::new()
+        .route(
+            "/metrics",
+            get(|| async { ([(CONTENT_TYPE, TEXT_FORMAT)], render()) }),
+        )
+        .merge(routes);
+    tokio::spawn(async move {
+        tracing::info!("Serving metrics on http://{address}/metrics");
+        let server =
+            axum::Server::try_bind(&address).map(|server| server.serve(app.into_make_service()));
+        match server {
+            Ok(server) => {
+                if let Err(err) = server.await {
+                    tracing::error!("Metrics server stopped: {err:?}");
+                }
+            }
+            Err(err) => tracing::error!("Failed to listen for metrics on {address}: {err:?}"),
+        }
+    });
+}
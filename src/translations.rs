@@ -0,0 +1,122 @@
+//! Reloads the translations from `locales_dir` (`locales` in the working
+//! directory by default) whenever a file there changes, so operators can fix a string
+//! without restarting the bot. Keys the files no longer have fall back to the
+//! translations built into the binary.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+use rust_i18n::Backend;
+
+const DEFAULT_DIR: &str = "locales";
+/// How often the directory is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Locale, then key, then the translation.
+type Tables = HashMap<&'static str, HashMap<&'static str, &'static str>>;
+
+static TABLES: LazyLock<RwLock<Tables>> = LazyLock::new(RwLock::default);
+/// `rust_i18n` borrows translations for as long as the backend lives, so every string
+/// loaded stays; a string is only leaked once, however often its file is reloaded.
+static INTERNED: LazyLock<Mutex<HashSet<&'static str>>> = LazyLock::new(Mutex::default);
+
+/// Answers from the files loaded last, ahead of the built-in translations.
+pub struct LiveTranslations;
+
+impl Backend for LiveTranslations {
+    fn available_locales(&self) -> Vec<&str> {
+        let tables = TABLES.read().unwrap_or_else(|err| err.into_inner());
+        tables.keys().copied().collect()
+    }
+
+    fn translate(&self, locale: &str, key: &str) -> Option<&str> {
+        let tables = TABLES.read().unwrap_or_else(|err| err.into_inner());
+        tables.get(locale)?.get(key).copied()
+    }
+}
+
+/// Loads the directory, if there is one, and reloads it in the background whenever a
+/// file in it is added, changed or removed.
+pub fn spawn_watcher(dir: Option<&Path>) {
+    let dir = dir.map_or_else(|| PathBuf::from(DEFAULT_DIR), Path::to_path_buf);
+    if !dir.is_dir() {
+        tracing::debug!(
+            "No translations at `{}`; using the built-in ones",
+            dir.display()
+        );
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut seen = None;
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let modified = modification_times(&dir);
+            if seen.as_ref() == Some(&modified) {
+                continue;
+            }
+            let first = seen.is_none();
+            seen = Some(modified);
+
+            let loading = dir.clone();
+            match tokio::task::spawn_blocking(move || load(&loading)).await {
+                Ok(locales) if first => {
+                    tracing::info!("Loaded {locales} translations from `{}`", dir.display())
+                }
+                Ok(locales) => {
+                    tracing::info!("Reloaded {locales} translations from `{}`", dir.display())
+                }
+                // The loader panics on files it cannot parse.
+                Err(err) => tracing::warn!(
+                    "Keeping the previous translations, `{}` failed to load: {err}",
+                    dir.display()
+                ),
+            }
+        }
+    });
+}
+
+/// Replaces the tables with the files in `dir`, returning how many locales they hold.
+fn load(dir: &Path) -> usize {
+    let loaded: BTreeMap<String, BTreeMap<String, String>> =
+        rust_i18n_support::load_locales(&dir.to_string_lossy(), |_| false);
+    let mut interned = INTERNED.lock().unwrap_or_else(|err| err.into_inner());
+    let mut intern = |text: String| match interned.get(text.as_str()) {
+        Some(text) => *text,
+        None => {
+            let text: &'static str = Box::leak(text.into_boxed_str());
+            interned.insert(text);
+            text
+        }
+    };
+    let tables: Tables = loaded
+        .into_iter()
+        .map(|(locale, strings)| {
+            let strings = strings
+                .into_iter()
+                .map(|(key, text)| (intern(key), intern(text)))
+                .collect();
+            (intern(locale), strings)
+        })
+        .collect();
+    let locales = tables.len();
+    *TABLES.write().unwrap_or_else(|err| err.into_inner()) = tables;
+    locales
+}
+
+/// When each file in `dir` was last changed, to tell whether anything needs reloading.
+fn modification_times(dir: &Path) -> BTreeMap<PathBuf, SystemTime> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return BTreeMap::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            Some((entry.path(), modified))
+        })
+        .collect()
+}
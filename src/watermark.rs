@@ -0,0 +1,161 @@
+use anyhow::Result;
+use image::{Rgb, RgbImage};
+
+use crate::canvas::{self, Typeface};
+use crate::exif::{self, ShotFacts};
+
+const DEFAULT_OPACITY: f32 = 0.7;
+const TEXT_COLOR: Rgb<u8> = Rgb([255, 255, 255]);
+const BACKDROP_COLOR: Rgb<u8> = Rgb([0, 0, 0]);
+
+/// Which corner of the image the overlay sits in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+impl Corner {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "tl" | "topleft" | "top-left" => Some(Self::TopLeft),
+            "tr" | "topright" | "top-right" => Some(Self::TopRight),
+            "bl" | "bottomleft" | "bottom-left" => Some(Self::BottomLeft),
+            "br" | "bottomright" | "bottom-right" => Some(Self::BottomRight),
+            _ => None,
+        }
+    }
+}
+
+/// Where the overlay goes and how opaque its backdrop is.
+#[derive(Clone, Copy, Debug)]
+pub struct WatermarkOptions {
+    pub corner: Corner,
+    /// Backdrop opacity between 0 and 1; the text itself is always opaque.
+    pub opacity: f32,
+}
+
+impl Default for WatermarkOptions {
+    fn default() -> Self {
+        Self {
+            corner: Corner::default(),
+            opacity: DEFAULT_OPACITY,
+        }
+    }
+}
+
+impl WatermarkOptions {
+    /// Parses `/watermark` arguments such as `tl 50%`; either part may be left out.
+    pub fn parse(args: &str) -> Option<Self> {
+        let mut options = Self::default();
+        for arg in args.split_whitespace() {
+            let arg = arg.to_ascii_lowercase();
+            if let Some(corner) = Corner::parse(&arg) {
+                options.corner = corner;
+                continue;
+            }
+            let percent: u8 = arg.trim_end_matches('%').parse().ok()?;
+            if percent > 100 {
+                return None;
+            }
+            options.opacity = f32::from(percent) / 100.0;
+        }
+        Some(options)
+    }
+}
+
+/// Decodes `bytes`, draws `lines` into the chosen corner and returns the result as JPEG.
+pub fn render(
+    typeface: &Typeface,
+    bytes: &[u8],
+    lines: &[String],
+    options: WatermarkOptions,
+) -> Result<Vec<u8>> {
+    let mut image = canvas::decode(bytes)?;
+    draw_overlay(typeface, &mut image, lines, options);
+    canvas::encode_jpeg(&image)
+}
+
+fn draw_overlay(
+    typeface: &Typeface,
+    image: &mut RgbImage,
+    lines: &[String],
+    options: WatermarkOptions,
+) {
+    if lines.is_empty() {
+        return;
+    }
+
+    // Scale with the image so the overlay reads the same on thumbnails and full size.
+    let short_side = image.width().min(image.height()) as f32;
+    let size = (short_side / 36.0).max(12.0);
+    let line_height = typeface.line_height(size);
+    let padding = (size / 2.0).round() as u32;
+
+    let text_width = lines
+        .iter()
+        .map(|line| typeface.text_width(size, line))
+        .fold(0.0, f32::max)
+        .ceil() as u32;
+    let box_width = (text_width + 2 * padding).min(image.width());
+    let box_height =
+        ((line_height * lines.len() as f32).ceil() as u32 + 2 * padding).min(image.height());
+
+    let margin = padding;
+    let left = match options.corner {
+        Corner::TopLeft | Corner::BottomLeft => margin,
+        Corner::TopRight | Corner::BottomRight => image.width().saturating_sub(box_width + margin),
+    };
+    let top = match options.corner {
+        Corner::TopLeft | Corner::TopRight => margin,
+        Corner::BottomLeft | Corner::BottomRight => {
+            image.height().saturating_sub(box_height + margin)
+        }
+    };
+
+    canvas::fill_rect(
+        image,
+        (left, top),
+        (box_width, box_height),
+        BACKDROP_COLOR,
+        options.opacity,
+    );
+    for (index, line) in lines.iter().enumerate() {
+        let baseline =
+            top as f32 + padding as f32 + typeface.ascent(size) + line_height * index as f32;
+        typeface.draw_text(
+            image,
+            size,
+            (left as f32 + padding as f32, baseline),
+            line,
+            TEXT_COLOR,
+        );
+    }
+}
+
+/// Camera, lens and the exposure triangle, one line each; unknown values are left out.
+pub fn overlay_lines(facts: &ShotFacts) -> Vec<String> {
+    let mut lines = Vec::new();
+    if facts.camera != exif::UNKNOWN_CAMERA {
+        lines.push(facts.camera.clone());
+    }
+    if facts.lens != exif::UNKNOWN_LENS {
+        lines.push(facts.lens.clone());
+    }
+    let exposure: Vec<&str> = [
+        &facts.focal_length,
+        &facts.aperture,
+        &facts.shutter,
+        &facts.iso,
+    ]
+    .into_iter()
+    .filter_map(|value| value.as_deref())
+    .collect();
+    if !exposure.is_empty() {
+        lines.push(exposure.join(" · "));
+    }
+    lines
+}
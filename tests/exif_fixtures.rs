@@ -0,0 +1,143 @@
+//! Snapshot tests over the images in `tests/fixtures`: what `ParsedExif` makes of each
+//! and the caption the bot writes for it, without any network lookups.
+//!
+//! Snapshots live in `tests/snapshots`, and a missing one fails its test.
+//! `UPDATE_SNAPSHOTS=1 cargo test` records them all, after adding a fixture or an intended
+//! change; review the diff before committing it.
+
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use fotobot_rs::exif::{self, CaptionOptions, ExifSummary, ParsedExif};
+
+fn fixture(name: &str) -> Vec<u8> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    std::fs::read(&path).unwrap_or_else(|err| panic!("Failed to read {}: {err}", path.display()))
+}
+
+fn assert_snapshot(name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(name);
+    let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some_and(|value| value != "0");
+    if update {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    match std::fs::read_to_string(&path) {
+        Ok(expected) => assert_eq!(
+            expected,
+            actual,
+            "{} changed; rerun with UPDATE_SNAPSHOTS=1 if that is intended",
+            path.display()
+        ),
+        Err(err) => panic!(
+            "{} is missing ({err}); record it with UPDATE_SNAPSHOTS=1",
+            path.display()
+        ),
+    }
+}
+
+/// The parsed fields as pretty JSON, or what went wrong reading them.
+fn parsed(bytes: &[u8]) -> String {
+    match exif::read_exif(Cursor::new(bytes)) {
+        Ok(Some(exif)) => {
+            let parsed = ParsedExif::from_exif(&exif, None);
+            serde_json::to_string_pretty(&parsed).unwrap() + "\n"
+        }
+        Ok(None) => String::from("no EXIF data\n"),
+        Err(err) => format!("error: {err:#}\n"),
+    }
+}
+
+fn caption(bytes: &[u8], language: &str, options: CaptionOptions) -> String {
+    match exif::read_metadata(Cursor::new(bytes)) {
+        Ok(metadata) => {
+            ExifSummary::without_lookups(metadata, Some(language), options).caption + "\n"
+        }
+        Err(err) => format!("error: {err:#}\n"),
+    }
+}
+
+/// Snapshots the parsed fields and the English caption with the default options.
+fn check(name: &str) {
+    let bytes = fixture(name);
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+    assert_snapshot(&format!("{stem}.parsed.json"), &parsed(&bytes));
+    assert_snapshot(
+        &format!("{stem}.caption.txt"),
+        &caption(&bytes, "en", CaptionOptions::default()),
+    );
+}
+
+macro_rules! fixtures {
+    ($($test:ident => $name:literal,)*) => {
+        $(
+            #[test]
+            fn $test() {
+                check($name);
+            }
+        )*
+    };
+}
+
+fixtures! {
+    canon_eos_r5 => "canon_eos_r5.jpg",
+    nikon_z6ii_night_gps => "nikon_z6ii_night_gps.jpg",
+    iphone_15_pro_gps => "iphone_15_pro_gps.jpg",
+    sony_a7iv_tiff => "sony_a7iv.tiff",
+    fujifilm_xt5_png => "fujifilm_xt5.png",
+    pixel_8_gps_webp => "pixel_8_gps.webp",
+    galaxy_s23_ultra_gps_heic => "galaxy_s23_ultra_gps.heic",
+    dji_drone_gps => "dji_drone_gps.jpg",
+    gps_only => "gps_only.jpg",
+    stable_diffusion_png => "stable_diffusion.png",
+    truncated_exif => "truncated_exif.jpg",
+    broken_byte_order => "broken_byte_order.jpg",
+    fullwidth_datetime => "fullwidth_datetime.jpg",
+    no_exif_jpeg => "no_exif.jpg",
+    no_exif_png => "no_exif.png",
+}
+
+#[test]
+fn captions_follow_the_language() {
+    let bytes = fixture("iphone_15_pro_gps.jpg");
+    assert_snapshot(
+        "iphone_15_pro_gps.caption.zh-CN.txt",
+        &caption(&bytes, "zh-CN", CaptionOptions::default()),
+    );
+}
+
+#[test]
+fn hidden_coordinates_stay_out_of_the_caption() {
+    let bytes = fixture("nikon_z6ii_night_gps.jpg");
+    let options = CaptionOptions {
+        hide_coordinates: true,
+        hide_location: true,
+        ..CaptionOptions::default()
+    };
+    let caption = caption(&bytes, "en", options);
+    assert!(!caption.contains("35.65"), "{caption}");
+    assert_snapshot("nikon_z6ii_night_gps.caption.hidden.txt", &caption);
+}
+
+#[test]
+fn containers_carry_the_same_fields() {
+    // The generator writes the same kind of IFDs into every container, so each one
+    // must come out with its camera, whatever the format.
+    for (name, camera) in [
+        ("sony_a7iv.tiff", "ILCE-7M4"),
+        ("fujifilm_xt5.png", "X-T5"),
+        ("pixel_8_gps.webp", "Pixel 8"),
+        ("galaxy_s23_ultra_gps.heic", "Galaxy S23 Ultra"),
+    ] {
+        let exif = exif::read_exif(Cursor::new(fixture(name)))
+            .unwrap_or_else(|err| panic!("{name}: {err:#}"))
+            .unwrap_or_else(|| panic!("{name} has no EXIF data"));
+        let parsed = ParsedExif::from_exif(&exif, None);
+        assert!(parsed.camera.contains(camera), "{name}: {}", parsed.camera);
+    }
+}
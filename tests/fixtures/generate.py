@@ -0,0 +1,336 @@
+#!/usr/bin/env python3
+"""Writes the images in this directory, each a tiny picture with known EXIF data.
+
+The files are committed, so this only needs to run again to change the corpus:
+
+    python3 tests/fixtures/generate.py
+
+Only the standard library is used. The pictures are single gray pixels or blocks; what
+matters is the metadata, written the way the cameras named in the file names write it.
+"""
+
+import struct
+import zlib
+from pathlib import Path
+
+HERE = Path(__file__).resolve().parent
+
+BYTE, ASCII, SHORT, LONG, RATIONAL, UNDEFINED, SRATIONAL = 1, 2, 3, 4, 5, 7, 10
+EXIF_POINTER, GPS_POINTER = 0x8769, 0x8825
+
+
+def ascii(text):
+    return (ASCII, text.encode() + b"\0")
+
+
+def short(*values):
+    return (SHORT, list(values))
+
+
+def long(*values):
+    return (LONG, list(values))
+
+
+def rational(*values):
+    return (RATIONAL, list(values))
+
+
+def undefined(data):
+    return (UNDEFINED, data)
+
+
+def byte(*values):
+    return (BYTE, bytes(values))
+
+
+def encode_value(kind, value, order):
+    """The count and bytes of one entry's value."""
+    if kind in (ASCII, UNDEFINED, BYTE):
+        return len(value), value
+    if kind == SHORT:
+        return len(value), b"".join(struct.pack(order + "H", v) for v in value)
+    if kind == LONG:
+        return len(value), b"".join(struct.pack(order + "I", v) for v in value)
+    if kind in (RATIONAL, SRATIONAL):
+        code = "II" if kind == RATIONAL else "ii"
+        return len(value), b"".join(struct.pack(order + code, *v) for v in value)
+    raise ValueError(kind)
+
+
+def encode_ifd(entries, start, order):
+    """One IFD at offset `start`, with the values that do not fit in an entry after it."""
+    entries = sorted(entries.items())
+    data_start = start + 2 + 12 * len(entries) + 4
+    table = struct.pack(order + "H", len(entries))
+    data = b""
+    for tag, (kind, value) in entries:
+        count, raw = encode_value(kind, value, order)
+        if len(raw) <= 4:
+            field = raw.ljust(4, b"\0")
+        else:
+            field = struct.pack(order + "I", data_start + len(data))
+            data += raw + (b"\0" if len(raw) % 2 else b"")
+        table += struct.pack(order + "HHI", tag, kind, count) + field
+    return table + struct.pack(order + "I", 0) + data
+
+
+def tiff(ifd0, exif=None, gps=None, big_endian=False):
+    """A TIFF header with IFD0 and the EXIF and GPS IFDs it points to."""
+    order = ">" if big_endian else "<"
+    header = (b"MM" if big_endian else b"II") + struct.pack(order + "HI", 42, 8)
+    ifd0 = dict(ifd0)
+    if exif is not None:
+        ifd0[EXIF_POINTER] = long(0)
+    if gps is not None:
+        ifd0[GPS_POINTER] = long(0)
+    # Pointers fit in their entries, so the sizes are known before the offsets.
+    offset = 8 + len(encode_ifd(ifd0, 8, order))
+    if exif is not None:
+        ifd0[EXIF_POINTER] = long(offset)
+        offset += len(encode_ifd(exif, offset, order))
+    if gps is not None:
+        ifd0[GPS_POINTER] = long(offset)
+
+    body = encode_ifd(ifd0, 8, order)
+    if exif is not None:
+        body += encode_ifd(exif, ifd0[EXIF_POINTER][1][0], order)
+    if gps is not None:
+        body += encode_ifd(gps, ifd0[GPS_POINTER][1][0], order)
+    return header + body
+
+
+def degrees(value):
+    """Degrees, minutes and seconds as the GPS IFD stores them."""
+    value = abs(value)
+    whole = int(value)
+    minutes = int((value - whole) * 60)
+    seconds = round(((value - whole) * 60 - minutes) * 60 * 100)
+    return rational((whole, 1), (minutes, 1), (seconds, 100))
+
+
+def gps_ifd(latitude, longitude, altitude=None, stamp=None):
+    gps = {
+        0x0000: byte(2, 3, 0, 0),
+        0x0001: ascii("N" if latitude >= 0 else "S"),
+        0x0002: degrees(latitude),
+        0x0003: ascii("E" if longitude >= 0 else "W"),
+        0x0004: degrees(longitude),
+    }
+    if altitude is not None:
+        gps[0x0005] = byte(0 if altitude >= 0 else 1)
+        gps[0x0006] = rational((round(abs(altitude) * 10), 10))
+    if stamp is not None:
+        date, (hours, minutes, seconds) = stamp
+        gps[0x0007] = rational((hours, 1), (minutes, 1), (seconds, 1))
+        gps[0x001D] = ascii(date)
+    return gps
+
+
+def jpeg(tiff_data=None):
+    """An 8x8 gray baseline JPEG, with the EXIF segment first when there is one."""
+    segments = b"\xff\xd8"
+    if tiff_data is not None:
+        payload = b"Exif\0\0" + tiff_data
+        segments += b"\xff\xe1" + struct.pack(">H", len(payload) + 2) + payload
+    segments += b"\xff\xdb\x00\x43\x00" + bytes([1] * 64)
+    segments += b"\xff\xc0\x00\x0b\x08\x00\x08\x00\x08\x01\x01\x11\x00"
+    # One-symbol Huffman tables: DC category 0 and the end of block, both coded `0`.
+    segments += b"\xff\xc4\x00\x14\x00" + bytes([1] + [0] * 15) + b"\x00"
+    segments += b"\xff\xc4\x00\x14\x10" + bytes([1] + [0] * 15) + b"\x00"
+    segments += b"\xff\xda\x00\x08\x01\x01\x00\x00\x3f\x00"
+    return segments + b"\x3f\xff\xd9"
+
+
+def png(tiff_data=None, text=None):
+    """A 1x1 gray PNG, with an `eXIf` chunk and a `tEXt` chunk when given."""
+
+    def chunk(kind, data):
+        crc = zlib.crc32(kind + data) & 0xFFFFFFFF
+        return struct.pack(">I", len(data)) + kind + data + struct.pack(">I", crc)
+
+    out = b"\x89PNG\r\n\x1a\n" + chunk(b"IHDR", struct.pack(">IIBBBBB", 1, 1, 8, 0, 0, 0, 0))
+    if text is not None:
+        keyword, value = text
+        out += chunk(b"tEXt", keyword.encode() + b"\0" + value.encode())
+    if tiff_data is not None:
+        out += chunk(b"eXIf", tiff_data)
+    out += chunk(b"IDAT", zlib.compress(b"\x00\x80"))
+    return out + chunk(b"IEND", b"")
+
+
+def webp(tiff_data):
+    """A 1x1 lossless WebP with an `EXIF` chunk."""
+
+    def chunk(kind, data):
+        return kind + struct.pack("<I", len(data)) + data + (b"\0" if len(data) % 2 else b"")
+
+    bits = []
+
+    def write(value, count):
+        bits.extend((value >> i) & 1 for i in range(count))
+
+    write(0, 14 + 14 + 1 + 3)  # 1x1, no alpha, version 0
+    write(0, 3)  # no transform, no color cache, no meta prefix codes
+    for symbol in (128, 128, 128, 255):  # green, red, blue and alpha as simple codes
+        write(1, 1)
+        write(0, 1)
+        write(1, 1)
+        write(symbol, 8)
+    write(0b001, 3)  # distance: a simple code with the 1-bit symbol 0
+    write(0, 1)
+    packed = bytes(
+        sum(bit << i for i, bit in enumerate(bits[start : start + 8]))
+        for start in range(0, len(bits), 8)
+    )
+    vp8l = b"\x2f" + packed
+    vp8x = struct.pack("<B3x", 0x08) + (0).to_bytes(3, "little") + (0).to_bytes(3, "little")
+    body = b"WEBP" + chunk(b"VP8X", vp8x) + chunk(b"VP8L", vp8l) + chunk(b"EXIF", tiff_data)
+    return b"RIFF" + struct.pack("<I", len(body)) + body
+
+
+def heif(tiff_data):
+    """A HEIF container holding only the `Exif` item; the HEVC image itself is left out."""
+
+    def box(kind, data):
+        return struct.pack(">I", len(data) + 8) + kind + data
+
+    def full_box(kind, version, data):
+        return box(kind, struct.pack(">I", version << 24) + data)
+
+    ftyp = box(b"ftyp", b"heic" + struct.pack(">I", 0) + b"mif1heic")
+    hdlr = full_box(b"hdlr", 0, b"\0" * 4 + b"pict" + b"\0" * 12 + b"\0")
+    infe = full_box(b"infe", 2, struct.pack(">HH", 1, 0) + b"Exif" + b"\0")
+    iinf = full_box(b"iinf", 0, struct.pack(">H", 1) + infe)
+    payload = struct.pack(">I", 0) + tiff_data
+
+    def meta(offset):
+        # 4-byte offsets and lengths, one item with one extent.
+        extent = struct.pack(">HHHHII", 1, 1, 0, 1, offset, len(payload))
+        iloc = full_box(b"iloc", 0, bytes([0x44, 0x00]) + extent)
+        return full_box(b"meta", 0, hdlr + iinf + iloc)
+
+    # The offset of the payload depends on the size of `meta`, which does not depend on it.
+    offset = len(ftyp) + len(meta(0)) + 8
+    return ftyp + meta(offset) + box(b"mdat", payload)
+
+
+def camera(make, model, software=None, description=None):
+    ifd0 = {0x010F: ascii(make), 0x0110: ascii(model)}
+    if software is not None:
+        ifd0[0x0131] = ascii(software)
+    if description is not None:
+        ifd0[0x010E] = ascii(description)
+    return ifd0
+
+
+def shot(taken, aperture, exposure, iso, focal, focal_35mm=None, lens=None, offset=None):
+    exif = {
+        0x829A: rational(exposure),
+        0x829D: rational(aperture),
+        0x8827: short(iso),
+        0x9000: undefined(b"0232"),
+        0x9003: ascii(taken),
+        0x9004: ascii(taken),
+        0x920A: rational(focal),
+    }
+    if focal_35mm is not None:
+        exif[0xA405] = short(focal_35mm)
+    if lens is not None:
+        exif[0xA434] = ascii(lens)
+    if offset is not None:
+        exif[0x9011] = ascii(offset)
+    return exif
+
+
+def corpus():
+    canon = tiff(
+        camera("Canon", "Canon EOS R5"),
+        shot(
+            "2023:06:14 18:32:05",
+            (28, 10),
+            (1, 250),
+            400,
+            (50, 1),
+            lens="RF24-70mm F2.8 L IS USM",
+            offset="+02:00",
+        ),
+    )
+    nikon = tiff(
+        camera("NIKON CORPORATION", "NIKON Z 6_2"),
+        shot("2024:01:20 22:41:10", (40, 10), (30, 1), 3200, (24, 1), focal_35mm=24),
+        gps_ifd(35.658581, 139.745433, altitude=40.5, stamp=("2024:01:20", (13, 41, 10))),
+        big_endian=True,
+    )
+    iphone = tiff(
+        camera("Apple", "iPhone 15 Pro", software="17.4.1", description="Christ the Redeemer"),
+        shot(
+            "2024:03:02 09:15:44",
+            (178, 100),
+            (1, 1200),
+            64,
+            (6765, 1000),
+            focal_35mm=24,
+            lens="iPhone 15 Pro back triple camera 6.765mm f/1.78",
+            offset="-03:00",
+        ),
+        gps_ifd(-22.951916, -43.210487, altitude=704.0),
+    )
+    sony = tiff(
+        camera("SONY", "ILCE-7M4"),
+        shot("2022:10:08 07:03:30", (80, 10), (1, 60), 100, (200, 1), lens="FE 70-200mm F2.8 GM OSS II"),
+    )
+    fujifilm = tiff(
+        camera("FUJIFILM", "X-T5"),
+        shot("2023:11:25 15:20:00", (56, 10), (1, 1000), 125, (33, 1), focal_35mm=50, lens="XF33mmF1.4 R LM WR"),
+    )
+    pixel = tiff(
+        camera("Google", "Pixel 8"),
+        shot("2024:05:18 12:00:01", (168, 100), (1, 4000), 48, (690, 100), focal_35mm=25),
+        gps_ifd(51.500729, -0.124625, stamp=("2024:05:18", (11, 0, 1))),
+    )
+    samsung = tiff(
+        camera("samsung", "Galaxy S23 Ultra"),
+        shot("2023:08:30 19:45:12", (17, 10), (1, 120), 200, (630, 100), focal_35mm=23),
+        gps_ifd(37.566535, 126.977969, altitude=38.0),
+    )
+    drone = tiff(
+        camera("DJI", "FC3582"),
+        shot("2024:07:04 06:12:40", (17, 10), (1, 1600), 100, (672, 100), focal_35mm=24),
+        gps_ifd(46.558056, 7.835278, altitude=2184.3, stamp=("2024:07:04", (4, 12, 40))),
+    )
+    gps_only = tiff({}, None, gps_ifd(-33.856784, 151.215297))
+    generated = tiff({0x0131: ascii("Stable Diffusion 1.5")})
+
+    # IFD0 claims 40 entries, but the segment ends after the first two.
+    truncated = tiff(camera("Canon", "Canon EOS 5D Mark IV"), None)
+    truncated = truncated[:8] + struct.pack("<H", 40) + truncated[10:36]
+    broken = b"XX\x2a\x00\x08\x00\x00\x00" + b"\x00" * 16
+    # A full-width digit puts a character boundary inside the date's byte range.
+    fullwidth = tiff(
+        camera("Panasonic", "DC-S5M2"),
+        shot("2024:02:1\uff10 08:00:00", (40, 10), (1, 125), 200, (35, 1)),
+    )
+
+    return {
+        "canon_eos_r5.jpg": jpeg(canon),
+        "nikon_z6ii_night_gps.jpg": jpeg(nikon),
+        "iphone_15_pro_gps.jpg": jpeg(iphone),
+        "sony_a7iv.tiff": sony,
+        "fujifilm_xt5.png": png(fujifilm),
+        "pixel_8_gps.webp": webp(pixel),
+        "galaxy_s23_ultra_gps.heic": heif(samsung),
+        "dji_drone_gps.jpg": jpeg(drone),
+        "gps_only.jpg": jpeg(gps_only),
+        "stable_diffusion.png": png(generated, text=("parameters", "a lighthouse at dusk, Steps: 20")),
+        "truncated_exif.jpg": jpeg(truncated),
+        "broken_byte_order.jpg": jpeg(broken),
+        "fullwidth_datetime.jpg": jpeg(fullwidth),
+        "no_exif.jpg": jpeg(),
+        "no_exif.png": png(),
+    }
+
+
+if __name__ == "__main__":
+    for name, data in corpus().items():
+        (HERE / name).write_bytes(data)
+        print(f"{name}: {len(data)} bytes")